@@ -15,6 +15,8 @@ pub enum Error {
     InvalidInput(String),
     /// An IO error.
     IO(String),
+    /// Query execution was cancelled by the caller, see CancelToken.
+    Cancelled,
     /// Arithmetic integer overflow occurred.
     OverflowError,
     /// A write action was attempted in a read-only transaction.
@@ -36,6 +38,7 @@ impl std::fmt::Display for Error {
             Error::Abort => write!(f, "operation aborted"),
             Error::InvalidData(msg) => write!(f, "invalid data: {msg}"),
             Error::InvalidInput(msg) => write!(f, "invalid input: {msg}"),
+            Error::Cancelled => write!(f, "query cancelled"),
             Error::IO(msg) => write!(f, "io error: {msg}"),
             Error::OverflowError => write!(f, "integer overflow occurred"),
             Error::ReadOnly => write!(f, "read-only transaction"),
@@ -63,6 +66,8 @@ impl Error {
             // Input errors are (likely) deterministic. They might not be in
             // case data was corrupted in flight, but we ignore this case.
             Error::InvalidInput(_) => true,
+            // Cancellation depends on the timing of the caller.
+            Error::Cancelled => false,
             // IO errors are typically local to the node (e.g. faulty disk).
             Error::IO(_) => false,
             // Possible data corruption local to this node.
@@ -119,11 +119,11 @@ pub fn create_random_full_page(schema: &Arc<Table>, seed: Option<u64>) -> TableP
         let tuple = row.to_tuple(schema).unwrap();
 
         let tuple_byte_size = tuple.data.len();
-        if payload_size + tuple_byte_size + 4 > RUSTY_DB_PAGE_SIZE_BYTES {
+        if payload_size + tuple_byte_size + 8 > RUSTY_DB_PAGE_SIZE_BYTES {
             break;
         }
         page.insert_tuple(TupleMetadata::new(false), tuple);
-        payload_size += tuple_byte_size + 4; // 4 bytes for tuple metadata;
+        payload_size += tuple_byte_size + 8; // 8 bytes for tuple metadata;
 
         // Make each tuple different
         local_seed += 1;
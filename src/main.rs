@@ -31,21 +31,19 @@ fn main() -> Result<()> {
 
 fn execute<'a, E: Engine<'a>>(command: &str, session: &mut Session<'a, E>) -> Result<()> {
     match session.execute(command)? {
-        StatementResult::Explain(_) => {
-            todo!();
-        }
+        StatementResult::Explain(plan) => println!("{plan}"),
         StatementResult::CreateTable { name } => println!("[console] Created table '{}'.", name),
-        StatementResult::DropTable { name, existed } => match existed {
+        StatementResult::CreateTableAs { name, count } => {
+            println!("[console] Created table '{}' with {} tuples.", name, count)
+        }
+        StatementResult::DropTable { name, existed, .. } => match existed {
             true => println!("[console] Dropped table '{}'.", name),
             false => println!("[console] Table '{}' does not exist.", name),
         },
         StatementResult::Delete { count } => println!("[console] Deleted {} tuples.", count),
-        StatementResult::Insert {
-            count,
-            record_ids: _,
-        } => println!("[console] Inserted {} tuples.", count),
+        StatementResult::Insert { count, .. } => println!("[console] Inserted {} tuples.", count),
         StatementResult::Update { count } => println!("[console] Updated {} tuples.", count),
-        StatementResult::Select { columns, rows } => {
+        StatementResult::Select { columns, rows, .. } => {
             print_columns(&columns);
             print_rows(&rows);
         }
@@ -1,8 +1,9 @@
 use crate::common::Result;
 use crate::errinput;
-use crate::sql::planner::Expression;
+use crate::sql::planner::{Expression, NullOrder};
 use crate::storage::page::RecordId;
 use crate::storage::tuple::{Row, Rows};
+use crate::types::field::Field;
 use crate::types::Table;
 use std::collections::BTreeMap;
 
@@ -29,12 +30,77 @@ pub trait Engine<'a>: Sized {
 pub trait Transaction {
     /// Deletes tuples of a table by record id (RID), if they exist.
     fn delete(&self, table: &str, ids: &[RecordId]) -> Result<()>;
-    /// Inserts tuples into a table, and returns a vector of their corresponding record ids.
-    fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<Vec<RecordId>>;
+    /// Returns an estimate of the number of rows in a table, for planning and
+    /// progress reporting. It's read from storage metadata without decoding
+    /// any rows, and may be approximate.
+    fn estimate_rows(&self, table_name: &str) -> Result<u64>;
+    /// Returns whether any row of the table has the given value in the column,
+    /// stopping at the first match. NULL never matches, as with =.
+    fn exists(&self, table_name: &str, column_index: usize, value: &Field) -> Result<bool>;
+    /// Fetches a single row of a table by record id, or None if it doesn't
+    /// exist or was deleted. The record id must come from the same table.
+    fn get(&self, table_name: &str, rid: &RecordId) -> Result<Option<Row>>;
+    /// Inserts tuples into a table, and returns their corresponding record ids along with
+    /// the inserted rows, which are moved into the result rather than read back.
+    fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<Vec<(RecordId, Row)>>;
     /// Sequentially scans a table's tuples, applying a filter if specified.
     fn scan(&self, table_name: &str, filter: Option<Expression>) -> Result<Rows>;
+    /// Like scan(), but stops scanning after `limit` matching rows, e.g. for
+    /// EXISTS checks or LIMIT probes that only need the first few rows. Rows
+    /// past the limit are never read or decoded.
+    fn scan_limited(
+        &self,
+        table_name: &str,
+        filter: Option<Expression>,
+        limit: usize,
+    ) -> Result<Rows>;
+    /// Like scan(), but checks each tuple's layout against the table schema
+    /// before decoding it, to guard against schema changes that stored tuples
+    /// weren't migrated for. Tuples that don't match are decoded with the
+    /// newest earlier schema version that they match, and converted to the
+    /// current schema, matching up columns across renames: new columns get
+    /// their default value (or NULL), dropped columns are discarded, and
+    /// changed types are coerced.
+    /// Errors if a tuple matches no schema version, rather than returning
+    /// garbage. See Row::check_layout() for which changes can be detected.
+    fn scan_validated(&self, table_name: &str, filter: Option<Expression>) -> Result<Rows>;
+    /// Sequentially scans a table, including each row with the given probability
+    /// (between 0 and 1) independently, i.e. Bernoulli sampling. The random
+    /// number generator is seeded with `seed`, so the sample is reproducible.
+    /// Rows that aren't sampled are never decoded.
+    fn scan_sample(&self, table_name: &str, probability: f64, seed: u64) -> Result<Rows>;
+    /// Returns up to `limit` rows of a table following the record id `after`, or
+    /// from the start if `None`, in scan order. The last returned record id is
+    /// a cursor for the next page, allowing keyset pagination. Vacuuming the
+    /// table invalidates cursors.
+    fn scan_after(
+        &self,
+        table_name: &str,
+        after: Option<RecordId>,
+        limit: usize,
+    ) -> Result<Vec<(RecordId, Row)>>;
     /// Updates the table's tuples with record id in `rows` to the corresponding given tuple.
-    fn update(&self, table_name: &str, rows: BTreeMap<RecordId, Row>) -> Result<()>;
+    /// Returns the tuples' record ids in the order of `rows`, which change for
+    /// tuples that the update moves, e.g. because their size changed.
+    fn update(&self, table_name: &str, rows: BTreeMap<RecordId, Row>) -> Result<Vec<RecordId>>;
+    /// Inserts the row if its primary key value is new, or replaces the existing
+    /// row with that key otherwise, and returns the affected record id. Omitted
+    /// trailing columns take their default value. Errors if the table has no
+    /// primary key or the row doesn't match the schema.
+    fn upsert(&self, table_name: &str, row: Row) -> Result<RecordId>;
+    /// Returns where ORDER BY sorts NULLs when the query doesn't specify NULLS
+    /// FIRST or LAST, or None to sort them as the smallest value.
+    fn null_order(&self) -> Option<NullOrder>;
+    /// Sets where ORDER BY sorts NULLs by default for subsequent statements,
+    /// e.g. from a session variable.
+    fn set_null_order(&mut self, null_order: Option<NullOrder>);
+    /// Returns whether hash joins and aggregations intern the strings of the
+    /// keys they buffer, such that repeated strings share an allocation.
+    fn intern_strings(&self) -> bool;
+    /// Physically removes a table's deleted tuples, compacting its storage, and
+    /// returns the number of tuples reclaimed. Record ids of the remaining
+    /// tuples may change.
+    fn vacuum(&self, table_name: &str) -> Result<u64>;
 }
 
 /// Stores table schema information.
@@ -48,6 +114,14 @@ pub trait Catalog {
     /// Fetches the schema for the table corresponding to `table_name`.
     /// Returns `None` if no such table exists.
     fn get_table(&self, table_name: &str) -> Result<Option<Table>>;
+    /// Renames a column of the table corresponding to `table_name`, without
+    /// rewriting its rows. Errors if the table or column doesn't exist, or if
+    /// the new column name is taken.
+    fn rename_column(&self, table_name: &str, old: &str, new: &str) -> Result<()>;
+    /// Fetches statistics for the table corresponding to `table_name`, used
+    /// for query optimization. The row count should be cheap to compute, e.g.
+    /// Transaction::estimate_rows(). Errors if no such table exists.
+    fn table_stats(&self, table_name: &str) -> Result<TableStats>;
 
     /// Fetches the schema for the table corresponding to `table_id`.
     /// Errors if no such table exists.
@@ -56,3 +130,10 @@ pub trait Catalog {
             .ok_or_else(|| errinput!("No table with name {table_name} exists."))
     }
 }
+
+/// Table statistics, used for query optimization.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableStats {
+    /// The number of rows in the table.
+    pub row_count: u64,
+}
@@ -1,20 +1,27 @@
-use crate::common::Result;
-use crate::sql::engine::{Catalog, Session};
-use crate::sql::planner::Expression;
+use crate::common::{Error, Result};
+use crate::sql::engine::{Catalog, Session, TableStats};
+use crate::sql::planner::{Expression, NullOrder, Planner};
 use crate::storage::page::RecordId;
 use crate::storage::simple::Simple;
-use crate::storage::tuple::{Row, Rows};
-use crate::storage::{simple, Key};
+use crate::storage::tuple::{Row, Rows, Tuple};
+use crate::storage::{simple, Key, TableVersion};
 use crate::types::field::Field;
 use crate::types::Table;
-use crate::{errinput, storage};
-use std::collections::BTreeMap;
+use crate::{errdata, errinput, storage};
+use rand::Rng as _;
+use rand_chacha::ChaCha8Rng;
+use rand_core::SeedableRng as _;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// A SQL engine using local storage. This is a single-transaction,
 /// basic execution engine without concurrency support.
 pub struct Local<E: storage::Engine + 'static> {
     /// The local non-concurrent storage engine.
     pub simple: Simple<E>,
+    /// Where ORDER BY sorts NULLs by default. See Transaction::null_order().
+    null_order: Option<NullOrder>,
+    /// Whether to intern buffered key strings. See Transaction::intern_strings().
+    intern_strings: bool,
 }
 
 // Inherent implementation
@@ -23,9 +30,26 @@ impl<'a, E: storage::Engine> Local<E> {
     pub fn new(engine: E) -> Self {
         Self {
             simple: Simple::new(engine),
+            null_order: None,
+            intern_strings: false,
         }
     }
 
+    /// Sets where ORDER BY sorts NULLs when a query doesn't specify NULLS
+    /// FIRST or LAST, e.g. to match another SQL dialect.
+    pub fn with_null_order(mut self, null_order: NullOrder) -> Self {
+        self.null_order = Some(null_order);
+        self
+    }
+
+    /// Interns the strings of hash join and GROUP BY keys while buffering
+    /// them, which saves memory when the keys repeat, at the cost of a hash
+    /// lookup per buffered key.
+    pub fn with_string_interning(mut self) -> Self {
+        self.intern_strings = true;
+        self
+    }
+
     /// Creates a session which executes SQL statements.
     /// Does not outlive engine.
     pub fn session(&'a self) -> Session<'a, Self> {
@@ -39,21 +63,31 @@ impl<'a, E: storage::Engine> super::Engine<'a> for Local<E> {
 
     /// Begins a read-write transaction
     fn begin(&'a self) -> Result<Self::Transaction> {
-        Ok(Transaction::new(self.simple.begin()?))
+        Ok(Transaction::new(self.simple.begin()?, self.null_order, self.intern_strings))
     }
 }
 
 /// A SQL transaction, wrapping a simple transaction.
 pub struct Transaction<E: storage::Engine + 'static> {
     txn: simple::Transaction<E>,
+    null_order: Option<NullOrder>,
+    intern_strings: bool,
 }
 
 #[allow(dead_code)]
 impl<E: storage::Engine> Transaction<E> {
     /// Creates a new SQL transaction using the given simple transaction.
     /// This "transaction" is just a reference to the engine wrapped in a mutex.
-    fn new(txn: simple::Transaction<E>) -> Self {
-        Self { txn }
+    fn new(
+        txn: simple::Transaction<E>,
+        null_order: Option<NullOrder>,
+        intern_strings: bool,
+    ) -> Self {
+        Self {
+            txn,
+            null_order,
+            intern_strings,
+        }
     }
 }
 
@@ -66,29 +100,118 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
         Ok(())
     }
 
-    fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<Vec<RecordId>> {
+    fn estimate_rows(&self, table_name: &str) -> Result<u64> {
+        // The page headers track live tuple counts, so this is exact.
+        self.must_get_table(table_name)?;
+        self.txn.tuple_count(table_name)
+    }
+
+    fn exists(&self, table_name: &str, column_index: usize, value: &Field) -> Result<bool> {
+        let schema = self.must_get_table(table_name)?;
+        if column_index >= schema.col_count() {
+            return errinput!("column index {column_index} out of bounds");
+        }
+        let filter = Expression::Equal(
+            Expression::Column(column_index).into(),
+            Expression::Constant(value.clone()).into(),
+        );
+        Ok(self.scan(table_name, Some(filter))?.next().transpose()?.is_some())
+    }
+
+    fn get(&self, table_name: &str, rid: &RecordId) -> Result<Option<Row>> {
+        let schema = self.must_get_table(table_name)?;
+        // The table page rejects invalid and deleted record ids as invalid
+        // input, while e.g. checksum mismatches are propagated.
+        match self.txn.get(Key::new(table_name, rid)) {
+            Ok(tuple) => Ok(Some(Row::from_tuple(tuple, &schema)?)),
+            Err(Error::InvalidInput(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn insert(&self, table_name: &str, rows: Vec<Row>) -> Result<Vec<(RecordId, Row)>> {
         let schema = self.txn.fetch_table(table_name)?.unwrap();
         rows.into_iter()
-            .map(|row| self.txn.insert(table_name, row.to_tuple(&schema)?))
+            .map(|row| {
+                let rid = self.txn.insert(table_name, row.to_tuple(&schema)?)?;
+                Ok((rid, row))
+            })
             .collect()
     }
 
     fn scan(&self, table_name: &str, filter: Option<Expression>) -> Result<Rows> {
         let schema = self.txn.fetch_table(table_name)?.unwrap();
-        let unpack = move |(rid, tuple)| (rid, Row::from_tuple(tuple, &schema).unwrap());
         let iter = self.txn.scan(table_name);
 
         // No filter; just return a row iterator
         let Some(filter) = filter else {
+            let unpack = move |(rid, tuple)| (rid, Row::from_tuple(tuple, &schema).unwrap());
             return Ok(Box::new(
                 iter.map(move |result| result.and_then(|item| Ok(unpack(item)))),
             ));
         };
         // Return a row iterator that filters out tuples that do not satisfy the predicate.
+        // Only the columns referenced by the filter are decoded up front, and the
+        // remaining columns (e.g. text fields) are only decoded for matching rows.
+        let mut referenced = BTreeSet::new();
+        filter.walk(&mut |expr| {
+            if let Expression::Column(index) = expr {
+                referenced.insert(*index);
+            }
+            true
+        });
+        let (first, rest): (Vec<_>, Vec<_>) =
+            (0..schema.col_count()).partition(|index| referenced.contains(index));
         let iter = iter.filter_map(move |result| {
             result
-                .and_then(|item| {
-                    let (rid, row) = unpack(item);
+                .and_then(|(rid, tuple)| {
+                    let mut row = Row::deserialize_columns(&tuple.data, &schema, &first);
+                    match filter.evaluate(Some(&row))? {
+                        Field::Boolean(true) => {
+                            row.deserialize_into(&tuple.data, &schema, &rest);
+                            Ok(Some((rid, row)))
+                        }
+                        Field::Boolean(false) | Field::Null => Ok(None),
+                        value => errinput!("filter returned {value}, expected boolean."),
+                    }
+                })
+                .transpose()
+        });
+        Ok(Box::new(iter))
+    }
+
+    fn scan_limited(
+        &self,
+        table_name: &str,
+        filter: Option<Expression>,
+        limit: usize,
+    ) -> Result<Rows> {
+        // The scan is lazy, so it stops once the limit is reached.
+        Ok(Box::new(self.scan(table_name, filter)?.take(limit)))
+    }
+
+    fn scan_validated(&self, table_name: &str, filter: Option<Expression>) -> Result<Rows> {
+        let schema = self.must_get_table(table_name)?;
+        // Try the newest versions first, since they're likely the most common.
+        let mut versions = self.txn.fetch_table_versions(table_name)?;
+        versions.reverse();
+        // Columns missing from earlier versions take their default value.
+        let planner = Planner::new(self);
+        let defaults = schema
+            .columns()
+            .iter()
+            .map(|column| match planner.build_default(column)? {
+                Some(default) => default.evaluate(None),
+                None => Ok(Field::Null),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let iter = self.txn.scan(table_name).filter_map(move |result| {
+            result
+                .and_then(|(rid, tuple)| {
+                    let row = decode_versioned(tuple, &schema, &versions, &defaults)?;
+                    let Some(filter) = &filter else {
+                        return Ok(Some((rid, row)));
+                    };
                     match filter.evaluate(Some(&row))? {
                         Field::Boolean(true) => Ok(Some((rid, row))),
                         Field::Boolean(false) | Field::Null => Ok(None),
@@ -100,13 +223,108 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
         Ok(Box::new(iter))
     }
 
-    fn update(&self, table_name: &str, rows: BTreeMap<RecordId, Row>) -> Result<()> {
+    fn scan_sample(&self, table_name: &str, probability: f64, seed: u64) -> Result<Rows> {
+        if !(0.0..=1.0).contains(&probability) {
+            return errinput!("invalid sample probability {probability}");
+        }
         let schema = self.must_get_table(table_name)?;
-        for (rid, row) in rows {
-            self.txn
-                .update(Key::new(table_name, &rid), row.to_tuple(&schema)?)?;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let iter = self.txn.scan(table_name).filter_map(move |result| {
+            result
+                .and_then(|(rid, tuple)| match rng.gen_bool(probability) {
+                    true => Ok(Some((rid, Row::from_tuple(tuple, &schema)?))),
+                    false => Ok(None),
+                })
+                .transpose()
+        });
+        Ok(Box::new(iter))
+    }
+
+    fn scan_after(
+        &self,
+        table_name: &str,
+        after: Option<RecordId>,
+        limit: usize,
+    ) -> Result<Vec<(RecordId, Row)>> {
+        let schema = self.must_get_table(table_name)?;
+        self.txn
+            .scan_after(table_name, after.as_ref(), limit)?
+            .into_iter()
+            .map(|(rid, tuple)| Ok((rid, Row::from_tuple(tuple, &schema)?)))
+            .collect()
+    }
+
+    fn update(&self, table_name: &str, rows: BTreeMap<RecordId, Row>) -> Result<Vec<RecordId>> {
+        let schema = self.must_get_table(table_name)?;
+        // Encode every row up front, so an invalid row fails the update before
+        // anything is written, then hand the whole batch to storage at once.
+        let values = rows
+            .into_iter()
+            .map(|(rid, row)| Ok((rid, row.to_tuple(&schema)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.txn.update_batch(table_name, values)
+    }
+
+    fn upsert(&self, table_name: &str, row: Row) -> Result<RecordId> {
+        let schema = self.must_get_table(table_name)?;
+        let Some(key_index) = schema.primary_key() else {
+            return errinput!("table {table_name} has no primary key");
+        };
+        if row.size() > schema.col_count() {
+            return errinput!("expected {} values, got {}", schema.col_count(), row.size());
         }
-        Ok(())
+
+        // Omitted trailing columns take their default value, as with INSERT,
+        // and the row must match the schema before it's encoded.
+        let planner = Planner::new(self);
+        let mut values: Vec<Field> = row.into_iter().collect();
+        for column in &schema.columns()[values.len()..] {
+            let Some(default) = planner.build_default(column)? else {
+                return errinput!("column {} has no default value", column.get_name());
+            };
+            values.push(default.evaluate(None)?);
+        }
+        let row = Row::from(values);
+        schema.validate_row(&row)?;
+        let key = row.get_field(key_index)?;
+        if key.is_null() {
+            return errinput!("NULL primary key for table {table_name}");
+        }
+
+        let filter = Expression::Equal(
+            Expression::Column(key_index).into(),
+            Expression::Constant(key).into(),
+        );
+        let Some((rid, existing)) = self.scan(table_name, Some(filter))?.next().transpose()? else {
+            let (rid, _) = self.insert(table_name, vec![row])?.remove(0);
+            return Ok(rid);
+        };
+
+        // An update moves the tuple within its page if its size changes, where
+        // it may not fit, so delete and reinsert it in that case instead.
+        let tuple = row.to_tuple(&schema)?;
+        if tuple.data.len() == existing.to_tuple(&schema)?.data.len() {
+            return self.txn.update(Key::new(table_name, &rid), tuple);
+        }
+        self.txn.delete(Key::new(table_name, &rid))?;
+        self.txn.insert(table_name, tuple)
+    }
+
+    fn null_order(&self) -> Option<NullOrder> {
+        self.null_order
+    }
+
+    fn set_null_order(&mut self, null_order: Option<NullOrder>) {
+        self.null_order = null_order;
+    }
+
+    fn intern_strings(&self) -> bool {
+        self.intern_strings
+    }
+
+    fn vacuum(&self, table_name: &str) -> Result<u64> {
+        self.must_get_table(table_name)?;
+        self.txn.vacuum(table_name)
     }
 }
 
@@ -144,4 +362,49 @@ impl<E: storage::Engine> Catalog for Transaction<E> {
     fn get_table(&self, table_name: &str) -> Result<Option<Table>> {
        self.txn.fetch_table(table_name)
     }
+
+    fn rename_column(&self, table_name: &str, old: &str, new: &str) -> Result<()> {
+        self.must_get_table(table_name)?;
+        self.txn.rename_column(table_name, old, new)
+    }
+
+    fn table_stats(&self, table_name: &str) -> Result<TableStats> {
+        // The optimizer uses the same page header counts as estimate_rows().
+        let row_count = super::Transaction::estimate_rows(self, table_name)?;
+        Ok(TableStats { row_count })
+    }
+}
+
+/// Decodes a tuple with the table schema, or if the tuple doesn't have its
+/// layout, with the first of the given earlier schema versions that it has.
+/// Rows decoded with an earlier version are converted to the current schema,
+/// using the given column defaults for added columns. See
+/// Transaction::scan_validated().
+fn decode_versioned(
+    tuple: Tuple,
+    schema: &Table,
+    versions: &[TableVersion],
+    defaults: &[Field],
+) -> Result<Row> {
+    let Err(err) = Row::check_layout(&tuple.data, schema) else {
+        return Row::from_tuple(tuple, schema);
+    };
+    let Some(version) = versions
+        .iter()
+        .find(|version| Row::check_layout(&tuple.data, &version.schema).is_ok())
+    else {
+        return errdata!("tuple matches no schema version of table {}: {err}", schema.name());
+    };
+    let row = Row::from_tuple(tuple, &version.schema)?;
+    let values = schema
+        .columns()
+        .iter()
+        .zip(&version.columns)
+        .zip(defaults)
+        .map(|((column, index), default)| match index {
+            Some(index) => row.get_field(*index)?.coerce_to(column.get_data_type()),
+            None => Ok(default.clone()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Row::from(values))
 }
@@ -2,6 +2,6 @@ mod engine;
 mod local;
 mod session;
 
-pub use engine::{Catalog, Engine, Transaction};
+pub use engine::{Catalog, Engine, TableStats, Transaction};
 pub use local::Local;
 pub use session::{Session, StatementResult};
@@ -1,45 +1,315 @@
-use super::Engine;
+use super::{Catalog as _, Engine, Transaction as _};
 use crate::common::{Error, Result};
+use crate::{errdata, errinput};
 use crate::sql::execution::ExecutionResult;
-use crate::sql::parser::Parser;
-use crate::sql::planner::Plan;
+use crate::sql::parser::{ast, Parser};
+use crate::sql::planner::{BoxedNode, NullOrder, Plan};
 use crate::storage::page::RecordId;
 use crate::storage::tuple::Row;
-use crate::types::field::Label;
+use crate::types::field::{Field, Label};
+use crate::types::{DataType, Table};
+use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Undoes a statement of a script that failed, see Session::execute_script().
+enum Undo {
+    /// Drops a table created by the script.
+    DropTable(String),
+    /// Deletes rows inserted by the script, by table and record id.
+    Delete(String, Vec<RecordId>),
+    /// Re-inserts rows deleted by the script, by table and former record id.
+    Insert(String, Vec<(RecordId, Row)>),
+    /// Restores rows updated by the script to their previous values, by table,
+    /// and the rows' record ids before and after the update.
+    Update(String, Vec<(RecordId, RecordId, Row)>),
+    /// Recreates a table dropped by the script, with its rows by former
+    /// record id.
+    CreateTable(Table, Vec<(RecordId, Row)>),
+}
+
+/// Record ids of rows restored on rollback, by table and former record id.
+type Moved = BTreeMap<(String, RecordId), RecordId>;
 
 /// A SQL session, which executes raw SQL statements against a query engine.
 pub struct Session<'a, E: Engine<'a>> {
     txn: E::Transaction,
+    /// Session variables, by name. See Session::set().
+    variables: BTreeMap<String, Field>,
+    /// The engine's NULL sort default, restored when default_null_order is
+    /// reset.
+    engine_null_order: Option<NullOrder>,
 }
 
 impl<'a, E: Engine<'a>> Session<'a, E> {
     /// Creates a new session with the given query engine.
     pub fn new(engine: &'a E) -> Self {
+        let txn = engine.begin().expect("Could not begin new transaction.");
         Self {
-            txn: engine.begin().expect("Could not begin new transaction."),
+            engine_null_order: txn.null_order(),
+            variables: BTreeMap::new(),
+            txn,
+        }
+    }
+
+    /// Executes the given plans in order as a script, e.g. for migrations or
+    /// seed data, and returns their results. SELECT results are buffered, so
+    /// they reflect the state at that point in the script. Stops at the first
+    /// error, identifying the failed statement by index, and rolls back the
+    /// statements before it.
+    ///
+    /// The engine can't roll back writes, so the script undoes its statements
+    /// itself: inserted rows are deleted, deleted rows re-inserted, updated
+    /// rows restored, created tables dropped, and dropped tables recreated
+    /// with their rows. To do so, DELETE and UPDATE save the rows they write
+    /// to beforehand, and DROP TABLE the table's rows. Restored rows may get
+    /// new record ids.
+    pub fn execute_script(&mut self, plans: Vec<Plan>) -> Result<Vec<ExecutionResult>> {
+        let mut results = Vec::with_capacity(plans.len());
+        let mut undo = Vec::new();
+        for (index, plan) in plans.into_iter().enumerate() {
+            match self.execute_undoable(plan) {
+                Ok((result, action)) => {
+                    results.push(result);
+                    undo.extend(action);
+                }
+                Err(err) => {
+                    self.rollback(undo)?;
+                    return match err {
+                        Error::InvalidInput(message) => errinput!("statement {index}: {message}"),
+                        err => Err(err),
+                    };
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Executes a script statement, returning its result and how to undo it,
+    /// if it wrote anything. See execute_script().
+    fn execute_undoable(&self, plan: Plan) -> Result<(ExecutionResult, Option<Undo>)> {
+        let plan = self.optimize(plan)?;
+        // Tables that already exist, e.g. for CREATE TABLE IF NOT EXISTS,
+        // aren't dropped on rollback.
+        let created = |name: &str| -> Result<Option<Undo>> {
+            let exists = self.txn.get_table(name)?.is_some();
+            Ok((!exists).then(|| Undo::DropTable(name.to_string())))
+        };
+        let mut undo = match &plan {
+            Plan::CreateTable { schema, .. } => created(schema.name())?,
+            Plan::CreateTableAs { name, .. } => created(name)?,
+            Plan::Delete { table, source } => {
+                let rows = self.written_rows(table, source)?;
+                Some(Undo::Insert(table.clone(), rows.into_iter().collect()))
+            }
+            Plan::DropTable { table, .. } => match self.txn.get_table(table)? {
+                Some(schema) => {
+                    let rows = self.txn.scan_validated(table, None)?.try_collect()?;
+                    Some(Undo::CreateTable(schema, rows))
+                }
+                None => None,
+            },
+            Plan::Insert { .. } | Plan::Select(_) | Plan::Update { .. } => None,
+        };
+        // Inserted and updated rows are undone by their record ids after the
+        // statement, since updates may move rows.
+        let inserted = match &plan {
+            Plan::Insert { table, .. } => Some(table.name().to_string()),
+            _ => None,
+        };
+        let updated = match &plan {
+            Plan::Update { table, source, .. } => {
+                Some((table.name().to_string(), self.written_rows(table.name(), source)?))
+            }
+            _ => None,
+        };
+
+        let result = plan.execute(&self.txn).and_then(Self::buffer_rows)?;
+        match (&result, inserted, updated) {
+            (ExecutionResult::Insert { record_ids, .. }, Some(table), _) => {
+                undo = Some(Undo::Delete(table, record_ids.clone()));
+            }
+            (ExecutionResult::Update { record_ids, .. }, _, Some((table, mut rows))) => {
+                let rows = record_ids
+                    .iter()
+                    .filter_map(|(id, new_id)| Some((id.clone(), new_id.clone(), rows.remove(id)?)))
+                    .collect();
+                undo = Some(Undo::Update(table, rows));
+            }
+            _ => {}
+        }
+        Ok((result, undo))
+    }
+
+    /// Returns the current rows that a DELETE or UPDATE source will write to,
+    /// by record id, by executing the source ahead of the statement. Plans are
+    /// deterministic given the same table contents (SAMPLE seeds are chosen
+    /// when planning), so the statement writes to the same rows.
+    fn written_rows(&self, table: &str, source: &BoxedNode) -> Result<BTreeMap<RecordId, Row>> {
+        let ExecutionResult::Select { rows, .. } =
+            Plan::Select(source.clone()).execute(&self.txn)?
+        else {
+            return errdata!("expected select result");
+        };
+        let mut written = BTreeMap::new();
+        for item in rows {
+            let (id, _) = item?;
+            if written.contains_key(&id) {
+                continue;
+            }
+            if let Some(row) = self.txn.get(table, &id)? {
+                written.insert(id, row);
+            }
+        }
+        Ok(written)
+    }
+
+    /// Undoes script statements, latest first. See execute_script().
+    fn rollback(&self, undo: Vec<Undo>) -> Result<()> {
+        // Restored rows get new record ids, which the undo actions of earlier
+        // statements must use instead.
+        let mut moved = Moved::new();
+        let locate = |moved: &Moved, table: &str, id: RecordId| {
+            moved.get(&(table.to_string(), id.clone())).cloned().unwrap_or(id)
+        };
+        for action in undo.into_iter().rev() {
+            match action {
+                Undo::DropTable(name) => {
+                    self.txn.drop_table(&name, false)?;
+                    // The ids don't apply to an earlier table of the same name.
+                    moved.retain(|(table, _), _| table != &name);
+                }
+                Undo::Delete(table, ids) => {
+                    let ids = ids.into_iter().map(|id| locate(&moved, &table, id)).collect_vec();
+                    self.txn.delete(&table, &ids)?
+                }
+                Undo::Insert(table, rows) => self.reinsert(&table, rows, &mut moved)?,
+                Undo::Update(table, rows) => {
+                    // Update the rows where they are now, by their current
+                    // record id, and record where they end up.
+                    let rows: BTreeMap<_, _> = rows
+                        .into_iter()
+                        .map(|(id, new_id, row)| (locate(&moved, &table, new_id), (id, row)))
+                        .collect();
+                    let (ids, rows): (Vec<_>, BTreeMap<_, _>) =
+                        rows.into_iter().map(|(current, (id, row))| (id, (current, row))).unzip();
+                    for (id, restored) in ids.into_iter().zip(self.txn.update(&table, rows)?) {
+                        moved.insert((table.clone(), id), restored);
+                    }
+                }
+                Undo::CreateTable(schema, rows) => {
+                    let table = schema.name().to_string();
+                    self.txn.create_table(schema)?;
+                    self.reinsert(&table, rows, &mut moved)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-inserts rows on rollback, recording their new record ids.
+    fn reinsert(&self, table: &str, rows: Vec<(RecordId, Row)>, moved: &mut Moved) -> Result<()> {
+        let (ids, rows): (Vec<_>, Vec<_>) = rows.into_iter().unzip();
+        for (id, (new_id, _)) in ids.into_iter().zip(self.txn.insert(table, rows)?) {
+            moved.insert((table.to_string(), id), new_id);
+        }
+        Ok(())
+    }
+
+    /// Buffers the rows of a SELECT result, returning any errors.
+    fn buffer_rows(result: ExecutionResult) -> Result<ExecutionResult> {
+        let ExecutionResult::Select {
+            rows,
+            columns,
+            types,
+        } = result
+        else {
+            return Ok(result);
+        };
+        let rows: Vec<_> = rows.try_collect()?;
+        Ok(ExecutionResult::Select {
+            rows: Box::new(rows.into_iter().map(Ok)),
+            columns,
+            types,
+        })
+    }
+
+    /// Sets a session variable, which applies to subsequent statements in the
+    /// session. Setting a variable to NULL resets it to the engine default.
+    /// The supported variables are:
+    ///
+    /// * default_null_order: 'first' or 'last', where ORDER BY sorts NULLs
+    ///   when a query doesn't specify NULLS FIRST or LAST.
+    /// * reorder_joins: TRUE or FALSE (default), whether inner hash joins are
+    ///   reordered to build on their smaller input. This changes the order in
+    ///   which they emit rows. See Plan::reorder_joins().
+    pub fn set(&mut self, key: &str, value: Field) -> Result<()> {
+        match (key, &value) {
+            ("default_null_order", Field::Null) => self.txn.set_null_order(self.engine_null_order),
+            ("default_null_order", Field::String(s)) if s.eq_ignore_ascii_case("first") => {
+                self.txn.set_null_order(Some(NullOrder::First))
+            }
+            ("default_null_order", Field::String(s)) if s.eq_ignore_ascii_case("last") => {
+                self.txn.set_null_order(Some(NullOrder::Last))
+            }
+            ("default_null_order", value) => {
+                return errinput!("invalid default_null_order {value}, must be 'first' or 'last'")
+            }
+            ("reorder_joins", Field::Null | Field::Boolean(_)) => {}
+            ("reorder_joins", value) => {
+                return errinput!("invalid reorder_joins {value}, must be TRUE or FALSE")
+            }
+            (key, _) => return errinput!("unknown session variable {key}"),
+        }
+        match value {
+            Field::Null => self.variables.remove(key),
+            value => self.variables.insert(key.to_string(), value),
+        };
+        Ok(())
+    }
+
+    /// Returns the value of a session variable, or None if it isn't set.
+    pub fn get(&self, key: &str) -> Option<&Field> {
+        self.variables.get(key)
+    }
+
+    /// Optimizes a plan, reordering joins if the reorder_joins variable is set.
+    fn optimize(&self, plan: Plan) -> Result<Plan> {
+        let plan = plan.optimize()?;
+        match self.get("reorder_joins") {
+            Some(Field::Boolean(true)) => plan.reorder_joins(&self.txn),
+            _ => Ok(plan),
         }
     }
 
     /// Executes a raw SQL statement.
     pub fn execute(&mut self, statement: &str) -> Result<StatementResult> {
-        Plan::build(Parser::new(statement).parse()?, &self.txn)?
-            .optimize()?
-            .execute(&self.txn)?
-            .try_into()
+        let statement = Parser::new(statement).parse()?;
+        if let ast::Statement::Explain(statement) = statement {
+            let plan = self.optimize(Plan::build(*statement, &self.txn)?)?;
+            return Ok(StatementResult::Explain(plan.explain(&self.txn)?));
+        }
+        let plan = self.optimize(Plan::build(statement, &self.txn)?)?;
+        plan.execute(&self.txn)?.try_into()
     }
 }
 
 /// A session statement result. Sent across the wire to SQL clients.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum StatementResult {
-    Explain(Plan),
+    /// The rendered plan, with estimated row counts.
+    Explain(String),
     CreateTable {
         name: String,
     },
+    CreateTableAs {
+        name: String,
+        count: u64,
+    },
     DropTable {
         name: String,
         existed: bool,
+        row_count: u64,
     },
     Delete {
         count: u64,
@@ -47,12 +317,14 @@ pub enum StatementResult {
     Insert {
         count: u64,
         record_ids: Vec<RecordId>,
+        rows: Vec<Row>,
     },
     Update {
         count: u64,
     },
     Select {
         columns: Vec<Label>,
+        types: Vec<DataType>,
         rows: Vec<Row>,
     },
 }
@@ -63,14 +335,36 @@ impl TryFrom<ExecutionResult> for StatementResult {
     fn try_from(result: ExecutionResult) -> Result<Self> {
         Ok(match result {
             ExecutionResult::CreateTable { name } => Self::CreateTable { name },
-            ExecutionResult::DropTable { name, existed } => Self::DropTable { name, existed },
+            ExecutionResult::CreateTableAs { name, count } => Self::CreateTableAs { name, count },
+            ExecutionResult::DropTable {
+                name,
+                existed,
+                row_count,
+            } => Self::DropTable {
+                name,
+                existed,
+                row_count,
+            },
             ExecutionResult::Delete { count } => Self::Delete { count },
-            ExecutionResult::Insert { count, record_ids } => Self::Insert { count, record_ids },
-            ExecutionResult::Update { count } => Self::Update { count },
-            ExecutionResult::Select { rows, columns } => {
+            ExecutionResult::Insert {
+                count,
+                record_ids,
+                rows,
+            } => Self::Insert {
+                count,
+                record_ids,
+                rows,
+            },
+            ExecutionResult::Update { count, .. } => Self::Update { count },
+            ExecutionResult::Select {
+                rows,
+                columns,
+                types,
+            } => {
                 let rows: Result<Vec<_>> = rows.into_iter().map(|r| Ok(r?.1)).collect();
                 Self::Select {
                     columns,
+                    types,
                     rows: rows?,
                 }
             }
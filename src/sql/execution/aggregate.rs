@@ -1,25 +1,383 @@
 use crate::common::Result;
+use crate::errinput;
 use crate::sql::planner::{Aggregate, Expression};
 
-use crate::storage::page::INVALID_RID;
+use crate::storage::page::{RecordId, INVALID_RID};
 use crate::storage::tuple::{Row, Rows};
-use crate::types::field::Field;
+use crate::types::field::{Field, Interner};
 use itertools::Itertools as _;
-use std::collections::BTreeMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+/// The default maximum number of GROUP BY buckets kept in memory.
+pub const MAX_BUCKETS: usize = 100_000;
+
+/// The number of partitions that rows are spilled to when exceeding the
+/// maximum number of in-memory buckets.
+const SPILL_PARTITIONS: usize = 16;
 
 /// Aggregates row values from the source according to the aggregates, using the
 /// group_by expressions as buckets. Emits rows with group_by buckets then
-/// aggregates in the given order.
+/// aggregates in the given order. Spills to disk beyond MAX_BUCKETS buckets.
+/// If intern is true, the buckets' strings are interned, such that strings
+/// repeated across buckets share an allocation, e.g. a for GROUP BY a, b.
 pub fn aggregate(
-    mut source: Rows,
+    source: Rows,
+    group_by: Vec<Expression>,
+    aggregates: Vec<Aggregate>,
+    intern: bool,
+) -> Result<Rows> {
+    aggregate_with_limit(source, group_by, aggregates, MAX_BUCKETS, intern)
+}
+
+/// Like aggregate(), but keeps at most max_buckets buckets in memory. Once the
+/// limit is reached, rows belonging to other buckets are partitioned by a hash
+/// of their bucket into temporary files, and each partition is aggregated
+/// separately afterwards (spilling again if needed). Buckets are then emitted
+/// in order within the in-memory buckets and within each partition, but not
+/// across them.
+pub fn aggregate_with_limit(
+    source: Rows,
+    group_by: Vec<Expression>,
+    aggregates: Vec<Aggregate>,
+    max_buckets: usize,
+    intern: bool,
+) -> Result<Rows> {
+    let rows = Box::new(source.map(|item| item.map(|(_, row)| row)));
+    aggregate_rows(rows, &group_by, &aggregates, max_buckets.max(1), 0, intern)
+}
+
+/// Like aggregate(), but aggregates the source rows once for each grouping set,
+/// e.g. for GROUP BY ROLLUP(a, b). A grouping set lists the indexes of the
+/// group_by expressions it groups by, and the others are emitted as NULL. Rows
+/// are emitted for each grouping set in turn.
+pub fn aggregate_grouping_sets(
+    source: Rows,
     group_by: Vec<Expression>,
     aggregates: Vec<Aggregate>,
+    grouping_sets: Vec<Vec<usize>>,
+    intern: bool,
+) -> Result<Rows> {
+    let sets = grouping_sets;
+    aggregate_grouping_sets_with_limit(source, group_by, aggregates, sets, MAX_BUCKETS, intern)
+}
+
+/// Like aggregate_grouping_sets(), but keeps at most max_buckets buckets in
+/// memory across all grouping sets. The sets are aggregated in a single pass
+/// over the source, and once the limit is reached, rows belonging to other
+/// buckets are spilled like in aggregate_with_limit(), partitioned separately
+/// for each set. A set's spilled partitions are aggregated after its in-memory
+/// buckets have been emitted.
+pub fn aggregate_grouping_sets_with_limit(
+    source: Rows,
+    group_by: Vec<Expression>,
+    aggregates: Vec<Aggregate>,
+    grouping_sets: Vec<Vec<usize>>,
+    max_buckets: usize,
+    intern: bool,
+) -> Result<Rows> {
+    let max_buckets = max_buckets.max(1);
+    let mut sets = grouping_sets
+        .into_iter()
+        .map(|set| {
+            let group_by = set.iter().map(|&index| group_by[index].clone()).collect();
+            let aggregator = Aggregator::new(group_by, aggregates.clone(), intern);
+            (set, aggregator, Partitions::new(0))
+        })
+        .collect_vec();
+    let mut buckets = 0;
+    for item in source {
+        let row = item?.1;
+        for (_, aggregator, partitions) in &mut sets {
+            let bucket = aggregator.bucket(&row)?;
+            if !aggregator.buckets.contains_key(&bucket) {
+                // A set without buckets always gets one, such that its
+                // in-memory rows aren't empty if it spills. Otherwise, an
+                // empty grand total set would emit a row of its own.
+                if buckets >= max_buckets && !aggregator.buckets.is_empty() {
+                    partitions.write(&bucket, &row)?;
+                    continue;
+                }
+                buckets += 1;
+            }
+            aggregator.add(bucket, &row)?;
+        }
+    }
+
+    let width = group_by.len();
+    let mut rows: Rows = Box::new(std::iter::empty());
+    for (set, aggregator, partitions) in sets {
+        let grouping_set = Some((set, width));
+        let pending = partitions.into_pending(&aggregator.group_by, grouping_set.clone())?;
+        let set_rows = Deferred {
+            pending,
+            current: Some((aggregator.into_rows()?, grouping_set)),
+            aggregates: aggregates.clone(),
+            max_buckets,
+            intern,
+        };
+        rows = Box::new(rows.chain(set_rows));
+    }
+    Ok(rows)
+}
+
+/// Aggregates the given rows, spilling beyond max_buckets buckets. The depth
+/// is the number of times the rows have already been spilled.
+fn aggregate_rows(
+    rows: Box<dyn Iterator<Item = Result<Row>>>,
+    group_by: &[Expression],
+    aggregates: &[Aggregate],
+    max_buckets: usize,
+    depth: usize,
+    intern: bool,
 ) -> Result<Rows> {
-    let mut aggregator = Aggregator::new(group_by, aggregates);
-    while let Some((_, row)) = source.next().transpose()? {
-        aggregator.add(row)?;
+    let mut aggregator = Aggregator::new(group_by.to_vec(), aggregates.to_vec(), intern);
+    let mut partitions = Partitions::new(depth);
+    for row in rows {
+        let row = row?;
+        let bucket = aggregator.bucket(&row)?;
+        if aggregator.buckets.len() >= max_buckets && !aggregator.buckets.contains_key(&bucket) {
+            partitions.write(&bucket, &row)?;
+            continue;
+        }
+        aggregator.add(bucket, &row)?;
+    }
+
+    // A spilled partition's buckets are disjoint from the in-memory buckets
+    // and the other partitions' buckets, so they can be aggregated separately.
+    // Every partition contains fewer buckets than this one, since the
+    // in-memory buckets weren't spilled, so this terminates.
+    let partitions = Deferred {
+        pending: partitions.into_pending(group_by, None)?,
+        current: None,
+        aggregates: aggregates.to_vec(),
+        max_buckets,
+        intern,
+    };
+    Ok(Box::new(aggregator.into_rows()?.chain(partitions)))
+}
+
+/// Rows spilled to temporary files, partitioned by a hash of their bucket.
+struct Partitions {
+    spills: Vec<Option<Spill>>,
+    /// The number of times the rows had already been spilled before.
+    depth: usize,
+}
+
+impl Partitions {
+    fn new(depth: usize) -> Self {
+        Self {
+            spills: (0..SPILL_PARTITIONS).map(|_| None).collect(),
+            depth,
+        }
+    }
+
+    /// Spills a row to its bucket's partition.
+    fn write(&mut self, bucket: &[Field], row: &Row) -> Result<()> {
+        // Salt the hash with the depth, since all rows spilled again at this
+        // depth hashed to the same partition at the previous one.
+        let mut hasher = DefaultHasher::new();
+        self.depth.hash(&mut hasher);
+        bucket.hash(&mut hasher);
+        let spill = &mut self.spills[hasher.finish() as usize % SPILL_PARTITIONS];
+        if spill.is_none() {
+            *spill = Some(Spill::new()?);
+        }
+        spill.as_mut().unwrap().write(row)
+    }
+
+    /// Returns the non-empty partitions, to be aggregated by a Deferred
+    /// iterator using the given group_by expressions.
+    fn into_pending(
+        self,
+        group_by: &[Expression],
+        grouping_set: Option<GroupingSet>,
+    ) -> Result<VecDeque<Pending>> {
+        self.spills
+            .into_iter()
+            .flatten()
+            .map(|spill| {
+                Ok(Pending {
+                    rows: spill.into_reader()?,
+                    group_by: group_by.to_vec(),
+                    depth: self.depth + 1,
+                    grouping_set: grouping_set.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Spilled rows that are yet to be aggregated by a Deferred iterator.
+#[derive(Clone)]
+struct Pending {
+    rows: SpillReader,
+    group_by: Vec<Expression>,
+    /// The number of times the rows have been spilled. See aggregate_rows().
+    depth: usize,
+    grouping_set: Option<GroupingSet>,
+}
+
+/// For grouping sets, the group_by positions of the set's bucket values and
+/// the total number of group_by expressions.
+type GroupingSet = (Vec<usize>, usize);
+
+/// Aggregates spilled rows in turn, emitting each one's aggregates before
+/// aggregating the next one, such that at most one of them has its buckets in
+/// memory at a time.
+#[derive(Clone)]
+struct Deferred {
+    pending: VecDeque<Pending>,
+    current: Option<(Rows, Option<GroupingSet>)>,
+    aggregates: Vec<Aggregate>,
+    max_buckets: usize,
+    intern: bool,
+}
+
+impl Iterator for Deferred {
+    type Item = Result<(RecordId, Row)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((rows, grouping_set)) = &mut self.current {
+                let Some(item) = rows.next() else {
+                    self.current = None;
+                    continue;
+                };
+                let Some((set, width)) = grouping_set else {
+                    return Some(item);
+                };
+                // Place the set's bucket values at their group_by positions,
+                // with NULL for the group_by expressions that aren't in it.
+                return Some(item.map(|(rid, row)| {
+                    let mut values = row.into_iter();
+                    let mut row = vec![Field::Null; *width];
+                    for (&index, value) in set.iter().zip(values.by_ref()) {
+                        row[index] = value;
+                    }
+                    row.extend(values);
+                    (rid, Row::from(row))
+                }));
+            }
+            let pending = self.pending.pop_front()?;
+            let rows = aggregate_rows(
+                Box::new(pending.rows),
+                &pending.group_by,
+                &self.aggregates,
+                self.max_buckets,
+                pending.depth,
+                self.intern,
+            );
+            match rows {
+                Ok(rows) => self.current = Some((rows, pending.grouping_set)),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// The number of spill files created by the current thread.
+    static SPILLS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns the number of temporary files the current thread has spilled rows
+/// to, e.g. to check whether an aggregation fit in memory.
+pub fn spill_count() -> usize {
+    SPILLS.with(Cell::get)
+}
+
+/// Rows spilled to a temporary file, which is removed when dropped.
+struct Spill {
+    writer: BufWriter<File>,
+    count: usize,
+}
+
+impl Spill {
+    fn new() -> Result<Self> {
+        SPILLS.with(|spills| spills.set(spills.get() + 1));
+        Ok(Self {
+            writer: BufWriter::new(tempfile::tempfile()?),
+            count: 0,
+        })
+    }
+
+    /// Appends a row to the file.
+    fn write(&mut self, row: &Row) -> Result<()> {
+        bincode::serialize_into(&mut self.writer, row)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Reads back the spilled rows, in the order they were written.
+    fn into_reader(self) -> Result<SpillReader> {
+        let file = self.writer.into_inner().map_err(|err| err.into_error())?;
+        let file = FileReader {
+            file: Arc::new(Mutex::new(file)),
+            offset: 0,
+        };
+        Ok(SpillReader {
+            reader: BufReader::new(file),
+            remaining: self.count,
+        })
+    }
+}
+
+/// Reads spilled rows. Clones read the remaining rows independently of the
+/// original, like other row iterators.
+struct SpillReader {
+    reader: BufReader<FileReader>,
+    remaining: usize,
+}
+
+impl Clone for SpillReader {
+    fn clone(&self) -> Self {
+        // The buffered bytes haven't been consumed yet.
+        let offset = self.reader.get_ref().offset - self.reader.buffer().len() as u64;
+        let file = FileReader {
+            file: self.reader.get_ref().file.clone(),
+            offset,
+        };
+        Self {
+            reader: BufReader::new(file),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl Iterator for SpillReader {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(bincode::deserialize_from(&mut self.reader).map_err(Into::into))
+    }
+}
+
+/// Reads a file shared between spill readers from its own offset.
+struct FileReader {
+    file: Arc<Mutex<File>>,
+    offset: u64,
+}
+
+impl Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|err| std::io::Error::other(err.to_string()))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let n = file.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
     }
-    aggregator.into_rows()
 }
 
 /// Computes bucketed aggregates for rows.
@@ -63,11 +421,14 @@ struct Aggregator {
     //     Expression::Column("age")         // AVG(age)
     // ]
     expressions: Vec<Expression>,
+
+    /// Interns the strings of new buckets, if enabled.
+    interner: Option<Interner>,
 }
 
 impl Aggregator {
     /// Creates a new aggregator for the given GROUP BY buckets and aggregates.
-    fn new(group_by: Vec<Expression>, aggregates: Vec<Aggregate>) -> Self {
+    fn new(group_by: Vec<Expression>, aggregates: Vec<Aggregate>, intern: bool) -> Self {
         use Aggregate::*;
         
         // Create new accumulators
@@ -77,7 +438,8 @@ impl Aggregator {
         let expressions = aggregates
             .into_iter()
             .map(|aggregate| match aggregate {
-                Average(expr) | Count(expr) | Max(expr) | Min(expr) | Sum(expr) => expr,
+                ArrayAgg(expr) | Average(expr) | Count(expr) | Max(expr) | Min(expr)
+                | PercentileCont(expr, _) | Sum(expr) => expr,
             })
             .collect();
         
@@ -86,19 +448,22 @@ impl Aggregator {
             empty: accumulators,
             group_by,
             expressions,
+            interner: intern.then(Interner::new),
         }
     }
 
-    /// Adds a row to the aggregator.
-    fn add(&mut self, row: Row) -> Result<()> {
-        // Compute the bucket value
+    /// Computes the bucket value of a row.
+    fn bucket(&self, row: &Row) -> Result<Vec<Field>> {
         // Get the "group by" values related to the aggregation
         // For example, if we group by major, then bucket might be "Computer Science", "Math"
-        let bucket: Vec<Field> = self
-            .group_by
+        self.group_by
             .iter()
-            .map(|expr| expr.evaluate(Some(&row)))
-            .try_collect()?;
+            .map(|expr| expr.evaluate(Some(row)))
+            .try_collect()
+    }
+
+    /// Adds a row to the aggregator, in the given bucket (see bucket()).
+    fn add(&mut self, bucket: Vec<Field>, row: &Row) -> Result<()> {
 
         // Compute and accumulate the input values.
         //
@@ -107,12 +472,20 @@ impl Aggregator {
         // have to update each accumulator with the result of evaluating the accumulator's
         // corresponding expression on the row.
         
+        // Intern the strings of new buckets, if enabled.
+        let bucket = match &mut self.interner {
+            Some(interner) if !self.buckets.contains_key(&bucket) => {
+                bucket.into_iter().map(|value| interner.intern(value)).collect()
+            }
+            _ => bucket,
+        };
+
         // Get or create the accumulators for this bucket
         let accumulators = self.buckets.entry(bucket).or_insert_with(|| self.empty.clone());
         
         // For each expression, evaluate it and feed the result to the corresponding accumulator
         for (i, expression) in self.expressions.iter().enumerate(){
-            let value = expression.evaluate(Some(&row))?;
+            let value = expression.evaluate(Some(row))?;
             accumulators[i].add(value)?;
         }
 
@@ -158,10 +531,13 @@ impl Aggregator {
 /// to keep these in a vector (could use boxed trait objects too).
 #[derive(Clone)]
 enum Accumulator {
+    Array(Vec<Field>),
     Average { count: i32, sum: Field },
     Count(i32),
     Max(Option<Field>),
     Min(Option<Field>),
+    /// Buffers the non-NULL values, which are sorted when computing the value.
+    Percentile { values: Vec<f64>, fraction: f64 },
     Sum(Option<Field>),
 }
 
@@ -169,6 +545,7 @@ impl Accumulator {
     /// Creates a new accumulator from an aggregate kind.
     fn new(aggregate: &Aggregate) -> Self {
         match aggregate {
+            &Aggregate::ArrayAgg(_) => Self::Array(Vec::new()),
             &Aggregate::Average(_) => Self::Average{
                 count: 0,
                 sum: Field::Integer(0)
@@ -176,6 +553,10 @@ impl Accumulator {
             &Aggregate::Count(_) => Self::Count(0),
             &Aggregate::Max(_) => Self::Max(None),
             &Aggregate::Min(_) => Self::Min(None),
+            &Aggregate::PercentileCont(_, fraction) => Self::Percentile {
+                values: Vec::new(),
+                fraction,
+            },
             &Aggregate::Sum(_) => Self::Sum(None),
         }
     }
@@ -220,6 +601,10 @@ impl Accumulator {
     /// ```
     fn add(&mut self, value: Field) -> Result<()> {
         match self {
+            // It is an Array accumulator, which keeps all values (including
+            // NULLs) in input order
+            Accumulator::Array(values) => values.push(value),
+
             // It is an Average accumulator            
             Accumulator::Average { count, sum } => {
                 *sum = sum.checked_add(&value)?;
@@ -255,6 +640,14 @@ impl Accumulator {
                 *min = Some(value)
             },
             
+            // It is a Percentile accumulator, which ignores NULLs
+            Accumulator::Percentile { values, .. } => match value {
+                Field::Null => {}
+                Field::Integer(i) => values.push(i as f64),
+                Field::Float(f) => values.push(f as f64),
+                value => return errinput!("can't compute percentile of {value}"),
+            },
+
             // It is a Sum accumulator
             Accumulator::Sum(Some(sum)) => *sum = sum.checked_add(&value)?,
             Accumulator::Sum(sum @ None) => *sum = Some(Field::Integer(0).checked_add(&value)?),
@@ -266,6 +659,8 @@ impl Accumulator {
     /// Returns the aggregate value.
     fn value(self) -> Result<Field> {
         match self {
+            Accumulator::Array(values) if values.is_empty() => Ok(Field::Null),
+            Accumulator::Array(values) => Ok(Field::Array(values)),
             Accumulator::Average { count, sum } => {
                 if count == 0 {
                     Ok(Field::Null)
@@ -278,6 +673,20 @@ impl Accumulator {
             Accumulator::Count(count) => Ok(Field::Integer(count)),
             Accumulator::Max(field) => Ok(field.unwrap_or(Field::Null)),
             Accumulator::Min(field) => Ok(field.unwrap_or(Field::Null)),
+            Accumulator::Percentile { values, .. } if values.is_empty() => Ok(Field::Null),
+            Accumulator::Percentile {
+                mut values,
+                fraction,
+            } => {
+                // Interpolate between the values around the fractional
+                // position. NaNs sort last.
+                values.sort_by(f64::total_cmp);
+                let position = fraction * (values.len() - 1) as f64;
+                let lower = values[position.floor() as usize];
+                let upper = values[position.ceil() as usize];
+                let value = lower + (upper - lower) * position.fract();
+                Ok(Field::Float(value as f32))
+            }
             Accumulator::Sum(field) => Ok(field.unwrap_or(Field::Null))
         }
     }
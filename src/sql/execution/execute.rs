@@ -1,10 +1,14 @@
 use crate::common::Result;
+use crate::{errdata, errinput};
 use crate::sql::engine::{Catalog, Transaction};
-use crate::sql::execution::{aggregate, join, source, transform};
-use crate::sql::planner::{BoxedNode, Node, Plan};
-use crate::storage::page::RecordId;
-use crate::storage::tuple::Rows;
-use crate::types::field::Label;
+use crate::sql::execution::{aggregate, join, source, subquery, transform, CancelToken};
+use crate::sql::planner::{BoxedNode, Expression, Node, Plan};
+use crate::storage::page::{RecordId, INVALID_RID};
+use crate::storage::tuple::{Row, Rows};
+use crate::types::field::{Field, Label};
+use crate::types::{Column, DataType, Table};
+use itertools::Itertools as _;
+use std::collections::BTreeMap;
 
 use super::write;
 
@@ -14,17 +18,20 @@ use super::write;
 /// implements the Catalog trait, to separate the concerns of `catalog` to planning
 /// and `txn` to execution.
 ///
-/// Hint: `execute(source, txn)?` returns a `Rows` source iterator, which you might
+/// Hint: `execute(source, txn, cancel)?` returns a `Rows` source iterator, which you might
 /// need for some of the plans. (The `execute` method actually returns `Result<Rows>`,
 /// but the `?` operator will automatically unwrap the result if it's an `Ok(Rows)`
 /// value. Otherwise, the method will immediately exit and return the `Err()` value
 /// returned from `execute`.) For more information about the try-operator `?`, see:
 /// - https://doc.rust-lang.org/rust-by-example/std/result/question_mark.html
 /// - https://stackoverflow.com/questions/42917566/what-is-this-question-mark-operator-about
+///
+/// Execution errors with Error::Cancelled once the cancel token is cancelled.
 pub fn execute_plan(
     plan: Plan,
     catalog: &impl Catalog,
     txn: &impl Transaction,
+    cancel: &CancelToken,
 ) -> Result<ExecutionResult> {
     Ok(match plan {
         // Creates a table with the given schema, returning a `CreateTable` execution
@@ -32,18 +39,38 @@ pub fn execute_plan(
         //
         // You'll need to handle the case when `Catalog::create_table` returns an Error
         // (hint: use the ? operator).
-        Plan::CreateTable { schema } => {
+        Plan::CreateTable {
+            schema,
+            if_not_exists,
+        } => {
             let name = schema.name().to_string();
-            catalog.create_table(schema)?;
+            // With IF NOT EXISTS, an existing table is left as is, even if its
+            // schema differs from the given one.
+            if !if_not_exists || catalog.get_table(&name)?.is_none() {
+                catalog.create_table(schema)?;
+            }
             ExecutionResult::CreateTable { name }
         }
+        // Creates a table with a schema inferred from the source rows, and
+        // inserts the rows into it.
+        Plan::CreateTableAs { name, source } => {
+            let labels = (0..source.columns()).map(|i| source.column_label(i)).collect_vec();
+            let types = (0..source.columns()).map(|i| source.column_type(i)).collect_vec();
+            let rows: Vec<Row> =
+                execute(source, txn, cancel)?.map_ok(|(_, row)| row).try_collect()?;
+            let (schema, rows) = infer_table(&name, &labels, &types, rows)?;
+            catalog.create_table(schema.clone())?;
+            let rows = Box::new(rows.into_iter().map(|row| Ok((INVALID_RID, row))));
+            let count = write::insert(txn, schema, rows)?.len() as u64;
+            ExecutionResult::CreateTableAs { name, count }
+        }
         // Deletes the rows emitted from the source node from the given table.
         //
         // Hint: you'll need to use the `write::delete` method that you also have implement,
         // which returns the number of rows that were deleted if successful (another hint:
         // use the ? operator. Last reminder!).
         Plan::Delete { table, source } => {
-            let result_rows = execute(source, txn)?;
+            let result_rows = execute(source, txn, cancel)?;
             let count = write::delete(txn, table, result_rows)?;
             ExecutionResult::Delete { count }
         }
@@ -51,6 +78,11 @@ pub fn execute_plan(
         //
         // Returns an error if the table does not exist unless `if_exists` is true.
         Plan::DropTable { table, if_exists } => {
+            // Count the rows before they're gone, from storage metadata.
+            let row_count = match catalog.get_table(&table)? {
+                Some(_) => txn.estimate_rows(&table)?,
+                None => 0,
+            };
             let existed = catalog.drop_table(&table, if_exists)?;
             
             if !existed && !if_exists{
@@ -60,6 +92,7 @@ pub fn execute_plan(
             ExecutionResult::DropTable {
                 name: table,
                 existed,
+                row_count,
             }
         }
         // Inserts the rows emitted from the source node into the given table.
@@ -68,24 +101,44 @@ pub fn execute_plan(
         // which returns the record id's corresponding to the rows that were inserted into
         // the table.
         Plan::Insert { table, source } => {
-            let result_rows = execute(source, txn)?;
-            let record_ids = write::insert(txn, table, result_rows)?;
+            // Check VALUES against the table schema, and convert query rows
+            // to the column types. VALUES is checked like any other node.
+            let columns = source.columns();
+            let result_rows = match *source.inner {
+                Node::Values { rows } => {
+                    cancel.check()?;
+                    check_rows(source::values(rows, Some(&table))?, columns, cancel)
+                }
+                _ => transform::coerce(execute(source, txn, cancel)?, &table),
+            };
+            let (record_ids, rows): (Vec<_>, Vec<_>) =
+                write::insert(txn, table, result_rows)?.into_iter().unzip();
             let count = record_ids.len() as u64;
-            ExecutionResult::Insert { count, record_ids }
+            ExecutionResult::Insert {
+                count,
+                record_ids,
+                rows,
+            }
         }
         // Obtains a `Rows` iterator of the emitted rows and the emitted rows' corresponding
-        // column labels from the root node, packaging the two as an `ExecutionResult::Select`.
+        // column labels and types from the root node, packaging them as an `ExecutionResult::Select`.
         //
         // Hint: the i'th column label of a row emitted from the root can be obtained by calling
         // `root.column_label(i)`.
         Plan::Select(root) => {
             let column_count = root.columns();
             let mut column_labels = Vec::new();
+            let mut column_types = Vec::new();
             for i in 0..column_count{
                 column_labels.push(root.column_label(i));
+                column_types.push(root.column_type(i));
+            }
+            let result_rows = execute(root, txn, cancel)?;
+            ExecutionResult::Select {
+                rows: result_rows,
+                columns: column_labels,
+                types: column_types,
             }
-            let result_rows = execute(root, txn)?;
-            ExecutionResult::Select { rows: result_rows, columns: column_labels }
         }
         // Updates the rows emitted from the source node in the given table.
         //
@@ -96,9 +149,12 @@ pub fn execute_plan(
             source,
             expressions,
         } => {
-            let result_rows = execute(source, txn)?;
-            let count = write::update(txn, table.name().to_string(), result_rows, expressions)?;
-            ExecutionResult::Update { count }
+            let result_rows = execute(source, txn, cancel)?;
+            let record_ids = write::update(txn, &table, result_rows, expressions)?;
+            ExecutionResult::Update {
+                count: record_ids.len() as u64,
+                record_ids,
+            }
         }
     })
 }
@@ -108,20 +164,84 @@ pub fn execute_plan(
 /// Tuples stream through the plan node tree from the branches to the root. Nodes
 /// recursively pull input rows upwards from their child node(s), process them,
 /// and hand the resulting rows off to their parent node.
-pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
+///
+/// In debug builds, each node's rows are checked against the node's declared
+/// column count, to catch planner/executor mismatches early.
+///
+/// Each node's rows are also checked against the cancel token, such that long
+/// running scans, joins, and sorts stop once it's cancelled.
+pub fn execute(node: BoxedNode, txn: &impl Transaction, cancel: &CancelToken) -> Result<Rows> {
+    cancel.check()?;
+    let columns = node.columns();
+    let rows = execute_node(node, txn, cancel)?;
+    Ok(check_rows(rows, columns, cancel))
+}
+
+/// Wraps a node's rows in the column count check (in debug builds) and the
+/// cancel token check, see execute().
+fn check_rows(mut rows: Rows, columns: usize, cancel: &CancelToken) -> Rows {
+    if cfg!(debug_assertions) {
+        rows = check_columns(rows, columns);
+    }
+    check_cancelled(rows, cancel.clone())
+}
+
+/// Wraps the rows in a check of the cancel token before each row.
+fn check_cancelled(rows: Rows, cancel: CancelToken) -> Rows {
+    Box::new(rows.map(move |item| {
+        cancel.check()?;
+        item
+    }))
+}
+
+/// Wraps the rows in a check that the first row has the given number of
+/// columns, erroring otherwise. The remaining rows are passed through as is.
+fn check_columns(rows: Rows, columns: usize) -> Rows {
+    let mut checked = false;
+    Box::new(rows.map(move |item| {
+        let (rid, row) = item?;
+        if !checked {
+            checked = true;
+            if row.size() != columns {
+                return errdata!("expected {columns} columns, node emitted {}", row.size());
+            }
+        }
+        Ok((rid, row))
+    }))
+}
+
+/// Executes a single plan node, see execute().
+fn execute_node(node: BoxedNode, txn: &impl Transaction, cancel: &CancelToken) -> Result<Rows> {
     Ok(match *node.inner {
         Node::Aggregate {
             source,
             group_by,
             aggregates,
+            grouping_sets,
         } => {
-            let source = execute(source, txn)?;
-            aggregate::aggregate(source, group_by, aggregates)?
+            let source = execute(source, txn, cancel)?;
+            let intern = txn.intern_strings();
+            match grouping_sets {
+                Some(sets) => {
+                    aggregate::aggregate_grouping_sets(source, group_by, aggregates, sets, intern)?
+                }
+                None => aggregate::aggregate(source, group_by, aggregates, intern)?,
+            }
         }
 
-        Node::Filter { source, predicate } => {
-            let result_rows = execute(source, txn)?;
-            transform::filter(result_rows, predicate)
+        Node::Filter {
+            source,
+            predicate,
+            epsilon,
+        } => {
+            if let Some(epsilon) = epsilon {
+                Field::check_epsilon(epsilon)?;
+            }
+            let result_rows = execute(source, txn, cancel)?;
+            if predicate.contains_subquery() {
+                return subquery::filter(result_rows, predicate, epsilon, txn, cancel);
+            }
+            transform::filter(result_rows, predicate, epsilon)
         }
 
         Node::HashJoin {
@@ -130,11 +250,13 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             right,
             right_column,
             outer,
+            epsilon,
         } => {
             let right_size = right.columns();
-            let left = execute(left, txn)?;
-            let right = execute(right, txn)?;
-            join::hash(left, left_column, right, right_column, right_size, outer)?
+            let left = execute(left, txn, cancel)?;
+            let right = execute(right, txn, cancel)?;
+            let intern = txn.intern_strings();
+            join::hash(left, left_column, right, right_column, right_size, outer, epsilon, intern)?
         }
 
         Node::IndexLookup {
@@ -155,10 +277,15 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
         }
 
         Node::Limit { source, limit } => {
-            let result_rows = execute(source, txn)?;
+            let result_rows = execute(source, txn, cancel)?;
             transform::limit(result_rows, limit)
         }
 
+        Node::Sample { source, size, seed } => {
+            let source = execute(source, txn, cancel)?;
+            transform::sample(source, size, seed)?
+        }
+
         Node::NestedLoopJoin {
             left,
             right,
@@ -166,8 +293,8 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             outer,
         } => {
             let right_size = right.columns();
-            let left = execute(left, txn)?;
-            let right = execute(right, txn)?;
+            let left = execute(left, txn, cancel)?;
+            let right = execute(right, txn, cancel)?;
             join::nested_loop(left, right, right_size, predicate, outer)?
         }
 
@@ -184,7 +311,11 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             source,
             key: orders,
         } => {
-            let source = execute(source, txn)?;
+            let source = execute(source, txn, cancel)?;
+            let orders = orders
+                .into_iter()
+                .map(|(expr, dir, nulls)| (expr, dir, nulls.or(txn.null_order())))
+                .collect();
             transform::order(source, orders)?
         }
 
@@ -193,13 +324,49 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             expressions,
             aliases: _,
         } => {
-            let source_rows = execute(source, txn)?;
-            transform::project(source_rows, expressions)
+            if expressions.iter().any(|expr| expr.contains_subquery()) {
+                let source_rows = execute(source, txn, cancel)?;
+                return subquery::project(source_rows, expressions, txn, cancel);
+            }
+            // Scan-filter-project pipelines are fused into a single iterator
+            // adapter over the scan, instead of executing each node separately.
+            // Constant filters aren't fused, since filter() skips the scan
+            // entirely if they're false.
+            match *source.inner {
+                Node::Scan { table, filter, .. } => {
+                    let rows = txn.scan(table.name(), filter)?;
+                    transform::filter_project(rows, None, expressions, cancel.clone())
+                }
+                Node::Filter {
+                    source,
+                    predicate,
+                    epsilon: None,
+                } if matches!(*source.inner, Node::Scan { .. })
+                        && !matches!(predicate, Expression::Constant(_))
+                        && !predicate.contains_subquery() =>
+                {
+                    let Node::Scan { table, filter, .. } = *source.inner else {
+                        unreachable!("checked above")
+                    };
+                    let rows = txn.scan(table.name(), filter)?;
+                    transform::filter_project(rows, Some(predicate), expressions, cancel.clone())
+                }
+                node => transform::project(execute(node.into(), txn, cancel)?, expressions),
+            }
         }
 
         Node::Remap { source, targets } => {
-            let source = execute(source, txn)?;
-            transform::remap(source, targets)
+            let source = execute(source, txn, cancel)?;
+            transform::remap(source, targets)?
+        }
+
+        Node::SemiJoin {
+            source,
+            subquery,
+            anti,
+        } => {
+            let source = execute(source, txn, cancel)?;
+            subquery::semi_join(source, subquery, anti, txn, cancel)?
         }
 
         Node::Scan {
@@ -211,18 +378,94 @@ pub fn execute(node: BoxedNode, txn: &impl Transaction) -> Result<Rows> {
             result_rows
         }
 
-        Node::Values { rows } => source::values(rows),
+        Node::Values { rows } => source::values(rows, None)?,
     })
 }
 
+/// Infers a table schema for CREATE TABLE AS from the source column labels and
+/// rows. A column takes the type of its non-NULL values, where mixed integers
+/// and floats are promoted to floats. Columns without any non-NULL values take
+/// the source node's column type, if known. All columns are nullable. Errors
+/// on values that can't be stored, i.e. arrays and intervals, or if a column's
+/// type can't be inferred. The rows are checked against the inferred schema.
+fn infer_table(
+    name: &str,
+    labels: &[Label],
+    source_types: &[DataType],
+    rows: Vec<Row>,
+) -> Result<(Table, Vec<Row>)> {
+    let mut types = vec![DataType::Invalid; labels.len()];
+    for row in &rows {
+        for (i, field) in row.iter().enumerate() {
+            let unstorable = match field {
+                Field::Array(_) => Some("array"),
+                Field::Interval(_) => Some("interval"),
+                _ => None,
+            };
+            if let Some(kind) = unstorable {
+                let column = labels[i].as_header();
+                return errinput!("column {column} has {kind} values, which can't be stored");
+            }
+            types[i] = match (types[i], field.get_type()) {
+                (t, DataType::Invalid) | (DataType::Invalid, t) => t,
+                (DataType::Int, DataType::Float) | (DataType::Float, DataType::Int) => {
+                    DataType::Float
+                }
+                (t, u) if t == u => t,
+                (t, u) => {
+                    let column = labels[i].as_header();
+                    return errinput!("column {column} has values of type {t} and {u}");
+                }
+            };
+        }
+    }
+    for (i, t) in types.iter_mut().enumerate() {
+        if *t == DataType::Invalid {
+            *t = source_types[i];
+        }
+        if *t == DataType::Invalid {
+            return errinput!("can't infer the type of column {}", labels[i].as_header());
+        }
+    }
+
+    let columns = labels
+        .iter()
+        .zip(&types)
+        .map(|(label, t)| Column::new(label.as_header(), *t, true, None, None))
+        .collect();
+    let table = Table::builder().name(name).columns(columns).build();
+
+    let rows: Vec<_> = rows
+        .into_iter()
+        .map(|row| {
+            let values = row.into_iter().zip(&types).map(|(field, t)| match (field, t) {
+                (Field::Integer(i), DataType::Float) => Field::Float(i as f32),
+                (field, _) => field,
+            });
+            Row::from(values.collect_vec())
+        })
+        .collect();
+    for row in &rows {
+        table.validate_row(row)?;
+    }
+    Ok((table, rows))
+}
+
 /// A plan execution result.
 pub enum ExecutionResult {
     CreateTable {
         name: String,
     },
+    CreateTableAs {
+        name: String,
+        count: u64,
+    },
     DropTable {
         name: String,
         existed: bool,
+        /// The number of rows in the table when it was dropped, or 0 if it
+        /// didn't exist.
+        row_count: u64,
     },
     Delete {
         count: u64,
@@ -230,12 +473,106 @@ pub enum ExecutionResult {
     Insert {
         count: u64,
         record_ids: Vec<RecordId>,
+        /// The inserted rows, including any default values.
+        rows: Vec<Row>,
     },
     Update {
         count: u64,
+        /// The record ids of the updated rows, by their previous record id.
+        /// They differ for rows that the update moved.
+        record_ids: BTreeMap<RecordId, RecordId>,
     },
     Select {
         rows: Rows,
         columns: Vec<Label>,
+        /// The column types, known even if there are no rows. DataType::Invalid
+        /// if the type can't be determined statically.
+        types: Vec<DataType>,
     },
 }
+
+/// The maximum width of a rendered table cell, beyond which values are
+/// truncated.
+const MAX_CELL_WIDTH: usize = 32;
+
+impl ExecutionResult {
+    /// Renders the result for display, as an ASCII table with column headers
+    /// for SELECT results, or a short summary for other statements. Consumes
+    /// and buffers the SELECT rows.
+    pub fn to_table_string(self) -> Result<String> {
+        self.to_table_string_with(None)
+    }
+
+    /// Like to_table_string(), but renders floats with the given number of
+    /// decimal places, if any. See Field::to_plain_string_with().
+    pub fn to_table_string_with(self, float_precision: Option<usize>) -> Result<String> {
+        Ok(match self {
+            ExecutionResult::CreateTable { name } => format!("table {name} created"),
+            ExecutionResult::CreateTableAs { name, count } => {
+                format!("table {name} created with {}", plural_rows(count))
+            }
+            ExecutionResult::DropTable {
+                name,
+                existed: true,
+                ..
+            } => format!("table {name} dropped"),
+            ExecutionResult::DropTable {
+                name,
+                existed: false,
+                ..
+            } => {
+                format!("table {name} does not exist")
+            }
+            ExecutionResult::Delete { count } => format!("{} deleted", plural_rows(count)),
+            ExecutionResult::Insert { count, .. } => format!("{} inserted", plural_rows(count)),
+            ExecutionResult::Update { count, .. } => format!("{} updated", plural_rows(count)),
+            ExecutionResult::Select { rows, columns, .. } => {
+                let header = columns.iter().map(|label| truncate(label.to_string())).collect();
+                let format = |field: &Field| truncate(field.to_plain_string_with(float_precision));
+                let rows: Vec<Vec<String>> =
+                    rows.map_ok(|(_, row)| row.iter().map(format).collect()).try_collect()?;
+                render_table(header, &rows)
+            }
+        })
+    }
+}
+
+/// Formats a row count, e.g. "1 row" or "3 rows".
+fn plural_rows(count: u64) -> String {
+    match count {
+        1 => "1 row".to_string(),
+        count => format!("{count} rows"),
+    }
+}
+
+/// Truncates a cell value to MAX_CELL_WIDTH characters, marking it with "...".
+fn truncate(value: String) -> String {
+    if value.chars().count() <= MAX_CELL_WIDTH {
+        return value;
+    }
+    value.chars().take(MAX_CELL_WIDTH - 3).chain("...".chars()).collect()
+}
+
+/// Renders an ASCII table with the given header and rows, left-aligning the
+/// cells in each column, followed by the row count.
+fn render_table(header: Vec<String>, rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let separator = format!("+{}+", widths.iter().map(|w| "-".repeat(w + 2)).join("+"));
+    let line = |cells: &[String]| {
+        let mut cells = cells.iter().zip(&widths).map(|(cell, &width)| format!(" {cell:width$} "));
+        format!("|{}|", cells.join("|"))
+    };
+
+    let mut output = vec![separator.clone(), line(&header), separator.clone()];
+    output.extend(rows.iter().map(|row| line(row)));
+    if !rows.is_empty() {
+        output.push(separator);
+    }
+    output.push(format!("({})", plural_rows(rows.len() as u64)));
+    output.join("\n")
+}
@@ -1,9 +1,9 @@
 use crate::common::Result;
 use crate::sql::planner::Expression;
 
-use crate::storage::page::{RecordId, INVALID_RID};
+use crate::storage::page::RecordId;
 use crate::storage::tuple::{Row, Rows};
-use crate::types::field::Field;
+use crate::types::field::{Field, Interner};
 use itertools::Itertools as _;
 use std::collections::HashMap;
 use std::iter::Peekable;
@@ -12,7 +12,8 @@ use std::iter::Peekable;
 /// source, optionally filtering on the join predicate. If outer is true, and
 /// there are no matches in the right source for a row in the left source, a
 /// joined row with NULL values for the right source is returned (typically used
-/// for a LEFT JOIN).
+/// for a LEFT JOIN). Joined rows carry the left row's record ID, such that e.g.
+/// UPDATE FROM can write to the left table.
 pub fn nested_loop(
     left: Rows,
     right: Rows,
@@ -25,6 +26,26 @@ pub fn nested_loop(
     )?))
 }
 
+/// Returns the columns that must be non-NULL for the predicate to evaluate to
+/// true, i.e. the column operands of equalities in its top-level conjunction.
+fn null_rejecting_columns(predicate: &Expression) -> Vec<usize> {
+    match predicate {
+        Expression::And(lhs, rhs) => {
+            let mut columns = null_rejecting_columns(lhs);
+            columns.extend(null_rejecting_columns(rhs));
+            columns
+        }
+        Expression::Equal(lhs, rhs) => [lhs, rhs]
+            .into_iter()
+            .filter_map(|expr| match **expr {
+                Expression::Column(index) => Some(index),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 /// NestedLoopIterator implements nested loop joins.
 ///
 /// This could be trivially implemented with cartesian_product(), but we need
@@ -44,6 +65,9 @@ struct NestedLoopIterator {
     right_match: bool,
     /// The join predicate.
     predicate: Option<Expression>,
+    /// Joined row columns that must be non-NULL for the predicate to match,
+    /// i.e. the equi-join keys. Rows with NULL keys are skipped early.
+    keys: Vec<usize>,
     /// If true, emit a row when there is no match in the right source.
     outer: bool,
 }
@@ -58,6 +82,7 @@ impl NestedLoopIterator {
     ) -> Result<Self> {
         let left = left.peekable();
         let right_init = right.clone();
+        let keys = predicate.as_ref().map(null_rejecting_columns).unwrap_or_default();
         Ok(Self {
             left,
             right,
@@ -65,6 +90,7 @@ impl NestedLoopIterator {
             right_size,
             right_match: false,
             predicate,
+            keys,
             outer,
         })
     }
@@ -78,20 +104,34 @@ impl NestedLoopIterator {
         loop {
             // If we have a current left row, try to find a match
             if let Some(row_result) = self.left.peek(){
-                let left_row = match row_result{
-                    Ok((_record_id, row)) => row,
+                let (left_rid, left_row) = match row_result{
+                    Ok((record_id, row)) => (record_id.clone(), row),
                     Err(_) => return Ok(None),
                 };
+                // A NULL join key never matches, so skip the right source if
+                // the left row has a NULL key. The right source is reset below
+                // when moving to the next left row.
+                let left_size = left_row.size();
+                let is_null = |row: &Row, index| row.get_field(index).is_ok_and(|f| f.is_null());
+                if self.keys.iter().any(|&k| k < left_size && is_null(left_row, k)) {
+                    self.right = Box::new(std::iter::empty());
+                }
+
                 // Look for matches in the right iterator
                 while let Some(right_result) = self.right.next(){
                     let (_, right_row) = right_result?;
-                    
+
+                    // Skip right rows with NULL keys without evaluating the
+                    // predicate.
+                    let right_null = |&k: &usize| {
+                        k >= left_size && is_null(&right_row, k - left_size)
+                    };
+                    if self.keys.iter().any(right_null) {
+                        continue;
+                    }
+
                     // Create combined row
-                    let combined_row = Row::from(
-                    left_row.iter()
-                        .chain(right_row.iter())
-                        .collect::<Vec<&Field>>()
-                    );
+                    let combined_row = left_row.append(&right_row);
 
                     // Check if predicate matches (if any)
                     let matches = if let Some(ref predicate) = self.predicate {
@@ -105,7 +145,7 @@ impl NestedLoopIterator {
                     
                     if matches {
                         self.right_match = true;
-                        return Ok(Some((INVALID_RID, combined_row)));
+                        return Ok(Some((left_rid, combined_row)));
                     }
                 }
                 // No more right rows for this left row
@@ -114,18 +154,11 @@ impl NestedLoopIterator {
                 if let Some((left_rid, left_row)) = left_result {
                     // If this was an outer join and no matches found, emit NULL row
                     if self.outer && !self.right_match {
-                        let null_fields: Vec<Field> = (0..self.right_size)
-                            .map(|_| Field::Null)
-                            .collect();
-                        let combined_row = Row::from(
-                            left_row.iter()
-                                .chain(null_fields.iter())
-                                .cloned()
-                                .collect::<Vec<Field>>()
-                        );
+                        let null_row = Row::from(vec![Field::Null; self.right_size]);
+                        let combined_row = left_row.append(&null_row);
                         self.right_match = false;
                         self.right = self.right_init.clone();
-                        return Ok(Some((INVALID_RID, combined_row)));
+                        return Ok(Some((left_rid, combined_row)));
                     }
                     
                     // Reset for next left row
@@ -156,6 +189,17 @@ impl Iterator for NestedLoopIterator {
 /// matching rows in the hash table. If outer is true, and there is no match
 /// in the right source for a row in the left source, a row with NULL values
 /// for the right source is emitted instead.
+///
+/// Rows are emitted in left source order. For each left row, its matches are
+/// emitted in right source order, since the hash table appends right rows to
+/// each key's match list as they're read. Joined rows carry the left row's
+/// record ID.
+///
+/// If epsilon is given, float keys are compared with that tolerance, by
+/// hashing them on their rounded key instead (see `Field::float_key`). If
+/// intern is true, the right rows' key strings are interned as they're
+/// buffered, such that repeated keys share an allocation.
+#[allow(clippy::too_many_arguments)]
 pub fn hash(
     left: Rows,
     left_column: usize,
@@ -163,44 +207,57 @@ pub fn hash(
     right_column: usize,
     right_size: usize,
     outer: bool,
+    epsilon: Option<f32>,
+    intern: bool,
 ) -> Result<Rows> {
-    // Build the hash table from the right source.
+    if let Some(epsilon) = epsilon {
+        Field::check_epsilon(epsilon)?;
+    }
+    let key = move |value: Field| match epsilon {
+        Some(epsilon) => value.float_key(epsilon),
+        None => Ok(value),
+    };
+
+    // Build the hash table from the right source. Matches for each key are kept
+    // in insertion order. When interning, the key is replaced in place by its
+    // interned string, which is shared with the hash table key.
     let mut rows = right;
     let mut right: HashMap<Field, Vec<Row>> = HashMap::new();
-    while let Some((_, row)) = rows.next().transpose()? {
-        let value = row.get_field(right_column)?.clone();
+    let mut interner = intern.then(Interner::new);
+    while let Some((_, mut row)) = rows.next().transpose()? {
+        let mut value = row.get_field(right_column)?;
         if value.is_undefined() {
             continue; // NULL and NAN equality is always false
         }
-        right.entry(value).or_default().push(row);
+        if let Some(interner) = &mut interner {
+            value = interner.intern(value);
+            row.update_field(right_column, value.clone())?;
+        }
+        right.entry(key(value)?).or_default().push(row);
     }
 
-    // Set up an iterator for an empty right row in the outer case.
-    let empty = std::iter::repeat(Field::Null).take(right_size);
+    // Set up an empty right row for the outer case.
+    let empty = Row::from(vec![Field::Null; right_size]);
 
     // Set up the join iterator.
     let join = left.flat_map(move |result| -> Rows {
         // Pass through errors.
-        let Ok((_, row)) = result else {
+        let Ok((rid, row)) = result else {
             return Box::new(std::iter::once(result));
         };
         // Join the left row with any matching right rows.
-        match right.get(&row.get_field(left_column).unwrap()) {
+        let value = match row.get_field(left_column).and_then(key) {
+            Ok(value) => value,
+            Err(err) => return Box::new(std::iter::once(Err(err))),
+        };
+        match right.get(&value) {
             Some(matches) => Box::new(
                 std::iter::once(row)
                     .cartesian_product(matches.clone())
-                    .map(|(l, r)| {
-                        (
-                            INVALID_RID,
-                            Row::from(l.iter().chain(r.iter()).collect::<Vec<&Field>>()),
-                        )
-                    })
+                    .map(move |(l, r)| (rid.clone(), l.append(&r)))
                     .map(Ok),
             ),
-            None if outer => Box::new(std::iter::once(Ok((
-                INVALID_RID,
-                Row::from(row.into_iter().chain(empty.clone()).collect::<Vec<_>>()),
-            )))),
+            None if outer => Box::new(std::iter::once(Ok((rid, row.append(&empty))))),
             None => Box::new(std::iter::empty()),
         }
     });
@@ -1,9 +1,14 @@
 //! SQL Query Execution Engine and related machinery.
-mod aggregate;
+pub(crate) mod aggregate;
+mod cancel;
 mod execute;
 mod join;
 mod source;
-mod transform;
+mod subquery;
+pub(crate) mod transform;
 mod write;
 
+pub use aggregate::spill_count;
+pub use cancel::CancelToken;
 pub use execute::{execute_plan, ExecutionResult};
+pub use transform::inspect;
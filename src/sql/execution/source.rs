@@ -1,9 +1,11 @@
 use crate::common::Result;
+use crate::errinput;
 use crate::sql::engine::Transaction;
 use crate::sql::planner::Expression;
 use crate::storage::page::INVALID_RID;
 use crate::storage::tuple::{Row, Rows};
 use crate::types::field::Field;
+use itertools::Itertools as _;
 use crate::types::Table;
 
 /// A table source via sequential scan
@@ -16,12 +18,38 @@ pub fn nothing() -> Rows {
     Box::new(std::iter::empty())
 }
 
-/// Emits predefined constant values.
-pub fn values(tuples: Vec<Vec<Expression>>) -> Rows {
-    let iter = tuples.into_iter().map(|tuple| {
-        let evaluated: Result<Vec<Field>> =
-            tuple.into_iter().map(|expr| expr.evaluate(None)).collect();
-        evaluated.map(|fields| (INVALID_RID, Row::from(fields)))
+/// Emits predefined constant values. All rows must have the same number of
+/// columns. If a target schema is given (e.g. for INSERT), rows must have its
+/// columns, and values must have the column's type or be NULL, where integers
+/// are converted to floats for float columns. Errors identify the row index.
+///
+/// The rows are moved into the iterator and evaluated one at a time as they're
+/// emitted, so large VALUES lists aren't copied or materialized up front.
+/// Cloning the iterator, e.g. to rescan a nested loop join's right side, clones
+/// the rows that haven't been emitted yet.
+pub fn values(tuples: Vec<Vec<Expression>>, schema: Option<&Table>) -> Result<Rows> {
+    let width = match schema {
+        Some(schema) => schema.col_count(),
+        None => tuples.first().map_or(0, Vec::len),
+    };
+    if let Some((index, tuple)) = tuples.iter().find_position(|tuple| tuple.len() != width) {
+        return errinput!("values row {index} has {} columns, expected {width}", tuple.len());
+    }
+    let types = schema.map(|schema| {
+        schema.columns().iter().map(|c| (c.get_name(), c.get_data_type())).collect_vec()
     });
-    Box::new(iter)
+    let iter = tuples.into_iter().enumerate().map(move |(index, tuple)| {
+        let mut fields: Vec<Field> =
+            tuple.into_iter().map(|expr| expr.evaluate(None)).try_collect()?;
+        for (field, (name, data_type)) in fields.iter_mut().zip(types.iter().flatten()) {
+            *field = match field.coerce_to(*data_type) {
+                Ok(value) => value,
+                Err(_) => {
+                    return errinput!("values row {index} has {field} for {data_type} column {name}")
+                }
+            };
+        }
+        Ok((INVALID_RID, Row::from(fields)))
+    });
+    Ok(Box::new(iter))
 }
@@ -0,0 +1,195 @@
+use crate::common::Result;
+use crate::errinput;
+use crate::sql::engine::Transaction;
+use crate::sql::execution::execute::execute;
+use crate::sql::execution::{transform, CancelToken};
+use crate::sql::planner::{BoxedNode, Expression};
+use crate::storage::tuple::{Row, Rows};
+use crate::types::field::Field;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+
+/// Filters the input rows using a predicate containing subqueries (i.e. WHERE).
+///
+/// Subqueries need the transaction to execute, which can't be moved into the
+/// lazy row iterator, so the source rows are buffered and the predicate's
+/// subqueries are bound to each row in turn.
+pub fn filter(
+    source: Rows,
+    predicate: Expression,
+    epsilon: Option<f32>,
+    txn: &impl Transaction,
+    cancel: &CancelToken,
+) -> Result<Rows> {
+    // Uncorrelated subqueries yield the same value for every row, so they're
+    // executed once up front. If none remain, the rows are filtered lazily.
+    let predicate = bind_uncorrelated(predicate, txn, cancel)?;
+    if !predicate.contains_subquery() {
+        return Ok(transform::filter(source, predicate, epsilon));
+    }
+    let mut rows = Vec::new();
+    for item in source {
+        let (rid, row) = item?;
+        let predicate = bind(&predicate, &row, txn, cancel)?;
+        let value = match epsilon {
+            Some(epsilon) => predicate.evaluate_tolerant(Some(&row), epsilon)?,
+            None => predicate.evaluate(Some(&row))?,
+        };
+        match value {
+            Field::Boolean(true) => rows.push(Ok((rid, row))),
+            Field::Boolean(false) | Field::Null => {}
+            value => return errinput!("filter returned {value}, expected boolean"),
+        }
+    }
+    Ok(Box::new(rows.into_iter()))
+}
+
+/// Projects the rows using expressions containing subqueries (i.e. SELECT).
+/// Like filter(), this buffers the source rows.
+pub fn project(
+    source: Rows,
+    expressions: Vec<Expression>,
+    txn: &impl Transaction,
+    cancel: &CancelToken,
+) -> Result<Rows> {
+    let mut rows = Vec::new();
+    for item in source {
+        let (rid, row) = item?;
+        let mut projected = Vec::with_capacity(expressions.len());
+        for expression in &expressions {
+            projected.push(bind(expression, &row, txn, cancel)?.evaluate(Some(&row))?);
+        }
+        rows.push(Ok((rid, Row::from(projected))));
+    }
+    Ok(Box::new(rows.into_iter()))
+}
+
+/// Semi-joins the input rows with a correlated subquery (i.e. WHERE EXISTS),
+/// emitting the rows for which the subquery yields any rows, or, if anti is
+/// true, yields no rows (i.e. WHERE NOT EXISTS). Like filter(), this buffers
+/// the source rows.
+///
+/// The outer column values referenced by the subquery are used as the probe
+/// key: the subquery is executed once per distinct key, and the result is
+/// reused for later rows with the same key.
+pub fn semi_join(
+    source: Rows,
+    subquery: BoxedNode,
+    anti: bool,
+    txn: &impl Transaction,
+    cancel: &CancelToken,
+) -> Result<Rows> {
+    let columns = outer_columns(&subquery)?;
+    let mut matches: HashMap<Vec<Field>, bool> = HashMap::new();
+    let mut rows = Vec::new();
+    for item in source {
+        let (rid, row) = item?;
+        let key = columns
+            .iter()
+            .map(|&i| row.get_field(i))
+            .collect::<Result<Vec<_>>>()?;
+        let matched = match matches.get(&key) {
+            Some(&matched) => matched,
+            None => {
+                // Only the first row is needed, so stop executing after it.
+                let node = bind_outer(subquery.clone(), &row)?;
+                let matched = execute(node, txn, cancel)?.next().transpose()?.is_some();
+                matches.insert(key, matched);
+                matched
+            }
+        };
+        if matched != anti {
+            rows.push(Ok((rid, row)));
+        }
+    }
+    Ok(Box::new(rows.into_iter()))
+}
+
+/// Binds the subqueries in an expression to the given row, by executing them
+/// and replacing them with their resulting value.
+fn bind(
+    expr: &Expression,
+    row: &Row,
+    txn: &impl Transaction,
+    cancel: &CancelToken,
+) -> Result<Expression> {
+    expr.clone().transform(
+        &|expr| match expr {
+            Expression::ScalarSubquery(node) => {
+                Ok(Expression::Constant(scalar(node, row, txn, cancel)?))
+            }
+            expr => Ok(expr),
+        },
+        &Ok,
+    )
+}
+
+/// Executes the uncorrelated scalar subqueries in an expression, i.e. those
+/// that don't reference the enclosing query's columns, and replaces them with
+/// their resulting value.
+fn bind_uncorrelated(
+    expr: Expression,
+    txn: &impl Transaction,
+    cancel: &CancelToken,
+) -> Result<Expression> {
+    expr.transform(
+        &|expr| match expr {
+            Expression::ScalarSubquery(node) if outer_columns(&node)?.is_empty() => {
+                let empty = Row::from(Vec::<Field>::new());
+                Ok(Expression::Constant(scalar(node, &empty, txn, cancel)?))
+            }
+            expr => Ok(expr),
+        },
+        &Ok,
+    )
+}
+
+/// Executes a scalar subquery for the given outer row. Returns NULL if the
+/// subquery yields no rows, and errors if it yields more than one value.
+fn scalar(
+    node: BoxedNode,
+    row: &Row,
+    txn: &impl Transaction,
+    cancel: &CancelToken,
+) -> Result<Field> {
+    let mut rows = execute(bind_outer(node, row)?, txn, cancel)?;
+    let Some((_, result)) = rows.next().transpose()? else {
+        return Ok(Field::Null);
+    };
+    if rows.next().is_some() {
+        return errinput!("subquery returned more than one row");
+    }
+    if result.size() != 1 {
+        return errinput!("subquery returned {} columns, expected 1", result.size());
+    }
+    result.get_field(0)
+}
+
+/// Replaces a subquery's outer column references with values from the given
+/// row of the enclosing query.
+fn bind_outer(node: BoxedNode, row: &Row) -> Result<BoxedNode> {
+    let bind = |expr| match expr {
+        Expression::OuterColumn(index) => Ok(Expression::Constant(row.get_field(index)?)),
+        expr => Ok(expr),
+    };
+    Ok(node
+        .inner
+        .transform(&|node| node.transform_expressions(&bind, &Ok), &Ok)?
+        .into())
+}
+
+/// Returns the indexes of the enclosing query's columns that are referenced
+/// by a subquery, in ascending order.
+fn outer_columns(node: &BoxedNode) -> Result<Vec<usize>> {
+    let columns = RefCell::new(BTreeSet::new());
+    let collect = |expr| {
+        if let Expression::OuterColumn(index) = expr {
+            columns.borrow_mut().insert(index);
+        }
+        Ok(expr)
+    };
+    node.inner
+        .clone()
+        .transform(&|node| node.transform_expressions(&collect, &Ok), &Ok)?;
+    Ok(columns.into_inner().into_iter().collect())
+}
@@ -1,23 +1,44 @@
 use crate::common::Result;
-use crate::sql::planner::Direction;
+use crate::errinput;
+use crate::sql::execution::CancelToken;
+use crate::sql::planner::{Direction, NullOrder};
 use crate::sql::planner::Expression;
+use crate::storage::page::RecordId;
 use crate::storage::tuple::{Row, Rows};
 use crate::types::field::Field;
+use crate::types::Table;
 use itertools::{izip, Itertools as _};
+use rand::Rng as _;
+use rand_chacha::ChaCha8Rng;
+use rand_core::SeedableRng as _;
+use std::sync::Arc;
 
 /// Filters the input rows (i.e. WHERE).
 ///
 /// (Hint: look at the `iterator.rs` standard library API. There's a
 /// method that returns an iterator that only emits elements that
 /// satisfy a given predicate.)
-pub fn filter(source: Rows, predicate: Expression) -> Rows {
-    
+pub fn filter(source: Rows, predicate: Expression, epsilon: Option<f32>) -> Rows {
+    // A constant predicate (e.g. WHERE FALSE) keeps either all rows or none,
+    // so there's no need to evaluate it per row, or to pull any rows at all.
+    match predicate {
+        Expression::Constant(Field::Boolean(true)) => return source,
+        Expression::Constant(Field::Boolean(false) | Field::Null) => {
+            return super::source::nothing()
+        }
+        _ => {}
+    }
+
     // Create a new iterator that filters rows based on the predicate
     let filtered_iter = source.filter_map(move |x| {
         // Handle the Result from the iterator
         let processed = x.and_then(|(rid, row)| {
-            // Evaluate the predicate expression on this row
-            let evaluation_result = predicate.evaluate(Some(&row))?;
+            // Evaluate the predicate expression on this row, comparing floats
+            // within epsilon if given
+            let evaluation_result = match epsilon {
+                Some(epsilon) => predicate.evaluate_tolerant(Some(&row), epsilon)?,
+                None => predicate.evaluate(Some(&row))?,
+            };
             
             // Check what the predicate returned
             match evaluation_result {
@@ -43,6 +64,20 @@ pub fn filter(source: Rows, predicate: Expression) -> Rows {
 
 }
 
+/// Calls the given closure on each row emitted by the source, e.g. to log rows
+/// while troubleshooting a plan, and passes the rows through unchanged. Errors
+/// are passed through without calling the closure. This is the Rows equivalent
+/// of Iterator::inspect().
+pub fn inspect(source: Rows, f: impl Fn(&(RecordId, Row)) + 'static) -> Rows {
+    // Rows must be clonable, so share the closure between clones.
+    let f = Arc::new(f);
+    Box::new(source.inspect(move |result| {
+        if let Ok(item) = result {
+            f(item)
+        }
+    }))
+}
+
 /// Limits the result to the given number of rows (i.e. LIMIT).
 ///
 /// (Hint: look at the `iterator.rs` standard library API. There's a
@@ -51,14 +86,41 @@ pub fn limit(source: Rows, limit: usize) -> Rows {
     Box::new(source.take(limit))
 }
 
+/// Emits a uniform random sample of up to size rows from the source (i.e.
+/// SAMPLE), using reservoir sampling seeded with the given seed. The sampled
+/// rows are emitted in source order. Only buffers the sampled rows.
+pub fn sample(source: Rows, size: usize, seed: u64) -> Result<Rows> {
+    if size == 0 {
+        return Ok(super::source::nothing());
+    }
+    // Keep the first size rows, then replace a random sampled row with the
+    // i'th row with probability size/(i+1).
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut reservoir = Vec::with_capacity(size);
+    for (i, item) in source.enumerate() {
+        let item = item?;
+        if i < size {
+            reservoir.push((i, item));
+        } else if let Some(sampled) = reservoir.get_mut(rng.gen_range(0..=i)) {
+            *sampled = (i, item);
+        }
+    }
+    reservoir.sort_by_key(|(i, _)| *i);
+    Ok(Box::new(reservoir.into_iter().map(|(_, item)| Ok(item))))
+}
+
 /// Skips the given number of rows (i.e. OFFSET).
 #[allow(dead_code)]
 pub fn offset(source: Rows, offset: usize) -> Rows {
     Box::new(source.skip(offset))
 }
 
-/// Sorts the rows (i.e. ORDER BY).
-pub fn order(source: Rows, order: Vec<(Expression, Direction)>) -> Result<Rows> {
+/// Sorts the rows (i.e. ORDER BY). Keys with a NULL placement sort NULLs first
+/// or last regardless of direction, otherwise NULLs sort as the smallest value.
+pub fn order(
+    source: Rows,
+    order: Vec<(Expression, Direction, Option<NullOrder>)>,
+) -> Result<Rows> {
     // We can't use sort_by_cached_key(), since expression evaluation is
     // fallible, and since we may have to vary the sort direction of each
     // expression. Precompute the sort values instead, and map them based on
@@ -71,14 +133,24 @@ pub fn order(source: Rows, order: Vec<(Expression, Direction)>) -> Result<Rows>
     for (_, (_rid, row)) in &irows {
         let values: Vec<_> = order
             .iter()
-            .map(|(e, _)| e.evaluate(Some(&row)))
+            .map(|(e, _, _)| e.evaluate(Some(row)))
             .try_collect()?;
         sort_values.push(values)
     }
 
     irows.sort_by(|&(a, _), &(b, _)| {
-        let dirs = order.iter().map(|(_, dir)| dir);
-        for (a, b, dir) in izip!(&sort_values[a], &sort_values[b], dirs) {
+        // Compare the keys lexicographically, each in its own direction: the
+        // first unequal key decides, and later keys only break ties.
+        for (a, b, (_, dir, nulls)) in izip!(&sort_values[a], &sort_values[b], &order) {
+            match (nulls, a.is_null(), b.is_null()) {
+                (Some(NullOrder::First), true, false) | (Some(NullOrder::Last), false, true) => {
+                    return std::cmp::Ordering::Less
+                }
+                (Some(NullOrder::First), false, true) | (Some(NullOrder::Last), true, false) => {
+                    return std::cmp::Ordering::Greater
+                }
+                _ => {}
+            }
             match a.cmp(b) {
                 std::cmp::Ordering::Equal => {}
                 order if *dir == Direction::Descending => return order.reverse(),
@@ -99,6 +171,18 @@ pub fn order(source: Rows, order: Vec<(Expression, Direction)>) -> Result<Rows>
 /// the projection is selecting for. You'll want to build a projection
 /// row from the results of calling each expression on a given row.)
 pub fn project(source: Rows, expressions: Vec<Expression>) -> Rows {
+    // Projections of plain column references (a subset or reordering of the
+    // input columns) are done in place, reusing the input row's allocation and
+    // a scratch buffer instead of allocating a new row per input row.
+    if let Some(columns) = column_indexes(&expressions) {
+        let mut scratch = Vec::with_capacity(columns.len());
+        return Box::new(source.map(move |item| {
+            let (record_id, mut row) = item?;
+            row.project_columns(&columns, &mut scratch)?;
+            Ok((record_id, row))
+        }));
+    }
+
     Box::new(source.map(move |item| {
         item.and_then(|(record_id, row)| {
             // Create a vector to hold the projected field values
@@ -116,15 +200,81 @@ pub fn project(source: Rows, expressions: Vec<Expression>) -> Rows {
     }))
 }
 
+/// Filters and projects the source rows in a single pass, i.e. a fused
+/// filter() and project(). Used for scan-filter-project pipelines, avoiding a
+/// boxed iterator (and execution wrappers) per node. Since the execution
+/// wrappers only see the emitted rows, the cancel token is checked for every
+/// source row here, including rows that are filtered out.
+pub fn filter_project(
+    source: Rows,
+    predicate: Option<Expression>,
+    expressions: Vec<Expression>,
+    cancel: CancelToken,
+) -> Rows {
+    let columns = column_indexes(&expressions);
+    let mut scratch = Vec::new();
+    Box::new(source.filter_map(move |item| {
+        item.and_then(|(rid, mut row)| {
+            cancel.check()?;
+            if let Some(predicate) = &predicate {
+                match predicate.evaluate(Some(&row))? {
+                    Field::Boolean(true) => {}
+                    Field::Boolean(false) | Field::Null => return Ok(None),
+                    value => return errinput!("filter returned {value}, expected boolean"),
+                }
+            }
+            if let Some(columns) = &columns {
+                row.project_columns(columns, &mut scratch)?;
+                return Ok(Some((rid, row)));
+            }
+            let fields: Vec<Field> =
+                expressions.iter().map(|expr| expr.evaluate(Some(&row))).try_collect()?;
+            Ok(Some((rid, Row::from(fields))))
+        })
+        .transpose()
+    }))
+}
+
+/// Returns the column indexes referenced by the expressions, if they're all
+/// plain column references. Such projections can be done in place.
+fn column_indexes(expressions: &[Expression]) -> Option<Vec<usize>> {
+    expressions
+        .iter()
+        .map(|expr| match expr {
+            Expression::Column(index) => Some(*index),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Converts the values of the source rows to the given schema's column types
+/// where possible (see Field::coerce_to()), e.g. for INSERT ... SELECT. Other
+/// values are passed through as is, for write::insert() to reject.
+pub fn coerce(source: Rows, schema: &Table) -> Rows {
+    let types = schema.columns().iter().map(|column| column.get_data_type()).collect_vec();
+    Box::new(source.map_ok(move |(id, row)| {
+        let fields = row.into_iter().enumerate().map(|(i, field)| match types.get(i) {
+            Some(data_type) => field.coerce_to(*data_type).unwrap_or(field),
+            None => field,
+        });
+        (id, Row::from(fields.collect_vec()))
+    }))
+}
+
 /// Remaps source columns to target column indexes, or drops them if None.
-pub fn remap(source: Rows, targets: Vec<Option<usize>>) -> Rows {
+/// Target columns without a source column are NULL. Errors if multiple source
+/// columns map to the same target, rather than silently picking one of them.
+pub fn remap(source: Rows, targets: Vec<Option<usize>>) -> Result<Rows> {
+    if let Some(index) = targets.iter().flatten().duplicates().next() {
+        return errinput!("multiple columns remapped to column {index}");
+    }
     let size = targets
         .iter()
         .filter_map(|v| *v)
         .map(|i| i + 1)
         .max()
         .unwrap_or(0);
-    Box::new(source.map_ok(move |(rid, row)| {
+    Ok(Box::new(source.map_ok(move |(rid, row)| {
         let mut out = vec![Field::Null; size];
         for (value, target) in row.into_iter().zip(&targets) {
             if let Some(index) = target {
@@ -132,5 +282,5 @@ pub fn remap(source: Rows, targets: Vec<Option<usize>>) -> Rows {
             }
         }
         (rid, Row::from(out))
-    }))
+    })))
 }
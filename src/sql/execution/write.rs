@@ -1,25 +1,27 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::common::Result;
+use crate::common::{Error, Result};
+use crate::errinput;
 use crate::sql::engine::Transaction;
 use crate::sql::planner::Expression;
 use crate::storage::page::RecordId;
-use crate::storage::tuple::Rows;
+use crate::storage::tuple::{Row, Rows};
 use crate::types::Table;
 
-/// Deletes rows, taking primary keys from the source (i.e. DELETE) using the
-/// primary_key column index. Returns the number of rows deleted.
+/// Deletes rows, taking record IDs from the source (i.e. DELETE). The source
+/// may emit the same record ID several times (e.g. from a DELETE USING join),
+/// but each row is only deleted once. Returns the number of rows deleted.
 pub fn delete(txn: &impl Transaction, table: String, source: Rows) -> Result<u64> {
-    // Create a new vector to store record_ids and store number of records
-    let mut record_ids = Vec::new();
-    let mut record_num = 0;
-    
+    // Create a new set to store distinct record_ids and store number of records
+    let mut record_ids = BTreeSet::new();
+
     // Collect all record IDs
     for item in source{
         let (record_id, _row) = item?;
-        record_ids.push(record_id);
-        record_num += 1;
+        record_ids.insert(record_id);
     }
+    let record_num = record_ids.len() as u64;
+    let record_ids = record_ids.into_iter().collect::<Vec<_>>();
 
     let _ = txn.delete(&table, &record_ids);
 
@@ -27,25 +29,35 @@ pub fn delete(txn: &impl Transaction, table: String, source: Rows) -> Result<u64
     
 }
 
-/// Inserts rows into a table (i.e. INSERT) from the given source.
-/// Returns the record IDs corresponding to the rows inserted into the table.
-pub fn insert(txn: &impl Transaction, table: Table, source: Rows) -> Result<Vec<RecordId>> {
+/// Inserts rows into a table (i.e. INSERT) from the given source. Returns the
+/// record IDs of the inserted rows, along with the rows themselves, including
+/// any default values. Errors on the first row that doesn't match the table
+/// schema (see Table::validate_row), before inserting anything.
+pub fn insert(txn: &impl Transaction, table: Table, source: Rows) -> Result<Vec<(RecordId, Row)>> {
     
     // Get table name and create a new vector to store rows
     let table_name = table.name();
     let mut vec_rows = Vec::new();
 
     // Collect all record IDs
-    for item in source{
+    for (index, item) in source.enumerate() {
         let (_record_id, row) = item?;
+        match table.validate_row(&row) {
+            Ok(()) => {}
+            Err(Error::InvalidInput(message)) => return errinput!("insert row {index}: {message}"),
+            Err(err) => return Err(err),
+        }
         vec_rows.push(row);
     }
 
     txn.insert(table_name, vec_rows)
 }
 
-/// Updates rows passed in from the source (i.e. UPDATE). Returns the number of
-/// rows updated.
+/// Updates rows passed in from the source (i.e. UPDATE). Returns the record
+/// IDs of the updated rows by their previous record ID, since updates may move
+/// rows. Source rows may have trailing columns after the table's columns (e.g.
+/// from a join), which are discarded. If several source rows have the same
+/// record ID, the last one wins.
 ///
 /// Hint: `<T,E> Option<Result<T,E>>::transpose(self) -> Result<Option<T>, E>` and
 /// the `?` operator might be useful here. An example of `transpose` from the docs:
@@ -59,10 +71,10 @@ pub fn insert(txn: &impl Transaction, table: Table, source: Rows) -> Result<Vec<
 /// ```
 pub fn update(
     txn: &impl Transaction,
-    table: String,
+    table: &Table,
     mut source: Rows,
     expressions: Vec<(usize, Expression)>,
-) -> Result<u64> {
+) -> Result<BTreeMap<RecordId, RecordId>> {
     
     let mut updates = BTreeMap::new();
 
@@ -70,17 +82,22 @@ pub fn update(
         let (record_id, mut row) = item?;
         for (column_index, expression) in &expressions{
             let new_value = expression.evaluate(Some(&row))?;
+            let column = table.get_column(*column_index);
+            if new_value.is_null() && !column.is_nullable() {
+                return errinput!("column {} can't be NULL", column.get_name());
+            }
             row.update_field(*column_index, new_value)?;
         }
+        if row.size() > table.col_count() {
+            row = Row::from(row.into_iter().take(table.col_count()).collect::<Vec<_>>());
+        }
         updates.insert(record_id, row);
     }
 
-    // Get the count before calling update
-    let count = updates.len() as u64;
-    
-    // Apply all updates to the database
-    txn.update(&table, updates)?;
-    
-    Ok(count)
+    // Apply all updates to the database, keeping the previous record IDs
+    let record_ids = updates.keys().cloned().collect::<Vec<_>>();
+    let moved = txn.update(table.name(), updates)?;
+
+    Ok(record_ids.into_iter().zip(moved).collect())
 
 }
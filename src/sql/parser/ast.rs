@@ -4,7 +4,11 @@ use std::collections::BTreeMap;
 /// Root node of the abstract syntax tree built from a
 /// SQL query by the parser. It is transformed by the
 /// planner into a tree of query execution plan nodes.
-#[derive(Debug)]
+///
+/// Statements are short-lived and only built once per query, so we don't box
+/// the large SELECT variant.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Statement {
     /// Begin a new transaction.
     Begin { read_only: bool, as_of: Option<u64> },
@@ -15,23 +19,42 @@ pub enum Statement {
     /// Explain a statement.
     Explain(Box<Statement>),
     /// Create a new table.
-    CreateTable { name: String, columns: Vec<Column> },
+    CreateTable {
+        name: String,
+        columns: Vec<Column>,
+        if_not_exists: bool,
+    },
+    /// Create a new table from the result of a query.
+    CreateTableAs { name: String, query: Box<Statement> },
     /// Drop a table.
     DropTable { name: String, if_exists: bool },
-    /// Delete matching rows.
+    /// Delete matching rows, optionally joined with other USING tables that
+    /// the WHERE expression can reference, and limited to the given number
+    /// of rows.
     Delete {
         table: String,
+        using: Vec<From>,
         r#where: Option<Expression>,
+        limit: Option<Expression>,
     },
     /// Insert new rows into a table.
     Insert {
         table: String,
+        columns: Option<Vec<String>>, // columns given by values, if specified
         values: Vec<Vec<Expression>>, // rows to insert
     },
-    /// Update rows in a table.
+    /// Insert the result of a query into a table.
+    InsertSelect {
+        table: String,
+        columns: Option<Vec<String>>, // columns given by the query, if specified
+        query: Box<Statement>,
+    },
+    /// Update rows in a table, optionally joined with other FROM tables that
+    /// the SET and WHERE expressions can reference.
     Update {
         table: String,
         set: BTreeMap<String, Option<Expression>>, // column → value, None for default value
+        from: Vec<From>,
         r#where: Option<Expression>,
     },
     /// Select matching rows.
@@ -41,14 +64,15 @@ pub enum Statement {
         r#where: Option<Expression>,
         group_by: Vec<Expression>,
         having: Option<Expression>,
-        order_by: Vec<(Expression, Direction)>,
+        order_by: Vec<(Expression, Direction, Option<NullOrder>)>,
         offset: Option<Expression>,
         limit: Option<Expression>,
+        sample: Option<(Expression, Option<Expression>)>, // size and seed
     },
 }
 
 /// A FROM item.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum From {
     /// A table.
     Table { name: String, alias: Option<String> },
@@ -62,7 +86,7 @@ pub enum From {
 }
 
 /// A CREATE TABLE column definition.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Column {
     pub name: String,
     pub datatype: DataType,
@@ -75,7 +99,7 @@ pub struct Column {
 }
 
 /// JOIN types.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum JoinType {
     Cross,
     Inner,
@@ -95,12 +119,19 @@ impl JoinType {
 }
 
 /// ORDER BY direction.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Direction {
     Ascending,
     Descending,
 }
 
+/// ORDER BY NULL placement: NULLS FIRST or NULLS LAST.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NullOrder {
+    First,
+    Last,
+}
+
 /// Expressions. Can be nested.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Expression {
@@ -112,8 +143,22 @@ pub enum Expression {
     Literal(Literal),
     /// A function call (name and parameters).
     Function(String, Vec<Expression>),
+    /// A conversion to the given data type: CAST(expr AS type).
+    Cast(Box<Expression>, DataType),
     /// An operator.
     Operator(Operator),
+    /// A parenthesized subquery yielding a single value, e.g. (SELECT 1).
+    /// It may reference columns of the enclosing query.
+    Subquery(Box<Statement>),
+    /// An EXISTS subquery, which is true if the subquery yields any rows. It
+    /// may reference columns of the enclosing query.
+    Exists(Box<Statement>),
+    /// The DEFAULT keyword, i.e. the column's default value. Only valid as an
+    /// INSERT value.
+    Default,
+    /// A row value, e.g. (a, b). Only valid when compared with another row
+    /// value of the same length, e.g. (a, b) < (1, 2).
+    Row(Vec<Expression>),
 }
 
 /// Expression literal values.
@@ -124,6 +169,10 @@ pub enum Literal {
     Integer(i32),
     Float(f32),
     String(String),
+    /// A time of day in seconds since midnight, e.g. TIME '12:30:00'.
+    Time(u32),
+    /// An interval in seconds, e.g. INTERVAL '1 hour'.
+    Interval(i32),
 }
 
 /// To allow using Expressions and Literals in e.g. hashmaps, implement simple
@@ -138,6 +187,8 @@ impl std::cmp::PartialEq for Literal {
             // Implies NaN == NaN but -NaN != NaN. Similarly with +/-0.0.
             (Self::Float(l), Self::Float(r)) => l.to_bits() == r.to_bits(),
             (Self::String(l), Self::String(r)) => l == r,
+            (Self::Time(l), Self::Time(r)) => l == r,
+            (Self::Interval(l), Self::Interval(r)) => l == r,
             (l, r) => core::mem::discriminant(l) == core::mem::discriminant(r),
         }
     }
@@ -154,6 +205,8 @@ impl std::hash::Hash for Literal {
             Self::Integer(v) => v.hash(state),
             Self::Float(v) => v.to_bits().hash(state),
             Self::String(v) => v.hash(state),
+            Self::Time(v) => v.hash(state),
+            Self::Interval(v) => v.hash(state),
         }
     }
 }
@@ -178,6 +231,9 @@ pub enum Operator {
     NotEqual(Box<Expression>, Box<Expression>),    // a != b
 
     Add(Box<Expression>, Box<Expression>),          // a + b
+    BitwiseAnd(Box<Expression>, Box<Expression>),   // a & b
+    BitwiseOr(Box<Expression>, Box<Expression>),    // a | b
+    BitwiseXor(Box<Expression>, Box<Expression>),   // a # b
     Divide(Box<Expression>, Box<Expression>),       // a / b
     Exponentiate(Box<Expression>, Box<Expression>), // a ^ b
     Factorial(Box<Expression>),                     // a!
@@ -187,7 +243,10 @@ pub enum Operator {
     Remainder(Box<Expression>, Box<Expression>),    // a % b
     Subtract(Box<Expression>, Box<Expression>),     // a - b
 
-    Like(Box<Expression>, Box<Expression>), // a LIKE b
+    Like(Box<Expression>, Box<Expression>),  // a LIKE b
+    ILike(Box<Expression>, Box<Expression>), // a ILIKE b
+
+    Index(Box<Expression>, Box<Expression>), // a[b]
 }
 
 impl Expression {
@@ -201,11 +260,16 @@ impl Expression {
         match self {
             Self::Operator(Add(lhs, rhs))
             | Self::Operator(And(lhs, rhs))
+            | Self::Operator(BitwiseAnd(lhs, rhs))
+            | Self::Operator(BitwiseOr(lhs, rhs))
+            | Self::Operator(BitwiseXor(lhs, rhs))
             | Self::Operator(Divide(lhs, rhs))
             | Self::Operator(Equal(lhs, rhs))
             | Self::Operator(Exponentiate(lhs, rhs))
             | Self::Operator(GreaterThan(lhs, rhs))
             | Self::Operator(GreaterThanOrEqual(lhs, rhs))
+            | Self::Operator(ILike(lhs, rhs))
+            | Self::Operator(Index(lhs, rhs))
             | Self::Operator(LessThan(lhs, rhs))
             | Self::Operator(LessThanOrEqual(lhs, rhs))
             | Self::Operator(Like(lhs, rhs))
@@ -221,9 +285,19 @@ impl Expression {
             | Self::Operator(Negate(expr))
             | Self::Operator(Not(expr)) => expr.walk(visitor),
 
+            Self::Cast(expr, _) => expr.walk(visitor),
+
             Self::Function(_, exprs) => exprs.iter().any(|expr| expr.walk(visitor)),
 
-            Self::All | Self::Column(_, _) | Self::Literal(_) => true,
+            Self::Row(exprs) => exprs.iter().all(|expr| expr.walk(visitor)),
+
+            // Subqueries are planned separately, so we don't descend into them.
+            Self::All
+            | Self::Column(_, _)
+            | Self::Default
+            | Self::Exists(_)
+            | Self::Literal(_)
+            | Self::Subquery(_) => true,
         }
     }
 
@@ -244,11 +318,16 @@ impl Expression {
         match self {
             Self::Operator(Add(lhs, rhs))
             | Self::Operator(And(lhs, rhs))
+            | Self::Operator(BitwiseAnd(lhs, rhs))
+            | Self::Operator(BitwiseOr(lhs, rhs))
+            | Self::Operator(BitwiseXor(lhs, rhs))
             | Self::Operator(Divide(lhs, rhs))
             | Self::Operator(Equal(lhs, rhs))
             | Self::Operator(Exponentiate(lhs, rhs))
             | Self::Operator(GreaterThan(lhs, rhs))
             | Self::Operator(GreaterThanOrEqual(lhs, rhs))
+            | Self::Operator(ILike(lhs, rhs))
+            | Self::Operator(Index(lhs, rhs))
             | Self::Operator(LessThan(lhs, rhs))
             | Self::Operator(LessThanOrEqual(lhs, rhs))
             | Self::Operator(Like(lhs, rhs))
@@ -267,9 +346,18 @@ impl Expression {
             | Self::Operator(Negate(expr))
             | Self::Operator(Not(expr)) => expr.collect(visitor, c),
 
-            Self::Function(_, exprs) => exprs.iter().for_each(|expr| expr.collect(visitor, c)),
+            Self::Cast(expr, _) => expr.collect(visitor, c),
+
+            Self::Function(_, exprs) | Self::Row(exprs) => {
+                exprs.iter().for_each(|expr| expr.collect(visitor, c))
+            }
 
-            Self::All | Self::Column(_, _) | Self::Literal(_) => {}
+            Self::All
+            | Self::Column(_, _)
+            | Self::Default
+            | Self::Exists(_)
+            | Self::Literal(_)
+            | Self::Subquery(_) => {}
         }
     }
 }
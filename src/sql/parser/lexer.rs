@@ -39,12 +39,17 @@ pub enum Token {
     Slash,              // /
     Caret,              // ^
     Percent,            // %
+    Ampersand,          // &
+    Pipe,               // |
+    Hash,               // #
     Exclamation,        // !
     Question,           // ?
     Comma,              // ,
     Semicolon,          // ;
     OpenParen,          // (
     CloseParen,         // )
+    OpenBracket,        // [
+    CloseBracket,       // ]
 }
 
 impl std::fmt::Display for Token {
@@ -68,12 +73,17 @@ impl std::fmt::Display for Token {
             Self::Slash => "/",
             Self::Caret => "^",
             Self::Percent => "%",
+            Self::Ampersand => "&",
+            Self::Pipe => "|",
+            Self::Hash => "#",
             Self::Exclamation => "!",
             Self::Question => "?",
             Self::Comma => ",",
             Self::Semicolon => ";",
             Self::OpenParen => "(",
             Self::CloseParen => ")",
+            Self::OpenBracket => "[",
+            Self::CloseBracket => "]",
         })
     }
 }
@@ -94,6 +104,7 @@ pub enum Keyword {
     Bool,
     Boolean,
     By,
+    Cast,
     Commit,
     Create,
     Cross,
@@ -110,12 +121,15 @@ pub enum Keyword {
     Group,
     Having,
     If,
+    ILike,
+    In,
     Index,
     Infinity,
     Inner,
     Insert,
     Int,
     Integer,
+    Interval,
     Into,
     Is,
     Join,
@@ -126,6 +140,7 @@ pub enum Keyword {
     NaN,
     Not,
     Null,
+    Nulls,
     Of,
     Offset,
     On,
@@ -136,8 +151,10 @@ pub enum Keyword {
     Primary,
     Read,
     References,
+    Repeatable,
     Right,
     Rollback,
+    Sample,
     Select,
     Set,
     String,
@@ -149,6 +166,7 @@ pub enum Keyword {
     True,
     Unique,
     Update,
+    Using,
     Values,
     Varchar,
     Where,
@@ -174,6 +192,7 @@ impl TryFrom<&str> for Keyword {
             "bool" => Self::Bool,
             "boolean" => Self::Boolean,
             "by" => Self::By,
+            "cast" => Self::Cast,
             "commit" => Self::Commit,
             "create" => Self::Create,
             "cross" => Self::Cross,
@@ -190,12 +209,15 @@ impl TryFrom<&str> for Keyword {
             "group" => Self::Group,
             "having" => Self::Having,
             "if" => Self::If,
+            "ilike" => Self::ILike,
+            "in" => Self::In,
             "index" => Self::Index,
             "infinity" => Self::Infinity,
             "inner" => Self::Inner,
             "insert" => Self::Insert,
             "int" => Self::Int,
             "integer" => Self::Integer,
+            "interval" => Self::Interval,
             "into" => Self::Into,
             "is" => Self::Is,
             "join" => Self::Join,
@@ -206,6 +228,7 @@ impl TryFrom<&str> for Keyword {
             "nan" => Self::NaN,
             "not" => Self::Not,
             "null" => Self::Null,
+            "nulls" => Self::Nulls,
             "of" => Self::Of,
             "offset" => Self::Offset,
             "on" => Self::On,
@@ -216,8 +239,10 @@ impl TryFrom<&str> for Keyword {
             "primary" => Self::Primary,
             "read" => Self::Read,
             "references" => Self::References,
+            "repeatable" => Self::Repeatable,
             "right" => Self::Right,
             "rollback" => Self::Rollback,
+            "sample" => Self::Sample,
             "select" => Self::Select,
             "set" => Self::Set,
             "string" => Self::String,
@@ -229,6 +254,7 @@ impl TryFrom<&str> for Keyword {
             "true" => Self::True,
             "unique" => Self::Unique,
             "update" => Self::Update,
+            "using" => Self::Using,
             "values" => Self::Values,
             "varchar" => Self::Varchar,
             "where" => Self::Where,
@@ -249,6 +275,7 @@ impl std::fmt::Display for Keyword {
             Self::Bool => "BOOL",
             Self::Boolean => "BOOLEAN",
             Self::By => "BY",
+            Self::Cast => "CAST",
             Self::Commit => "COMMIT",
             Self::Create => "CREATE",
             Self::Cross => "CROSS",
@@ -265,12 +292,15 @@ impl std::fmt::Display for Keyword {
             Self::Group => "GROUP",
             Self::Having => "HAVING",
             Self::If => "IF",
+            Self::ILike => "ILIKE",
+            Self::In => "IN",
             Self::Index => "INDEX",
             Self::Infinity => "INFINITY",
             Self::Inner => "INNER",
             Self::Insert => "INSERT",
             Self::Int => "INT",
             Self::Integer => "INTEGER",
+            Self::Interval => "INTERVAL",
             Self::Into => "INTO",
             Self::Is => "IS",
             Self::Join => "JOIN",
@@ -281,6 +311,7 @@ impl std::fmt::Display for Keyword {
             Self::NaN => "NAN",
             Self::Not => "NOT",
             Self::Null => "NULL",
+            Self::Nulls => "NULLS",
             Self::Of => "OF",
             Self::Offset => "OFFSET",
             Self::On => "ON",
@@ -291,8 +322,10 @@ impl std::fmt::Display for Keyword {
             Self::Primary => "PRIMARY",
             Self::Read => "READ",
             Self::References => "REFERENCES",
+            Self::Repeatable => "REPEATABLE",
             Self::Right => "RIGHT",
             Self::Rollback => "ROLLBACK",
+            Self::Sample => "SAMPLE",
             Self::Select => "SELECT",
             Self::Set => "SET",
             Self::String => "STRING",
@@ -304,6 +337,7 @@ impl std::fmt::Display for Keyword {
             Self::True => "TRUE",
             Self::Unique => "UNIQUE",
             Self::Update => "UPDATE",
+            Self::Using => "USING",
             Self::Values => "VALUES",
             Self::Varchar => "VARCHAR",
             Self::Where => "WHERE",
@@ -412,6 +446,17 @@ impl<'a> Lexer<'a> {
     fn scan_number(&mut self) -> Option<Token> {
         // Scan the integer part. There must be one digit.
         let mut number = self.next_if(|c| c.is_ascii_digit())?.to_string();
+        // Scan hexadecimal (0x) and binary (0b) integers. Any trailing
+        // alphanumeric characters are included, and validated by the parser.
+        if number == "0" {
+            if let Some(prefix) = self.next_if(|c| matches!(c, 'x' | 'X' | 'b' | 'B')) {
+                number.push(prefix);
+                while let Some(c) = self.next_if(|c| c.is_ascii_alphanumeric()) {
+                    number.push(c)
+                }
+                return Some(Token::Number(number));
+            }
+        }
         while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
             number.push(c)
         }
@@ -467,12 +512,17 @@ impl<'a> Lexer<'a> {
                 '/' => Token::Slash,
                 '^' => Token::Caret,
                 '%' => Token::Percent,
+                '&' => Token::Ampersand,
+                '|' => Token::Pipe,
+                '#' => Token::Hash,
                 '!' => Token::Exclamation,
                 '?' => Token::Question,
                 ',' => Token::Comma,
                 ';' => Token::Semicolon,
                 '(' => Token::OpenParen,
                 ')' => Token::CloseParen,
+                '[' => Token::OpenBracket,
+                ']' => Token::CloseBracket,
                 _ => return None,
             })
         })?;
@@ -3,6 +3,7 @@
 use super::{ast, Keyword, Lexer, Token};
 use crate::common::Result;
 use crate::errinput;
+use crate::types::field::Field;
 use crate::types::DataType;
 
 /// The SQL parser takes tokens from the lexer and parses the SQL syntax into an
@@ -177,7 +178,20 @@ impl<'a> Parser<'a> {
     fn parse_create_table(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Create.into())?;
         self.expect(Keyword::Table.into())?;
+        let mut if_not_exists = false;
+        if self.next_is(Keyword::If.into()) {
+            self.expect(Keyword::Not.into())?;
+            self.expect(Keyword::Exists.into())?;
+            if_not_exists = true;
+        }
         let name = self.next_ident()?;
+        if self.next_is(Keyword::As.into()) {
+            if if_not_exists {
+                return errinput!("IF NOT EXISTS is not supported for CREATE TABLE AS");
+            }
+            let query = Box::new(self.parse_select()?);
+            return Ok(ast::Statement::CreateTableAs { name, query });
+        }
         self.expect(Token::OpenParen)?;
         let mut columns = Vec::new();
         loop {
@@ -187,19 +201,29 @@ impl<'a> Parser<'a> {
             }
         }
         self.expect(Token::CloseParen)?;
-        Ok(ast::Statement::CreateTable { name, columns })
+        Ok(ast::Statement::CreateTable {
+            name,
+            columns,
+            if_not_exists,
+        })
     }
 
-    /// Parses a CREATE TABLE column definition.
-    fn parse_create_table_column(&mut self) -> Result<ast::Column> {
-        let name = self.next_ident()?;
-        let datatype = match self.next()? {
+    /// Parses a data type name, e.g. INTEGER.
+    fn parse_datatype(&mut self) -> Result<DataType> {
+        Ok(match self.next()? {
             Token::Keyword(Keyword::Bool | Keyword::Boolean) => DataType::Bool,
             Token::Keyword(Keyword::Float | Keyword::Double) => DataType::Float,
             Token::Keyword(Keyword::Int | Keyword::Integer) => DataType::Int,
             Token::Keyword(Keyword::String | Keyword::Text | Keyword::Varchar) => DataType::Text,
+            Token::Keyword(Keyword::Time) => DataType::Time,
             token => return errinput!("unexpected token {token}"),
-        };
+        })
+    }
+
+    /// Parses a CREATE TABLE column definition.
+    fn parse_create_table_column(&mut self) -> Result<ast::Column> {
+        let name = self.next_ident()?;
+        let datatype = self.parse_datatype()?;
         let mut column = ast::Column {
             name,
             datatype,
@@ -257,9 +281,18 @@ impl<'a> Parser<'a> {
         self.expect(Keyword::Delete.into())?;
         self.expect(Keyword::From.into())?;
         let table = self.next_ident()?;
+        let mut using = Vec::new();
+        if self.next_is(Keyword::Using.into()) {
+            using = self.parse_from_items()?;
+        }
         Ok(ast::Statement::Delete {
             table,
+            using,
             r#where: self.parse_where_clause()?,
+            limit: self
+                .next_is(Keyword::Limit.into())
+                .then(|| self.parse_expression())
+                .transpose()?,
         })
     }
 
@@ -281,6 +314,14 @@ impl<'a> Parser<'a> {
             self.expect(Token::CloseParen)?;
         }
 
+        if self.peek()? == Some(&Keyword::Select.into()) {
+            let query = Box::new(self.parse_select()?);
+            return Ok(ast::Statement::InsertSelect {
+                table,
+                columns,
+                query,
+            });
+        }
         self.expect(Keyword::Values.into())?;
 
         let mut values = Vec::new();
@@ -288,7 +329,10 @@ impl<'a> Parser<'a> {
             let mut row = Vec::new();
             self.expect(Token::OpenParen)?;
             loop {
-                row.push(self.parse_expression()?);
+                match self.next_is(Keyword::Default.into()) {
+                    true => row.push(ast::Expression::Default),
+                    false => row.push(self.parse_expression()?),
+                }
                 if !self.next_is(Token::Comma) {
                     break;
                 }
@@ -300,7 +344,11 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Ok(ast::Statement::Insert { table, values })
+        Ok(ast::Statement::Insert {
+            table,
+            columns,
+            values,
+        })
     }
 
     /// Parses an UPDATE statement.
@@ -311,10 +359,33 @@ impl<'a> Parser<'a> {
         let mut set = std::collections::BTreeMap::new();
         loop {
             let column = self.next_ident()?;
+            // Compound assignments like SET a += 1 are shorthand for
+            // SET a = a + 1.
+            let compound = self.next_if(|token| {
+                matches!(
+                    token,
+                    Token::Plus | Token::Minus | Token::Asterisk | Token::Slash | Token::Percent
+                )
+            });
             self.expect(Token::Equal)?;
-            let expr = (!self.next_is(Keyword::Default.into()))
+            let mut expr = (!self.next_is(Keyword::Default.into()))
                 .then(|| self.parse_expression())
                 .transpose()?;
+            if let Some(token) = compound {
+                let Some(rhs) = expr.take() else {
+                    return errinput!("can't use {token}= with DEFAULT");
+                };
+                let lhs = ast::Expression::Column(Some(table.clone()), column.clone()).into();
+                let rhs = rhs.into();
+                expr = Some(ast::Expression::Operator(match token {
+                    Token::Plus => ast::Operator::Add(lhs, rhs),
+                    Token::Minus => ast::Operator::Subtract(lhs, rhs),
+                    Token::Asterisk => ast::Operator::Multiply(lhs, rhs),
+                    Token::Slash => ast::Operator::Divide(lhs, rhs),
+                    Token::Percent => ast::Operator::Remainder(lhs, rhs),
+                    token => panic!("unexpected compound assignment token {token}"),
+                }));
+            }
             if set.contains_key(&column) {
                 return errinput!("column {column} set multiple times");
             }
@@ -326,6 +397,7 @@ impl<'a> Parser<'a> {
         Ok(ast::Statement::Update {
             table,
             set,
+            from: self.parse_from_clause()?,
             r#where: self.parse_where_clause()?,
         })
     }
@@ -347,9 +419,27 @@ impl<'a> Parser<'a> {
                 .next_is(Keyword::Offset.into())
                 .then(|| self.parse_expression())
                 .transpose()?,
+            sample: self.parse_sample_clause()?,
         })
     }
 
+    /// Parses a SAMPLE clause, if present: SAMPLE n [REPEATABLE (seed)].
+    fn parse_sample_clause(
+        &mut self,
+    ) -> Result<Option<(ast::Expression, Option<ast::Expression>)>> {
+        if !self.next_is(Keyword::Sample.into()) {
+            return Ok(None);
+        }
+        let size = self.parse_expression()?;
+        let mut seed = None;
+        if self.next_is(Keyword::Repeatable.into()) {
+            self.expect(Token::OpenParen)?;
+            seed = Some(self.parse_expression()?);
+            self.expect(Token::CloseParen)?;
+        }
+        Ok(Some((size, seed)))
+    }
+
     /// Parses a SELECT clause, if present.
     fn parse_select_clause(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
         if !self.next_is(Keyword::Select.into()) {
@@ -378,6 +468,11 @@ impl<'a> Parser<'a> {
         if !self.next_is(Keyword::From.into()) {
             return Ok(Vec::new());
         }
+        self.parse_from_items()
+    }
+
+    /// Parses a comma-separated list of FROM items, e.g. after FROM or USING.
+    fn parse_from_items(&mut self) -> Result<Vec<ast::From>> {
         let mut from = Vec::new();
         loop {
             let mut item = self.parse_from_table()?;
@@ -473,7 +568,9 @@ impl<'a> Parser<'a> {
     }
 
     /// Parses an ORDER BY clause, if present.
-    fn parse_order_by_clause(&mut self) -> Result<Vec<(ast::Expression, ast::Direction)>> {
+    fn parse_order_by_clause(
+        &mut self,
+    ) -> Result<Vec<(ast::Expression, ast::Direction, Option<ast::NullOrder>)>> {
         if !self.next_is(Keyword::Order.into()) {
             return Ok(Vec::new());
         }
@@ -488,7 +585,17 @@ impl<'a> Parser<'a> {
                     _ => None,
                 })
                 .unwrap_or(ast::Direction::Ascending);
-            order_by.push((expr, order));
+            // FIRST and LAST aren't reserved keywords, since they're common
+            // identifiers.
+            let mut nulls = None;
+            if self.next_is(Keyword::Nulls.into()) {
+                nulls = match self.next_ident()?.as_str() {
+                    "first" => Some(ast::NullOrder::First),
+                    "last" => Some(ast::NullOrder::Last),
+                    ident => return errinput!("expected FIRST or LAST, got {ident}"),
+                };
+            }
+            order_by.push((expr, order, nulls));
             if !self.next_is(Token::Comma) {
                 break;
             }
@@ -512,7 +619,15 @@ impl<'a> Parser<'a> {
             let at_precedence = prefix.precedence() + prefix.associativity();
             prefix.build(self.parse_expression_at(at_precedence)?)
         } else {
-            self.parse_expression_atom()?
+            let mut atom = self.parse_expression_atom()?;
+            // Apply any array subscripts, e.g. a[1]. These bind tighter than
+            // any operator.
+            while self.next_is(Token::OpenBracket) {
+                let index = self.parse_expression()?;
+                self.expect(Token::CloseBracket)?;
+                atom = ast::Operator::Index(Box::new(atom), Box::new(index)).into();
+            }
+            atom
         };
         // Apply any postfix operators for the left-hand atom.
         while let Some(postfix) = self.parse_postfix_operator(min_precedence)? {
@@ -537,6 +652,7 @@ impl<'a> Parser<'a> {
     /// * A literal value.
     /// * A column name.
     /// * A function call.
+    /// * A parenthesized subquery, optionally preceded by EXISTS.
     /// * A parenthesized expression.
     fn parse_expression_atom(&mut self) -> Result<ast::Expression> {
         Ok(match self.next()? {
@@ -547,6 +663,10 @@ impl<'a> Parser<'a> {
             Token::Number(n) if n.chars().all(|c| c.is_ascii_digit()) => {
                 ast::Literal::Integer(n.parse()?).into()
             }
+            // Hexadecimal (0x) or binary (0b) integer.
+            Token::Number(n) if matches!(n.get(1..2), Some("x" | "X" | "b" | "B")) => {
+                ast::Literal::Integer(Field::parse_integer(&n)?).into()
+            }
             Token::Number(n) => ast::Literal::Float(n.parse()?).into(),
             Token::String(s) => ast::Literal::String(s).into(),
             Token::Keyword(Keyword::True) => ast::Literal::Boolean(true).into(),
@@ -554,6 +674,34 @@ impl<'a> Parser<'a> {
             Token::Keyword(Keyword::Infinity) => ast::Literal::Float(f32::INFINITY).into(),
             Token::Keyword(Keyword::NaN) => ast::Literal::Float(f32::NAN).into(),
             Token::Keyword(Keyword::Null) => ast::Literal::Null.into(),
+            // Typed time and interval literals, e.g. TIME '12:30' and INTERVAL '1 hour'.
+            Token::Keyword(Keyword::Time) => match self.next()? {
+                Token::String(s) => ast::Literal::Time(Field::parse_time(&s)?).into(),
+                token => return errinput!("expected time string, found {token}"),
+            },
+            Token::Keyword(Keyword::Interval) => match self.next()? {
+                Token::String(s) => ast::Literal::Interval(Field::parse_interval(&s)?).into(),
+                token => return errinput!("expected interval string, found {token}"),
+            },
+
+            // Type conversion, e.g. CAST(a AS FLOAT).
+            Token::Keyword(Keyword::Cast) => {
+                self.expect(Token::OpenParen)?;
+                let expr = self.parse_expression()?;
+                self.expect(Keyword::As.into())?;
+                let datatype = self.parse_datatype()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Cast(Box::new(expr), datatype)
+            }
+
+            // Substring search, using the SQL syntax POSITION(a IN b).
+            Token::Ident(name) if name == "position" && self.next_is(Token::OpenParen) => {
+                let substring = self.parse_expression()?;
+                self.expect(Keyword::In.into())?;
+                let string = self.parse_expression()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Function(name, vec![substring, string])
+            }
 
             // Function call.
             Token::Ident(name) if self.next_is(Token::OpenParen) => {
@@ -573,11 +721,33 @@ impl<'a> Parser<'a> {
             }
             Token::Ident(column) => ast::Expression::Column(None, column),
 
-            // Parenthesized expression.
+            // Scalar subquery.
+            Token::OpenParen if self.peek()? == Some(&Keyword::Select.into()) => {
+                let statement = self.parse_select()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Subquery(Box::new(statement))
+            }
+
+            // EXISTS subquery.
+            Token::Keyword(Keyword::Exists) => {
+                self.expect(Token::OpenParen)?;
+                let statement = self.parse_select()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Exists(Box::new(statement))
+            }
+
+            // Parenthesized expression, or a row value if there are several
+            // comma-separated expressions.
             Token::OpenParen => {
-                let expr = self.parse_expression()?;
+                let mut exprs = vec![self.parse_expression()?];
+                while self.next_is(Token::Comma) {
+                    exprs.push(self.parse_expression()?);
+                }
                 self.expect(Token::CloseParen)?;
-                expr
+                match exprs.len() {
+                    1 => exprs.remove(0),
+                    _ => ast::Expression::Row(exprs),
+                }
             }
 
             token => return errinput!("expected expression atom, found {token}"),
@@ -603,12 +773,15 @@ impl<'a> Parser<'a> {
     fn parse_infix_operator(&mut self, min_precedence: Precedence) -> Option<InfixOperator> {
         self.next_if_map(|token| {
             let operator = match token {
+                Token::Ampersand => InfixOperator::BitwiseAnd,
                 Token::Asterisk => InfixOperator::Multiply,
                 Token::Caret => InfixOperator::Exponentiate,
                 Token::Equal => InfixOperator::Equal,
                 Token::GreaterThan => InfixOperator::GreaterThan,
                 Token::GreaterThanOrEqual => InfixOperator::GreaterThanOrEqual,
+                Token::Hash => InfixOperator::BitwiseXor,
                 Token::Keyword(Keyword::And) => InfixOperator::And,
+                Token::Keyword(Keyword::ILike) => InfixOperator::ILike,
                 Token::Keyword(Keyword::Like) => InfixOperator::Like,
                 Token::Keyword(Keyword::Or) => InfixOperator::Or,
                 Token::LessOrGreaterThan => InfixOperator::NotEqual,
@@ -617,6 +790,7 @@ impl<'a> Parser<'a> {
                 Token::Minus => InfixOperator::Subtract,
                 Token::NotEqual => InfixOperator::NotEqual,
                 Token::Percent => InfixOperator::Remainder,
+                Token::Pipe => InfixOperator::BitwiseOr,
                 Token::Plus => InfixOperator::Add,
                 Token::Slash => InfixOperator::Divide,
                 _ => return None,
@@ -680,7 +854,7 @@ impl PrefixOperator {
     fn precedence(&self) -> Precedence {
         match self {
             Self::Not => 3,
-            Self::Minus | Self::Plus => 10,
+            Self::Minus | Self::Plus => 11,
         }
     }
 
@@ -705,11 +879,15 @@ impl PrefixOperator {
 enum InfixOperator {
     Add,                // a + b
     And,                // a AND b
+    BitwiseAnd,         // a & b
+    BitwiseOr,          // a | b
+    BitwiseXor,         // a # b
     Divide,             // a / b
     Equal,              // a = b
     Exponentiate,       // a ^ b
     GreaterThan,        // a > b
     GreaterThanOrEqual, // a >= b
+    ILike,              // a ILIKE b
     LessThan,           // a < b
     LessThanOrEqual,    // a <= b
     Like,               // a LIKE b
@@ -724,20 +902,22 @@ impl InfixOperator {
     /// The operator precedence.
     ///
     /// Mostly follows Postgres, except IS and LIKE having same precedence as =.
-    /// This is similar to SQLite and MySQL.
+    /// This is similar to SQLite and MySQL. Like Postgres, the bitwise
+    /// operators share a precedence level between comparisons and addition.
     fn precedence(&self) -> Precedence {
         match self {
             Self::Or => 1,
             Self::And => 2,
             // Self::Not => 3
-            Self::Equal | Self::NotEqual | Self::Like => 4, // and Self::Is
+            Self::Equal | Self::NotEqual | Self::Like | Self::ILike => 4, // and Self::Is
             Self::GreaterThan
             | Self::GreaterThanOrEqual
             | Self::LessThan
             | Self::LessThanOrEqual => 5,
-            Self::Add | Self::Subtract => 6,
-            Self::Multiply | Self::Divide | Self::Remainder => 7,
-            Self::Exponentiate => 8,
+            Self::BitwiseAnd | Self::BitwiseOr | Self::BitwiseXor => 6,
+            Self::Add | Self::Subtract => 7,
+            Self::Multiply | Self::Divide | Self::Remainder => 8,
+            Self::Exponentiate => 9,
         }
     }
 
@@ -755,11 +935,15 @@ impl InfixOperator {
         match self {
             Self::Add => ast::Operator::Add(lhs, rhs).into(),
             Self::And => ast::Operator::And(lhs, rhs).into(),
+            Self::BitwiseAnd => ast::Operator::BitwiseAnd(lhs, rhs).into(),
+            Self::BitwiseOr => ast::Operator::BitwiseOr(lhs, rhs).into(),
+            Self::BitwiseXor => ast::Operator::BitwiseXor(lhs, rhs).into(),
             Self::Divide => ast::Operator::Divide(lhs, rhs).into(),
             Self::Equal => ast::Operator::Equal(lhs, rhs).into(),
             Self::Exponentiate => ast::Operator::Exponentiate(lhs, rhs).into(),
             Self::GreaterThan => ast::Operator::GreaterThan(lhs, rhs).into(),
             Self::GreaterThanOrEqual => ast::Operator::GreaterThanOrEqual(lhs, rhs).into(),
+            Self::ILike => ast::Operator::ILike(lhs, rhs).into(),
             Self::LessThan => ast::Operator::LessThan(lhs, rhs).into(),
             Self::LessThanOrEqual => ast::Operator::LessThanOrEqual(lhs, rhs).into(),
             Self::Like => ast::Operator::Like(lhs, rhs).into(),
@@ -784,7 +968,7 @@ impl PostfixOperator {
     fn precedence(&self) -> Precedence {
         match self {
             Self::Is(_) | Self::IsNot(_) => 4,
-            Self::Factorial => 9,
+            Self::Factorial => 10,
         }
     }
 
@@ -1,9 +1,10 @@
-use crate::common::Result;
+use crate::common::{Error, Result};
 use crate::errinput;
 use crate::sql::parser::ast;
-use crate::sql::planner::Node;
+use crate::sql::planner::{BoxedNode, Node};
 use crate::storage::tuple::Row;
-use crate::types::field::{Field, Label};
+use crate::types::field::{Field, Label, Overflow};
+use crate::types::{DataType, Table};
 use serde::{Deserialize, Serialize};
 
 /// An expression, made up of nested operations and values. Values are either
@@ -19,6 +20,13 @@ pub enum Expression {
     Constant(Field),
     /// A column reference. Used as row index when evaluating expressions.
     Column(usize),
+    /// A column reference into the enclosing query's row, used by correlated
+    /// subqueries. Replaced by a constant before the subquery is executed.
+    OuterColumn(usize),
+    /// A subquery yielding a single value, or NULL if it yields no rows. It
+    /// must be bound to the current row and executed with the transaction
+    /// before the expression can be evaluated (see execution::subquery).
+    ScalarSubquery(BoxedNode),
 
     /// Logical AND of two booleans: a AND b.
     And(Box<Expression>, Box<Expression>),
@@ -36,14 +44,26 @@ pub enum Expression {
     /// Checks for the given value: IS NULL or IS NAN.
     Is(Box<Expression>, Field),
 
+    /// The absolute value of a number: abs(a).
+    Absolute(Box<Expression>),
     /// Adds two numbers: a + b.
     Add(Box<Expression>, Box<Expression>),
+    /// Bitwise AND of two integers: a & b.
+    BitwiseAnd(Box<Expression>, Box<Expression>),
+    /// Bitwise OR of two integers: a | b.
+    BitwiseOr(Box<Expression>, Box<Expression>),
+    /// Bitwise XOR of two integers: a # b.
+    BitwiseXor(Box<Expression>, Box<Expression>),
+    /// Rounds a number up to the nearest integer: ceil(a).
+    Ceil(Box<Expression>),
     /// Divides two numbers: a / b.
     Divide(Box<Expression>, Box<Expression>),
     /// Exponentiates two numbers, i.e. a ^ b.
     Exponentiate(Box<Expression>, Box<Expression>),
     /// Takes the factorial of a number: 4! = 4*3*2*1.
     Factorial(Box<Expression>),
+    /// Rounds a number down to the nearest integer: floor(a).
+    Floor(Box<Expression>),
     /// The identify function, which simply returns the same number: +a.
     Identity(Box<Expression>),
     /// Multiplies two numbers: a * b.
@@ -52,6 +72,10 @@ pub enum Expression {
     Negate(Box<Expression>),
     /// The remainder after dividing two numbers: a % b.
     Remainder(Box<Expression>, Box<Expression>),
+    /// Rounds a number to the given number of decimal digits, rounding
+    /// halfway cases away from zero: round(a, d). Negative digits round to
+    /// the left of the decimal point.
+    Round(Box<Expression>, Box<Expression>),
     /// Takes the square root of a number: √a.
     SquareRoot(Box<Expression>),
     /// Subtracts two numbers: a - b.
@@ -59,6 +83,31 @@ pub enum Expression {
 
     // Checks if a string matches a pattern: a LIKE b.
     Like(Box<Expression>, Box<Expression>),
+    /// Checks if a string matches a pattern, ignoring case: a ILIKE b.
+    ILike(Box<Expression>, Box<Expression>),
+    /// Removes leading and trailing whitespace from a string, or any of the
+    /// given characters if specified: trim(a) or trim(a, chars).
+    Trim(Box<Expression>, Option<Box<Expression>>),
+    /// Like Trim, but only removes leading characters: ltrim(a).
+    LeftTrim(Box<Expression>, Option<Box<Expression>>),
+    /// Like Trim, but only removes trailing characters: rtrim(a).
+    RightTrim(Box<Expression>, Option<Box<Expression>>),
+    /// Replaces all occurrences of a substring in a string: replace(a, from, to).
+    Replace(Box<Expression>, Box<Expression>, Box<Expression>),
+    /// The 1-based character position of the first occurrence of a substring
+    /// in a string, or 0 if not found: POSITION(a IN b).
+    Position(Box<Expression>, Box<Expression>),
+
+    /// Looks up an array element by its 1-based index: a[i]. NULL if the index
+    /// is out of bounds.
+    Index(Box<Expression>, Box<Expression>),
+    /// The number of elements in an array: array_length(a).
+    ArrayLength(Box<Expression>),
+    /// Converts a value to the given data type: CAST(a AS type). See
+    /// Field::cast_to().
+    Cast(Box<Expression>, DataType),
+    /// Yields NULL if the values are equal, otherwise the first: NULLIF(a, b).
+    NullIf(Box<Expression>, Box<Expression>),
 }
 
 impl Expression {
@@ -72,14 +121,19 @@ impl Expression {
         // Precedence levels, for grouping. Matches the parser precedence.
         fn precedence(expr: &Expression) -> u8 {
             match expr {
-                Column(_) | Constant(_) | SquareRoot(_) => 11,
-                Identity(_) | Negate(_) => 10,
-                Factorial(_) => 9,
-                Exponentiate(_, _) => 8,
-                Multiply(_, _) | Divide(_, _) | Remainder(_, _) => 7,
-                Add(_, _) | Subtract(_, _) => 6,
+                Column(_) | Constant(_) | OuterColumn(_) | ScalarSubquery(_) | SquareRoot(_) => 12,
+                Index(_, _) | ArrayLength(_) | Cast(_, _) | NullIf(_, _) | Absolute(_) => 12,
+                Ceil(_) | Floor(_) | Round(_, _) => 12,
+                Trim(_, _) | LeftTrim(_, _) | RightTrim(_, _) => 12,
+                Replace(_, _, _) | Position(_, _) => 12,
+                Identity(_) | Negate(_) => 11,
+                Factorial(_) => 10,
+                Exponentiate(_, _) => 9,
+                Multiply(_, _) | Divide(_, _) | Remainder(_, _) => 8,
+                Add(_, _) | Subtract(_, _) => 7,
+                BitwiseAnd(_, _) | BitwiseOr(_, _) | BitwiseXor(_, _) => 6,
                 GreaterThan(_, _) | LessThan(_, _) => 5,
-                Equal(_, _) | Like(_, _) | Is(_, _) => 4,
+                Equal(_, _) | Like(_, _) | ILike(_, _) | Is(_, _) => 4,
                 Not(_) => 3,
                 And(_, _) => 2,
                 Or(_, _) => 1,
@@ -101,6 +155,8 @@ impl Expression {
                 Label::None => format!("#{index}"),
                 label => format!("{label}"),
             },
+            OuterColumn(index) => format!("outer#{index}"),
+            ScalarSubquery(_) => "(subquery)".to_string(),
 
             And(lhs, rhs) => format!("{} AND {}", format(lhs), format(rhs)),
             Or(lhs, rhs) => format!("{} OR {}", format(lhs), format(rhs)),
@@ -113,18 +169,49 @@ impl Expression {
             Is(expr, Field::Float(f)) if f.is_nan() => format!("{} IS NAN", format(expr)),
             Is(_, v) => panic!("unexpected IS value {v}"),
 
+            Absolute(expr) => format!("abs({})", expr.format(node)),
             Add(lhs, rhs) => format!("{} + {}", format(lhs), format(rhs)),
+            BitwiseAnd(lhs, rhs) => format!("{} & {}", format(lhs), format(rhs)),
+            BitwiseOr(lhs, rhs) => format!("{} | {}", format(lhs), format(rhs)),
+            BitwiseXor(lhs, rhs) => format!("{} # {}", format(lhs), format(rhs)),
+            Ceil(expr) => format!("ceil({})", expr.format(node)),
             Divide(lhs, rhs) => format!("{} / {}", format(lhs), format(rhs)),
             Exponentiate(lhs, rhs) => format!("{} ^ {}", format(lhs), format(rhs)),
             Factorial(expr) => format!("{}!", format(expr)),
+            Floor(expr) => format!("floor({})", expr.format(node)),
             Identity(expr) => format(expr),
             Multiply(lhs, rhs) => format!("{} * {}", format(lhs), format(rhs)),
             Negate(expr) => format!("-{}", format(expr)),
             Remainder(lhs, rhs) => format!("{} % {}", format(lhs), format(rhs)),
+            Round(expr, digits) => format!("round({}, {})", expr.format(node), digits.format(node)),
             SquareRoot(expr) => format!("sqrt({})", format(expr)),
             Subtract(lhs, rhs) => format!("{} - {}", format(lhs), format(rhs)),
 
             Like(lhs, rhs) => format!("{} LIKE {}", format(lhs), format(rhs)),
+            ILike(lhs, rhs) => format!("{} ILIKE {}", format(lhs), format(rhs)),
+            Trim(expr, chars) | LeftTrim(expr, chars) | RightTrim(expr, chars) => {
+                let name = match self {
+                    LeftTrim(_, _) => "ltrim",
+                    RightTrim(_, _) => "rtrim",
+                    _ => "trim",
+                };
+                match chars {
+                    Some(chars) => format!("{name}({}, {})", expr.format(node), chars.format(node)),
+                    None => format!("{name}({})", expr.format(node)),
+                }
+            }
+            Replace(expr, from, to) => format!(
+                "replace({}, {}, {})",
+                expr.format(node),
+                from.format(node),
+                to.format(node)
+            ),
+            Position(lhs, rhs) => format!("POSITION({} IN {})", lhs.format(node), rhs.format(node)),
+
+            Index(array, index) => format!("{}[{}]", format(array), index.format(node)),
+            ArrayLength(expr) => format!("array_length({})", expr.format(node)),
+            Cast(expr, data_type) => format!("CAST({} AS {data_type})", expr.format(node)),
+            NullIf(lhs, rhs) => format!("nullif({}, {})", lhs.format(node), rhs.format(node)),
         }
     }
 
@@ -137,8 +224,44 @@ impl Expression {
 
     /// Evaluates an expression, returning a value. Column references look up
     /// values in the given row. If None, any Column references will panic.
+    /// Errors on integer overflow.
     pub fn evaluate(&self, row: Option<&Row>) -> Result<Field> {
+        self.evaluate_with(row, Overflow::Error)
+    }
+
+    /// Like evaluate(), but with the given integer overflow behavior for
+    /// addition, subtraction, multiplication, and negation.
+    pub fn evaluate_with(&self, row: Option<&Row>, overflow: Overflow) -> Result<Field> {
+        self.evaluate_mode(row, overflow, None)
+    }
+
+    /// Like evaluate(), but floats compare equal when they're within roughly
+    /// epsilon of each other, as with hash join float keys (see
+    /// `Field::float_key`). Errors if epsilon isn't positive and finite.
+    pub fn evaluate_tolerant(&self, row: Option<&Row>, epsilon: f32) -> Result<Field> {
+        Field::check_epsilon(epsilon)?;
+        self.evaluate_mode(row, Overflow::Error, Some(epsilon))
+    }
+
+    /// Evaluates the expression with the given overflow behavior and float
+    /// equality tolerance, see evaluate_with() and evaluate_tolerant().
+    fn evaluate_mode(
+        &self,
+        row: Option<&Row>,
+        overflow: Overflow,
+        epsilon: Option<f32>,
+    ) -> Result<Field> {
         use Field::*;
+        let eval = |expr: &Expression| expr.evaluate_mode(row, overflow, epsilon);
+        // Compares floats, within epsilon if given. NaN is never equal.
+        let float_eq = |lhs: f32, rhs: f32| -> Result<bool> {
+            match epsilon {
+                Some(epsilon) if lhs.is_finite() && rhs.is_finite() => {
+                    Ok(Float(lhs).float_key(epsilon)? == Float(rhs).float_key(epsilon)?)
+                }
+                _ => Ok(lhs == rhs),
+            }
+        };
         Ok(match self {
             // Constant values return themselves.
             Self::Constant(value) => value.clone(),
@@ -150,9 +273,14 @@ impl Expression {
                 None => panic!("can't reference column {index} with constant evaluation"),
             },
 
+            // Subqueries must be bound by the executor, which has access to
+            // the transaction, before the expression can be evaluated.
+            Self::OuterColumn(index) => return errinput!("unbound outer column {index}"),
+            Self::ScalarSubquery(_) => return errinput!("unbound subquery"),
+
             // Logical AND. Inputs must be boolean or NULL. NULLs generally
             // yield NULL, except the special case NULL AND false == false.
-            Self::And(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::And(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
                 (Boolean(lhs), Boolean(rhs)) => Boolean(lhs && rhs),
                 (Boolean(b), Null) | (Null, Boolean(b)) if !b => Boolean(false),
                 (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
@@ -161,7 +289,7 @@ impl Expression {
 
             // Logical OR. Inputs must be boolean or NULL. NULLs generally
             // yield NULL, except the special case NULL OR true == true.
-            Self::Or(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::Or(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
                 (Boolean(lhs), Boolean(rhs)) => Boolean(lhs || rhs),
                 (Boolean(b), Null) | (Null, Boolean(b)) if b => Boolean(true),
                 (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
@@ -169,30 +297,36 @@ impl Expression {
             },
 
             // Logical NOT. Input must be boolean or NULL.
-            Self::Not(expr) => match expr.evaluate(row)? {
+            Self::Not(expr) => match eval(expr)? {
                 Boolean(b) => Boolean(!b),
                 Null => Null,
                 value => return errinput!("can't NOT {value}"),
             },
 
             // Comparisons. Must be of same type, except floats and integers
-            // which are interchangeable. NULLs yield NULL, NaNs yield NaN.
+            // which are interchangeable. NULLs yield NULL, and comparisons
+            // with NaN are false (even NaN = NaN), so filters drop them.
+            // Strings compare by their UTF-8 bytes, i.e. a binary collation,
+            // which matches how ORDER BY sorts them.
             //
             // Does not dispatch to Value.cmp() because sorting and comparisons
             // are different for f64 NaN and -0.0 values.
             #[allow(clippy::float_cmp)]
-            Self::Equal(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::Equal(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
                 (Boolean(lhs), Boolean(rhs)) => Boolean(lhs == rhs),
                 (Integer(lhs), Integer(rhs)) => Boolean(lhs == rhs),
-                (Integer(lhs), Float(rhs)) => Boolean(lhs as f32 == rhs),
-                (Float(lhs), Integer(rhs)) => Boolean(lhs == rhs as f32),
-                (Float(lhs), Float(rhs)) => Boolean(lhs == rhs),
+                (Integer(lhs), Float(rhs)) => Boolean(float_eq(lhs as f32, rhs)?),
+                (Float(lhs), Integer(rhs)) => Boolean(float_eq(lhs, rhs as f32)?),
+                (Float(lhs), Float(rhs)) => Boolean(float_eq(lhs, rhs)?),
                 (String(lhs), String(rhs)) => Boolean(lhs == rhs),
+                (Time(lhs), Time(rhs)) => Boolean(lhs == rhs),
+                (Interval(lhs), Interval(rhs)) => Boolean(lhs == rhs),
+                (Array(lhs), Array(rhs)) => Boolean(lhs == rhs),
                 (Null, _) | (_, Null) => Null,
                 (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
             },
 
-            Self::GreaterThan(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::GreaterThan(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
                 #[allow(clippy::bool_comparison)]
                 (Boolean(lhs), Boolean(rhs)) => Boolean(lhs > rhs),
                 (Integer(lhs), Integer(rhs)) => Boolean(lhs > rhs),
@@ -200,11 +334,13 @@ impl Expression {
                 (Float(lhs), Integer(rhs)) => Boolean(lhs > rhs as f32),
                 (Float(lhs), Float(rhs)) => Boolean(lhs > rhs),
                 (String(lhs), String(rhs)) => Boolean(lhs > rhs),
+                (Time(lhs), Time(rhs)) => Boolean(lhs > rhs),
+                (Interval(lhs), Interval(rhs)) => Boolean(lhs > rhs),
                 (Null, _) | (_, Null) => Null,
                 (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
             },
 
-            Self::LessThan(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            Self::LessThan(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
                 #[allow(clippy::bool_comparison)]
                 (Boolean(lhs), Boolean(rhs)) => Boolean(lhs < rhs),
                 (Integer(lhs), Integer(rhs)) => Boolean(lhs < rhs),
@@ -212,12 +348,14 @@ impl Expression {
                 (Float(lhs), Integer(rhs)) => Boolean(lhs < rhs as f32),
                 (Float(lhs), Float(rhs)) => Boolean(lhs < rhs),
                 (String(lhs), String(rhs)) => Boolean(lhs < rhs),
+                (Time(lhs), Time(rhs)) => Boolean(lhs < rhs),
+                (Interval(lhs), Interval(rhs)) => Boolean(lhs < rhs),
                 (Null, _) | (_, Null) => Null,
                 (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
             },
 
-            Self::Is(expr, Null) => Boolean(expr.evaluate(row)? == Null),
-            Self::Is(expr, Float(f)) if f.is_nan() => match expr.evaluate(row)? {
+            Self::Is(expr, Null) => Boolean(eval(expr)? == Null),
+            Self::Is(expr, Float(f)) if f.is_nan() => match eval(expr)? {
                 Float(f) => Boolean(f.is_nan()),
                 Null => Null,
                 v => return errinput!("IS NAN can't be used with {}", v.get_type()),
@@ -226,53 +364,346 @@ impl Expression {
 
             // Mathematical operations. Inputs must be numbers, but integers and
             // floats are interchangeable (float when mixed). NULLs yield NULL.
-            // Errors on integer overflow, while floats yield infinity or NaN.
-            Self::Add(lhs, rhs) => lhs.evaluate(row)?.checked_add(&rhs.evaluate(row)?)?,
-            Self::Divide(lhs, rhs) => lhs.evaluate(row)?.checked_div(&rhs.evaluate(row)?)?,
-            Self::Exponentiate(lhs, rhs) => lhs.evaluate(row)?.checked_pow(&rhs.evaluate(row)?)?,
-            Self::Factorial(expr) => match expr.evaluate(row)? {
+            // Integer overflow errors or wraps depending on the overflow mode,
+            // while floats yield infinity or NaN.
+            Self::Add(lhs, rhs) => {
+                let (lhs, rhs) = (eval(lhs)?, eval(rhs)?);
+                match overflow {
+                    Overflow::Error => lhs.checked_add(&rhs)?,
+                    Overflow::Wrap => lhs.wrapping_add(&rhs)?,
+                }
+            }
+            Self::Divide(lhs, rhs) => eval(lhs)?.checked_div(&eval(rhs)?)?,
+            Self::Exponentiate(lhs, rhs) => eval(lhs)?.checked_pow(&eval(rhs)?)?,
+            Self::Factorial(expr) => match eval(expr)? {
                 Integer(i) if i < 0 => return errinput!("can't take factorial of negative number"),
                 Integer(i) => (1..=i).try_fold(Integer(1), |p, i| p.checked_mul(&Integer(i)))?,
                 Null => Null,
                 value => return errinput!("can't take factorial of {value}"),
             },
-            Self::Identity(expr) => match expr.evaluate(row)? {
+            Self::Identity(expr) => match eval(expr)? {
                 v @ (Integer(_) | Float(_) | Null) => v,
                 expr => return errinput!("can't take the identity of {expr}"),
             },
-            Self::Multiply(lhs, rhs) => lhs.evaluate(row)?.checked_mul(&rhs.evaluate(row)?)?,
-            Self::Negate(expr) => match expr.evaluate(row)? {
-                Integer(i) => Integer(-i),
+            Self::Multiply(lhs, rhs) => {
+                let (lhs, rhs) = (eval(lhs)?, eval(rhs)?);
+                match overflow {
+                    Overflow::Error => lhs.checked_mul(&rhs)?,
+                    Overflow::Wrap => lhs.wrapping_mul(&rhs)?,
+                }
+            }
+            Self::Absolute(expr) => match eval(expr)? {
+                Integer(i) => match overflow {
+                    Overflow::Error => Integer(i.checked_abs().ok_or(Error::OverflowError)?),
+                    Overflow::Wrap => Integer(i.wrapping_abs()),
+                },
+                Float(f) => Float(f.abs()),
+                Null => Null,
+                value => return errinput!("can't take absolute value of {value}"),
+            },
+            Self::Negate(expr) => match eval(expr)? {
+                Integer(i) => match overflow {
+                    Overflow::Error => Integer(i.checked_neg().ok_or(Error::OverflowError)?),
+                    Overflow::Wrap => Integer(i.wrapping_neg()),
+                },
                 Float(f) => Float(-f),
                 Null => Null,
                 value => return errinput!("can't negate {value}"),
             },
-            Self::Remainder(lhs, rhs) => lhs.evaluate(row)?.checked_mod(&rhs.evaluate(row)?)?,
-            Self::SquareRoot(expr) => match expr.evaluate(row)? {
+            Self::Remainder(lhs, rhs) => eval(lhs)?.checked_mod(&eval(rhs)?)?,
+            Self::Round(expr, digits) => eval(expr)?.checked_round(&eval(digits)?)?,
+            Self::Ceil(expr) | Self::Floor(expr) => match eval(expr)? {
+                Integer(i) => Integer(i),
+                Float(f) if matches!(self, Self::Ceil(_)) => Float(f.ceil()),
+                Float(f) => Float(f.floor()),
+                Null => Null,
+                value if matches!(self, Self::Ceil(_)) => return errinput!("can't ceil {value}"),
+                value => return errinput!("can't floor {value}"),
+            },
+            Self::SquareRoot(expr) => match eval(expr)? {
                 Integer(i) if i < 0 => return errinput!("can't take negative square root"),
                 Integer(i) => Float((i as f32).sqrt()),
                 Float(f) => Float(f.sqrt()),
                 Null => Null,
                 value => return errinput!("can't take square root of {value}"),
             },
-            Self::Subtract(lhs, rhs) => lhs.evaluate(row)?.checked_sub(&rhs.evaluate(row)?)?,
+            Self::Subtract(lhs, rhs) => {
+                let (lhs, rhs) = (eval(lhs)?, eval(rhs)?);
+                match overflow {
+                    Overflow::Error => lhs.checked_sub(&rhs)?,
+                    Overflow::Wrap => lhs.wrapping_sub(&rhs)?,
+                }
+            }
+
+            // Bitwise operations. Inputs must be integers. NULLs yield NULL.
+            Self::BitwiseAnd(lhs, rhs) => eval(lhs)?.bit_and(&eval(rhs)?)?,
+            Self::BitwiseOr(lhs, rhs) => eval(lhs)?.bit_or(&eval(rhs)?)?,
+            Self::BitwiseXor(lhs, rhs) => eval(lhs)?.bit_xor(&eval(rhs)?)?,
 
             // LIKE pattern matching, using _ and % as single- and
             // multi-character wildcards. Inputs must be strings. NULLs yield
-            // NULL. There's no support for escaping an _ and %.
-            Self::Like(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+            // NULL. There's no support for escaping an _ and %. ILIKE matches
+            // case-insensitively, using Unicode case folding.
+            Self::Like(lhs, rhs) | Self::ILike(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
                 (String(lhs), String(rhs)) => {
+                    let flags = if matches!(self, Self::ILike(_, _)) { "(?i)" } else { "" };
                     // We could precompile the pattern if it's constant, instead
                     // of recompiling it for every row, but this is fine.
                     let pattern = format!(
-                        "^{}$",
+                        "{flags}^{}$",
                         regex::escape(&rhs).replace('%', ".*").replace('_', ".")
                     );
                     Boolean(regex::Regex::new(&pattern)?.is_match(&lhs))
                 }
                 (String(_), Null) | (Null, String(_)) | (Null, Null) => Null,
+                (lhs, rhs) if matches!(self, Self::ILike(_, _)) => {
+                    return errinput!("can't ILIKE {lhs} and {rhs}")
+                }
                 (lhs, rhs) => return errinput!("can't LIKE {lhs} and {rhs}"),
             },
+
+            // String trimming. Without a character set, any Unicode whitespace
+            // is trimmed. Inputs must be strings. NULLs yield NULL.
+            Self::Trim(expr, chars)
+            | Self::LeftTrim(expr, chars)
+            | Self::RightTrim(expr, chars) => {
+                match (eval(expr)?, chars.as_deref().map(eval).transpose()?) {
+                    (String(string), chars @ (None | Some(String(_)))) => {
+                        let is_trimmed = |c: char| match &chars {
+                            Some(String(chars)) => chars.contains(c),
+                            _ => c.is_whitespace(),
+                        };
+                        let trimmed = match self {
+                            Self::LeftTrim(_, _) => string.trim_start_matches(is_trimmed),
+                            Self::RightTrim(_, _) => string.trim_end_matches(is_trimmed),
+                            _ => string.trim_matches(is_trimmed),
+                        };
+                        String(trimmed.into())
+                    }
+                    (String(_) | Null, Some(Null)) | (Null, None | Some(String(_))) => Null,
+                    (value, None) => return errinput!("can't trim {value}"),
+                    (value, Some(chars)) => return errinput!("can't trim {chars} from {value}"),
+                }
+            }
+
+            // Substring replacement and search. Inputs must be strings, and
+            // positions count characters, not bytes. NULLs yield NULL.
+            Self::Replace(expr, from, to) => match (eval(expr)?, eval(from)?, eval(to)?) {
+                // An empty substring doesn't occur, rather than occurring
+                // between every character.
+                (String(string), String(from), String(_)) if from.is_empty() => String(string),
+                (String(string), String(from), String(to)) => {
+                    String(string.replace(&*from, &to).into())
+                }
+                (String(_) | Null, String(_) | Null, String(_) | Null) => Null,
+                (string, from, to) => {
+                    return errinput!("can't replace {from} with {to} in {string}")
+                }
+            },
+            Self::Position(lhs, rhs) => match (eval(lhs)?, eval(rhs)?) {
+                (String(substring), String(string)) => match string.find(&*substring) {
+                    Some(offset) => Integer(string[..offset].chars().count() as i32 + 1),
+                    None => Integer(0),
+                },
+                (String(_) | Null, String(_) | Null) => Null,
+                (lhs, rhs) => return errinput!("can't find position of {lhs} in {rhs}"),
+            },
+
+            // Array operations. Indexes are 1-based, as in SQL, and out of
+            // bounds indexes yield NULL. NULLs yield NULL.
+            Self::Index(array, index) => match (eval(array)?, eval(index)?) {
+                (Array(values), Integer(i)) => usize::try_from(i)
+                    .ok()
+                    .and_then(|i| i.checked_sub(1))
+                    .and_then(|i| values.get(i).cloned())
+                    .unwrap_or(Null),
+                (Array(_), Null) | (Null, Integer(_) | Null) => Null,
+                (array, index) => return errinput!("can't index {array} with {index}"),
+            },
+            Self::ArrayLength(expr) => match eval(expr)? {
+                Array(values) => Integer(values.len() as i32),
+                Null => Null,
+                value => return errinput!("can't take array length of {value}"),
+            },
+
+            Self::Cast(expr, data_type) => eval(expr)?.cast_to(*data_type)?,
+
+            // NULLIF compares the values using SQL equality, so a NULL operand
+            // never matches and yields the first value.
+            Self::NullIf(lhs, rhs) => {
+                let lhs = eval(lhs)?;
+                let equal = Self::Equal(Self::Constant(lhs.clone()).into(), rhs.clone());
+                match eval(&equal)? {
+                    Boolean(true) => Null,
+                    _ => lhs,
+                }
+            }
+        })
+    }
+
+    /// Infers the expression's result type for rows of the given schema,
+    /// without evaluating it. Yields DataType::Invalid if the type can't be
+    /// determined statically, e.g. for NULL, integer division (which yields a
+    /// float if inexact), and array elements. Errors if it's ill-typed.
+    pub fn result_type(&self, schema: &Table) -> Result<DataType> {
+        self.result_type_with(&|index| match index < schema.col_count() {
+            true => Ok(schema.get_field_type(index)),
+            false => errinput!("column index {index} out of bounds"),
+        })
+    }
+
+    /// Like result_type(), but looks up column types with the given closure,
+    /// e.g. from a plan node's output columns.
+    pub(crate) fn result_type_with(
+        &self,
+        column_type: &impl Fn(usize) -> Result<DataType>,
+    ) -> Result<DataType> {
+        use DataType::*;
+        let infer = |expr: &Expression| expr.result_type_with(column_type);
+
+        // Numeric operations yield integers for integer inputs, and floats if
+        // either input is a float. Invalid inputs (e.g. NULL) yield Invalid.
+        let numeric = |op: &str, lhs: DataType, rhs: DataType| match (lhs, rhs) {
+            (Int, Int) => Ok(Int),
+            (Int | Float, Int | Float) => Ok(Float),
+            (Int | Float | Invalid, Int | Float | Invalid) => Ok(Invalid),
+            (lhs, rhs) => errinput!("can't {op} {lhs} and {rhs}"),
+        };
+
+        Ok(match self {
+            Self::Constant(value) => value.get_type(),
+            Self::Column(index) => column_type(*index)?,
+            Self::OuterColumn(_) | Self::ScalarSubquery(_) => Invalid,
+
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) => match (infer(lhs)?, infer(rhs)?) {
+                (Bool | Invalid, Bool | Invalid) => Bool,
+                (lhs, rhs) if matches!(self, Self::And(_, _)) => {
+                    return errinput!("can't AND {lhs} and {rhs}")
+                }
+                (lhs, rhs) => return errinput!("can't OR {lhs} and {rhs}"),
+            },
+            Self::Not(expr) => match infer(expr)? {
+                Bool | Invalid => Bool,
+                data_type => return errinput!("can't NOT {data_type}"),
+            },
+
+            Self::Equal(lhs, rhs) | Self::GreaterThan(lhs, rhs) | Self::LessThan(lhs, rhs) => {
+                match (infer(lhs)?, infer(rhs)?) {
+                    (Int | Float, Int | Float) | (Invalid, _) | (_, Invalid) => Bool,
+                    (lhs, rhs) if lhs == rhs => Bool,
+                    (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
+                }
+            }
+            Self::Is(expr, Field::Null) => {
+                infer(expr)?;
+                Bool
+            }
+            Self::Is(expr, _) => match infer(expr)? {
+                Float | Invalid => Bool,
+                data_type => return errinput!("IS NAN can't be used with {data_type}"),
+            },
+
+            // Times can be added to and subtracted from intervals, which
+            // don't have a data type, and subtracting times yields an interval.
+            Self::Add(lhs, rhs) => match (infer(lhs)?, infer(rhs)?) {
+                (Time, Invalid) | (Invalid, Time) => Invalid,
+                (lhs, rhs) => numeric("add", lhs, rhs)?,
+            },
+            Self::Subtract(lhs, rhs) => match (infer(lhs)?, infer(rhs)?) {
+                (Time, Time | Invalid) => Invalid,
+                (lhs, rhs) => numeric("subtract", lhs, rhs)?,
+            },
+            Self::Multiply(lhs, rhs) => numeric("multiply", infer(lhs)?, infer(rhs)?)?,
+            Self::Remainder(lhs, rhs) => numeric("mod", infer(lhs)?, infer(rhs)?)?,
+            // Integer division and negative integer exponents yield floats.
+            Self::Divide(lhs, rhs) => match numeric("divide", infer(lhs)?, infer(rhs)?)? {
+                Int => Invalid,
+                data_type => data_type,
+            },
+            Self::Exponentiate(lhs, rhs) => {
+                match numeric("exponentiate", infer(lhs)?, infer(rhs)?)? {
+                    Int => Invalid,
+                    data_type => data_type,
+                }
+            }
+            Self::Ceil(expr) | Self::Floor(expr) => match infer(expr)? {
+                data_type @ (Int | Float | Invalid) => data_type,
+                data_type => return errinput!("can't round {data_type}"),
+            },
+            Self::Round(expr, digits) => match (infer(expr)?, infer(digits)?) {
+                (data_type @ (Int | Float | Invalid), Int | Invalid) => data_type,
+                (lhs, rhs) => return errinput!("can't round {lhs} to {rhs} digits"),
+            },
+            Self::Factorial(expr) => match infer(expr)? {
+                Int | Invalid => Int,
+                data_type => return errinput!("can't take factorial of {data_type}"),
+            },
+            Self::Identity(expr) => match infer(expr)? {
+                data_type @ (Int | Float | Invalid) => data_type,
+                data_type => return errinput!("can't take the identity of {data_type}"),
+            },
+            Self::Absolute(expr) => match infer(expr)? {
+                data_type @ (Int | Float | Invalid) => data_type,
+                data_type => return errinput!("can't take absolute value of {data_type}"),
+            },
+            Self::Negate(expr) => match infer(expr)? {
+                data_type @ (Int | Float | Invalid) => data_type,
+                data_type => return errinput!("can't negate {data_type}"),
+            },
+            Self::SquareRoot(expr) => match infer(expr)? {
+                Int | Float | Invalid => Float,
+                data_type => return errinput!("can't take square root of {data_type}"),
+            },
+
+            Self::BitwiseAnd(lhs, rhs) | Self::BitwiseOr(lhs, rhs) | Self::BitwiseXor(lhs, rhs) => {
+                match (infer(lhs)?, infer(rhs)?) {
+                    (Int | Invalid, Int | Invalid) => Int,
+                    (lhs, rhs) => return errinput!("can't bitwise combine {lhs} and {rhs}"),
+                }
+            }
+
+            Self::Like(lhs, rhs) | Self::ILike(lhs, rhs) => match (infer(lhs)?, infer(rhs)?) {
+                (Text | Invalid, Text | Invalid) => Bool,
+                (lhs, rhs) => return errinput!("can't match {lhs} against pattern {rhs}"),
+            },
+            Self::Trim(expr, chars)
+            | Self::LeftTrim(expr, chars)
+            | Self::RightTrim(expr, chars) => {
+                let chars = chars.as_deref().map(infer).transpose()?;
+                match (infer(expr)?, chars) {
+                    (Text | Invalid, None | Some(Text | Invalid)) => Text,
+                    (data_type, None) => return errinput!("can't trim {data_type}"),
+                    (lhs, Some(rhs)) => return errinput!("can't trim {rhs} from {lhs}"),
+                }
+            }
+            Self::Replace(expr, from, to) => match (infer(expr)?, infer(from)?, infer(to)?) {
+                (Text | Invalid, Text | Invalid, Text | Invalid) => Text,
+                (string, from, to) => {
+                    return errinput!("can't replace {from} with {to} in {string}")
+                }
+            },
+            Self::Position(lhs, rhs) => match (infer(lhs)?, infer(rhs)?) {
+                (Text | Invalid, Text | Invalid) => Int,
+                (lhs, rhs) => return errinput!("can't find position of {lhs} in {rhs}"),
+            },
+
+            Self::Index(array, index) => match (infer(array)?, infer(index)?) {
+                (Invalid, Int | Invalid) => Invalid,
+                (array, index) => return errinput!("can't index {array} with {index}"),
+            },
+            Self::ArrayLength(expr) => match infer(expr)? {
+                Invalid => Int,
+                data_type => return errinput!("can't take array length of {data_type}"),
+            },
+
+            Self::Cast(expr, data_type) => {
+                infer(expr)?;
+                *data_type
+            }
+            Self::NullIf(lhs, rhs) => {
+                infer(rhs)?;
+                infer(lhs)?
+            }
         })
     }
 
@@ -285,25 +716,50 @@ impl Expression {
         match self {
             Self::Add(lhs, rhs)
             | Self::And(lhs, rhs)
+            | Self::BitwiseAnd(lhs, rhs)
+            | Self::BitwiseOr(lhs, rhs)
+            | Self::BitwiseXor(lhs, rhs)
             | Self::Divide(lhs, rhs)
             | Self::Equal(lhs, rhs)
             | Self::Exponentiate(lhs, rhs)
             | Self::GreaterThan(lhs, rhs)
+            | Self::Index(lhs, rhs)
             | Self::LessThan(lhs, rhs)
             | Self::Like(lhs, rhs)
+            | Self::ILike(lhs, rhs)
             | Self::Multiply(lhs, rhs)
+            | Self::NullIf(lhs, rhs)
             | Self::Or(lhs, rhs)
+            | Self::Position(lhs, rhs)
             | Self::Remainder(lhs, rhs)
+            | Self::Round(lhs, rhs)
             | Self::Subtract(lhs, rhs) => lhs.walk(visitor) && rhs.walk(visitor),
 
-            Self::Factorial(expr)
+            Self::Absolute(expr)
+            | Self::ArrayLength(expr)
+            | Self::Ceil(expr)
+            | Self::Factorial(expr)
+            | Self::Floor(expr)
             | Self::Identity(expr)
             | Self::Is(expr, _)
             | Self::Negate(expr)
             | Self::Not(expr)
-            | Self::SquareRoot(expr) => expr.walk(visitor),
+            | Self::SquareRoot(expr)
+            | Self::Cast(expr, _) => expr.walk(visitor),
+
+            Self::Trim(expr, chars)
+            | Self::LeftTrim(expr, chars)
+            | Self::RightTrim(expr, chars) => {
+                expr.walk(visitor) && chars.as_ref().is_none_or(|chars| chars.walk(visitor))
+            }
+            Self::Replace(expr, from, to) => {
+                expr.walk(visitor) && from.walk(visitor) && to.walk(visitor)
+            }
 
-            Self::Constant(_) | Self::Column(_) => true,
+            Self::Constant(_)
+            | Self::Column(_)
+            | Self::OuterColumn(_)
+            | Self::ScalarSubquery(_) => true,
         }
     }
 
@@ -331,25 +787,50 @@ impl Expression {
         self = match self {
             Self::Add(lhs, rhs) => Self::Add(xform(lhs)?, xform(rhs)?),
             Self::And(lhs, rhs) => Self::And(xform(lhs)?, xform(rhs)?),
+            Self::BitwiseAnd(lhs, rhs) => Self::BitwiseAnd(xform(lhs)?, xform(rhs)?),
+            Self::BitwiseOr(lhs, rhs) => Self::BitwiseOr(xform(lhs)?, xform(rhs)?),
+            Self::BitwiseXor(lhs, rhs) => Self::BitwiseXor(xform(lhs)?, xform(rhs)?),
             Self::Divide(lhs, rhs) => Self::Divide(xform(lhs)?, xform(rhs)?),
             Self::Equal(lhs, rhs) => Self::Equal(xform(lhs)?, xform(rhs)?),
             Self::Exponentiate(lhs, rhs) => Self::Exponentiate(xform(lhs)?, xform(rhs)?),
             Self::GreaterThan(lhs, rhs) => Self::GreaterThan(xform(lhs)?, xform(rhs)?),
+            Self::Index(lhs, rhs) => Self::Index(xform(lhs)?, xform(rhs)?),
             Self::LessThan(lhs, rhs) => Self::LessThan(xform(lhs)?, xform(rhs)?),
             Self::Like(lhs, rhs) => Self::Like(xform(lhs)?, xform(rhs)?),
+            Self::ILike(lhs, rhs) => Self::ILike(xform(lhs)?, xform(rhs)?),
             Self::Multiply(lhs, rhs) => Self::Multiply(xform(lhs)?, xform(rhs)?),
+            Self::NullIf(lhs, rhs) => Self::NullIf(xform(lhs)?, xform(rhs)?),
             Self::Or(lhs, rhs) => Self::Or(xform(lhs)?, xform(rhs)?),
+            Self::Position(lhs, rhs) => Self::Position(xform(lhs)?, xform(rhs)?),
             Self::Remainder(lhs, rhs) => Self::Remainder(xform(lhs)?, xform(rhs)?),
+            Self::Round(lhs, rhs) => Self::Round(xform(lhs)?, xform(rhs)?),
             Self::SquareRoot(expr) => Self::SquareRoot(xform(expr)?),
             Self::Subtract(lhs, rhs) => Self::Subtract(xform(lhs)?, xform(rhs)?),
 
+            Self::Absolute(expr) => Self::Absolute(xform(expr)?),
+            Self::ArrayLength(expr) => Self::ArrayLength(xform(expr)?),
+            Self::Ceil(expr) => Self::Ceil(xform(expr)?),
+            Self::Floor(expr) => Self::Floor(xform(expr)?),
+            Self::Cast(expr, data_type) => Self::Cast(xform(expr)?, data_type),
             Self::Factorial(expr) => Self::Factorial(xform(expr)?),
             Self::Identity(expr) => Self::Identity(xform(expr)?),
             Self::Is(expr, value) => Self::Is(xform(expr)?, value),
             Self::Negate(expr) => Self::Negate(xform(expr)?),
             Self::Not(expr) => Self::Not(xform(expr)?),
 
-            expr @ (Self::Constant(_) | Self::Column(_)) => expr,
+            Self::Trim(expr, chars) => Self::Trim(xform(expr)?, chars.map(xform).transpose()?),
+            Self::LeftTrim(expr, chars) => {
+                Self::LeftTrim(xform(expr)?, chars.map(xform).transpose()?)
+            }
+            Self::RightTrim(expr, chars) => {
+                Self::RightTrim(xform(expr)?, chars.map(xform).transpose()?)
+            }
+            Self::Replace(expr, from, to) => Self::Replace(xform(expr)?, xform(from)?, xform(to)?),
+
+            expr @ (Self::Constant(_)
+            | Self::Column(_)
+            | Self::OuterColumn(_)
+            | Self::ScalarSubquery(_)) => expr,
         };
         self = after(self)?;
         Ok(self)
@@ -412,6 +893,12 @@ impl Expression {
         cnf
     }
 
+    /// Checks if the expression contains a subquery, which can't be evaluated
+    /// by evaluate() but must be bound by the executor first.
+    pub fn contains_subquery(&self) -> bool {
+        self.contains(&|expr| matches!(expr, Self::ScalarSubquery(_)))
+    }
+
     /// Creates an expression by ANDing together a vector, or None if empty.
     pub fn and_vec(exprs: Vec<Expression>) -> Option<Self> {
         let mut iter = exprs.into_iter();
@@ -6,5 +6,5 @@ mod planner;
 
 pub use expression::Expression;
 pub use node::{BoxedNode, Node};
-pub use plan::{Aggregate, Direction, Plan};
+pub use plan::{Aggregate, Direction, NullOrder, Plan};
 pub use planner::Planner;
@@ -1,7 +1,10 @@
 use crate::common::Result;
-use crate::sql::planner::{Aggregate, Direction, Expression};
+use crate::sql::engine::Catalog;
+use crate::sql::planner::optimizer::estimate_node;
+use crate::sql::planner::{Aggregate, Direction, Expression, NullOrder};
 use crate::types::field::{Field, Label};
-use crate::types::Table;
+use crate::types::{DataType, Table};
+use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 use std::ops::Deref;
 
@@ -34,28 +37,39 @@ impl Deref for BoxedNode {
 pub enum Node {
     /// Computes the given aggregate values for the given group_by buckets
     /// across all rows in the source node. The group_by columns are emitted
-    /// first, followed by the aggregate columns, in the given order.
+    /// first, followed by the aggregate columns, in the given order. The
+    /// planner projects them into SELECT order, e.g. for SELECT COUNT(*), b.
+    /// If grouping_sets is given, the rows are aggregated once per grouping set
+    /// (indexes into group_by), emitting NULL for the other group_by columns.
     Aggregate {
         source: BoxedNode,
         group_by: Vec<Expression>,
         aggregates: Vec<Aggregate>,
+        grouping_sets: Option<Vec<Vec<usize>>>,
     },
     /// Filters source rows, by discarding rows for which the predicate
-    /// evaluates to false.
+    /// evaluates to false. If epsilon is given, floats within roughly epsilon
+    /// of each other are equal, as with HashJoin (see
+    /// `Expression::evaluate_tolerant`).
     Filter {
         source: BoxedNode,
         predicate: Expression,
+        epsilon: Option<f32>,
     },
     /// Joins the left and right sources on the given columns by building an
     /// in-memory hashmap of the right source and looking up matches for each
     /// row in the left source. When outer is true (e.g. LEFT JOIN), a left row
     /// without a right match is emitted anyway, with NULLs for the right row.
+    /// Emits rows in left source order, and each left row's matches in right
+    /// source order. If epsilon is given, float keys within roughly epsilon of
+    /// each other match (see `Field::float_key`), rather than only equal ones.
     HashJoin {
         left: BoxedNode,
         left_column: usize,
         right: BoxedNode,
         right_column: usize,
         outer: bool,
+        epsilon: Option<f32>,
     },
     /// Looks up the given values in a secondary index and emits matching rows.
     /// NULL and NaN values are considered equal, to allow IS NULL and IS NAN
@@ -90,11 +104,18 @@ pub enum Node {
     Nothing { columns: Vec<Label> },
     /// Discards the first offset rows from source, emits the rest.
     Offset { source: BoxedNode, offset: usize },
+    /// Emits a uniform random sample of up to size rows from the source, in
+    /// source order, using the given random seed. Buffers the sampled rows.
+    Sample {
+        source: BoxedNode,
+        size: usize,
+        seed: u64,
+    },
     /// Sorts the source rows by the given sort key. Buffers the entire row set
-    /// in memory.
+    /// in memory. Keys without a NULL placement use the transaction's default.
     Order {
         source: BoxedNode,
-        key: Vec<(Expression, Direction)>,
+        key: Vec<(Expression, Direction, Option<NullOrder>)>,
     },
     /// Projects the input rows by evaluating the given expressions. Aliases are
     /// only used when displaying the plan.
@@ -107,10 +128,19 @@ pub enum Node {
     /// the column. Unspecified target columns yield Value::Null. The source →
     /// target mapping ensures a source column can only be mapped to a single
     /// target column, allowing the value to be moved rather than cloned.
+    /// Execution errors if multiple source columns map to the same target.
     Remap {
         source: BoxedNode,
         targets: Vec<Option<usize>>,
     },
+    /// Emits the source rows for which the subquery yields at least one row,
+    /// or, when anti is true, yields no rows (i.e. WHERE [NOT] EXISTS). The
+    /// subquery may reference source columns via Expression::OuterColumn.
+    SemiJoin {
+        source: BoxedNode,
+        subquery: BoxedNode,
+        anti: bool,
+    },
     /// A full table scan, with an optional pushed-down filter. The schema is
     /// used during plan optimization. The alias is only used for formatting.
     Scan {
@@ -123,6 +153,38 @@ pub enum Node {
 }
 
 impl Node {
+    /// Wraps the node in a projection of all its columns in order, i.e. the
+    /// expansion of SELECT * for programmatically built plans. For joins, this
+    /// includes the columns of both sides. The projection is unaliased, so
+    /// column labels are routed to the source (e.g. the joined tables). The
+    /// planner omits the projection for a bare SELECT *, since it doesn't
+    /// change the rows.
+    pub fn project_all(self) -> Self {
+        let expressions = (0..self.columns()).map(Expression::Column).collect::<Vec<_>>();
+        let aliases = vec![Label::None; expressions.len()];
+        Self::Projection {
+            source: self.into(),
+            expressions,
+            aliases,
+        }
+    }
+
+    /// Builds a nested loop join whose predicate is the conjunction of the
+    /// given join conditions. An empty condition list is a cross join.
+    pub fn nested_loop_join(
+        left: Node,
+        right: Node,
+        conditions: Vec<Expression>,
+        outer: bool,
+    ) -> Self {
+        Self::NestedLoopJoin {
+            left: left.into(),
+            right: right.into(),
+            predicate: Expression::and_vec(conditions),
+            outer,
+        }
+    }
+
     /// Returns the number of columns emitted by the node.
     pub fn columns(&self) -> usize {
         match self {
@@ -154,7 +216,9 @@ impl Node {
             Self::Filter { source, .. }
             | Self::Limit { source, .. }
             | Self::Offset { source, .. }
-            | Self::Order { source, .. } => source.columns(),
+            | Self::Order { source, .. }
+            | Self::Sample { source, .. }
+            | Self::SemiJoin { source, .. } => source.columns(),
 
             // And some are trivial.
             Self::Nothing { columns } => columns.len(),
@@ -223,7 +287,9 @@ impl Node {
             Self::Filter { source, .. }
             | Self::Limit { source, .. }
             | Self::Offset { source, .. }
-            | Self::Order { source, .. } => source.column_label(index),
+            | Self::Order { source, .. }
+            | Self::Sample { source, .. }
+            | Self::SemiJoin { source, .. } => source.column_label(index),
 
             // Nothing nodes contain the original columns of replaced nodes.
             Self::Nothing { columns } => columns.get(index).cloned().unwrap_or(Label::None),
@@ -233,6 +299,255 @@ impl Node {
         }
     }
 
+    /// Returns the data type of a column by tracing it through the plan tree,
+    /// or DataType::Invalid if it isn't known statically, e.g. for computed
+    /// expressions. Unlike the emitted values, this is available even when the
+    /// node doesn't emit any rows.
+    pub fn column_type(&self, index: usize) -> DataType {
+        match self {
+            // Source nodes use the table schema.
+            Self::IndexLookup { table, .. }
+            | Self::KeyLookup { table, .. }
+            | Self::Scan { table, .. } => table.get_field_type(index),
+
+            // Aggregate nodes use the GROUP BY expression type, or the
+            // aggregate function's result type. AVG yields an integer or
+            // float depending on the values, so its type is unknown, and
+            // ARRAY_AGG yields an array, which isn't a column type.
+            Self::Aggregate {
+                source,
+                group_by,
+                aggregates,
+                ..
+            } => match group_by.get(index) {
+                Some(expr) => Self::expression_type(expr, source),
+                None => match aggregates.get(index - group_by.len()) {
+                    Some(Aggregate::Count(_)) => DataType::Int,
+                    Some(Aggregate::PercentileCont(..)) => DataType::Float,
+                    Some(
+                        Aggregate::Max(expr) | Aggregate::Min(expr) | Aggregate::Sum(expr),
+                    ) => Self::expression_type(expr, source),
+                    Some(Aggregate::ArrayAgg(_) | Aggregate::Average(_)) | None => {
+                        DataType::Invalid
+                    }
+                },
+            },
+
+            // Projections use the expression type.
+            Self::Projection {
+                source,
+                expressions,
+                ..
+            } => match expressions.get(index) {
+                Some(expr) => Self::expression_type(expr, source),
+                None => DataType::Invalid,
+            },
+
+            // Remap looks up the source column, if any.
+            Self::Remap { source, targets } => targets
+                .iter()
+                .position(|t| t == &Some(index))
+                .map(|i| source.column_type(i))
+                .unwrap_or(DataType::Invalid),
+
+            // Joins dispatch to the appropriate source.
+            Self::HashJoin { left, right, .. } | Self::NestedLoopJoin { left, right, .. } => {
+                if index < left.columns() {
+                    left.column_type(index)
+                } else {
+                    right.column_type(index - left.columns())
+                }
+            }
+
+            // Simple nodes just dispatch to the source.
+            Self::Filter { source, .. }
+            | Self::Limit { source, .. }
+            | Self::Offset { source, .. }
+            | Self::Order { source, .. }
+            | Self::Sample { source, .. }
+            | Self::SemiJoin { source, .. } => source.column_type(index),
+
+            // Values use the type of the first row's constants.
+            Self::Values { rows } => match rows.first().and_then(|row| row.get(index)) {
+                Some(Expression::Constant(value)) => value.get_type(),
+                Some(_) | None => DataType::Invalid,
+            },
+
+            Self::Nothing { .. } => DataType::Invalid,
+        }
+    }
+
+    /// Returns the data type of an expression evaluated against the given
+    /// source node, if known statically (see Expression::result_type()).
+    /// Ill-typed expressions are Invalid, and error during execution.
+    fn expression_type(expr: &Expression, source: &Node) -> DataType {
+        expr.result_type_with(&|index| Ok(source.column_type(index)))
+            .unwrap_or(DataType::Invalid)
+    }
+
+    /// Renders the node and its descendants for EXPLAIN as lines of a tree,
+    /// annotating each node with its estimated row count. The prefix is drawn
+    /// before the node itself, and the indent before its children. Returns
+    /// the node's estimate, which is computed from its children's estimates
+    /// such that the tree is only estimated once.
+    pub(super) fn explain(
+        &self,
+        catalog: &impl Catalog,
+        prefix: &str,
+        indent: &str,
+        lines: &mut Vec<String>,
+    ) -> Result<u64> {
+        let join = |exprs: &[Expression], node: &Node| {
+            exprs.iter().map(|expr| expr.format(node)).join(", ")
+        };
+        let (title, children): (String, Vec<&Node>) = match self {
+            Self::Aggregate {
+                source,
+                group_by,
+                aggregates,
+                ..
+            } => {
+                let group_by = group_by.iter().map(|expr| expr.format(source));
+                let aggregates = aggregates.iter().map(|agg| agg.format(source));
+                let columns = group_by.chain(aggregates).join(", ");
+                (format!("Aggregate: {columns}"), vec![source])
+            }
+            Self::Filter {
+                source,
+                predicate,
+                epsilon,
+            } => {
+                let mut title = format!("Filter: {}", predicate.format(source));
+                if let Some(epsilon) = epsilon {
+                    title += &format!(" (epsilon {epsilon})");
+                }
+                (title, vec![source])
+            }
+            Self::HashJoin {
+                left,
+                left_column,
+                right,
+                right_column,
+                outer,
+                ..
+            } => {
+                let kind = if *outer { "outer" } else { "inner" };
+                let lhs = left.column_label(*left_column);
+                let rhs = right.column_label(*right_column);
+                (format!("HashJoin: {kind} on {lhs} = {rhs}"), vec![left, right])
+            }
+            Self::IndexLookup {
+                table,
+                column,
+                values,
+                ..
+            } => {
+                let column = table.get_column_name(*column);
+                let values = values.iter().join(", ");
+                (format!("IndexLookup: {}.{column} ({values})", table.name()), vec![])
+            }
+            Self::KeyLookup { table, keys, .. } => {
+                (format!("KeyLookup: {} ({})", table.name(), keys.iter().join(", ")), vec![])
+            }
+            Self::Limit { source, limit } => (format!("Limit: {limit}"), vec![source]),
+            Self::NestedLoopJoin {
+                left,
+                right,
+                predicate,
+                outer,
+            } => {
+                let kind = if *outer { "outer" } else { "inner" };
+                let title = match predicate {
+                    Some(predicate) => {
+                        format!("NestedLoopJoin: {kind} on {}", predicate.format(self))
+                    }
+                    None => format!("NestedLoopJoin: {kind}"),
+                };
+                (title, vec![left, right])
+            }
+            Self::Nothing { .. } => ("Nothing".to_string(), vec![]),
+            Self::Offset { source, offset } => (format!("Offset: {offset}"), vec![source]),
+            Self::Sample { source, size, .. } => (format!("Sample: {size}"), vec![source]),
+            Self::Order { source, key } => {
+                let key = key
+                    .iter()
+                    .map(|(expr, direction, nulls)| match nulls {
+                        Some(nulls) => format!("{} {direction} {nulls}", expr.format(source)),
+                        None => format!("{} {direction}", expr.format(source)),
+                    })
+                    .join(", ");
+                (format!("Order: {key}"), vec![source])
+            }
+            Self::Projection {
+                source,
+                expressions,
+                aliases,
+            } => {
+                let columns = expressions
+                    .iter()
+                    .zip(aliases)
+                    .map(|(expr, alias)| match alias {
+                        Label::None => expr.format(source),
+                        alias => format!("{} as {alias}", expr.format(source)),
+                    })
+                    .join(", ");
+                (format!("Projection: {columns}"), vec![source])
+            }
+            Self::Remap { source, targets } => {
+                let targets = targets
+                    .iter()
+                    .map(|target| match target {
+                        Some(index) => index.to_string(),
+                        None => "-".to_string(),
+                    })
+                    .join(", ");
+                (format!("Remap: {targets}"), vec![source])
+            }
+            Self::SemiJoin {
+                source,
+                subquery,
+                anti,
+            } => {
+                let kind = if *anti { "anti" } else { "semi" };
+                (format!("SemiJoin: {kind}"), vec![source, subquery])
+            }
+            Self::Scan {
+                table,
+                filter,
+                alias,
+            } => {
+                let mut title = format!("Scan: {}", table.name());
+                if let Some(alias) = alias {
+                    title += &format!(" as {alias}");
+                }
+                if let Some(filter) = filter {
+                    title += &format!(" ({})", filter.format(self));
+                }
+                (title, vec![])
+            }
+            Self::Values { rows } => {
+                let rows = rows.iter().map(|row| format!("({})", join(row, self))).join(", ");
+                (format!("Values: {rows}"), vec![])
+            }
+        };
+
+        // The node's line is filled in once its children are estimated.
+        let line = lines.len();
+        lines.push(String::new());
+        let mut inputs = Vec::with_capacity(children.len());
+        for (i, child) in children.iter().enumerate() {
+            let (branch, child_indent) = match i == children.len() - 1 {
+                true => ("└─ ", "   "),
+                false => ("├─ ", "│  "),
+            };
+            let (prefix, indent) = (format!("{indent}{branch}"), format!("{indent}{child_indent}"));
+            inputs.push(child.explain(catalog, &prefix, &indent, lines)?);
+        }
+        let rows = estimate_node(self, &inputs, catalog)?;
+        lines[line] = format!("{prefix}{title} [rows={rows}]");
+        Ok(rows)
+    }
+
     /// Recursively transforms query nodes depth-first by applying the given
     /// closures before and after descending.
     pub fn transform(
@@ -252,14 +567,21 @@ impl Node {
                 source,
                 group_by,
                 aggregates,
+                grouping_sets,
             } => Self::Aggregate {
                 source: xform(source)?,
                 group_by,
                 aggregates,
+                grouping_sets,
             },
-            Self::Filter { source, predicate } => Self::Filter {
+            Self::Filter {
+                source,
+                predicate,
+                epsilon,
+            } => Self::Filter {
                 source: xform(source)?,
                 predicate,
+                epsilon,
             },
             Self::HashJoin {
                 left,
@@ -267,12 +589,14 @@ impl Node {
                 right,
                 right_column,
                 outer,
+                epsilon,
             } => Self::HashJoin {
                 left: xform(left)?,
                 left_column,
                 right: xform(right)?,
                 right_column,
                 outer,
+                epsilon,
             },
             Self::Limit { source, limit } => Self::Limit {
                 source: xform(source)?,
@@ -293,6 +617,11 @@ impl Node {
                 source: xform(source)?,
                 offset,
             },
+            Self::Sample { source, size, seed } => Self::Sample {
+                source: xform(source)?,
+                size,
+                seed,
+            },
             Self::Order { source, key } => Self::Order {
                 source: xform(source)?,
                 key,
@@ -310,6 +639,16 @@ impl Node {
                 source: xform(source)?,
                 targets,
             },
+            // The subquery is a separate query, so we don't descend into it.
+            Self::SemiJoin {
+                source,
+                subquery,
+                anti,
+            } => Self::SemiJoin {
+                source: xform(source)?,
+                subquery,
+                anti,
+            },
 
             Self::IndexLookup { .. }
             | Self::KeyLookup { .. }
@@ -332,9 +671,14 @@ impl Node {
             Self::Filter {
                 source,
                 mut predicate,
+                epsilon,
             } => {
                 predicate = predicate.transform(before, after)?;
-                Self::Filter { source, predicate }
+                Self::Filter {
+                    source,
+                    predicate,
+                    epsilon,
+                }
             }
             Self::NestedLoopJoin {
                 left,
@@ -353,7 +697,7 @@ impl Node {
             Self::Order { source, mut key } => {
                 key = key
                     .into_iter()
-                    .map(|(expr, dir)| Ok((expr.transform(before, after)?, dir)))
+                    .map(|(expr, dir, nulls)| Ok((expr.transform(before, after)?, dir, nulls)))
                     .collect::<Result<_>>()?;
                 Self::Order { source, key }
             }
@@ -409,7 +753,9 @@ impl Node {
             | Self::Nothing { .. }
             | Self::Offset { .. }
             | Self::Remap { .. }
-            | Self::Scan { filter: None, .. } => self,
+            | Self::Sample { .. }
+            | Self::Scan { filter: None, .. }
+            | Self::SemiJoin { .. } => self,
         })
     }
 }
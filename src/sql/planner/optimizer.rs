@@ -1,9 +1,169 @@
 use crate::common::Result;
-use crate::sql::planner::BoxedNode;
+use crate::sql::engine::Catalog;
+use crate::sql::planner::{BoxedNode, Expression, Node};
 //
 // /// A plan optimizer, which recursively transforms a plan node to make plan
 // /// execution more efficient where possible.
 pub type Optimizer = fn(BoxedNode) -> Result<BoxedNode>;
 //
 // /// The set of optimizers, and the order in which they are applied.
-pub static OPTIMIZERS: &[(&str, Optimizer)] = &[];
+pub static OPTIMIZERS: &[(&str, Optimizer)] = &[("Filter pushdown", filter_pushdown)];
+
+/// Pushes filter predicates into table scans directly below them, so that rows
+/// are filtered by Transaction::scan() while decoding them rather than by a
+/// separate Filter node. Predicates with subqueries must be bound by the
+/// executor first, and are left as is. So are constant predicates, which the
+/// Filter node short-circuits without scanning the table, and predicates with
+/// a float tolerance, which scans don't support.
+pub fn filter_pushdown(node: BoxedNode) -> Result<BoxedNode> {
+    let push = |node| match node {
+        Node::Filter {
+            source,
+            predicate,
+            epsilon: None,
+        } if !predicate.contains_subquery() && !matches!(predicate, Expression::Constant(_)) =>
+        {
+            match *source.inner {
+                Node::Scan {
+                    table,
+                    alias,
+                    filter,
+                } => {
+                    let filter =
+                        Expression::and_vec(filter.into_iter().chain([predicate]).collect());
+                    Ok(Node::Scan {
+                        table,
+                        alias,
+                        filter,
+                    })
+                }
+                source => Ok(Node::Filter {
+                    source: source.into(),
+                    predicate,
+                    epsilon: None,
+                }),
+            }
+        }
+        node => Ok(node),
+    };
+    Ok(node.inner.transform(&Ok, &push)?.into())
+}
+
+/// Reorders inner hash joins such that the input with the fewest estimated
+/// rows is on the right, where it's used as the hash table build side. The
+/// original column order is restored with a Remap node, so the rewrite is
+/// invisible to parent nodes. Outer joins must preserve their left rows, and
+/// are left as is.
+///
+/// Unlike the OPTIMIZERS, this needs table statistics from the catalog.
+pub fn reorder_joins(node: BoxedNode, catalog: &impl Catalog) -> Result<BoxedNode> {
+    let reorder = |node| match node {
+        Node::HashJoin {
+            left,
+            left_column,
+            right,
+            right_column,
+            outer: false,
+            epsilon,
+        } if estimate_rows(&left, catalog)? < estimate_rows(&right, catalog)? => {
+            let (left_size, right_size) = (left.columns(), right.columns());
+            let join = Node::HashJoin {
+                left: right,
+                left_column: right_column,
+                right: left,
+                right_column: left_column,
+                outer: false,
+                epsilon,
+            };
+            // Move the right columns after the left columns again.
+            let targets = (0..right_size)
+                .map(|i| Some(left_size + i))
+                .chain((0..left_size).map(Some))
+                .collect();
+            Ok(Node::Remap {
+                source: join.into(),
+                targets,
+            })
+        }
+        node => Ok(node),
+    };
+    Ok(node.inner.transform(&Ok, &reorder)?.into())
+}
+
+/// The fraction of rows assumed to match a filter predicate when estimating
+/// row counts, since there are no column statistics.
+const FILTER_SELECTIVITY: f64 = 0.25;
+
+/// Estimates the number of rows emitted by a node and its descendants. See
+/// estimate_node().
+pub(super) fn estimate_rows(node: &Node, catalog: &impl Catalog) -> Result<u64> {
+    let inputs = match node {
+        Node::HashJoin { left, right, .. } | Node::NestedLoopJoin { left, right, .. } => {
+            vec![estimate_rows(left, catalog)?, estimate_rows(right, catalog)?]
+        }
+        Node::Aggregate { source, .. }
+        | Node::Filter { source, .. }
+        | Node::Limit { source, .. }
+        | Node::Offset { source, .. }
+        | Node::Order { source, .. }
+        | Node::Projection { source, .. }
+        | Node::Remap { source, .. }
+        | Node::Sample { source, .. }
+        | Node::SemiJoin { source, .. } => vec![estimate_rows(source, catalog)?],
+        Node::IndexLookup { .. }
+        | Node::KeyLookup { .. }
+        | Node::Nothing { .. }
+        | Node::Scan { .. }
+        | Node::Values { .. } => Vec::new(),
+    };
+    estimate_node(node, &inputs, catalog)
+}
+
+/// Estimates the number of rows emitted by a node, given the estimates for
+/// its inputs (the source, or the left and right join inputs), so callers can
+/// compute estimates for a whole tree bottom-up in one pass. Uses table row
+/// counts and simple assumptions about the other nodes: filters (including
+/// semi-joins and join predicates) are assumed to keep FILTER_SELECTIVITY of
+/// their rows, and equi-joins to match each row of the larger input once.
+/// Used for join reordering and EXPLAIN.
+pub(super) fn estimate_node(node: &Node, inputs: &[u64], catalog: &impl Catalog) -> Result<u64> {
+    let filtered = |rows: u64| (rows as f64 * FILTER_SELECTIVITY).ceil() as u64;
+    let input = |i: usize| inputs.get(i).copied().unwrap_or(0);
+    Ok(match node {
+        Node::Scan {
+            table,
+            filter: None,
+            ..
+        } => catalog.table_stats(table.name())?.row_count,
+        Node::Scan { table, .. } => filtered(catalog.table_stats(table.name())?.row_count),
+        Node::IndexLookup { values, .. } => values.len() as u64,
+        Node::KeyLookup { keys, .. } => keys.len() as u64,
+        Node::Values { rows } => rows.len() as u64,
+        Node::Nothing { .. } => 0,
+
+        Node::Aggregate { group_by, .. } => match group_by.is_empty() {
+            true => 1,
+            false => input(0),
+        },
+        Node::HashJoin { .. } => input(0).max(input(1)),
+        Node::NestedLoopJoin {
+            predicate, outer, ..
+        } => {
+            let mut rows = input(0).saturating_mul(input(1));
+            if predicate.is_some() {
+                rows = filtered(rows);
+            }
+            // Outer joins emit every left row at least once.
+            if *outer {
+                rows = rows.max(input(0));
+            }
+            rows
+        }
+        Node::Limit { limit, .. } => input(0).min(*limit as u64),
+        Node::Sample { size, .. } => input(0).min(*size as u64),
+        Node::Offset { offset, .. } => input(0).saturating_sub(*offset as u64),
+        Node::Filter { .. } | Node::SemiJoin { .. } => filtered(input(0)),
+
+        Node::Order { .. } | Node::Projection { .. } | Node::Remap { .. } => input(0),
+    })
+}
@@ -1,10 +1,10 @@
 use crate::common::Result;
 use crate::sql::engine::{Catalog, Transaction};
 use crate::sql::execution;
-use crate::sql::execution::ExecutionResult;
+use crate::sql::execution::{CancelToken, ExecutionResult};
 use crate::sql::parser::ast;
 use crate::sql::planner::expression::Expression;
-use crate::sql::planner::optimizer::OPTIMIZERS;
+use crate::sql::planner::optimizer::{reorder_joins, OPTIMIZERS};
 use crate::sql::planner::{BoxedNode, Node, Planner};
 use crate::types::Table;
 use serde::{Deserialize, Serialize};
@@ -12,8 +12,13 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Plan {
     /// A CREATE TABLE plan. Creates a new table with the given schema. Errors
-    /// if the table already exists or the schema is invalid.
-    CreateTable { schema: Table },
+    /// if the table already exists, unless if_not_exists is true, or if the
+    /// schema is invalid.
+    CreateTable { schema: Table, if_not_exists: bool },
+    /// A CREATE TABLE AS plan. Creates a new table with a schema inferred from
+    /// the source's column labels and rows, then inserts the source rows into
+    /// it. Errors if the table already exists.
+    CreateTableAs { name: String, source: BoxedNode },
     /// A DROP TABLE plan. Drops the given table. Errors if the table does not
     /// exist, unless if_exists is true.
     DropTable { table: String, if_exists: bool },
@@ -24,13 +29,17 @@ pub enum Plan {
         // primary_key: usize,
         source: BoxedNode,
     },
-    /// An INSERT plan. Inserts rows from source (typically a Values node) into table.
+    /// An INSERT plan. Inserts rows from source (a Values node, or a query for
+    /// INSERT ... SELECT) into table. Query values are converted to the table's
+    /// column types where possible.
     Insert { table: Table, source: BoxedNode },
     /// An UPDATE plan. Updates rows in table that match the rows from source,
     /// where primary_key specifies the primary key column index in the source
     /// rows. The given column/expression pairs specify the row updates to make,
-    /// evaluated using the existing source row, which must be a complete row
-    /// from the update table.
+    /// evaluated using the existing source row, which must start with a
+    /// complete row from the update table. Any trailing columns (e.g. from an
+    /// UPDATE FROM join) are discarded when writing the row, and the source
+    /// rows must carry the update table's record IDs.
     Update {
         table: Table,
         // primary_key: usize,
@@ -50,21 +59,54 @@ impl Plan {
 
     /// Executes the plan, consuming it.
     pub fn execute(self, txn: &(impl Transaction + Catalog)) -> Result<ExecutionResult> {
-        execution::execute_plan(self, txn, txn)
+        self.execute_with(txn, &CancelToken::new())
+    }
+
+    /// Executes the plan, consuming it. Errors with Error::Cancelled once the
+    /// given token is cancelled, including while iterating over the rows of
+    /// the result.
+    pub fn execute_with(
+        self,
+        txn: &(impl Transaction + Catalog),
+        cancel: &CancelToken,
+    ) -> Result<ExecutionResult> {
+        execution::execute_plan(self, txn, txn, cancel)
     }
 
     /// Optimizes the plan, consuming it.
     pub fn optimize(self) -> Result<Self> {
-        let optimize = |node| OPTIMIZERS.iter().try_fold(node, |node, (_, opt)| opt(node));
+        self.transform_source(|node| OPTIMIZERS.iter().try_fold(node, |node, (_, opt)| opt(node)))
+    }
+
+    /// Reorders inner hash joins to build on their smaller input, using table
+    /// statistics from the catalog. This changes the order in which the joins
+    /// emit rows, so it's opt-in rather than one of the OPTIMIZERS. See
+    /// optimizer::reorder_joins().
+    ///
+    /// DELETE and UPDATE sources are left as is: joins pass on the record IDs
+    /// of their left input, which must be the rows of the written table.
+    pub fn reorder_joins(self, catalog: &impl Catalog) -> Result<Self> {
+        match self {
+            Self::Delete { .. } | Self::Update { .. } => Ok(self),
+            plan => plan.transform_source(|node| reorder_joins(node, catalog)),
+        }
+    }
+
+    /// Transforms the plan's source node, if any, with the given closure.
+    fn transform_source(self, transform: impl Fn(BoxedNode) -> Result<BoxedNode>) -> Result<Self> {
         Ok(match self {
             Self::CreateTable { .. } | Self::DropTable { .. } => self,
+            Self::CreateTableAs { name, source } => Self::CreateTableAs {
+                name,
+                source: transform(source)?,
+            },
             Self::Delete { table, source } => Self::Delete {
                 table,
-                source: optimize(source)?,
+                source: transform(source)?,
             },
             Self::Insert { table, source } => Self::Insert {
                 table,
-                source: optimize(source)?,
+                source: transform(source)?,
             },
             Self::Update {
                 table,
@@ -72,33 +114,69 @@ impl Plan {
                 expressions,
             } => Self::Update {
                 table,
-                source: optimize(source)?,
+                source: transform(source)?,
                 expressions,
             },
-            Self::Select(root) => Self::Select(optimize(root)?),
+            Self::Select(root) => Self::Select(transform(root)?),
         })
     }
+
+    /// Renders the plan as a tree for EXPLAIN, annotating each node with its
+    /// estimated row count. Estimates use table statistics from the catalog,
+    /// and simple selectivity assumptions for filters and joins.
+    pub fn explain(&self, catalog: &impl Catalog) -> Result<String> {
+        let (title, root) = match self {
+            Self::CreateTable { schema, .. } => (format!("CreateTable: {}", schema.name()), None),
+            Self::CreateTableAs { name, source } => {
+                (format!("CreateTableAs: {name}"), Some(source))
+            }
+            Self::DropTable { table, .. } => (format!("DropTable: {table}"), None),
+            Self::Delete { table, source } => (format!("Delete: {table}"), Some(source)),
+            Self::Insert { table, source } => (format!("Insert: {}", table.name()), Some(source)),
+            Self::Update { table, source, .. } => {
+                (format!("Update: {}", table.name()), Some(source))
+            }
+            Self::Select(root) => {
+                let mut lines = Vec::new();
+                root.explain(catalog, "", "", &mut lines)?;
+                return Ok(lines.join("\n"));
+            }
+        };
+        let mut lines = vec![title];
+        if let Some(root) = root {
+            root.explain(catalog, "└─ ", "   ", &mut lines)?;
+        }
+        Ok(lines.join("\n"))
+    }
 }
 
 /// An aggregate function.
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Aggregate {
+    ArrayAgg(Expression),
     Average(Expression),
     Count(Expression),
     Max(Expression),
     Min(Expression),
+    /// The continuous percentile at the given fraction in [0, 1], linearly
+    /// interpolating between the nearest values, e.g. 0.5 for the median.
+    PercentileCont(Expression, f64),
     Sum(Expression),
 }
 
-#[allow(dead_code)]
 impl Aggregate {
-    fn format(&self, node: &Node) -> String {
+    /// Formats the aggregate, using the source node to look up column labels.
+    pub(super) fn format(&self, node: &Node) -> String {
         match self {
+            Self::ArrayAgg(expr) => format!("array_agg({})", expr.format(node)),
             Self::Average(expr) => format!("avg({})", expr.format(node)),
             Self::Count(expr) => format!("count({})", expr.format(node)),
             Self::Max(expr) => format!("max({})", expr.format(node)),
             Self::Min(expr) => format!("min({})", expr.format(node)),
+            Self::PercentileCont(expr, fraction) => {
+                format!("percentile_cont({}, {fraction})", expr.format(node))
+            }
             Self::Sum(expr) => format!("sum({})", expr.format(node)),
         }
     }
@@ -129,6 +207,32 @@ impl From<ast::Direction> for Direction {
     }
 }
 
+/// Where NULLs sort, regardless of the sort direction. Without one, NULLs sort
+/// as the smallest value, i.e. first when ascending and last when descending.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NullOrder {
+    First,
+    Last,
+}
+
+impl std::fmt::Display for NullOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::First => f.write_str("nulls first"),
+            Self::Last => f.write_str("nulls last"),
+        }
+    }
+}
+
+impl From<ast::NullOrder> for NullOrder {
+    fn from(nulls: ast::NullOrder) -> Self {
+        match nulls {
+            ast::NullOrder::First => Self::First,
+            ast::NullOrder::Last => Self::Last,
+        }
+    }
+}
+
 /// Inverts a Remap targets vector to a vector of source indexes, with None
 /// for columns that weren't targeted.
 pub fn remap_sources(targets: &[Option<usize>]) -> Vec<Option<usize>> {
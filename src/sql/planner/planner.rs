@@ -3,10 +3,11 @@ use crate::errinput;
 use crate::sql::engine::Catalog;
 use crate::sql::parser::ast;
 use crate::sql::parser::ast::Statement;
+use crate::sql::parser::Parser;
 use crate::sql::planner::plan::remap_sources;
 use crate::sql::planner::{Aggregate, Expression, Node, Plan};
 use crate::types::field::{Field, Label};
-use crate::types::{Column, Table};
+use crate::types::{Column, DataType, Table};
 use itertools::Itertools as _;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
@@ -26,21 +27,39 @@ impl<'a, C: Catalog> Planner<'a, C> {
     pub fn build(&mut self, statement: Statement) -> Result<Plan> {
         use ast::Statement::*;
         match statement {
-            Explain(_) => {
-                todo!()
-            }
-            CreateTable { name, columns } => self.build_create_table(name, columns),
+            Explain(_) => errinput!("EXPLAIN must be handled by the session"),
+            CreateTable {
+                name,
+                columns,
+                if_not_exists,
+            } => self.build_create_table(name, columns, if_not_exists),
+            CreateTableAs { name, query } => self.build_create_table_as(name, *query),
             DropTable { name, if_exists } => Ok(Plan::DropTable {
                 table: name,
                 if_exists,
             }),
-            Delete { table, r#where } => self.build_delete(table, r#where),
-            Insert { table, values } => self.build_insert(table, values),
+            Delete {
+                table,
+                using,
+                r#where,
+                limit,
+            } => self.build_delete(table, using, r#where, limit),
+            Insert {
+                table,
+                columns,
+                values,
+            } => self.build_insert(table, columns, values),
+            InsertSelect {
+                table,
+                columns,
+                query,
+            } => self.build_insert_select(table, columns, *query),
             Update {
                 table,
                 set,
+                from,
                 r#where,
-            } => self.build_update(table, set, r#where),
+            } => self.build_update(table, set, from, r#where),
             Select {
                 select,
                 from,
@@ -50,9 +69,22 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 order_by,
                 offset,
                 limit,
-            } => self.build_select(
-                select, from, r#where, group_by, having, order_by, offset, limit,
-            ),
+                sample,
+            } => Ok(Plan::Select(
+                self.build_select(
+                    Scope::new(),
+                    select,
+                    from,
+                    r#where,
+                    group_by,
+                    having,
+                    order_by,
+                    offset,
+                    limit,
+                    sample,
+                )?
+                .into(),
+            )),
             _ => {
                 panic!("Statement either invalid or not yet implemented.")
             }
@@ -80,6 +112,17 @@ impl<'a, C: Catalog> Planner<'a, C> {
         mut aggregates: Vec<ast::Expression>,
         scope: &mut Scope,
     ) -> Result<Node> {
+        // GROUP BY ROLLUP(a, b) groups by (a, b), then (a), then ().
+        let mut rollup = false;
+        if let [ast::Expression::Function(name, _)] = group_by.as_slice() {
+            if name == "rollup" {
+                let Some(ast::Expression::Function(_, args)) = group_by.pop() else {
+                    unreachable!()
+                };
+                (group_by, rollup) = (args, true);
+            }
+        }
+
         // Construct a child scope with the group_by and aggregate AST
         // expressions, for lookups. Discard duplicate expressions.
         let mut child_scope = scope.spawn();
@@ -87,44 +130,62 @@ impl<'a, C: Catalog> Planner<'a, C> {
         aggregates.retain(|expr| child_scope.add_aggregate(expr, scope).is_some());
 
         // Build the node from the remaining unique expressions.
-        let group_by = group_by
+        let group_by: Vec<_> = group_by
             .into_iter()
-            .map(|expr| Self::build_expression(expr, scope))
+            .map(|expr| self.build_expression(expr, scope))
             .try_collect()?;
         let aggregates = aggregates
             .into_iter()
-            .map(|expr| Self::build_aggregate_function(expr, scope))
+            .map(|expr| self.build_aggregate_function(expr, scope))
             .try_collect()?;
+        let grouping_sets =
+            rollup.then(|| (0..=group_by.len()).rev().map(|n| (0..n).collect()).collect());
 
         *scope = child_scope;
         Ok(Node::Aggregate {
             source: source.into(),
             group_by,
             aggregates,
+            grouping_sets,
         })
     }
 
     /// Builds an aggregate function from an AST expression.
-    fn build_aggregate_function(expr: ast::Expression, scope: &Scope) -> Result<Aggregate> {
+    fn build_aggregate_function(&self, expr: ast::Expression, scope: &Scope) -> Result<Aggregate> {
         let ast::Expression::Function(name, mut args) = expr else {
             panic!("aggregate expression must be function");
         };
-        if args.len() != 1 {
-            return errinput!("{name} takes 1 argument");
-        }
+        // PERCENTILE_CONT(expr, fraction) also takes a constant fraction.
+        let fraction = match (name.as_str(), args.len()) {
+            ("percentile_cont", 2) => {
+                let fraction = self.build_expression(args.remove(1), &Scope::new())?;
+                match fraction.evaluate(None)? {
+                    Field::Integer(i) if (0..=1).contains(&i) => Some(i as f64),
+                    Field::Float(f) if (0.0..=1.0).contains(&f) => Some(f as f64),
+                    value => {
+                        return errinput!("percentile fraction must be in [0, 1], got {value}")
+                    }
+                }
+            }
+            ("percentile_cont", _) => return errinput!("{name} takes 2 arguments"),
+            (_, 1) => None,
+            _ => return errinput!("{name} takes 1 argument"),
+        };
         if args[0].contains(&|expr| Self::is_aggregate_function(expr)) {
             return errinput!("aggregate functions can't be nested");
         }
         // Special-case COUNT(*) since expressions don't support tuples.
         let expr = match (name.as_str(), args.remove(0)) {
             ("count", ast::Expression::All) => Expression::Constant(Field::Boolean(true)),
-            (_, arg) => Self::build_expression(arg, scope)?,
+            (_, arg) => self.build_expression(arg, scope)?,
         };
         Ok(match name.as_str() {
+            "array_agg" => Aggregate::ArrayAgg(expr),
             "avg" => Aggregate::Average(expr),
             "count" => Aggregate::Count(expr),
             "min" => Aggregate::Min(expr),
             "max" => Aggregate::Max(expr),
+            "percentile_cont" => Aggregate::PercentileCont(expr, fraction.unwrap_or_default()),
             "sum" => Aggregate::Sum(expr),
             name => return errinput!("unknown aggregate function {name}"),
         })
@@ -133,13 +194,30 @@ impl<'a, C: Catalog> Planner<'a, C> {
     /// Checks whether a given AST expression is an aggregate function.
     fn is_aggregate_function(expr: &ast::Expression) -> bool {
         if let ast::Expression::Function(name, _) = expr {
-            return ["avg", "count", "max", "min", "sum"].contains(&name.as_str());
+            return [
+                "array_agg",
+                "avg",
+                "count",
+                "max",
+                "min",
+                "percentile_cont",
+                "sum",
+            ]
+            .contains(&name.as_str());
         }
         false
     }
 
     /// Builds a CREATE TABLE plan.
-    fn build_create_table(&self, name: String, columns: Vec<ast::Column>) -> Result<Plan> {
+    fn build_create_table(
+        &self,
+        name: String,
+        columns: Vec<ast::Column>,
+        if_not_exists: bool,
+    ) -> Result<Plan> {
+        if columns.iter().filter(|c| c.primary_key).count() > 1 {
+            return errinput!("multiple primary keys for table {name}");
+        }
         let table = Table::builder()
             .name(&name)
             .columns(
@@ -147,53 +225,160 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     .into_iter()
                     .map(|c| {
                         let nullable = c.nullable.unwrap_or(false);
-                        Ok(Column::new(
-                            &c.name,
-                            c.datatype,
-                            nullable,
-                            match c.default {
-                                Some(expr) => Some(Self::evaluate_constant(expr)?),
-                                None if nullable => Some(Field::Null),
-                                None => None,
-                            },
-                            None,
-                        ))
+                        // Literal defaults are stored as values, while other
+                        // expressions are evaluated on every insert.
+                        let (default, default_expr) = match c.default {
+                            Some(expr @ ast::Expression::Literal(_)) => {
+                                (Some(self.evaluate_constant(expr)?), None)
+                            }
+                            Some(expr) => (None, Some(self.build_expression(expr, &Scope::new())?)),
+                            None if nullable => (Some(Field::Null), None),
+                            None => (None, None),
+                        };
+                        let mut column = Column::new(&c.name, c.datatype, nullable, default, None);
+                        column.set_primary_key(c.primary_key);
+                        if let Some(expr) = default_expr {
+                            // The expression is stored as SQL text, so make
+                            // sure that it parses back to the same expression.
+                            column.set_default_expr(&expr.format_constant());
+                            if self.build_default(&column)?.as_ref() != Some(&expr) {
+                                return errinput!("can't store default expression for {}", c.name);
+                            }
+                        }
+                        Ok(column)
                     })
                     .collect::<Result<_>>()?,
             )
             .build();
-        Ok(Plan::CreateTable { schema: table })
+        Ok(Plan::CreateTable {
+            schema: table,
+            if_not_exists,
+        })
+    }
+
+    /// Builds a CREATE TABLE AS plan. The query's columns must have distinct,
+    /// non-empty names, which become the new table's column names.
+    fn build_create_table_as(&mut self, name: String, query: ast::Statement) -> Result<Plan> {
+        let Plan::Select(source) = self.build(query)? else {
+            return errinput!("CREATE TABLE AS requires a SELECT query");
+        };
+        let mut names = HashSet::new();
+        for index in 0..source.columns() {
+            let label = source.column_label(index);
+            if label == Label::None {
+                return errinput!("column {index} of CREATE TABLE AS needs a name, use AS");
+            }
+            if !names.insert(label.as_header().to_string()) {
+                return errinput!("duplicate column {} in CREATE TABLE AS", label.as_header());
+            }
+        }
+        Ok(Plan::CreateTableAs { name, source })
     }
 
-    /// Builds a DELETE plan.
-    fn build_delete(&self, table: String, r#where: Option<ast::Expression>) -> Result<Plan> {
+    /// Builds a DELETE plan. A LIMIT bounds the number of source rows, and thus
+    /// the number of deleted rows. With USING, it bounds the joined rows, which
+    /// may contain a table row several times.
+    fn build_delete(
+        &self,
+        table: String,
+        using: Vec<ast::From>,
+        r#where: Option<ast::Expression>,
+        limit: Option<ast::Expression>,
+    ) -> Result<Plan> {
         let table = self.catalog.must_get_table(&table)?;
-        let scope = Scope::from_table(&table)?;
-        let filter = r#where
-            .map(|expr| Self::build_expression(expr, &scope))
-            .transpose()?;
+        let mut scope = Scope::from_table(&table)?;
+        let mut source = self.build_write_source(&table, using, r#where, &mut scope)?;
+        if let Some(limit) = limit {
+            let limit = match self.evaluate_constant(limit)? {
+                Field::Integer(limit) if limit >= 0 => limit as usize,
+                limit => return errinput!("invalid limit {limit}"),
+            };
+            source = Node::Limit {
+                source: source.into(),
+                limit,
+            };
+        }
         Ok(Plan::Delete {
             table: table.name().to_string(),
-            source: Node::Scan {
-                table,
+            source: source.into(),
+        })
+    }
+
+    /// Builds the source node for an UPDATE or DELETE, i.e. the table rows
+    /// matching the WHERE predicate. The scope must contain the table's
+    /// columns.
+    ///
+    /// If other tables are given (i.e. UPDATE FROM or DELETE USING), the table
+    /// is joined with them, using the WHERE clause as the join predicate. The
+    /// table is on the left, so the joined rows carry its record IDs and its
+    /// columns come first. The other tables' columns are added to the scope.
+    fn build_write_source(
+        &self,
+        table: &Table,
+        from: Vec<ast::From>,
+        r#where: Option<ast::Expression>,
+        scope: &mut Scope,
+    ) -> Result<Node> {
+        let from = match from.is_empty() {
+            true => None,
+            false => Some(self.build_from_clause(from, scope)?),
+        };
+        let filter = r#where
+            .map(|expr| self.build_expression(expr, scope))
+            .transpose()?;
+        Ok(match from {
+            None => Node::Scan {
+                table: table.clone(),
                 alias: None,
                 filter,
-            }
-            .into(),
+            },
+            Some(from) => Node::NestedLoopJoin {
+                left: Node::Scan {
+                    table: table.clone(),
+                    alias: None,
+                    filter: None,
+                }
+                .into(),
+                right: from.into(),
+                predicate: filter,
+                outer: false,
+            },
         })
     }
 
     /// Builds an INSERT plan.
-    fn build_insert(&self, table: String, values: Vec<Vec<ast::Expression>>) -> Result<Plan> {
+    ///
+    /// Values are given for the specified columns, or for the table's columns
+    /// in order. Any other columns, as well as DEFAULT values, take the
+    /// column's default value.
+    fn build_insert(
+        &self,
+        table: String,
+        columns: Option<Vec<String>>,
+        values: Vec<Vec<ast::Expression>>,
+    ) -> Result<Plan> {
         let table = self.catalog.must_get_table(&table)?;
         let scope = Scope::new();
+        let targets = Self::build_insert_targets(&table, columns)?;
+        let default = |index| self.build_insert_default(&table, index);
 
         let mut rows = Vec::new();
         for exprs in values {
-            let mut row = Vec::new();
-            for expr in exprs {
-                row.push(Self::build_expression(expr, &scope)?);
+            if exprs.len() > targets.len() {
+                return errinput!("expected {} values, got {}", targets.len(), exprs.len());
             }
+            let mut row = vec![None; table.col_count()];
+            for (expr, &index) in exprs.into_iter().zip(&targets) {
+                row[index] = Some(match expr {
+                    ast::Expression::Default => default(index)?,
+                    expr => self.build_expression(expr, &scope)?,
+                });
+            }
+            let row = row
+                .into_iter()
+                .enumerate()
+                .map(|(index, expr)| expr.map(Ok).unwrap_or_else(|| default(index)))
+                .collect::<Result<_>>()?;
             rows.push(row);
         }
         Ok(Plan::Insert {
@@ -202,15 +387,108 @@ impl<'a, C: Catalog> Planner<'a, C> {
         })
     }
 
+    /// Builds an INSERT plan for a SELECT query. The query must yield a value
+    /// for each target column, with a type that can be converted to the
+    /// column's type. Omitted columns take their default value.
+    fn build_insert_select(
+        &mut self,
+        table: String,
+        columns: Option<Vec<String>>,
+        query: ast::Statement,
+    ) -> Result<Plan> {
+        let table = self.catalog.must_get_table(&table)?;
+        let targets = Self::build_insert_targets(&table, columns)?;
+        let Plan::Select(source) = self.build(query)? else {
+            return errinput!("INSERT requires a SELECT query");
+        };
+        if source.columns() != targets.len() {
+            return errinput!("expected {} columns, got {}", targets.len(), source.columns());
+        }
+
+        // Check that the query's column types can be converted to the target
+        // columns' types. Integral floats and strings are checked for each
+        // value during execution, as is the type of e.g. NULL (Invalid).
+        for (i, &index) in targets.iter().enumerate() {
+            let column = table.get_column(index);
+            match (source.column_type(i), column.get_data_type()) {
+                (from, to) if from == to => {}
+                (DataType::Invalid | DataType::Text, _) => {}
+                (DataType::Int, DataType::Float) | (DataType::Float, DataType::Int) => {}
+                (from, to) => {
+                    return errinput!("can't insert {from} into {to} column {}", column.get_name())
+                }
+            }
+        }
+
+        // Reorder the query's columns to the table's, filling in defaults.
+        if targets.iter().copied().ne(0..table.col_count()) {
+            let expressions = (0..table.col_count())
+                .map(|index| match targets.iter().position(|&target| target == index) {
+                    Some(i) => Ok(Expression::Column(i)),
+                    None => self.build_insert_default(&table, index),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let aliases = vec![Label::None; expressions.len()];
+            return Ok(Plan::Insert {
+                table,
+                source: Node::Projection {
+                    source,
+                    expressions,
+                    aliases,
+                }
+                .into(),
+            });
+        }
+        Ok(Plan::Insert { table, source })
+    }
+
+    /// Maps the INSERT column list to column indexes, or all columns in order
+    /// if not given.
+    fn build_insert_targets(table: &Table, columns: Option<Vec<String>>) -> Result<Vec<usize>> {
+        let Some(columns) = columns else {
+            return Ok((0..table.col_count()).collect());
+        };
+        let mut targets = Vec::with_capacity(columns.len());
+        for column in columns {
+            let Some(index) = table.field_name_to_index(Some(&column)) else {
+                return errinput!("unknown column {column} in table {}", table.name());
+            };
+            if targets.contains(&index) {
+                return errinput!("column {column} given multiple times");
+            }
+            targets.push(index);
+        }
+        Ok(targets)
+    }
+
+    /// Looks up the default value of the given column, for INSERT.
+    fn build_insert_default(&self, table: &Table, index: usize) -> Result<Expression> {
+        match self.build_default(table.get_column(index))? {
+            Some(default) => Ok(default),
+            None => errinput!("column {} has no default value", table.get_column_name(index)),
+        }
+    }
+
+    /// Builds the expression yielding a column's default value, if any, i.e.
+    /// its default expression or literal default. Default expressions are
+    /// stored as SQL text, and parsed here.
+    pub fn build_default(&self, column: &Column) -> Result<Option<Expression>> {
+        let Some(sql) = column.default_expr() else {
+            return Ok(column.default().cloned().map(Expression::Constant));
+        };
+        let expr = Parser::new(sql).parse_expression()?;
+        Ok(Some(self.build_expression(expr, &Scope::new())?))
+    }
+
     /// Collects aggregate functions from SELECT, HAVING, and ORDER BY clauses.
     fn collect_aggregates(
         select: &[(ast::Expression, Option<String>)],
         having: &Option<ast::Expression>,
-        order_by: &[(ast::Expression, ast::Direction)],
+        order_by: &[(ast::Expression, ast::Direction, Option<ast::NullOrder>)],
     ) -> Vec<ast::Expression> {
         let select = select.iter().map(|(expr, _)| expr);
         let having = having.iter();
-        let order_by = order_by.iter().map(|(expr, _)| expr);
+        let order_by = order_by.iter().map(|(expr, _, _)| expr);
         let mut aggregates = Vec::new();
         for expr in select.chain(having).chain(order_by) {
             expr.collect(&|expr| Self::is_aggregate_function(expr), &mut aggregates)
@@ -233,12 +511,12 @@ impl<'a, C: Catalog> Planner<'a, C> {
     fn build_select_hidden(
         &self,
         having: &Option<ast::Expression>,
-        order_by: &[(ast::Expression, ast::Direction)],
+        order_by: &[(ast::Expression, ast::Direction, Option<ast::NullOrder>)],
         scope: &Scope,
         child_scope: &mut Scope,
     ) -> Vec<Expression> {
         let mut hidden = Vec::new();
-        for expr in having.iter().chain(order_by.iter().map(|(expr, _)| expr)) {
+        for expr in having.iter().chain(order_by.iter().map(|(expr, _, _)| expr)) {
             expr.walk(&mut |expr| {
                 // If this is an aggregate or GROUP BY expression that isn't
                 // already available in the child scope, add a hidden column.
@@ -273,7 +551,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
 
     /// Builds an expression from an AST expression, looking up columns and
     /// aggregate expressions in the scope.
-    pub fn build_expression(expr: ast::Expression, scope: &Scope) -> Result<Expression> {
+    pub fn build_expression(&self, expr: ast::Expression, scope: &Scope) -> Result<Expression> {
         use Expression::*;
 
         // Look up aggregate functions or GROUP BY expressions. These were added
@@ -284,7 +562,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
 
         // Helper for building a boxed expression.
         let build = |expr: Box<ast::Expression>| -> Result<Box<Expression>> {
-            Ok(Box::new(Self::build_expression(*expr, scope)?))
+            Ok(Box::new(self.build_expression(*expr, scope)?))
         };
 
         Ok(match expr {
@@ -297,16 +575,90 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 ast::Literal::Boolean(b) => Field::Boolean(b),
                 ast::Literal::Integer(i) => Field::Integer(i),
                 ast::Literal::Float(f) => Field::Float(f),
-                ast::Literal::String(s) => Field::String(s),
+                ast::Literal::String(s) => Field::String(s.into()),
+                ast::Literal::Time(t) => Field::Time(t),
+                ast::Literal::Interval(i) => Field::Interval(i),
             }),
             ast::Expression::Column(table, name) => {
-                Column(scope.lookup_column(table.as_deref(), &name)?)
+                match scope.lookup_column(table.as_deref(), &name) {
+                    Ok(index) => Column(index),
+                    // Fall back to the enclosing query's columns, if any. This
+                    // makes the expression a correlated subquery.
+                    Err(err) => match &scope.outer {
+                        Some(outer) => OuterColumn(
+                            outer
+                                .lookup_column(table.as_deref(), &name)
+                                .map_err(|_| err)?,
+                        ),
+                        None => return Err(err),
+                    },
+                }
             }
             ast::Expression::Function(name, mut args) => match (name.as_str(), args.len()) {
                 // NB: aggregate functions are processed above.
+                ("abs", 1) => Absolute(build(Box::new(args.remove(0)))?),
+                ("array_length", 1) => ArrayLength(build(Box::new(args.remove(0)))?),
+                ("ceil", 1) => Ceil(build(Box::new(args.remove(0)))?),
+                ("floor", 1) => Floor(build(Box::new(args.remove(0)))?),
+                ("nullif", 2) => {
+                    let (lhs, rhs) = (args.remove(0), args.remove(0));
+                    NullIf(build(Box::new(lhs))?, build(Box::new(rhs))?)
+                }
+                ("mod", 2) => {
+                    let (lhs, rhs) = (args.remove(0), args.remove(0));
+                    Remainder(build(Box::new(lhs))?, build(Box::new(rhs))?)
+                }
+                ("position", 2) => {
+                    let (substring, string) = (args.remove(0), args.remove(0));
+                    Position(build(Box::new(substring))?, build(Box::new(string))?)
+                }
+                ("replace", 3) => {
+                    let (string, from, to) = (args.remove(0), args.remove(0), args.remove(0));
+                    Replace(
+                        build(Box::new(string))?,
+                        build(Box::new(from))?,
+                        build(Box::new(to))?,
+                    )
+                }
+                ("round", 1) => Round(
+                    build(Box::new(args.remove(0)))?,
+                    Box::new(Constant(Field::Integer(0))),
+                ),
+                ("round", 2) => {
+                    let (expr, digits) = (args.remove(0), args.remove(0));
+                    Round(build(Box::new(expr))?, build(Box::new(digits))?)
+                }
                 ("sqrt", 1) => SquareRoot(build(Box::new(args.remove(0)))?),
+                ("trim" | "ltrim" | "rtrim", 1 | 2) => {
+                    let expr = build(Box::new(args.remove(0)))?;
+                    let chars = args.pop().map(|chars| build(Box::new(chars))).transpose()?;
+                    match name.as_str() {
+                        "ltrim" => LeftTrim(expr, chars),
+                        "rtrim" => RightTrim(expr, chars),
+                        _ => Trim(expr, chars),
+                    }
+                }
                 (name, n) => return errinput!("unknown function {name} with {n} arguments"),
             },
+            ast::Expression::Cast(expr, data_type) => Cast(build(expr)?, data_type),
+            ast::Expression::Default => return errinput!("DEFAULT is only valid as an INSERT value"),
+            ast::Expression::Exists(_) => {
+                return errinput!("EXISTS is only supported as a WHERE condition")
+            }
+            ast::Expression::Row(_) => {
+                return errinput!("row values can only be compared with row values")
+            }
+            ast::Expression::Subquery(statement) => {
+                let node = self.build_subquery(*statement, scope)?;
+                if node.columns() != 1 {
+                    return errinput!("subquery must return a single column");
+                }
+                ScalarSubquery(node.into())
+            }
+            // Row value comparisons are expanded into scalar comparisons.
+            ast::Expression::Operator(op) if Self::is_row_comparison(&op) => {
+                return self.build_expression(Self::expand_row_comparison(op)?, scope)
+            }
             ast::Expression::Operator(op) => match op {
                 ast::Operator::And(lhs, rhs) => And(build(lhs)?, build(rhs)?),
                 ast::Operator::Not(expr) => Not(build(expr)?),
@@ -333,9 +685,14 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     Equal(build(lhs)?, build(rhs)?).into(),
                 ),
                 ast::Operator::Like(lhs, rhs) => Like(build(lhs)?, build(rhs)?),
+                ast::Operator::ILike(lhs, rhs) => ILike(build(lhs)?, build(rhs)?),
+                ast::Operator::Index(lhs, rhs) => Index(build(lhs)?, build(rhs)?),
                 ast::Operator::NotEqual(lhs, rhs) => Not(Equal(build(lhs)?, build(rhs)?).into()),
 
                 ast::Operator::Add(lhs, rhs) => Add(build(lhs)?, build(rhs)?),
+                ast::Operator::BitwiseAnd(lhs, rhs) => BitwiseAnd(build(lhs)?, build(rhs)?),
+                ast::Operator::BitwiseOr(lhs, rhs) => BitwiseOr(build(lhs)?, build(rhs)?),
+                ast::Operator::BitwiseXor(lhs, rhs) => BitwiseXor(build(lhs)?, build(rhs)?),
                 ast::Operator::Divide(lhs, rhs) => Divide(build(lhs)?, build(rhs)?),
                 ast::Operator::Exponentiate(lhs, rhs) => Exponentiate(build(lhs)?, build(rhs)?),
                 ast::Operator::Factorial(expr) => Factorial(build(expr)?),
@@ -348,57 +705,132 @@ impl<'a, C: Catalog> Planner<'a, C> {
         })
     }
 
+    /// Returns true if the operator compares two row values, e.g. (a, b) < (1, 2).
+    fn is_row_comparison(op: &ast::Operator) -> bool {
+        use ast::Operator::*;
+        match op {
+            Equal(lhs, rhs)
+            | GreaterThan(lhs, rhs)
+            | GreaterThanOrEqual(lhs, rhs)
+            | LessThan(lhs, rhs)
+            | LessThanOrEqual(lhs, rhs)
+            | NotEqual(lhs, rhs) => {
+                matches!((&**lhs, &**rhs), (ast::Expression::Row(_), ast::Expression::Row(_)))
+            }
+            _ => false,
+        }
+    }
+
+    /// Expands a row value comparison into comparisons of its components,
+    /// since expression evaluation only supports scalar values. Rows are
+    /// equal if all components are equal, and are otherwise ordered by their
+    /// first unequal component, e.g. (a, b) < (1, 2) is a < 1 OR (a = 1 AND
+    /// b < 2). As with scalar comparisons, a NULL component yields NULL unless
+    /// the other components decide the result, e.g. (1, NULL) < (2, 0) is true.
+    fn expand_row_comparison(op: ast::Operator) -> Result<ast::Expression> {
+        use ast::Operator::*;
+        let (Equal(lhs, rhs)
+        | GreaterThan(lhs, rhs)
+        | GreaterThanOrEqual(lhs, rhs)
+        | LessThan(lhs, rhs)
+        | LessThanOrEqual(lhs, rhs)
+        | NotEqual(lhs, rhs)) = &op
+        else {
+            panic!("not a row comparison"); // enforced by is_row_comparison()
+        };
+        let (ast::Expression::Row(lhs), ast::Expression::Row(rhs)) = (&**lhs, &**rhs) else {
+            panic!("not a row comparison"); // enforced by is_row_comparison()
+        };
+        if lhs.len() != rhs.len() {
+            return errinput!("can't compare rows of {} and {} values", lhs.len(), rhs.len());
+        }
+        let pairs = lhs.iter().zip(rhs).map(|(lhs, rhs)| (lhs.clone().into(), rhs.clone().into()));
+
+        // Equality is a conjunction of the component equalities.
+        if let Equal(_, _) | NotEqual(_, _) = op {
+            let equal = pairs
+                .map(|(lhs, rhs)| Equal(lhs, rhs).into())
+                .reduce(|lhs: ast::Expression, rhs| And(lhs.into(), rhs.into()).into())
+                .expect("rows can't be empty"); // enforced by parser
+            return Ok(match op {
+                NotEqual(_, _) => Not(equal.into()).into(),
+                _ => equal,
+            });
+        }
+
+        // Ordering compares the last component with the given operator, and
+        // strictly compares the preceding ones, building the expression inside
+        // out from the last component.
+        let mut pairs = pairs.rev();
+        let (lhs, rhs) = pairs.next().expect("rows can't be empty"); // enforced by parser
+        let mut expr: ast::Expression = match op {
+            GreaterThan(_, _) => GreaterThan(lhs, rhs),
+            GreaterThanOrEqual(_, _) => GreaterThanOrEqual(lhs, rhs),
+            LessThan(_, _) => LessThan(lhs, rhs),
+            LessThanOrEqual(_, _) => LessThanOrEqual(lhs, rhs),
+            _ => unreachable!(),
+        }
+        .into();
+        for (lhs, rhs) in pairs {
+            let strict = match op {
+                GreaterThan(_, _) | GreaterThanOrEqual(_, _) => {
+                    GreaterThan(lhs.clone(), rhs.clone())
+                }
+                _ => LessThan(lhs.clone(), rhs.clone()),
+            };
+            let tie: ast::Expression = And(Equal(lhs, rhs).into(), expr.into()).into();
+            expr = Or(strict.into(), tie.into()).into();
+        }
+        Ok(expr)
+    }
+
     /// Builds an UPDATE plan.
     fn build_update(
         &self,
         table: String,
         set: BTreeMap<String, Option<ast::Expression>>,
+        from: Vec<ast::From>,
         r#where: Option<ast::Expression>,
     ) -> Result<Plan> {
         let table = self.catalog.must_get_table(&table)?;
-        let scope = Scope::from_table(&table)?;
-        let filter = r#where
-            .map(|expr| Self::build_expression(expr, &scope))
-            .transpose()?;
+        let mut scope = Scope::from_table(&table)?;
+        let source = self.build_write_source(&table, from, r#where, &mut scope)?;
         let mut expressions = Vec::with_capacity(set.len());
         for (column, expr) in set {
-            let index = scope.lookup_column(None, &column)?;
+            let Some(index) = table.field_name_to_index(Some(&column)) else {
+                return errinput!("unknown column {column} in table {}", table.name());
+            };
             let expr = match expr {
-                Some(expr) => Self::build_expression(expr, &scope)?,
-                None => match &table.get_column(index).default() {
-                    Some(default) => Expression::Constant((*default).clone()),
+                Some(expr) => self.build_expression(expr, &scope)?,
+                None => match self.build_default(table.get_column(index))? {
+                    Some(default) => default,
                     None => return errinput!("column {column} has no default value"),
                 },
             };
             expressions.push((index, expr));
         }
         Ok(Plan::Update {
-            table: table.clone(),
-            source: Node::Scan {
-                table,
-                alias: None,
-                filter,
-            }
-            .into(),
+            table,
+            source: source.into(),
             expressions,
         })
     }
 
-    /// Builds a SELECT plan.
+    /// Builds a SELECT query node, resolving columns in the given scope.
     #[allow(clippy::too_many_arguments)]
     fn build_select(
         &self,
+        mut scope: Scope,
         mut select: Vec<(ast::Expression, Option<String>)>,
         from: Vec<ast::From>,
         r#where: Option<ast::Expression>,
         group_by: Vec<ast::Expression>,
         having: Option<ast::Expression>,
-        order_by: Vec<(ast::Expression, ast::Direction)>,
+        order_by: Vec<(ast::Expression, ast::Direction, Option<ast::NullOrder>)>,
         offset: Option<ast::Expression>,
         limit: Option<ast::Expression>,
-    ) -> Result<Plan> {
-        let mut scope = Scope::new();
-
+        sample: Option<(ast::Expression, Option<ast::Expression>)>,
+    ) -> Result<Node> {
         // Build FROM clause.
         let mut node = if !from.is_empty() {
             self.build_from_clause(from, &mut scope)?
@@ -420,7 +852,9 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     .into_iter()
                     .flat_map(|(expr, alias)| match expr {
                         ast::Expression::All => itertools::Either::Left(
-                            (0..node.columns()).map(|i| (node.column_label(i).into(), None)),
+                            // Use the scope's labels rather than the node's, since they're
+                            // qualified by table aliases rather than table names.
+                            scope.columns.iter().map(|label| (label.clone().into(), None)),
                         ),
                         expr => itertools::Either::Right(std::iter::once((expr, alias))),
                     })
@@ -428,13 +862,42 @@ impl<'a, C: Catalog> Planner<'a, C> {
             }
         }
 
-        // Build WHERE clause
+        // Build WHERE clause. Top-level [NOT] EXISTS conditions are split out
+        // into semi-joins, which filter the rows after the remaining predicate.
         if let Some(r#where) = r#where {
-            let predicate = Self::build_expression(r#where, &scope)?;
-            node = Node::Filter {
-                source: node.into(),
-                predicate,
-            };
+            let mut exists = Vec::new();
+            let mut conjuncts = Vec::new();
+            for expr in Self::split_conjunction(r#where) {
+                match expr {
+                    ast::Expression::Exists(statement) => exists.push((*statement, false)),
+                    ast::Expression::Operator(ast::Operator::Not(expr))
+                        if matches!(*expr, ast::Expression::Exists(_)) =>
+                    {
+                        if let ast::Expression::Exists(statement) = *expr {
+                            exists.push((*statement, true));
+                        }
+                    }
+                    expr => conjuncts.push(expr),
+                }
+            }
+            let predicate = conjuncts
+                .into_iter()
+                .reduce(|lhs, rhs| ast::Operator::And(lhs.into(), rhs.into()).into());
+            if let Some(predicate) = predicate {
+                node = Node::Filter {
+                    source: node.into(),
+                    predicate: self.build_expression(predicate, &scope)?,
+                    epsilon: None,
+                };
+            }
+            for (statement, anti) in exists {
+                let subquery = self.build_subquery(statement, &scope)?;
+                node = Node::SemiJoin {
+                    source: node.into(),
+                    subquery: Self::strip_exists_subquery(subquery).into(),
+                    anti,
+                };
+            }
         }
 
         // Build aggregate functions and GROUP BY clause.
@@ -452,7 +915,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
             let mut expressions = Vec::with_capacity(select.len());
             let mut aliases = Vec::with_capacity(select.len());
             for (expr, alias) in select {
-                expressions.push(Self::build_expression(expr, &scope)?);
+                expressions.push(self.build_expression(expr, &scope)?);
                 aliases.push(Label::from(alias));
             }
 
@@ -469,15 +932,18 @@ impl<'a, C: Catalog> Planner<'a, C> {
             };
         }
 
-        // Build HAVING clause
+        // Build HAVING clause. This filters the projected rows, so the
+        // predicate can refer to aggregates by their SELECT alias, e.g.
+        // SELECT SUM(a) AS total ... HAVING total > 100.
         if let Some(having) = having {
             if scope.aggregates.is_empty() {
                 return errinput!("HAVING requires GROUP BY or aggregate function");
             }
-            let predicate = Self::build_expression(having, &scope)?;
+            let predicate = self.build_expression(having, &scope)?;
             node = Node::Filter {
                 source: node.into(),
                 predicate,
+                epsilon: None,
             };
         }
 
@@ -485,7 +951,10 @@ impl<'a, C: Catalog> Planner<'a, C> {
         if !order_by.is_empty() {
             let key = order_by
                 .into_iter()
-                .map(|(expr, dir)| Ok((Self::build_expression(expr, &scope)?, dir.into())))
+                .map(|(expr, dir, nulls)| {
+                    let expr = self.build_expression(expr, &scope)?;
+                    Ok((expr, dir.into(), nulls.map(Into::into)))
+                })
                 .collect::<Result<_>>()?;
             node = Node::Order {
                 source: node.into(),
@@ -495,7 +964,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
 
         // Build OFFSET clause.
         if let Some(offset) = offset {
-            let offset = match Self::evaluate_constant(offset)? {
+            let offset = match self.evaluate_constant(offset)? {
                 Field::Integer(offset) if offset >= 0 => offset as usize,
                 offset => return errinput!("invalid offset {offset}"),
             };
@@ -507,7 +976,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
 
         // Build LIMIT clause.
         if let Some(limit) = limit {
-            let limit = match Self::evaluate_constant(limit)? {
+            let limit = match self.evaluate_constant(limit)? {
                 Field::Integer(limit) if limit >= 0 => limit as usize,
                 limit => return errinput!("invalid limit {limit}"),
             };
@@ -517,6 +986,24 @@ impl<'a, C: Catalog> Planner<'a, C> {
             }
         }
 
+        // Build SAMPLE clause. Without REPEATABLE, the seed is random.
+        if let Some((size, seed)) = sample {
+            let size = match self.evaluate_constant(size)? {
+                Field::Integer(size) if size >= 0 => size as usize,
+                size => return errinput!("invalid sample size {size}"),
+            };
+            let seed = match seed.map(|seed| self.evaluate_constant(seed)).transpose()? {
+                Some(Field::Integer(seed)) => seed as u64,
+                Some(seed) => return errinput!("invalid sample seed {seed}"),
+                None => rand::random(),
+            };
+            node = Node::Sample {
+                source: node.into(),
+                size,
+                seed,
+            }
+        }
+
         // Remove any hidden columns before emitting the result.
         if let Some(targets) = scope.remap_hidden() {
             node = Node::Remap {
@@ -525,7 +1012,74 @@ impl<'a, C: Catalog> Planner<'a, C> {
             }
         }
 
-        Ok(Plan::Select(node.into()))
+        Ok(node)
+    }
+
+    /// Splits an AST expression into its AND-ed conjuncts, e.g. a AND (b AND c)
+    /// yields [a, b, c].
+    fn split_conjunction(expr: ast::Expression) -> Vec<ast::Expression> {
+        match expr {
+            ast::Expression::Operator(ast::Operator::And(lhs, rhs)) => {
+                let mut exprs = Self::split_conjunction(*lhs);
+                exprs.extend(Self::split_conjunction(*rhs));
+                exprs
+            }
+            expr => vec![expr],
+        }
+    }
+
+    /// Strips nodes from an EXISTS subquery that don't affect whether it emits
+    /// any rows, i.e. the SELECT projection and ORDER BY, such that execution
+    /// can stop after the first row without buffering or evaluating the SELECT
+    /// expressions. Filters (e.g. HAVING) may depend on the projection, so
+    /// nothing below them is stripped.
+    fn strip_exists_subquery(node: Node) -> Node {
+        match node {
+            Node::Order { source, .. }
+            | Node::Projection { source, .. }
+            | Node::Remap { source, .. } => Self::strip_exists_subquery(*source.inner),
+            Node::Limit { source, limit } => Node::Limit {
+                source: Self::strip_exists_subquery(*source.inner).into(),
+                limit,
+            },
+            Node::Offset { source, offset } => Node::Offset {
+                source: Self::strip_exists_subquery(*source.inner).into(),
+                offset,
+            },
+            node => node,
+        }
+    }
+
+    /// Builds a subquery node. Column references that can't be resolved in
+    /// the subquery are looked up in the enclosing scope, yielding outer
+    /// column references which are bound to the current row when executed.
+    fn build_subquery(&self, statement: ast::Statement, scope: &Scope) -> Result<Node> {
+        let ast::Statement::Select {
+            select,
+            from,
+            r#where,
+            group_by,
+            having,
+            order_by,
+            offset,
+            limit,
+            sample,
+        } = statement
+        else {
+            return errinput!("subquery must be a SELECT statement");
+        };
+        self.build_select(
+            scope.correlate(),
+            select,
+            from,
+            r#where,
+            group_by,
+            having,
+            order_by,
+            offset,
+            limit,
+            sample,
+        )
     }
 
     /// Builds a FROM clause consisting of one or more items. Each item is
@@ -542,12 +1096,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
         // Build and implicitly join additional items.
         for from in items {
             let right = self.build_from(from, scope)?;
-            node = Node::NestedLoopJoin {
-                left: node.into(),
-                right: right.into(),
-                predicate: None,
-                outer: false,
-            };
+            node = Node::nested_loop_join(node, right, Vec::new(), false);
         }
         Ok(node)
     }
@@ -589,16 +1138,12 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 let (left_size, right_size) = (left.columns(), right.columns());
 
                 // Build the join node.
-                let predicate = predicate
-                    .map(|e| Self::build_expression(e, &scope))
-                    .transpose()?;
+                let conditions = predicate
+                    .into_iter()
+                    .map(|e| self.build_expression(e, &scope))
+                    .collect::<Result<Vec<_>>>()?;
                 let outer = r#type.is_outer();
-                let mut node = Node::NestedLoopJoin {
-                    left: left.into(),
-                    right: right.into(),
-                    predicate,
-                    outer,
-                };
+                let mut node = Node::nested_loop_join(left, right, conditions, outer);
 
                 // For right joins, swap the columns.
                 if r#type == ast::JoinType::Right {
@@ -621,8 +1166,8 @@ impl<'a, C: Catalog> Planner<'a, C> {
     }
 
     /// Builds and evaluates a constant AST expression. Errors on column refs.
-    fn evaluate_constant(expr: ast::Expression) -> Result<Field> {
-        Self::build_expression(expr, &Scope::new())?.evaluate(None)
+    fn evaluate_constant(&self, expr: ast::Expression) -> Result<Field> {
+        self.build_expression(expr, &Scope::new())?.evaluate(None)
     }
 }
 
@@ -636,7 +1181,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
 /// currently visible and what names they have. During expression planning, the
 /// scope is used to resolve column names to column indexes, which are placed in
 /// the plan and used during execution.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Scope {
     /// The currently visible columns. If empty, only constant expressions can
     /// be used (no column references).
@@ -658,6 +1203,9 @@ pub struct Scope {
     /// expressions through SELECT projection nodes if the expressions aren't
     /// already projected. They should be removed before emitting results.
     hidden: HashSet<usize>,
+    /// The scope of the enclosing query, for correlated subqueries. Columns
+    /// that aren't found in this scope are looked up here.
+    outer: Option<Box<Scope>>,
 }
 
 impl Scope {
@@ -670,6 +1218,7 @@ impl Scope {
             unqualified: HashMap::new(),
             aggregates: HashMap::new(),
             hidden: HashSet::new(),
+            outer: None,
         }
     }
 
@@ -684,9 +1233,19 @@ impl Scope {
     pub fn spawn(&self) -> Self {
         let mut child = Scope::new();
         child.tables.clone_from(&self.tables); // retain table names
+        child.outer.clone_from(&self.outer);
         child
     }
 
+    /// Creates a new, empty scope for a subquery, which can reference the
+    /// columns of this scope as outer columns.
+    fn correlate(&self) -> Self {
+        Self {
+            outer: Some(Box::new(self.clone())),
+            ..Self::new()
+        }
+    }
+
     /// Adds a table to the scope. The label is either the table's original name
     /// or an alias, and must be unique. All table columns are added, in order.
     fn add_table(&mut self, table: &Table, alias: Option<&str>) -> Result<()> {
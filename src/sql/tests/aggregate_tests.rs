@@ -0,0 +1,438 @@
+use crate::sql::engine::Local;
+use crate::sql::execution::aggregate;
+use crate::sql::planner::{Aggregate, Expression};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::storage::page::INVALID_RID;
+use crate::storage::tuple::{Row, Rows};
+use crate::types::field::Field;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[test]
+fn test_having_aggregate_alias() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // HAVING is evaluated after the SELECT projection, so it can refer to
+    // aggregates (and other columns) by their SELECT alias.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE sales (id INT PRIMARY KEY, region STRING, amount INT)")
+        .execute(
+            "INSERT INTO sales VALUES (1, 'east', 60), (2, 'east', 70), (3, 'west', 20), \
+             (4, 'north', 150)",
+        )
+        .select_expect(
+            "SELECT region, SUM(amount) AS total FROM sales GROUP BY region \
+             HAVING total > 100 ORDER BY region",
+            "sales.region, total ; east, 130 ; north, 150",
+        )
+        .select_expect(
+            "SELECT region, SUM(amount) AS total, COUNT(*) AS n FROM sales GROUP BY region \
+             HAVING total / n > 60 AND n > 1",
+            "sales.region, total, n ; east, 130, 2",
+        )
+        .select_expect(
+            "SELECT region, SUM(amount) AS total FROM sales GROUP BY region \
+             HAVING total > 100 AND region != 'east'",
+            "sales.region, total ; north, 150",
+        )
+        .select_expect(
+            "SELECT region AS r, MAX(amount) AS m FROM sales GROUP BY region \
+             HAVING r = 'west' OR m >= 150 ORDER BY r",
+            "r, m ; north, 150 ; west, 20",
+        );
+}
+
+#[test]
+fn test_array_agg() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // ARRAY_AGG collects values in input order. Arrays can be indexed from 1,
+    // yielding NULL when out of bounds, and have a length.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE sales (id INT PRIMARY KEY, region STRING, amount INT)")
+        .execute(
+            "INSERT INTO sales VALUES (1, 'east', 60), (2, 'west', 20), (3, 'east', 70), \
+             (4, 'east', 10)",
+        )
+        .select_expect(
+            "SELECT region, ARRAY_AGG(amount) AS amounts FROM sales GROUP BY region",
+            "sales.region, amounts ; east, [60, 70, 10] ; west, [20]",
+        )
+        .select_expect(
+            "SELECT region, ARRAY_AGG(amount)[2] AS second, ARRAY_AGG(amount)[0] AS zero, \
+             ARRAY_LENGTH(ARRAY_AGG(amount)) AS n FROM sales GROUP BY region",
+            "sales.region, second, zero, n ; east, 70, NULL, 3 ; west, NULL, NULL, 1",
+        )
+        .select_expect(
+            "SELECT ARRAY_LENGTH(ARRAY_AGG(id)) AS n, ARRAY_AGG(id)[-1 + 2] * 10 AS first \
+             FROM sales WHERE amount > 15",
+            "n, first ; 3, 10",
+        )
+        .select_expect(
+            "SELECT ARRAY_AGG(id) AS ids FROM sales WHERE FALSE",
+            "ids ; NULL",
+        );
+
+    let mut session = engine.session();
+    assert!(session.execute("SELECT amount[1] FROM sales").is_err());
+    assert!(session
+        .execute("SELECT ARRAY_LENGTH(amount) FROM sales")
+        .is_err());
+
+    // Arrays are equal if their elements are, e.g. when grouping or joining.
+    let array = |values: &[i32]| Field::Array(values.iter().map(|&v| v.into()).collect());
+    let set: HashSet<Field> = [array(&[1, 2]), array(&[1, 2]), array(&[2, 1])].into();
+    assert_eq!(set.len(), 2);
+    let equal = |lhs, rhs| {
+        Expression::Equal(
+            Expression::Constant(lhs).into(),
+            Expression::Constant(rhs).into(),
+        )
+        .evaluate(None)
+    };
+    assert_eq!(
+        equal(array(&[1, 2]), array(&[1, 2])),
+        Ok(Field::Boolean(true))
+    );
+    assert_eq!(
+        equal(array(&[1, 2]), array(&[1])),
+        Ok(Field::Boolean(false))
+    );
+}
+
+#[test]
+fn test_aggregate_spill() {
+    // Rows (group, value) spread over 50 groups, interleaved.
+    let source = || -> Rows {
+        Box::new((0..500).map(|i| {
+            let row = Row::from(vec![Field::Integer(i % 50), Field::Integer(i)]);
+            Ok((INVALID_RID, row))
+        }))
+    };
+    let group_by = vec![Expression::Column(0)];
+    let aggregates = vec![
+        Aggregate::Count(Expression::Column(1)),
+        Aggregate::Sum(Expression::Column(1)),
+        Aggregate::Max(Expression::Column(1)),
+    ];
+
+    let in_memory: Vec<Row> =
+        aggregate::aggregate(source(), group_by.clone(), aggregates.clone(), false)
+            .unwrap()
+            .map(|r| r.unwrap().1)
+            .collect();
+    assert_eq!(in_memory.len(), 50);
+
+    // Low thresholds force most groups to be spilled, and the
+    // partitions to be spilled again. The results are equal once sorted, also
+    // with interning.
+    for (max_buckets, intern) in [(1, false), (2, false), (10, false), (2, true)] {
+        let mut spilled: Vec<Row> = aggregate::aggregate_with_limit(
+            source(),
+            group_by.clone(),
+            aggregates.clone(),
+            max_buckets,
+            intern,
+        )
+        .unwrap()
+        .map(|r| r.unwrap().1)
+        .collect();
+        spilled.sort_by_key(|row| row.to_string(None));
+        let mut expect = in_memory.clone();
+        expect.sort_by_key(|row| row.to_string(None));
+        assert_eq!(spilled, expect, "max_buckets={max_buckets}");
+    }
+
+    // Spilled partitions are aggregated lazily, and clones of the result read
+    // the remaining rows independently, also from the spill files.
+    let mut rows =
+        aggregate::aggregate_with_limit(source(), group_by.clone(), aggregates.clone(), 5, false)
+            .unwrap();
+    rows.nth(20).unwrap().unwrap();
+    let clone = rows.clone();
+    let rest: Vec<Row> = rows.map(|r| r.unwrap().1).collect();
+    assert_eq!(rest.len(), 29);
+    assert_eq!(clone.map(|r| r.unwrap().1).collect::<Vec<_>>(), rest);
+
+    // Without GROUP BY there's a single bucket, also for an empty source.
+    let empty: Rows = Box::new(std::iter::empty());
+    let rows: Vec<Row> = aggregate::aggregate_with_limit(empty, vec![], aggregates, 1, false)
+        .unwrap()
+        .map(|r| r.unwrap().1)
+        .collect();
+    assert_eq!(rows.len(), 1);
+}
+
+#[test]
+fn test_group_by_rollup() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // ROLLUP(a, b) emits the (a, b) groups, then the (a) subtotals with a NULL
+    // b, then the grand total with NULL a and b. NULLs sort first.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE sales (id INT PRIMARY KEY, region STRING, year INT, amount INT)")
+        .execute(
+            "INSERT INTO sales VALUES (1, 'east', 2023, 10), (2, 'east', 2023, 20), \
+             (3, 'east', 2024, 30), (4, 'west', 2024, 40)",
+        )
+        .select_expect(
+            "SELECT region, year, SUM(amount) AS total FROM sales GROUP BY ROLLUP(region, year)",
+            "sales.region, sales.year, total ; east, 2023, 30 ; east, 2024, 30 ; \
+             west, 2024, 40 ; east, NULL, 60 ; west, NULL, 40 ; NULL, NULL, 100",
+        )
+        .select_expect(
+            "SELECT region, year, COUNT(*) AS n FROM sales GROUP BY ROLLUP(region, year) \
+             ORDER BY region, year",
+            "sales.region, sales.year, n ; NULL, NULL, 4 ; east, NULL, 3 ; \
+             east, 2023, 2 ; east, 2024, 1 ; west, NULL, 1 ; west, 2024, 1",
+        );
+}
+
+#[test]
+fn test_group_by_interning() {
+    // Rows (region, year), where each string is a separate allocation.
+    let source = || -> Rows {
+        Box::new((0..40).map(|i| {
+            let region = if i % 2 == 0 { "east" } else { "west" };
+            let row = Row::from(vec![Field::from(region), Field::Integer(2020 + i % 4)]);
+            Ok((INVALID_RID, row))
+        }))
+    };
+    let group_by = vec![Expression::Column(0), Expression::Column(1)];
+    let aggregates = vec![Aggregate::Count(Expression::Column(1))];
+    let east = |intern| {
+        let rows = aggregate::aggregate(source(), group_by.clone(), aggregates.clone(), intern);
+        rows.unwrap()
+            .filter_map(|r| match r.unwrap().1.get_field(0).unwrap() {
+                Field::String(s) if &*s == "east" => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // With interning, the buckets' repeated strings share an allocation.
+    let shared = east(true);
+    assert_eq!(shared.len(), 2);
+    assert!(Arc::ptr_eq(&shared[0], &shared[1]));
+    let unshared = east(false);
+    assert_eq!(unshared.len(), 2);
+    assert!(!Arc::ptr_eq(&unshared[0], &unshared[1]));
+}
+
+#[test]
+fn test_grouping_sets_spill() {
+    // Rows (a, b, value) with 5 a values and 35 (a, b) combinations.
+    let source = || -> Rows {
+        Box::new((0..350).map(|i| {
+            let row = Row::from(vec![
+                Field::Integer(i % 5),
+                Field::Integer(i % 7),
+                Field::Integer(i),
+            ]);
+            Ok((INVALID_RID, row))
+        }))
+    };
+    let group_by = vec![Expression::Column(0), Expression::Column(1)];
+    let aggregates = vec![Aggregate::Sum(Expression::Column(2))];
+    let sets = vec![vec![0, 1], vec![0], vec![]];
+    let run = |max_buckets| {
+        let rows = aggregate::aggregate_grouping_sets_with_limit(
+            source(),
+            group_by.clone(),
+            aggregates.clone(),
+            sets.clone(),
+            max_buckets,
+            false,
+        )
+        .unwrap();
+        let mut rows: Vec<String> = rows.map(|r| r.unwrap().1.to_string(None)).collect();
+        rows.sort();
+        rows
+    };
+
+    // Grouping sets spill like plain GROUP BY, with the same results.
+    let in_memory = run(aggregate::MAX_BUCKETS);
+    assert_eq!(in_memory.len(), 35 + 5 + 1);
+    assert!(in_memory.contains(&"NULL, NULL, 61075".to_string()));
+    for max_buckets in [1, 3] {
+        assert_eq!(run(max_buckets), in_memory, "max_buckets={max_buckets}");
+    }
+}
+
+#[test]
+fn test_grouping_sets_in_memory() {
+    let source = || -> Rows {
+        Box::new((0..20).map(|i| {
+            let row = Row::from(vec![Field::Integer(i % 2), Field::Integer(i)]);
+            Ok((INVALID_RID, row))
+        }))
+    };
+    let run = |max_buckets| {
+        let rows = aggregate::aggregate_grouping_sets_with_limit(
+            source(),
+            vec![Expression::Column(0)],
+            vec![Aggregate::Sum(Expression::Column(1))],
+            vec![vec![0], vec![]],
+            max_buckets,
+            false,
+        )
+        .unwrap();
+        rows.map(|r| r.unwrap().1.to_string(None))
+            .collect::<Vec<_>>()
+    };
+
+    // A ROLLUP within the bucket limit never spills to disk, and emits each
+    // grouping set in turn.
+    let spills = aggregate::spill_count();
+    assert_eq!(
+        run(aggregate::MAX_BUCKETS),
+        vec!["0, 90", "1, 100", "NULL, 190"]
+    );
+    assert_eq!(run(3), vec!["0, 90", "1, 100", "NULL, 190"]);
+    assert_eq!(aggregate::spill_count(), spills);
+
+    // It only spills once the limit is exceeded.
+    assert_eq!(run(2), vec!["0, 90", "1, 100", "NULL, 190"]);
+    assert!(aggregate::spill_count() > spills);
+}
+
+#[test]
+fn test_group_by_null_keys() {
+    // Rows (a, b, v), where several a and b values are NULL.
+    let source = || -> Rows {
+        let null = Field::Null;
+        let x = Field::from("x");
+        let rows = vec![
+            vec![null.clone(), x.clone(), Field::Integer(1)],
+            vec![Field::Integer(1), x.clone(), Field::Integer(2)],
+            vec![null.clone(), x.clone(), Field::Integer(4)],
+            vec![null.clone(), null.clone(), Field::Integer(8)],
+            vec![Field::Integer(1), null.clone(), Field::Integer(16)],
+            vec![null.clone(), null.clone(), Field::Integer(32)],
+        ];
+        Box::new(
+            rows.into_iter()
+                .map(|row| Ok((INVALID_RID, Row::from(row)))),
+        )
+    };
+    let aggregates = vec![
+        Aggregate::Sum(Expression::Column(2)),
+        Aggregate::Count(Expression::Column(2)),
+    ];
+    let row = |values: &[Field]| Row::from(values.to_vec());
+
+    // All NULL group-by values form a single group, both in memory and when
+    // spilled to disk, also as a component of a multi-column key.
+    for max_buckets in [aggregate::MAX_BUCKETS, 1] {
+        let run = |group_by: Vec<Expression>| {
+            let mut rows: Vec<Row> = aggregate::aggregate_with_limit(
+                source(),
+                group_by,
+                aggregates.clone(),
+                max_buckets,
+                false,
+            )
+            .unwrap()
+            .map(|r| r.unwrap().1)
+            .collect();
+            rows.sort_by(|a, b| a.iter().cmp(b.iter()));
+            rows
+        };
+        let (null, one, x) = (Field::Null, Field::Integer(1), Field::from("x"));
+        let int = Field::Integer;
+
+        assert_eq!(
+            run(vec![Expression::Column(0)]),
+            vec![
+                row(&[null.clone(), int(45), int(4)]),
+                row(&[one.clone(), int(18), int(2)])
+            ],
+        );
+        assert_eq!(
+            run(vec![Expression::Column(0), Expression::Column(1)]),
+            vec![
+                row(&[null.clone(), null.clone(), int(40), int(2)]),
+                row(&[null.clone(), x.clone(), int(5), int(2)]),
+                row(&[one.clone(), null.clone(), int(16), int(1)]),
+                row(&[one.clone(), x.clone(), int(2), int(1)]),
+            ],
+        );
+    }
+}
+
+#[test]
+fn test_aggregate_select_order() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // The Aggregate node emits group_by columns first, but output rows follow
+    // the SELECT order, including aggregates between group_by columns.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE emp (id INT PRIMARY KEY, dept STRING, level INT, salary INT)")
+        .execute(
+            "INSERT INTO emp VALUES (1, 'eng', 1, 100), (2, 'eng', 2, 150), \
+             (3, 'ops', 1, 80), (4, 'eng', 1, 120)",
+        )
+        .select_expect(
+            "SELECT COUNT(*) AS n, dept FROM emp GROUP BY dept ORDER BY dept",
+            "n, emp.dept ; 3, eng ; 1, ops",
+        )
+        .select_expect(
+            "SELECT level, SUM(salary) AS total, dept FROM emp GROUP BY dept, level \
+             ORDER BY dept, level",
+            "emp.level, total, emp.dept ; 1, 220, eng ; 2, 150, eng ; 1, 80, ops",
+        )
+        .select_expect(
+            "SELECT MAX(salary) AS top, COUNT(*) AS n, level FROM emp GROUP BY level \
+             ORDER BY level",
+            "top, n, emp.level ; 120, 3, 1 ; 150, 1, 2",
+        );
+}
+
+#[test]
+fn test_percentile_cont() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // The median interpolates between the middle values for even counts, and
+    // fractions 0 and 1 yield the min and max.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE emp (id INT PRIMARY KEY, dept STRING, salary INT)")
+        .execute(
+            "INSERT INTO emp VALUES (1, 'eng', 100), (2, 'eng', 150), (3, 'eng', 120), \
+             (4, 'ops', 80), (5, 'ops', 90), (6, 'ops', 60), (7, 'ops', 85)",
+        )
+        .select_expect(
+            "SELECT dept, PERCENTILE_CONT(salary, 0.5) AS median FROM emp \
+             GROUP BY dept ORDER BY dept",
+            "emp.dept, median ; eng, 120 ; ops, 82.5",
+        )
+        .select_expect(
+            "SELECT PERCENTILE_CONT(salary, 0) AS low, MIN(salary) AS min, \
+             PERCENTILE_CONT(salary, 1) AS high, MAX(salary) AS max FROM emp",
+            "low, min, high, max ; 60, 60, 150, 150",
+        )
+        .select_expect(
+            "SELECT PERCENTILE_CONT(salary, 0.25) AS p FROM emp WHERE dept = 'ops'",
+            "p ; 75",
+        )
+        .select_expect(
+            "SELECT PERCENTILE_CONT(salary, 0.5) AS p FROM emp WHERE id > 10",
+            "p ; NULL",
+        );
+
+    // The fraction must be a constant in [0, 1].
+    let mut session = engine.session();
+    for sql in [
+        "SELECT PERCENTILE_CONT(salary, 1.5) FROM emp",
+        "SELECT PERCENTILE_CONT(salary, -0.5) FROM emp",
+        "SELECT PERCENTILE_CONT(salary, id) FROM emp",
+        "SELECT PERCENTILE_CONT(salary) FROM emp",
+        "SELECT PERCENTILE_CONT(dept, 0.5) FROM emp",
+    ] {
+        assert!(session.execute(sql).is_err(), "{sql} should error");
+    }
+}
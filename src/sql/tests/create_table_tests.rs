@@ -0,0 +1,311 @@
+use crate::common::Error;
+use crate::sql::engine::{Catalog, Engine, Local, StatementResult};
+use crate::sql::execution::ExecutionResult;
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::types::{DataType, Table};
+
+#[test]
+fn test_create_table_as() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price INT, stock INT)")
+        .execute("INSERT INTO items VALUES (1, 'apple', 3, 4), (2, 'pear', 5, 2), (3, 'fig', 8, 0)")
+        .execute(
+            "CREATE TABLE cheap AS SELECT name, price / 2 AS half, stock > 0 AS available \
+             FROM items WHERE price < 6",
+        )
+        // 3 / 2 and 5 / 2 yield floats, while 8 / 2 isn't selected. The column
+        // still becomes a float column.
+        .select_expect(
+            "SELECT * FROM cheap",
+            "cheap.name, cheap.half, cheap.available ; apple, 1.5, true ; pear, 2.5, true",
+        );
+
+    let mut session = engine.session();
+    let result = session.execute("CREATE TABLE copy AS SELECT id, name FROM items").unwrap();
+    assert_eq!(
+        result,
+        StatementResult::CreateTableAs {
+            name: "copy".to_string(),
+            count: 3
+        }
+    );
+    let result = session.execute("CREATE TABLE halves AS SELECT price / 2 FROM items");
+    assert!(result.is_err(), "unnamed column should error");
+    let result = session.execute("CREATE TABLE copy AS SELECT id FROM items");
+    assert!(result.is_err(), "existing table should error");
+}
+
+#[test]
+fn test_create_table_as_unstorable() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO items VALUES (1, 'apple'), (2, 'pear')");
+
+    // Arrays and intervals can't be stored, and a column of only NULLs has no
+    // type. None of them create the table.
+    let mut session = engine.session();
+    let result = session.execute("CREATE TABLE a AS SELECT ARRAY_AGG(id) AS ids FROM items");
+    let message = "column ids has array values, which can't be stored";
+    assert!(matches!(result, Err(Error::InvalidInput(m)) if m == message));
+    let sql = "CREATE TABLE b AS SELECT TIME '10:00:00' - TIME '09:00:00' AS d FROM items";
+    let message = "column d has interval values, which can't be stored";
+    assert!(matches!(session.execute(sql), Err(Error::InvalidInput(m)) if m == message));
+    let result = session.execute("CREATE TABLE n AS SELECT NULL AS n FROM items");
+    let message = "can't infer the type of column n";
+    assert!(matches!(result, Err(Error::InvalidInput(m)) if m == message));
+    assert_eq!(engine.begin().unwrap().get_table("a").unwrap(), None);
+    assert_eq!(engine.begin().unwrap().get_table("b").unwrap(), None);
+    assert_eq!(engine.begin().unwrap().get_table("n").unwrap(), None);
+}
+
+#[test]
+fn test_create_table_if_not_exists() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO items VALUES (1, 'apple')");
+
+    // Without IF NOT EXISTS, an existing table errors.
+    let mut session = engine.session();
+    let result = session.execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING)");
+    assert!(result.is_err(), "existing table should error");
+
+    // With it, an existing table is a no-op, keeping its schema and rows even
+    // if the given schema differs.
+    let result = session
+        .execute("CREATE TABLE IF NOT EXISTS items (id INT PRIMARY KEY, price FLOAT)")
+        .unwrap();
+    assert_eq!(
+        result,
+        StatementResult::CreateTable {
+            name: "items".to_string()
+        }
+    );
+    SqlStudentRunner::new(&engine)
+        .select_expect("SELECT * FROM items", "items.id, items.name ; 1, apple")
+        // A new table is created as usual.
+        .execute("CREATE TABLE IF NOT EXISTS prices (id INT PRIMARY KEY, price FLOAT)")
+        .execute("INSERT INTO prices VALUES (1, 2.5)")
+        .select_expect("SELECT * FROM prices", "prices.id, prices.price ; 1, 2.5");
+
+    // CREATE TABLE AS doesn't support it.
+    let result = session.execute("CREATE TABLE IF NOT EXISTS copy AS SELECT id FROM items");
+    assert!(result.is_err(), "IF NOT EXISTS with AS should error");
+}
+
+#[test]
+fn test_drop_table_row_count() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let values = (0..300).map(|i| format!("({i}, 'item {i}')")).collect::<Vec<_>>();
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING)")
+        .execute(&format!("INSERT INTO items VALUES {}", values.join(", ")))
+        .execute("DELETE FROM items WHERE id >= 250");
+
+    // The result reports the rows the table held, excluding deleted ones.
+    let mut session = engine.session();
+    let result = session.execute("DROP TABLE items").unwrap();
+    assert_eq!(
+        result,
+        StatementResult::DropTable {
+            name: "items".to_string(),
+            existed: true,
+            row_count: 250,
+        }
+    );
+
+    // A missing table reports no rows.
+    let result = session.execute("DROP TABLE IF EXISTS items").unwrap();
+    assert_eq!(
+        result,
+        StatementResult::DropTable {
+            name: "items".to_string(),
+            existed: false,
+            row_count: 0,
+        }
+    );
+}
+
+#[test]
+fn test_rename_column() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price INT)")
+        .execute("INSERT INTO items VALUES (1, 'apple', 3), (2, 'pear', 5)");
+
+    let txn = engine.begin().unwrap();
+    txn.rename_column("items", "name", "title").unwrap();
+
+    // Missing tables and columns error, as do names that are taken.
+    assert!(txn.rename_column("missing", "name", "title").is_err());
+    assert!(txn.rename_column("items", "name", "label").is_err());
+    assert!(txn.rename_column("items", "price", "id").is_err());
+    drop(txn);
+
+    // Existing rows are read with the new name, and the old one is gone.
+    SqlStudentRunner::new(&engine)
+        .select_expect(
+            "SELECT title, price FROM items WHERE title = 'pear'",
+            "items.title, items.price ; pear, 5",
+        )
+        .execute("INSERT INTO items (id, title, price) VALUES (3, 'fig', 8)")
+        .select_expect(
+            "SELECT id, title FROM items ORDER BY id",
+            "items.id, items.title ; 1, apple ; 2, pear ; 3, fig",
+        );
+    let mut session = engine.session();
+    assert!(session.execute("SELECT name FROM items").is_err());
+}
+
+#[test]
+fn test_execute_script() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let table = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .build();
+    let values = |rows: &[(i32, &str)]| {
+        let rows = rows.iter().map(|&(id, name)| {
+            vec![Expression::Constant(id.into()), Expression::Constant(name.into())]
+        });
+        Node::Values {
+            rows: rows.collect(),
+        }
+        .into()
+    };
+    let scan = Node::Scan {
+        table: table.clone(),
+        filter: None,
+        alias: None,
+    };
+
+    // Create, insert, then select. Each statement sees the previous ones'
+    // effects, and the SELECT rows are read before the later insert.
+    let mut session = engine.session();
+    let results = session
+        .execute_script(vec![
+            Plan::CreateTable {
+                schema: table.clone(),
+                if_not_exists: false,
+            },
+            Plan::Insert {
+                table: table.clone(),
+                source: values(&[(1, "apple"), (2, "pear")]),
+            },
+            Plan::Select(scan.clone().into()),
+            Plan::Insert {
+                table: table.clone(),
+                source: values(&[(3, "fig")]),
+            },
+        ])
+        .unwrap();
+    assert_eq!(results.len(), 4);
+    let mut results = results.into_iter();
+    assert!(matches!(results.next(), Some(ExecutionResult::CreateTable { .. })));
+    assert!(matches!(results.next(), Some(ExecutionResult::Insert { count: 2, .. })));
+    let Some(ExecutionResult::Select { rows, .. }) = results.next() else {
+        panic!("expected select result");
+    };
+    let rows = rows.map(|r| r.unwrap().1.to_string(None)).collect::<Vec<_>>();
+    assert_eq!(rows, vec!["1, apple", "2, pear"]);
+
+    // The script stops at the first error, identifying the statement, and
+    // rolls back the statements before it: the created table is dropped, and
+    // the rows inserted into the existing table deleted. An existing table
+    // isn't dropped for CREATE TABLE IF NOT EXISTS.
+    let other = Table::builder()
+        .name("other")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .build();
+    let Err(Error::InvalidInput(message)) = session.execute_script(vec![
+        Plan::CreateTable {
+            schema: other.clone(),
+            if_not_exists: false,
+        },
+        Plan::Insert {
+            table: other.clone(),
+            source: values(&[(1, "fig")]),
+        },
+        Plan::Insert {
+            table: table.clone(),
+            source: values(&[(4, "plum")]),
+        },
+        Plan::CreateTable {
+            schema: table.clone(),
+            if_not_exists: true,
+        },
+        Plan::CreateTable {
+            schema: table.clone(),
+            if_not_exists: false,
+        },
+        Plan::Insert {
+            table: table.clone(),
+            source: values(&[(5, "kiwi")]),
+        },
+    ]) else {
+        panic!("expected invalid input error");
+    };
+    assert!(message.starts_with("statement 4: "), "{message}");
+    assert!(engine.begin().unwrap().get_table("other").unwrap().is_none());
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT id FROM items ORDER BY id",
+        "items.id ; 1 ; 2 ; 3",
+    );
+
+    // Updated rows are restored, deleted rows re-inserted, and dropped tables
+    // recreated with their rows, also for rows that a later statement moved.
+    let by_id = |id: i32| {
+        let filter = Expression::Equal(
+            Expression::Column(0).into(),
+            Expression::Constant(id.into()).into(),
+        );
+        Node::Scan {
+            table: table.clone(),
+            filter: Some(filter),
+            alias: None,
+        }
+        .into()
+    };
+    let Err(Error::InvalidInput(message)) = session.execute_script(vec![
+        Plan::Update {
+            table: table.clone(),
+            source: by_id(1),
+            expressions: vec![(1, Expression::Constant("plum".into()))],
+        },
+        Plan::Delete {
+            table: "items".to_string(),
+            source: by_id(2),
+        },
+        Plan::Delete {
+            table: "items".to_string(),
+            source: by_id(1),
+        },
+        Plan::DropTable {
+            table: "items".to_string(),
+            if_exists: false,
+        },
+        Plan::DropTable {
+            table: "items".to_string(),
+            if_exists: false,
+        },
+    ]) else {
+        panic!("expected invalid input error");
+    };
+    assert!(message.starts_with("statement 4: "), "{message}");
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT * FROM items ORDER BY id",
+        "items.id, items.name ; 1, apple ; 2, pear ; 3, fig",
+    );
+}
@@ -0,0 +1,200 @@
+use crate::common::Result;
+use crate::sql::engine::{Catalog, Engine, Local, StatementResult, Transaction};
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
+use crate::storage::disk::disk_manager::DiskManager;
+use crate::storage::heap::TableHeap;
+use crate::storage::tuple::Row;
+use crate::types::field::Field;
+use crate::types::{DataType, Table};
+use itertools::Itertools as _;
+use std::sync::{Arc, RwLock};
+
+#[test]
+fn test_delete_using_join() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE customers (id INT PRIMARY KEY, name STRING)")
+        .execute("CREATE TABLE orders (id INT PRIMARY KEY, customer INT, amount INT)")
+        .execute("INSERT INTO customers VALUES (1, 'alice'), (2, 'bob'), (3, 'bob')")
+        .execute("INSERT INTO orders VALUES (1, 1, 10), (2, 2, 20), (3, 3, 30), (4, 1, 40)");
+
+    // The joined rows carry the orders' record IDs, so the matching orders
+    // are deleted, while the customers are left as is.
+    let mut session = engine.session();
+    let result = session
+        .execute("DELETE FROM orders USING customers WHERE customers.id = orders.customer AND name = 'bob'")
+        .unwrap();
+    assert_eq!(result, StatementResult::Delete { count: 2 });
+
+    // An order matching several USING rows is only deleted once.
+    let result = session
+        .execute("DELETE FROM orders USING customers c WHERE orders.amount > 20 OR c.id = orders.customer AND c.id = 1")
+        .unwrap();
+    assert_eq!(result, StatementResult::Delete { count: 2 });
+
+    SqlStudentRunner::new(&engine)
+        .select_expect(
+            "SELECT * FROM orders",
+            "orders.id, orders.customer, orders.amount",
+        )
+        .select_expect(
+            "SELECT * FROM customers",
+            "customers.id, customers.name ; 1, alice ; 2, bob ; 3, bob",
+        );
+}
+
+#[test]
+fn test_delete_using_reorder_joins() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE customers (id INT PRIMARY KEY, name STRING)")
+        .execute("CREATE TABLE orders (id INT PRIMARY KEY, customer INT)")
+        .execute("INSERT INTO customers VALUES (1, 'alice'), (2, 'bob'), (3, 'carol')")
+        .execute("INSERT INTO orders VALUES (1, 1), (2, 2), (3, 3), (4, 1), (5, 2), (6, 9)");
+
+    // Join reordering doesn't change which orders are deleted.
+    let mut session = engine.session();
+    session.set("reorder_joins", Field::Boolean(true)).unwrap();
+    let result = session
+        .execute(
+            "DELETE FROM orders USING customers \
+             WHERE customers.id = orders.customer AND name = 'bob'",
+        )
+        .unwrap();
+    assert_eq!(result, StatementResult::Delete { count: 2 });
+    drop(session);
+
+    // The filtered orders are estimated to be fewer than the customers, but
+    // the hash join must keep the orders on the left to delete their record
+    // IDs, so it isn't reordered.
+    let txn = engine.begin().unwrap();
+    let orders = Node::Scan {
+        table: txn.must_get_table("orders").unwrap(),
+        filter: Some(Expression::GreaterThan(
+            Expression::Column(0).into(),
+            Expression::Constant(Field::Integer(0)).into(),
+        )),
+        alias: None,
+    };
+    let customers = Node::Scan {
+        table: txn.must_get_table("customers").unwrap(),
+        filter: None,
+        alias: None,
+    };
+    let plan = Plan::Delete {
+        table: "orders".to_string(),
+        source: Node::HashJoin {
+            left: orders.into(),
+            left_column: 1,
+            right: customers.into(),
+            right_column: 0,
+            outer: false,
+            epsilon: None,
+        }
+        .into(),
+    };
+    assert_eq!(plan.clone().reorder_joins(&txn).unwrap(), plan);
+    plan.reorder_joins(&txn).unwrap().execute(&txn).unwrap();
+    drop(txn);
+
+    SqlStudentRunner::new(&engine)
+        .select_expect("SELECT * FROM orders", "orders.id, orders.customer ; 6, 9")
+        .select_expect(
+            "SELECT * FROM customers",
+            "customers.id, customers.name ; 1, alice ; 2, bob ; 3, carol",
+        );
+}
+
+#[test]
+fn test_delete_tombstone() {
+    let bpm = Arc::new(RwLock::new(
+        BufferPoolManager::builder()
+            .disk_manager(Arc::new(RwLock::new(DiskManager::new("sql-test-file"))))
+            .pool_size(10)
+            .replacer_k(2)
+            .build(),
+    ));
+    let schema = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .build();
+    let mut heap = TableHeap::new(schema.clone(), &bpm);
+    let rids = (1..=3)
+        .map(|id| heap.insert_tuple(Row::from(vec![Field::Integer(id)]).to_tuple(&schema)?))
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+    heap.delete_tuple(&rids[1]).unwrap();
+
+    // Scans and lookups skip the tombstoned tuple...
+    let scanned = heap
+        .iter()
+        .map_ok(|(rid, _)| rid)
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(scanned, vec![rids[0].clone(), rids[2].clone()]);
+    assert!(heap.get_tuple(&rids[1]).is_err());
+
+    // ...although it still physically exists, marked as deleted.
+    assert!(heap.get_tuple_metadata(&rids[1]).unwrap().is_deleted());
+    assert!(!heap.get_tuple_metadata(&rids[0]).unwrap().is_deleted());
+}
+
+#[test]
+fn test_vacuum() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO items VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e')")
+        .execute("DELETE FROM items WHERE id = 2 OR id = 4")
+        // Changing the size of a row tombstones its old version.
+        .execute("UPDATE items SET name = 'eee' WHERE id = 5");
+
+    let txn = engine.begin().unwrap();
+    assert_eq!(txn.vacuum("items").unwrap(), 3);
+    assert_eq!(txn.vacuum("items").unwrap(), 0);
+    assert!(txn.vacuum("missing").is_err());
+    assert_eq!(txn.scan("items", None).unwrap().count(), 3);
+
+    SqlStudentRunner::new(&engine)
+        .select_expect(
+            "SELECT * FROM items",
+            "items.id, items.name ; 1, a ; 3, c ; 5, eee",
+        )
+        .execute("DELETE FROM items WHERE id = 3")
+        .select_expect(
+            "SELECT * FROM items",
+            "items.id, items.name ; 1, a ; 5, eee",
+        );
+}
+
+#[test]
+fn test_delete_limit() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE jobs (id INT PRIMARY KEY, done BOOLEAN)")
+        .execute(
+            "INSERT INTO jobs VALUES (1, TRUE), (2, TRUE), (3, FALSE), (4, TRUE), (5, TRUE), \
+             (6, TRUE)",
+        );
+
+    // Only the first two of the five matching rows are deleted.
+    let mut session = engine.session();
+    let result = session
+        .execute("DELETE FROM jobs WHERE done = TRUE LIMIT 2")
+        .unwrap();
+    assert_eq!(result, StatementResult::Delete { count: 2 });
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT id FROM jobs WHERE done = TRUE",
+        "jobs.id ; 4 ; 5 ; 6",
+    );
+
+    assert!(session.execute("DELETE FROM jobs LIMIT -1").is_err());
+    let result = session.execute("DELETE FROM jobs LIMIT 0").unwrap();
+    assert_eq!(result, StatementResult::Delete { count: 0 });
+}
@@ -0,0 +1,373 @@
+use crate::sql::engine::{Local, Session};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::common::Error;
+use crate::sql::planner::Expression;
+use crate::types::field::Field;
+use crate::types::{DataType, Table};
+
+#[test]
+fn test_bitwise_operators() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE perms (id INT PRIMARY KEY, flags INT)")
+        .execute("INSERT INTO perms VALUES (1, 0b101), (2, 0b110), (3, 0b001)")
+        // Mask out the write bit, and select rows with the read bit set.
+        .select_expect(
+            "SELECT id, flags & 0b010 AS writable, flags | 0b1000 AS admin, flags # 0b111 AS flipped \
+             FROM perms WHERE flags & 0b100 = 0b100",
+            "perms.id, writable, admin, flipped ; 1, 0, 13, 2 ; 2, 2, 14, 1",
+        )
+        // Bitwise operators bind tighter than comparisons but looser than
+        // arithmetic, as in Postgres.
+        .select_expect("SELECT 1 | 2 + 4 AS a, 6 & 3 = 2 AS b", "a, b ; 7, true")
+        .select_expect("SELECT NULL & 1 AS a, 1 | NULL AS b, NULL # NULL AS c", "a, b, c ; NULL, NULL, NULL");
+
+    let mut session = engine.session();
+    assert!(session.execute("SELECT 1.0 & 1").is_err());
+    assert!(session.execute("SELECT 'a' | 1").is_err());
+    assert!(session.execute("SELECT TRUE # FALSE").is_err());
+}
+
+#[test]
+fn test_field_bitwise() {
+    assert_eq!(
+        Field::Integer(0b1100).bit_and(&Field::Integer(0b1010)),
+        Ok(Field::Integer(0b1000))
+    );
+    assert_eq!(
+        Field::Integer(0b1100).bit_or(&Field::Integer(0b1010)),
+        Ok(Field::Integer(0b1110))
+    );
+    assert_eq!(
+        Field::Integer(0b1100).bit_xor(&Field::Integer(0b1010)),
+        Ok(Field::Integer(0b0110))
+    );
+    assert_eq!(
+        Field::Integer(-1).bit_and(&Field::Integer(i32::MIN)),
+        Ok(Field::Integer(i32::MIN))
+    );
+    assert_eq!(Field::Null.bit_or(&Field::Integer(1)), Ok(Field::Null));
+    assert!(Field::Float(1.0).bit_and(&Field::Integer(1)).is_err());
+}
+
+#[test]
+fn test_nullif() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE ratios (id INT PRIMARY KEY, x INT, y INT)")
+        .execute("INSERT INTO ratios VALUES (1, 10, 2), (2, 10, 0)")
+        // Equal operands yield NULL, otherwise the first operand. Integers
+        // and floats compare equal as in =.
+        .select_expect(
+            "SELECT NULLIF(1, 1) AS a, NULLIF(1, 2) AS b, NULLIF('x', 'y') AS c, \
+             NULLIF(1, 1.0) AS d",
+            "a, b, c, d ; NULL, 1, x, NULL",
+        )
+        // A NULL operand never compares equal.
+        .select_expect(
+            "SELECT NULLIF(NULL, 1) AS a, NULLIF(1, NULL) AS b, NULLIF(NULL, NULL) AS c",
+            "a, b, c ; NULL, 1, NULL",
+        )
+        // NULLIF avoids division by zero.
+        .select_expect(
+            "SELECT id, x / NULLIF(y, 0) AS r FROM ratios ORDER BY id",
+            "ratios.id, r ; 1, 5 ; 2, NULL",
+        );
+
+    let mut session = engine.session();
+    assert!(session.execute("SELECT 1 / 0").is_err());
+    assert!(session.execute("SELECT NULLIF(1, 'a')").is_err());
+    assert!(session.execute("SELECT NULLIF(1)").is_err());
+}
+
+#[test]
+fn test_ilike() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE names (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO names VALUES (1, 'Alice'), (2, 'ALBERT'), (3, 'bob'), (4, 'Ålborg')")
+        // ILIKE ignores case in both the string and the pattern, unlike LIKE.
+        .select_expect(
+            "SELECT id FROM names WHERE name ILIKE 'al%' ORDER BY id",
+            "names.id ; 1 ; 2",
+        )
+        .select_expect("SELECT id FROM names WHERE name LIKE 'al%'", "names.id")
+        .select_expect(
+            "SELECT id FROM names WHERE name ILIKE '_O_' OR name ILIKE 'åLB%' ORDER BY id",
+            "names.id ; 3 ; 4",
+        )
+        // NULLs yield NULL, as for LIKE.
+        .select_expect(
+            "SELECT NULL ILIKE 'a' AS a, 'a' ILIKE NULL AS b, 'A' ILIKE 'a' AS c",
+            "a, b, c ; NULL, NULL, true",
+        );
+
+    let mut session = engine.session();
+    assert!(session.execute("SELECT 1 ILIKE 'a'").is_err());
+}
+
+#[test]
+fn test_mod_abs() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE numbers (id INT PRIMARY KEY, n INT, f FLOAT)")
+        .execute("INSERT INTO numbers VALUES (1, -5, -1.5), (2, 7, 2.5), (3, 12, 0.0)")
+        // MOD() is the same as %, and ABS() keeps the input type.
+        .select_expect(
+            "SELECT MOD(7, 3) AS a, MOD(-7, 3) AS b, MOD(7.5, 2) AS c, ABS(-5) AS d, \
+             ABS(-2.5) AS e, ABS(3) AS f",
+            "a, b, c, d, e, f ; 1, -1, 1.5, 5, 2.5, 3",
+        )
+        .select_expect(
+            "SELECT id, ABS(n) AS n, ABS(f) AS f FROM numbers WHERE MOD(ABS(n), 2) = 1 ORDER BY id",
+            "numbers.id, n, f ; 1, 5, 1.5 ; 2, 7, 2.5",
+        )
+        // NULLs yield NULL.
+        .select_expect(
+            "SELECT MOD(NULL, 2) AS a, MOD(2, NULL) AS b, ABS(NULL) AS c",
+            "a, b, c ; NULL, NULL, NULL",
+        );
+
+    // Division by zero, overflow, and non-numeric values error.
+    let mut session = engine.session();
+    for sql in ["SELECT MOD(7, 0)", "SELECT MOD(7.0, 0.0)", "SELECT 7 % 0"] {
+        assert!(matches!(
+            session.execute(sql),
+            Err(Error::InvalidInput(message)) if message == "can't divide by zero"
+        ));
+    }
+    assert!(matches!(
+        session.execute("SELECT ABS(-2147483647 - 1)"),
+        Err(Error::OverflowError)
+    ));
+    assert!(session.execute("SELECT ABS('a')").is_err());
+    assert!(session.execute("SELECT MOD('a', 2)").is_err());
+    assert!(session.execute("SELECT MOD(1)").is_err());
+}
+
+#[test]
+fn test_round_floor_ceil() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE prices (id INT PRIMARY KEY, price FLOAT, qty INT)")
+        .execute("INSERT INTO prices VALUES (1, 2.567, 1234), (2, -2.5, -15)")
+        .select_expect(
+            "SELECT ROUND(2.567, 2) AS a, ROUND(2.5) AS b, FLOOR(2.9) AS c, CEIL(2.1) AS d",
+            "a, b, c, d ; 2.57, 3, 2, 3",
+        )
+        // Halfway cases round away from zero, and negative digits round to
+        // the left of the decimal point. Integers stay integers.
+        .select_expect(
+            "SELECT id, ROUND(price) AS r, FLOOR(price) AS f, CEIL(price) AS c, \
+             ROUND(qty, -1) AS q, FLOOR(qty) AS fq FROM prices ORDER BY id",
+            "prices.id, r, f, c, q, fq ; 1, 3, 2, 3, 1230, 1234 ; 2, -3, -3, -2, -20, -15",
+        )
+        .select_expect(
+            "SELECT ROUND(1234.5, -2) AS a, ROUND(1.5, 20) AS b, ROUND(7, -20) AS c",
+            "a, b, c ; 1200, 1.5, 0",
+        )
+        // NULLs yield NULL.
+        .select_expect(
+            "SELECT ROUND(NULL) AS a, ROUND(1.5, NULL) AS b, FLOOR(NULL) AS c, CEIL(NULL) AS d",
+            "a, b, c, d ; NULL, NULL, NULL, NULL",
+        );
+
+    // Non-numeric values and digits error.
+    let mut session = engine.session();
+    assert!(session.execute("SELECT ROUND('a')").is_err());
+    assert!(session.execute("SELECT ROUND(1.5, 1.5)").is_err());
+    assert!(session.execute("SELECT FLOOR('a')").is_err());
+    assert!(session.execute("SELECT CEIL(TRUE)").is_err());
+    assert!(session.execute("SELECT ROUND(1, 2, 3)").is_err());
+}
+
+#[test]
+fn test_trim() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Result strings are trimmed when compared, so the one-sided trims are
+    // checked with equality.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE names (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO names VALUES (1, '  alice  '), (2, 'bob'), (3, '\t carol\n')")
+        .select_expect(
+            "SELECT id, TRIM(name) AS t, LTRIM(name) = 'alice  ' AS l, \
+             RTRIM(name) = '  alice' AS r FROM names ORDER BY id",
+            "names.id, t, l, r ; 1, alice, true, true ; 2, bob, false, false ; \
+             3, carol, false, false",
+        )
+        // A character set can be given instead of whitespace.
+        .select_expect(
+            "SELECT TRIM('xxhixyx', 'xy') AS t, LTRIM('xxhix', 'x') AS l, \
+             RTRIM('xxhix', 'x') AS r, TRIM('  hi  ', '') = '  hi  ' AS n",
+            "t, l, r, n ; hi, hix, xxhi, true",
+        )
+        // NULLs yield NULL.
+        .select_expect(
+            "SELECT TRIM(NULL) AS a, LTRIM(NULL, 'x') AS b, RTRIM('x', NULL) AS c",
+            "a, b, c ; NULL, NULL, NULL",
+        );
+
+    // Non-string values error.
+    let mut session = engine.session();
+    assert!(session.execute("SELECT TRIM(1)").is_err());
+    assert!(session.execute("SELECT LTRIM('a', 1)").is_err());
+    assert!(session.execute("SELECT RTRIM('a', 'b', 'c')").is_err());
+}
+
+#[test]
+fn test_replace_position() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE paths (id INT PRIMARY KEY, path STRING)")
+        .execute("INSERT INTO paths VALUES (1, 'a/b/c'), (2, 'ab')")
+        // All occurrences are replaced, and an empty substring is never found.
+        .select_expect(
+            "SELECT id, REPLACE(path, '/', '::') AS r, REPLACE(path, '', 'x') AS e, \
+             POSITION('b' IN path) AS p, POSITION('z' IN path) AS z FROM paths ORDER BY id",
+            "paths.id, r, e, p, z ; 1, a::b::c, a/b/c, 3, 0 ; 2, ab, ab, 2, 0",
+        )
+        // Positions count characters rather than bytes.
+        .select_expect(
+            "SELECT POSITION('é' IN 'café au lait') AS a, POSITION('au' IN 'café au lait') AS b",
+            "a, b ; 4, 6",
+        )
+        // NULLs yield NULL.
+        .select_expect(
+            "SELECT REPLACE(NULL, 'a', 'b') AS a, REPLACE('a', 'a', NULL) AS b, \
+             POSITION(NULL IN 'a') AS c, POSITION('a' IN NULL) AS d",
+            "a, b, c, d ; NULL, NULL, NULL, NULL",
+        );
+
+    // Non-string values error, as does a missing IN.
+    let mut session = engine.session();
+    assert!(session.execute("SELECT REPLACE(1, '1', '2')").is_err());
+    assert!(session.execute("SELECT POSITION(1 IN 'a1')").is_err());
+    assert!(session.execute("SELECT POSITION('a', 'a')").is_err());
+}
+
+#[test]
+fn test_expression_result_type() {
+    let table = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .column("price", DataType::Float, true, None, None)
+        .column("name", DataType::Text, true, None, None)
+        .build();
+    let column = |index| Box::new(Expression::Column(index));
+    let constant = |value| Box::new(Expression::Constant(value));
+
+    // Arithmetic promotes integers to floats, and integer division may yield
+    // either, so its type is unknown.
+    let add = Expression::Add(column(0), constant(Field::Integer(1)));
+    assert_eq!(add.result_type(&table).unwrap(), DataType::Int);
+    let multiply = Expression::Multiply(column(0), column(1));
+    assert_eq!(multiply.result_type(&table).unwrap(), DataType::Float);
+    let divide = Expression::Divide(column(0), constant(Field::Integer(2)));
+    assert_eq!(divide.result_type(&table).unwrap(), DataType::Invalid);
+    let cast = Expression::Cast(Box::new(divide), DataType::Int);
+    assert_eq!(cast.result_type(&table).unwrap(), DataType::Int);
+
+    // Comparisons yield booleans, including against NULL.
+    let greater = Expression::GreaterThan(Box::new(multiply), constant(Field::Integer(10)));
+    assert_eq!(greater.result_type(&table).unwrap(), DataType::Bool);
+    let equal = Expression::Equal(column(2), constant(Field::Null));
+    assert_eq!(equal.result_type(&table).unwrap(), DataType::Bool);
+
+    // Ill-typed expressions and unknown columns error.
+    let compare = Expression::LessThan(column(2), column(0));
+    assert!(matches!(
+        compare.result_type(&table),
+        Err(Error::InvalidInput(message)) if message == "can't compare varchar and int"
+    ));
+    let subtract = Expression::Subtract(column(2), constant(Field::Integer(1)));
+    assert!(matches!(
+        subtract.result_type(&table),
+        Err(Error::InvalidInput(message)) if message == "can't subtract varchar and int"
+    ));
+    assert!(Expression::Not(column(3)).result_type(&table).is_err());
+}
+
+#[test]
+fn test_text_range_comparisons() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Text compares bytewise, so uppercase letters sort before lowercase ones
+    // and accented letters after both. Filters agree with ORDER BY.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE names (id INT PRIMARY KEY, name STRING)")
+        .execute(
+            "INSERT INTO names VALUES (1, 'alice'), (2, 'Bob'), (3, 'mallory'), (4, 'Zed'), \
+             (5, 'Émile'), (6, 'M')",
+        )
+        .select_expect(
+            "SELECT name FROM names ORDER BY name",
+            "names.name ; Bob ; M ; Zed ; alice ; mallory ; Émile",
+        )
+        .select_expect(
+            "SELECT name FROM names WHERE name > 'M' ORDER BY name",
+            "names.name ; Zed ; alice ; mallory ; Émile",
+        )
+        .select_expect(
+            "SELECT name FROM names WHERE name < 'M' ORDER BY name",
+            "names.name ; Bob",
+        )
+        .select_expect(
+            "SELECT name FROM names WHERE name >= 'M' AND name < 'a' ORDER BY name",
+            "names.name ; M ; Zed",
+        )
+        .select_expect(
+            "SELECT name FROM names WHERE 'mallory' < name ORDER BY name",
+            "names.name ; Émile",
+        );
+}
+
+#[test]
+fn test_row_value_comparisons() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Rows are equal if all components are, and otherwise ordered by their
+    // first unequal component, which suits keyset pagination.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE pages (id INT PRIMARY KEY, a INT, b INT)")
+        .execute("INSERT INTO pages VALUES (1, 1, 1), (2, 1, 2), (3, 1, 3), (4, 2, 1), (5, 2, 2)")
+        .select_expect("SELECT id FROM pages WHERE (a, b) = (1, 2)", "pages.id ; 2")
+        .select_expect("SELECT id FROM pages WHERE (a, b) != (1, 2)", "pages.id ; 1 ; 3 ; 4 ; 5")
+        .select_expect("SELECT id FROM pages WHERE (a, b) < (2, 1)", "pages.id ; 1 ; 2 ; 3")
+        .select_expect("SELECT id FROM pages WHERE (a, b) <= (2, 1)", "pages.id ; 1 ; 2 ; 3 ; 4")
+        .select_expect(
+            "SELECT id FROM pages WHERE (a, b) > (1, 2) ORDER BY a, b LIMIT 2",
+            "pages.id ; 3 ; 4",
+        )
+        .select_expect("SELECT id FROM pages WHERE (b, a) >= (2, 2)", "pages.id ; 3 ; 5");
+
+    // A NULL component yields NULL, unless other components decide the result.
+    SqlStudentRunner::new(&engine)
+        .select_expect("SELECT (1, NULL) = (1, 2) AS r", "r ; NULL")
+        .select_expect("SELECT (1, NULL) = (2, 2) AS r", "r ; false")
+        .select_expect("SELECT (1, NULL) < (2, 0) AS r", "r ; true")
+        .select_expect("SELECT (NULL, 1) < (2, 0) AS r", "r ; NULL")
+        .select_expect("SELECT (1, NULL) < (1, 2) AS r", "r ; NULL")
+        .select_expect("SELECT (1, 2, 3) < (1, 2, 3) AS r", "r ; false");
+
+    // Rows must have the same length, and can only be compared with rows.
+    let mut session = Session::new(&engine);
+    assert!(session.execute("SELECT (1, 2) = (1, 2, 3)").is_err());
+    assert!(session.execute("SELECT (1, 2) = 1").is_err());
+    assert!(session.execute("SELECT (1, 2)").is_err());
+}
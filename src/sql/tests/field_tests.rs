@@ -0,0 +1,436 @@
+use crate::common::Error;
+use crate::sql::engine::Local;
+use crate::sql::planner::Expression;
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::storage::tuple::Row;
+use crate::types::field::{Field, Overflow};
+use crate::types::DataType;
+
+#[test]
+fn test_field_display() {
+    assert_eq!(Field::Null.to_string(), "NULL");
+    assert_eq!(Field::Boolean(true).to_string(), "TRUE");
+    assert_eq!(Field::Boolean(false).to_string(), "FALSE");
+    assert_eq!(Field::Integer(-42).to_string(), "-42");
+
+    // Floats round-trip without lossy rounding, and always keep a decimal
+    // point so they're distinguishable from integers.
+    assert_eq!(Field::Float(1.0).to_string(), "1.0");
+    assert_eq!(Field::Float(-0.0).to_string(), "-0.0");
+    assert_eq!(Field::Float(0.1).to_string(), "0.1");
+    assert_eq!(Field::Float(16777216.0).to_string(), "16777216.0");
+    assert_eq!(Field::Float(3.4028235e38).to_string(), "3.4028235e38");
+    assert_eq!(Field::Float(f32::NAN).to_string(), "NaN");
+    assert_eq!(Field::Float(f32::INFINITY).to_string(), "Infinity");
+    assert_eq!(Field::Float(f32::NEG_INFINITY).to_string(), "-Infinity");
+
+    // Text is quoted as a SQL string literal.
+    assert_eq!(Field::from("").to_string(), "''");
+    assert_eq!(Field::from("abc").to_string(), "'abc'");
+    assert_eq!(Field::from("it's").to_string(), "'it''s'");
+}
+
+#[test]
+fn test_field_plain_string() {
+    assert_eq!(Field::Null.to_plain_string(), "NULL");
+    assert_eq!(Field::Boolean(true).to_plain_string(), "true");
+    assert_eq!(Field::Integer(7).to_plain_string(), "7");
+    assert_eq!(Field::Float(1.0).to_plain_string(), "1");
+    assert_eq!(Field::Float(1.5).to_plain_string(), "1.5");
+    assert_eq!(Field::Float(f32::NEG_INFINITY).to_plain_string(), "-inf");
+    assert_eq!(Field::from("it's").to_plain_string(), "it's");
+}
+
+#[test]
+fn test_field_error_messages() {
+    let err = Field::from("a")
+        .checked_add(&Field::Integer(1))
+        .unwrap_err();
+    assert_eq!(err, Error::InvalidInput("can't add 'a' and 1".into()));
+
+    let err = Field::Boolean(true)
+        .checked_mod(&Field::Float(2.5))
+        .unwrap_err();
+    assert_eq!(err, Error::InvalidInput("can't mod TRUE and 2.5".into()));
+
+    let err = Field::Integer(1)
+        .checked_div(&Field::Integer(0))
+        .unwrap_err();
+    assert_eq!(err, Error::InvalidInput("can't divide by zero".into()));
+}
+
+#[test]
+fn test_field_parse_integer_radix() {
+    assert_eq!(Field::parse("0xFF", DataType::Int), Ok(Field::Integer(255)));
+    assert_eq!(
+        Field::parse("0x7fffffff", DataType::Int),
+        Ok(Field::Integer(i32::MAX))
+    );
+    assert_eq!(
+        Field::parse("-0x80000000", DataType::Int),
+        Ok(Field::Integer(i32::MIN))
+    );
+    assert_eq!(
+        Field::parse("0b1010", DataType::Int),
+        Ok(Field::Integer(10))
+    );
+    assert_eq!(Field::parse("-0B11", DataType::Int), Ok(Field::Integer(-3)));
+    assert_eq!(Field::parse("42", DataType::Int), Ok(Field::Integer(42)));
+
+    // Out-of-range values and invalid digits error.
+    assert_eq!(
+        Field::parse("0x100000000", DataType::Int),
+        Err(Error::InvalidInput(
+            "integer 0x100000000 out of range".into()
+        ))
+    );
+    assert_eq!(
+        Field::parse("0x80000000", DataType::Int),
+        Err(Error::InvalidInput(
+            "integer 0x80000000 out of range".into()
+        ))
+    );
+    assert!(Field::parse("0xFFFFFFFFFFFFFFFFFF", DataType::Int).is_err());
+    assert_eq!(
+        Field::parse("0b102", DataType::Int),
+        Err(Error::InvalidInput("invalid integer 0b102".into()))
+    );
+    assert!(Field::parse("0x", DataType::Int).is_err());
+    assert!(Field::parse("0x-1", DataType::Int).is_err());
+}
+
+#[test]
+fn test_radix_literals() {
+    let engine = Local::new(create_storage_engine());
+
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE flags (id INT PRIMARY KEY, bits INT)")
+        .execute("INSERT INTO flags VALUES (1, 0xFF), (2, 0b1010), (3, -0x10)")
+        .select_expect(
+            "SELECT * FROM flags",
+            "flags.id, flags.bits ; 1, 255 ; 2, 10 ; 3, -16",
+        )
+        .select_expect("SELECT 0XfF + 0B1 AS sum", "sum ; 256");
+
+    let mut session = engine.session();
+    assert!(session.execute("SELECT 0x100000000").is_err());
+    assert!(session.execute("SELECT 0xFG").is_err());
+}
+
+#[test]
+fn test_field_overflow() {
+    let max = Field::Integer(i32::MAX);
+    let one = Field::Integer(1);
+    assert_eq!(max.checked_add(&one), Err(Error::OverflowError));
+    assert_eq!(max.wrapping_add(&one), Ok(Field::Integer(i32::MIN)));
+    assert_eq!(Field::Integer(i32::MIN).wrapping_sub(&one), Ok(max.clone()));
+    assert_eq!(max.wrapping_mul(&Field::Integer(2)), Ok(Field::Integer(-2)));
+
+    // Non-integer operands behave as with checked arithmetic.
+    assert_eq!(max.wrapping_add(&Field::Null), Ok(Field::Null));
+    assert!(max.wrapping_add(&Field::from("a")).is_err());
+
+    // The evaluator defaults to erroring, but can wrap instead.
+    let expr = Expression::Add(
+        Expression::Constant(max.clone()).into(),
+        Expression::Constant(one).into(),
+    );
+    assert_eq!(expr.evaluate(None), Err(Error::OverflowError));
+    assert_eq!(
+        expr.evaluate_with(None, Overflow::Error),
+        Err(Error::OverflowError)
+    );
+    assert_eq!(
+        expr.evaluate_with(None, Overflow::Wrap),
+        Ok(Field::Integer(i32::MIN))
+    );
+
+    let expr = Expression::Negate(Expression::Constant(Field::Integer(i32::MIN)).into());
+    assert_eq!(expr.evaluate(None), Err(Error::OverflowError));
+    assert_eq!(
+        expr.evaluate_with(None, Overflow::Wrap),
+        Ok(Field::Integer(i32::MIN))
+    );
+}
+
+#[test]
+fn test_time_interval_arithmetic() {
+    // Times and intervals parse from strings, and display as typed literals.
+    assert_eq!(
+        Field::parse("09:30", DataType::Time).unwrap(),
+        Field::Time(34200)
+    );
+    assert_eq!(Field::parse_time("23:59:59").unwrap(), 86399);
+    assert!(Field::parse_time("24:00").is_err());
+    assert!(Field::parse_time("9:30").is_err());
+    assert_eq!(Field::parse_interval("1 day 2 hours").unwrap(), 93600);
+    assert_eq!(Field::parse_interval("-90 seconds").unwrap(), -90);
+    assert_eq!(
+        Field::parse_interval("-01:30:00 15 minutes").unwrap(),
+        -4500
+    );
+    assert!(Field::parse_interval("1 fortnight").is_err());
+    assert!(Field::parse_interval("").is_err());
+    assert_eq!(Field::Time(34200).to_string(), "TIME '09:30:00'");
+    assert_eq!(Field::Interval(-5400).to_string(), "INTERVAL '-01:30:00'");
+    assert_eq!(Field::Interval(93600).to_plain_string(), "26:00:00");
+
+    // Subtracting times yields an interval, and adding or subtracting an
+    // interval to a time yields a time, wrapping around midnight.
+    let (nine, five) = (Field::Time(9 * 3600), Field::Time(17 * 3600));
+    assert_eq!(five.checked_sub(&nine).unwrap(), Field::Interval(8 * 3600));
+    assert_eq!(nine.checked_sub(&five).unwrap(), Field::Interval(-8 * 3600));
+    assert_eq!(
+        nine.checked_add(&Field::Interval(-3600)).unwrap(),
+        Field::Time(8 * 3600)
+    );
+    assert_eq!(
+        five.checked_add(&Field::Interval(8 * 3600)).unwrap(),
+        Field::Time(3600)
+    );
+    assert_eq!(
+        nine.checked_sub(&Field::Interval(10 * 3600)).unwrap(),
+        Field::Time(23 * 3600)
+    );
+    assert_eq!(nine.checked_add(&Field::Null).unwrap(), Field::Null);
+    assert!(nine.checked_add(&five).is_err());
+    assert!(nine.checked_add(&Field::Integer(1)).is_err());
+
+    // The same works in SQL, with TIME columns and typed literals.
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE shifts (id INT PRIMARY KEY, start TIME, finish TIME)")
+        .execute(
+            "INSERT INTO shifts VALUES (1, TIME '09:00', TIME '17:30'), \
+             (2, TIME '22:00', TIME '06:00:15')",
+        )
+        .select_expect(
+            "SELECT id, finish - start AS length, start + INTERVAL '90 minutes' AS break \
+             FROM shifts ORDER BY id",
+            "shifts.id, length, break ; 1, 08:30:00, 10:30:00 ; 2, -15:59:45, 23:30:00",
+        )
+        .select_expect(
+            "SELECT id FROM shifts WHERE finish - start > INTERVAL '1 hour'",
+            "shifts.id ; 1",
+        );
+}
+
+#[test]
+fn test_field_float_hash() {
+    use std::hash::{BuildHasher, RandomState};
+
+    // Equal fields hash alike, including signed zeros and NaNs.
+    let state = RandomState::new();
+    let hash = |field: Field| state.hash_one(field);
+    assert_eq!(Field::Float(0.0), Field::Float(-0.0));
+    assert_eq!(hash(Field::Float(0.0)), hash(Field::Float(-0.0)));
+    assert_eq!(hash(Field::Float(f32::NAN)), hash(Field::Float(-f32::NAN)));
+    assert_ne!(hash(Field::Float(1.0)), hash(Field::Float(-1.0)));
+}
+
+#[test]
+fn test_row_hash_eq() {
+    use std::collections::HashSet;
+    use std::hash::{BuildHasher, RandomState};
+
+    // Rows with equal fields compare and hash alike, including NULLs, NaNs,
+    // and signed zeros.
+    let state = RandomState::new();
+    let hash = |row: &Row| state.hash_one(row);
+    let row = |fields: Vec<Field>| Row::from(fields);
+    let (a, b) = (
+        row(vec![1.into(), Field::Null, "x".into(), Field::Float(0.0)]),
+        row(vec![1.into(), Field::Null, "x".into(), Field::Float(-0.0)]),
+    );
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+    let nan = row(vec![Field::Float(f32::NAN), Field::Null]);
+    assert_eq!(
+        hash(&nan),
+        hash(&row(vec![Field::Float(-f32::NAN), Field::Null]))
+    );
+
+    // Rows differing in any field, or in length, are distinct.
+    let rows = HashSet::from([
+        a.clone(),
+        b,
+        row(vec![1.into(), Field::Null, "y".into(), Field::Float(0.0)]),
+        row(vec![1.into(), 2.into(), "x".into(), Field::Float(0.0)]),
+        row(vec![1.into(), Field::Null, "x".into()]),
+        nan.clone(),
+        nan,
+    ]);
+    assert_eq!(rows.len(), 5);
+
+    // Rows order lexicographically, with NULLs first.
+    let mut rows = vec![
+        row(vec![2.into(), "a".into()]),
+        row(vec![1.into(), "b".into()]),
+        row(vec![1.into(), Field::Null]),
+    ];
+    rows.sort();
+    assert_eq!(
+        rows,
+        vec![
+            row(vec![1.into(), Field::Null]),
+            row(vec![1.into(), "b".into()]),
+            row(vec![2.into(), "a".into()]),
+        ]
+    );
+}
+
+#[test]
+fn test_field_coerce_to() {
+    use DataType::*;
+
+    // NULL and values of the same type pass through.
+    for data_type in [Bool, Int, Float, Text, Time] {
+        assert_eq!(Field::Null.coerce_to(data_type).unwrap(), Field::Null);
+    }
+    assert_eq!(Field::Integer(7).coerce_to(Int).unwrap(), Field::Integer(7));
+
+    // Integers widen to floats, and integral floats narrow to integers.
+    assert_eq!(
+        Field::Integer(-3).coerce_to(Float).unwrap(),
+        Field::Float(-3.0)
+    );
+    assert_eq!(Field::Float(4.0).coerce_to(Int).unwrap(), Field::Integer(4));
+    assert_eq!(
+        Field::Float(-0.0).coerce_to(Int).unwrap(),
+        Field::Integer(0)
+    );
+    for f in [2.5, f32::NAN, f32::INFINITY, 3e9, -3e9] {
+        assert!(Field::Float(f).coerce_to(Int).is_err(), "{f}");
+    }
+
+    // Strings are parsed, ignoring surrounding whitespace.
+    assert_eq!(
+        Field::from(" 0x10 ").coerce_to(Int).unwrap(),
+        Field::Integer(16)
+    );
+    assert_eq!(
+        Field::from("1.5").coerce_to(Float).unwrap(),
+        Field::Float(1.5)
+    );
+    assert_eq!(
+        Field::from("TRUE").coerce_to(Bool).unwrap(),
+        Field::Boolean(true)
+    );
+    assert_eq!(
+        Field::from("01:02:03").coerce_to(Time).unwrap(),
+        Field::Time(3723)
+    );
+    for (value, data_type) in [("1.5", Int), ("yes", Bool), ("abc", Float), ("", Int)] {
+        assert!(Field::from(value).coerce_to(data_type).is_err(), "{value}");
+    }
+
+    // Only explicit casts convert booleans, numbers, and times to text, which
+    // parses back.
+    for (value, text) in [
+        (Field::Boolean(true), "TRUE"),
+        (Field::Integer(-1), "-1"),
+        (Field::Float(1.5), "1.5"),
+        (Field::Float(2.0), "2.0"),
+        (Field::Float(f32::NEG_INFINITY), "-Infinity"),
+        (Field::Time(3723), "01:02:03"),
+    ] {
+        assert!(value.coerce_to(Text).is_err());
+        let cast = value.cast_to(Text).unwrap();
+        assert_eq!(cast, Field::from(text));
+        assert_eq!(cast.cast_to(value.get_type()).unwrap(), value);
+    }
+
+    // Other conversions are rejected.
+    assert!(Field::Boolean(true).coerce_to(Int).is_err());
+    assert!(Field::Integer(1).coerce_to(Bool).is_err());
+    assert!(Field::Time(0).coerce_to(Int).is_err());
+    assert!(Field::Interval(60).coerce_to(Time).is_err());
+    assert!(Field::Interval(60).cast_to(Text).is_err());
+    assert!(Field::Array(vec![]).cast_to(Text).is_err());
+
+    // CAST and INSERT use it.
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, score FLOAT)")
+        .execute("INSERT INTO t VALUES ('1', 2), (2.0, '2.5')")
+        .select_expect(
+            "SELECT CAST(id AS FLOAT) AS id, CAST(score * 2 AS INT) AS score FROM t",
+            "id, score ; 1, 4 ; 2, 5",
+        )
+        .select_expect(
+            "SELECT CAST('12' AS INTEGER) + 1, CAST(NULL AS TIME)",
+            ", ; 13, NULL",
+        )
+        .select_expect(
+            "SELECT CAST(1 AS TEXT), CAST(1.5 AS TEXT), CAST(score AS TEXT) = '2.5' FROM t \
+             WHERE id = 2",
+            ", , ; 1, 1.5, true",
+        );
+    let mut session = engine.session();
+    assert!(session.execute("SELECT CAST(2.5 AS INT)").is_err());
+    assert!(session.execute("INSERT INTO t VALUES (3.5, 1)").is_err());
+}
+
+#[test]
+fn test_row_append_project() {
+    let row = |fields: Vec<Field>| Row::from(fields);
+    let left = row(vec![1.into(), "a".into()]);
+    let right = row(vec![Field::Null, 2.5.into()]);
+
+    // Appending concatenates the fields, leaving both rows as is.
+    let joined = left.append(&right);
+    assert_eq!(
+        joined,
+        row(vec![1.into(), "a".into(), Field::Null, 2.5.into()])
+    );
+    assert_eq!(left.append(&Row::default()), left);
+    assert_eq!(Row::default().append(&right), right);
+
+    // Projecting may reorder, drop, and repeat columns.
+    assert_eq!(
+        joined.project(&[3, 0, 0]).unwrap(),
+        row(vec![2.5.into(), 1.into(), 1.into()])
+    );
+    assert_eq!(joined.project(&[]).unwrap(), Row::default());
+    assert_eq!(joined.size(), 4);
+
+    // Out of range indexes error.
+    assert!(matches!(joined.project(&[0, 4]), Err(Error::OutOfBounds)));
+    assert!(matches!(
+        Row::default().project(&[0]),
+        Err(Error::OutOfBounds)
+    ));
+}
+
+#[test]
+fn test_float_special_values() {
+    let parse = |value| Field::parse(value, DataType::Float).unwrap();
+    assert!(matches!(parse("NaN"), Field::Float(f) if f.is_nan()));
+    assert!(matches!(parse("nan"), Field::Float(f) if f.is_nan()));
+    assert_eq!(parse("Infinity"), Field::Float(f32::INFINITY));
+    assert_eq!(parse("-INFINITY"), Field::Float(f32::NEG_INFINITY));
+    assert_eq!(parse("inf"), Field::Float(f32::INFINITY));
+    assert!(Field::parse("infinite", DataType::Float).is_err());
+
+    // String values are parsed when inserted into float columns. NaN never
+    // matches a comparison or join, not even with itself, while infinities
+    // compare as usual.
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE a (id INT PRIMARY KEY, value FLOAT)")
+        .execute("CREATE TABLE b (id INT PRIMARY KEY, value FLOAT)")
+        .execute("INSERT INTO a VALUES (1, 'NaN'), (2, 'Infinity'), (3, '-Infinity'), (4, 1.5)")
+        .execute("INSERT INTO b VALUES (1, 'nan'), (2, 'infinity'), (3, 1.5)")
+        .select_expect("SELECT id FROM a WHERE value > 0", "a.id ; 2 ; 4")
+        .select_expect("SELECT id FROM a WHERE value <= 0", "a.id ; 3")
+        .select_expect("SELECT id FROM a WHERE value = NAN", "a.id")
+        .select_expect("SELECT id FROM a WHERE value IS NAN", "a.id ; 1")
+        .select_expect(
+            "SELECT a.id, b.id FROM a JOIN b ON a.value = b.value ORDER BY a.id",
+            "a.id, b.id ; 2, 2 ; 4, 3",
+        )
+        .select_expect(
+            "SELECT a.id, b.id FROM a, b WHERE a.value = b.value ORDER BY a.id",
+            "a.id, b.id ; 2, 2 ; 4, 3",
+        );
+}
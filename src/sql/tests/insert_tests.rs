@@ -0,0 +1,385 @@
+use crate::common::Error;
+use crate::sql::engine::{Catalog, Engine, Local, StatementResult, Transaction};
+use crate::sql::execution::ExecutionResult;
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::storage::tuple::Row;
+use crate::types::field::Field;
+
+#[test]
+fn test_insert_default() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // DEFAULT and omitted columns take the column's default value.
+    SqlStudentRunner::new(&engine)
+        .execute(
+            "CREATE TABLE tasks (id INT PRIMARY KEY, title STRING, \
+             status STRING DEFAULT 'open', priority INT DEFAULT 3)",
+        )
+        .execute("INSERT INTO tasks VALUES (1, 'a', DEFAULT, 5)")
+        .execute("INSERT INTO tasks (id, title, priority) VALUES (2, 'b', DEFAULT)")
+        .execute("INSERT INTO tasks (title, id) VALUES ('c', 3), ('d', 4)")
+        .execute("INSERT INTO tasks VALUES (5, 'e')")
+        .select_expect(
+            "SELECT * FROM tasks",
+            "tasks.id, tasks.title, tasks.status, tasks.priority ; \
+             1, a, open, 5 ; 2, b, open, 3 ; 3, c, open, 3 ; 4, d, open, 3 ; 5, e, open, 3",
+        );
+
+    let mut session = engine.session();
+    // The title column has no default.
+    assert!(session.execute("INSERT INTO tasks VALUES (6, DEFAULT)").is_err());
+    assert!(session.execute("INSERT INTO tasks (id) VALUES (6)").is_err());
+    // Invalid column lists and value counts.
+    assert!(session.execute("INSERT INTO tasks (id, missing) VALUES (6, 1)").is_err());
+    assert!(session.execute("INSERT INTO tasks (id, id) VALUES (6, 7)").is_err());
+    assert!(session.execute("INSERT INTO tasks (id, title) VALUES (6, 'f', 'open')").is_err());
+    // DEFAULT isn't an expression.
+    assert!(session.execute("INSERT INTO tasks VALUES (6, 'f', DEFAULT + 1)").is_err());
+    assert!(session.execute("SELECT DEFAULT").is_err());
+}
+
+#[test]
+fn test_insert_nullable_default() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // A nullable column can have a non-NULL default, and only defaults to NULL
+    // without one.
+    SqlStudentRunner::new(&engine)
+        .execute(
+            "CREATE TABLE tasks (id INT PRIMARY KEY, status STRING NULL DEFAULT 'active', \
+             done BOOLEAN NULL)",
+        )
+        .execute("INSERT INTO tasks (id) VALUES (1)")
+        .execute("INSERT INTO tasks VALUES (2, DEFAULT, DEFAULT)")
+        .select_expect(
+            "SELECT id, status FROM tasks ORDER BY id",
+            "tasks.id, tasks.status ; 1, active ; 2, active",
+        );
+    let table = engine.begin().unwrap().must_get_table("tasks").unwrap();
+    assert_eq!(table.get_column(1).default(), Some(&Field::from("active")));
+    assert_eq!(table.get_column(2).default(), Some(&Field::Null));
+}
+
+#[test]
+fn test_insert_default_expression() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Non-literal defaults are stored as SQL text and evaluated whenever the
+    // default is used, while literal defaults are stored as values.
+    SqlStudentRunner::new(&engine)
+        .execute(
+            "CREATE TABLE counters (id INT PRIMARY KEY, n INT DEFAULT 40 + 2, \
+             m INT DEFAULT 7)",
+        )
+        .execute("INSERT INTO counters (id) VALUES (1)")
+        .execute("INSERT INTO counters VALUES (2, DEFAULT, 1)")
+        .execute("UPDATE counters SET n = 0")
+        .execute("UPDATE counters SET n = DEFAULT WHERE id = 2")
+        .select_expect(
+            "SELECT * FROM counters",
+            "counters.id, counters.n, counters.m ; 1, 0, 7 ; 2, 42, 1",
+        );
+
+    let txn = engine.begin().unwrap();
+    let table = txn.must_get_table("counters").unwrap();
+    let n = table.get_column(1);
+    assert_eq!(n.default(), None);
+    assert_eq!(n.default_expr(), Some("40 + 2"));
+    assert_eq!(table.get_column(2).default(), Some(&Field::Integer(7)));
+    assert_eq!(table.get_column(2).default_expr(), None);
+    drop(txn);
+
+    // Defaults can't refer to columns.
+    let mut session = engine.session();
+    assert!(session
+        .execute("CREATE TABLE bad (id INT PRIMARY KEY, n INT DEFAULT id + 1)")
+        .is_err());
+}
+
+#[test]
+fn test_insert_returns_rows() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine).execute(
+        "CREATE TABLE tasks (id INT PRIMARY KEY, title STRING, status STRING DEFAULT 'open')",
+    );
+
+    // The result includes the stored rows, with the omitted column's default.
+    let mut session = engine.session();
+    let StatementResult::Insert {
+        count,
+        record_ids,
+        rows,
+    } = session
+        .execute("INSERT INTO tasks (id, title) VALUES (1, 'a'), (2, 'b')")
+        .unwrap()
+    else {
+        panic!("expected insert result");
+    };
+    assert_eq!(count, 2);
+    assert_eq!(record_ids.len(), 2);
+    assert_eq!(
+        rows,
+        vec![
+            Row::from(vec![Field::Integer(1), "a".into(), "open".into()]),
+            Row::from(vec![Field::Integer(2), "b".into(), "open".into()]),
+        ]
+    );
+}
+
+#[test]
+fn test_upsert() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE cache (name STRING PRIMARY KEY, hits INT, value STRING)")
+        .execute("CREATE TABLE nokey (id INT, value STRING)");
+    let txn = engine.begin().unwrap();
+    let row = |name: &str, hits: i32, value: &str| {
+        Row::from(vec![name.into(), Field::Integer(hits), value.into()])
+    };
+
+    // A new key is inserted, and an existing key is updated in place.
+    let rid = txn.upsert("cache", row("a", 1, "x")).unwrap();
+    txn.upsert("cache", row("b", 1, "y")).unwrap();
+    assert_eq!(txn.upsert("cache", row("a", 2, "z")).unwrap(), rid);
+
+    // A changed tuple size moves the row, returning its new record id.
+    let moved = txn.upsert("cache", row("a", 3, "longer")).unwrap();
+    assert_ne!(moved, rid);
+    drop(txn);
+
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT name, hits, value FROM cache ORDER BY name",
+        "cache.name, cache.hits, cache.value ; a, 3, longer ; b, 1, y",
+    );
+
+    // Tables without a primary key, wrong widths, and NULL keys error.
+    let txn = engine.begin().unwrap();
+    assert!(txn
+        .upsert("nokey", Row::from(vec![Field::Integer(1), "a".into()]))
+        .is_err());
+    assert!(txn
+        .upsert("cache", Row::from(vec![Field::from("a")]))
+        .is_err());
+    assert!(txn
+        .upsert(
+            "cache",
+            Row::from(vec![Field::Null, Field::Integer(1), "x".into()])
+        )
+        .is_err());
+
+    // Values must match the column types, like INSERT.
+    assert!(txn
+        .upsert(
+            "cache",
+            Row::from(vec![Field::from("c"), "many".into(), "x".into()])
+        )
+        .is_err());
+    drop(txn);
+
+    // Omitted trailing columns take their default value.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE counters (name STRING PRIMARY KEY, hits INT DEFAULT 40 + 2)");
+    let txn = engine.begin().unwrap();
+    txn.upsert("counters", Row::from(vec![Field::from("a")])).unwrap();
+    txn.upsert("counters", Row::from(vec!["b".into(), Field::Integer(1)])).unwrap();
+    drop(txn);
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT name, hits FROM counters ORDER BY name",
+        "counters.name, counters.hits ; a, 42 ; b, 1",
+    );
+}
+
+#[test]
+fn test_values_validation() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price FLOAT)");
+
+    // Ragged VALUES rows error up front, identifying the row.
+    let txn = engine.begin().unwrap();
+    let constant = |value: i32| Expression::Constant(Field::Integer(value));
+    let ragged = Node::Values {
+        rows: vec![vec![constant(1), constant(2)], vec![constant(3)]],
+    };
+    let Err(Error::InvalidInput(message)) = Plan::Select(ragged.into()).execute(&txn) else {
+        panic!("expected invalid input error");
+    };
+    assert_eq!(message, "values row 1 has 1 columns, expected 2");
+    drop(txn);
+
+    // Values must match the column types, or be NULL. Integers are converted
+    // to floats for float columns.
+    let mut session = engine.session();
+    let Err(Error::InvalidInput(message)) =
+        session.execute("INSERT INTO items VALUES (1, 'a', 1.5), (2, 3, 2.5)")
+    else {
+        panic!("expected invalid input error");
+    };
+    assert_eq!(message, "values row 1 has 3 for varchar column name");
+    assert!(session
+        .execute("INSERT INTO items VALUES (1, 'a', TRUE)")
+        .is_err());
+
+    SqlStudentRunner::new(&engine)
+        .execute("INSERT INTO items VALUES (1, 'a', 2), (2, 'b', 2.5)")
+        .select_expect(
+            "SELECT id, price FROM items ORDER BY id",
+            "items.id, items.price ; 1, 2 ; 2, 2.5",
+        );
+}
+
+#[test]
+fn test_insert_validates_rows() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price FLOAT)");
+    let txn = engine.begin().unwrap();
+    let table = txn.must_get_table("items").unwrap();
+
+    // Rows from sources other than VALUES (here wrapped in a LIMIT, which
+    // skips the VALUES checks) are validated against the table schema when
+    // inserted, identifying the first offending row and column.
+    let row = |values: Vec<Field>| values.into_iter().map(Expression::Constant).collect();
+    let insert = |rows: Vec<Vec<Expression>>| {
+        let source = Node::Limit {
+            source: Node::Values { rows }.into(),
+            limit: 10,
+        };
+        Plan::Insert {
+            table: table.clone(),
+            source: source.into(),
+        }
+        .execute(&txn)
+    };
+    let Err(Error::InvalidInput(message)) = insert(vec![
+        row(vec![Field::Integer(1), "a".into(), Field::Float(1.5)]),
+        row(vec![Field::Integer(2), Field::Integer(3), Field::Float(2.5)]),
+    ]) else {
+        panic!("expected invalid input error");
+    };
+    assert_eq!(message, "insert row 1: invalid value 3 for varchar column name");
+    let Err(Error::InvalidInput(message)) = insert(vec![row(vec![Field::Integer(1)])]) else {
+        panic!("expected invalid input error");
+    };
+    assert_eq!(message, "insert row 0: expected 3 columns, got 1");
+
+    // Nothing was inserted, while NULLs are accepted.
+    assert_eq!(txn.scan("items", None).unwrap().count(), 0);
+    assert!(insert(vec![row(vec![Field::Integer(1), "a".into(), Field::Null])]).is_ok());
+}
+
+#[test]
+fn test_insert_select() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING, price INT, code STRING)")
+        .execute(
+            "INSERT INTO items VALUES (1, 'apple', 3, '10'), (2, 'pear', 5, '20'), \
+             (3, 'fig', 8, 'x')",
+        )
+        .execute(
+            "CREATE TABLE archive (id INT PRIMARY KEY, name STRING, price FLOAT, \
+             note STRING DEFAULT 'copied')",
+        );
+
+    // Filtered rows are copied, with integer prices converted to floats, and
+    // the omitted column takes its default.
+    let mut session = engine.session();
+    let result = session
+        .execute(
+            "INSERT INTO archive (name, id, price) SELECT name, id, price FROM items \
+             WHERE price < 6",
+        )
+        .unwrap();
+    let StatementResult::Insert { count, rows, .. } = result else {
+        panic!("expected insert result");
+    };
+    assert_eq!(count, 2);
+    assert_eq!(
+        rows[1],
+        Row::from(vec![
+            Field::Integer(2),
+            Field::from("pear"),
+            Field::Float(5.0),
+            Field::from("copied")
+        ])
+    );
+    SqlStudentRunner::new(&engine)
+        .select_expect(
+            "SELECT * FROM archive ORDER BY id",
+            "archive.id, archive.name, archive.price, archive.note ; \
+             1, apple, 3, copied ; 2, pear, 5, copied",
+        )
+        // Strings are parsed as the column type, and all columns can be given.
+        .execute("INSERT INTO archive SELECT id * 10, name, code, 'parsed' FROM items WHERE id < 3")
+        .select_expect(
+            "SELECT id, price FROM archive WHERE note = 'parsed' ORDER BY id",
+            "archive.id, archive.price ; 10, 10 ; 20, 20",
+        );
+
+    // The query must match the target columns' count and types, and values
+    // must convert to the column type.
+    for sql in [
+        "INSERT INTO archive SELECT id, name FROM items",
+        "INSERT INTO archive (id, name) SELECT id, name, price FROM items",
+        "INSERT INTO archive (id, price) SELECT id, price > 3 FROM items",
+        "INSERT INTO archive (id, price) SELECT id + 100, code FROM items",
+        "INSERT INTO archive (id, name) SELECT id, name FROM missing",
+    ] {
+        assert!(session.execute(sql).is_err(), "{sql} should error");
+    }
+    SqlStudentRunner::new(&engine).select_expect("SELECT COUNT(*) AS n FROM archive", "n ; 4");
+}
+
+#[test]
+fn test_values_streaming() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Rows are evaluated as they're emitted, so an error in a later row
+    // doesn't prevent reading the earlier ones.
+    let txn = engine.begin().unwrap();
+    let constant = |value: i32| Expression::Constant(Field::Integer(value));
+    let values = Node::Values {
+        rows: vec![
+            vec![constant(1)],
+            vec![Expression::Divide(constant(1).into(), constant(0).into())],
+        ],
+    };
+    let Ok(ExecutionResult::Select { mut rows, .. }) = Plan::Select(values.into()).execute(&txn)
+    else {
+        panic!("expected select result");
+    };
+    assert_eq!(rows.next().unwrap().unwrap().1, Row::from(vec![Field::Integer(1)]));
+    assert!(rows.next().unwrap().is_err());
+    assert!(rows.next().is_none());
+    drop(txn);
+
+    // A large VALUES list is inserted in full.
+    let values = (0..5000).map(|i| format!("({i}, 'item {i}')")).collect::<Vec<_>>();
+    let mut session = engine.session();
+    session
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, name STRING)")
+        .unwrap();
+    let result = session
+        .execute(&format!("INSERT INTO items VALUES {}", values.join(", ")))
+        .unwrap();
+    let StatementResult::Insert { count, rows, .. } = result else {
+        panic!("expected insert result");
+    };
+    assert_eq!(count, 5000);
+    assert_eq!(rows[4999].get_field(1).unwrap(), Field::from("item 4999"));
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT COUNT(*) AS n, MAX(id) AS top FROM items",
+        "n, top ; 5000, 4999",
+    );
+}
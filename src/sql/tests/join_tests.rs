@@ -0,0 +1,557 @@
+use crate::sql::engine::{Catalog, Engine, Local, StatementResult};
+use crate::sql::execution::ExecutionResult;
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::sql::tests::utility::{collect_into_table, create_storage_engine, SqlStudentRunner};
+use crate::storage::tuple::Row;
+use crate::types::field::{Field, Interner};
+use itertools::Itertools as _;
+use std::sync::Arc;
+
+/// Builds a Values node from rows of constants.
+fn values(rows: Vec<Vec<Field>>) -> Node {
+    let rows = rows
+        .into_iter()
+        .map(|row| row.into_iter().map(Expression::Constant).collect())
+        .collect();
+    Node::Values { rows }
+}
+
+#[test]
+fn test_collect_into_table() {
+    let engine = Local::new(create_storage_engine());
+    let txn = engine.begin().unwrap();
+    let execute = |node: Node| {
+        let ExecutionResult::Select { rows, .. } = Plan::Select(node.into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        collect_into_table(rows)
+    };
+
+    let grid = vec![
+        vec![1.into(), "a".into(), Field::Null],
+        vec![2.into(), "b".into(), true.into()],
+    ];
+    assert_eq!(execute(values(grid.clone())).unwrap(), grid);
+    assert_eq!(execute(values(vec![])).unwrap(), Vec::<Vec<Field>>::new());
+
+    // Row errors are propagated.
+    let divide = Expression::Divide(
+        Expression::Constant(1.into()).into(),
+        Expression::Constant(0.into()).into(),
+    );
+    let node = Node::Values {
+        rows: vec![vec![Expression::Constant(1.into())], vec![divide]],
+    };
+    assert!(execute(node).is_err());
+}
+
+#[test]
+fn test_hash_join_order() {
+    let engine = Local::new(create_storage_engine());
+    let txn = engine.begin().unwrap();
+
+    // Left rows are emitted in order, and each left row's matches are emitted
+    // in right source order, regardless of how the keys hash.
+    let left = values(vec![
+        vec![2.into(), "b".into()],
+        vec![1.into(), "a".into()],
+        vec![3.into(), "c".into()],
+    ]);
+    let right = values(vec![
+        vec![1.into(), "x".into()],
+        vec![2.into(), "y".into()],
+        vec![1.into(), "z".into()],
+        vec![2.into(), "v".into()],
+        vec![1.into(), "w".into()],
+    ]);
+    let join = Node::HashJoin {
+        left: left.into(),
+        left_column: 0,
+        right: right.into(),
+        right_column: 0,
+        outer: true,
+        epsilon: None,
+    };
+
+    let ExecutionResult::Select { rows, .. } = Plan::Select(join.into()).execute(&txn).unwrap()
+    else {
+        panic!("expected select result");
+    };
+    let rows: Vec<String> = rows.map(|r| r.unwrap().1.to_string(None)).collect();
+    assert_eq!(
+        rows,
+        vec![
+            "2, b, 2, y",
+            "2, b, 2, v",
+            "1, a, 1, x",
+            "1, a, 1, z",
+            "1, a, 1, w",
+            "3, c, NULL, NULL",
+        ]
+    );
+}
+
+#[test]
+fn test_reorder_hash_joins() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE small (id INT PRIMARY KEY, name STRING)")
+        .execute("CREATE TABLE large (id INT PRIMARY KEY, small_id INT)")
+        .execute("INSERT INTO small VALUES (1, 'a'), (2, 'b')")
+        .execute("INSERT INTO large VALUES (1, 2), (2, 1), (3, 2), (4, 3), (5, 1)");
+    let txn = engine.begin().unwrap();
+    assert_eq!(txn.table_stats("large").unwrap().row_count, 5);
+
+    let scan = |name: &str| Node::Scan {
+        table: txn.must_get_table(name).unwrap(),
+        filter: None,
+        alias: None,
+    };
+    let join = |outer| Node::HashJoin {
+        left: scan("small").into(),
+        left_column: 0,
+        right: scan("large").into(),
+        right_column: 1,
+        outer,
+        epsilon: None,
+    };
+
+    // Reordering is opt-in, and not done by the plain optimizers.
+    let plan = Plan::Select(join(false).into()).optimize().unwrap();
+    assert_eq!(plan, Plan::Select(join(false).into()));
+
+    // The smaller table becomes the build (right) side, and a remap restores
+    // the original column order.
+    let plan = Plan::Select(join(false).into())
+        .reorder_joins(&txn)
+        .unwrap();
+    let Plan::Select(root) = &plan else {
+        panic!("expected select plan");
+    };
+    let Node::Remap { source, targets } = &**root else {
+        panic!("expected remap, got {root:?}");
+    };
+    assert_eq!(targets, &vec![Some(2), Some(3), Some(0), Some(1)]);
+    let Node::HashJoin {
+        left,
+        left_column,
+        right,
+        right_column,
+        outer,
+        ..
+    } = &**source
+    else {
+        panic!("expected hash join, got {source:?}");
+    };
+    assert!(matches!(&**left, Node::Scan { table, .. } if table.name() == "large"));
+    assert!(matches!(&**right, Node::Scan { table, .. } if table.name() == "small"));
+    assert_eq!((*left_column, *right_column, *outer), (1, 0, false));
+
+    // The results are the same, except for the emission order.
+    let execute = |plan: Plan| {
+        let ExecutionResult::Select { rows, .. } = plan.execute(&txn).unwrap() else {
+            panic!("expected select result");
+        };
+        rows.map(|r| r.unwrap().1.to_string(None))
+            .sorted()
+            .collect_vec()
+    };
+    let expect = vec!["1, a, 2, 1", "1, a, 5, 1", "2, b, 1, 2", "2, b, 3, 2"];
+    assert_eq!(execute(plan), expect);
+    assert_eq!(execute(Plan::Select(join(false).into())), expect);
+
+    // Outer joins must keep their left side, and aren't reordered.
+    let plan = Plan::Select(join(true).into()).reorder_joins(&txn).unwrap();
+    assert_eq!(plan, Plan::Select(join(true).into()));
+    drop(txn);
+
+    // Sessions reorder joins when the reorder_joins variable is set.
+    let mut session = engine.session();
+    session.set("reorder_joins", Field::Boolean(true)).unwrap();
+    assert_eq!(session.get("reorder_joins"), Some(&Field::Boolean(true)));
+    let sql = "SELECT COUNT(*) AS n FROM small JOIN large ON small.id = large.small_id";
+    let StatementResult::Select { rows, .. } = session.execute(sql).unwrap() else {
+        panic!("expected select result");
+    };
+    assert_eq!(rows, vec![Row::from(vec![Field::Integer(4)])]);
+    session.set("reorder_joins", Field::Null).unwrap();
+    assert_eq!(session.get("reorder_joins"), None);
+    assert!(session.set("reorder_joins", Field::from("yes")).is_err());
+}
+
+#[test]
+fn test_hash_join_interning() {
+    // Equal strings share an allocation once interned.
+    let mut interner = Interner::new();
+    let (Field::String(a), Field::String(b)) =
+        (interner.intern("x".into()), interner.intern("x".into()))
+    else {
+        panic!("expected strings");
+    };
+    assert!(Arc::ptr_eq(&a, &b));
+    assert_eq!(interner.intern(Field::Integer(1)), Field::Integer(1));
+
+    // Join orders against a low-cardinality status table, keyed by status.
+    let statuses = ["open", "closed", "pending"];
+    let left = values(
+        (0..300)
+            .map(|i| vec![i.into(), statuses[i as usize % 3].into()])
+            .collect(),
+    );
+    let right = values(
+        (0..30)
+            .map(|i| vec![statuses[i as usize % 3].into(), i.into()])
+            .collect(),
+    );
+    let execute = |engine: &Local<_>, node: Node| {
+        let txn = engine.begin().unwrap();
+        let ExecutionResult::Select { rows, .. } = Plan::Select(node.into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        rows.map(|r| r.unwrap().1).collect_vec()
+    };
+    let hash_join = Node::HashJoin {
+        left: left.clone().into(),
+        left_column: 1,
+        right: right.clone().into(),
+        right_column: 0,
+        outer: false,
+        epsilon: None,
+    };
+    let nested_loop_join = Node::NestedLoopJoin {
+        left: left.into(),
+        right: right.into(),
+        predicate: Some(Expression::Equal(
+            Expression::Column(1).into(),
+            Expression::Column(2).into(),
+        )),
+        outer: false,
+    };
+
+    // Interning is opt-in, and yields the same rows as a nested loop join.
+    let engine = Local::new(create_storage_engine());
+    let interning = Local::new(create_storage_engine()).with_string_interning();
+    let plain = execute(&engine, hash_join.clone());
+    let hashed = execute(&interning, hash_join);
+    let nested = execute(&engine, nested_loop_join);
+    assert_eq!(hashed.len(), 3000);
+    assert_eq!(hashed, nested);
+    assert_eq!(hashed, plain);
+
+    // When interning, the right-side key strings share a single allocation
+    // per value.
+    let keys = |rows: &[Row]| {
+        rows.iter()
+            .filter_map(|row| match row.get_field(2).unwrap() {
+                Field::String(s) if &*s == "open" => Some(s),
+                _ => None,
+            })
+            .collect_vec()
+    };
+    let shared = keys(&hashed);
+    assert_eq!(shared.len(), 1000);
+    assert!(shared.iter().all(|s| Arc::ptr_eq(s, &shared[0])));
+    let unshared = keys(&plain);
+    assert!(!unshared.iter().all(|s| Arc::ptr_eq(s, &unshared[0])));
+}
+
+#[test]
+fn test_hash_join_float_epsilon() {
+    let engine = Local::new(create_storage_engine());
+    let txn = engine.begin().unwrap();
+
+    // The sum differs from 3.3 by a rounding error.
+    let sum = Field::Float(1.1).checked_add(&Field::Float(2.2)).unwrap();
+    assert_ne!(sum, Field::Float(3.3));
+
+    let left = values(vec![
+        vec![sum, "a".into()],
+        vec![Field::Float(2.0), "b".into()],
+    ]);
+    let right = values(vec![
+        vec![Field::Float(3.3), "x".into()],
+        vec![Field::Float(2.5), "y".into()],
+    ]);
+    let join = |epsilon| Node::HashJoin {
+        left: left.clone().into(),
+        left_column: 0,
+        right: right.clone().into(),
+        right_column: 0,
+        outer: false,
+        epsilon,
+    };
+    let execute = |node: Node| {
+        let ExecutionResult::Select { rows, .. } = Plan::Select(node.into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        rows.map(|r| r.unwrap().1.get_field(1).unwrap().to_plain_string())
+            .collect_vec()
+    };
+
+    // Exact equality doesn't match the rounded sum, but a tolerance does,
+    // without matching unrelated values.
+    assert!(execute(join(None)).is_empty());
+    assert_eq!(execute(join(Some(1e-4))), vec!["a"]);
+    assert_eq!(execute(join(Some(0.1))), vec!["a"]);
+
+    // A filter on the same equality compares floats the same way.
+    let filter = |epsilon| Node::Filter {
+        source: left.clone().into(),
+        predicate: Expression::Equal(
+            Expression::Column(0).into(),
+            Expression::Constant(Field::Float(3.3)).into(),
+        ),
+        epsilon,
+    };
+    assert!(execute(filter(None)).is_empty());
+    assert_eq!(execute(filter(Some(1e-4))), vec!["a"]);
+
+    // Epsilons that aren't positive and finite are rejected.
+    for epsilon in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        for node in [join(Some(epsilon)), filter(Some(epsilon))] {
+            let result = Plan::Select(node.into()).execute(&txn);
+            let Err(err) = result else {
+                panic!("expected error for epsilon {epsilon}");
+            };
+            assert!(err.to_string().contains("invalid float epsilon"), "{err}");
+        }
+    }
+}
+
+#[test]
+fn test_self_join_qualified_names() {
+    let engine = Local::new(create_storage_engine());
+
+    // Both sides of a self-join have an id and a name column. Qualifying them
+    // with the table aliases resolves each to the correct side of the join.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE emp (id INT PRIMARY KEY, name STRING, manager INT)")
+        .execute(
+            "INSERT INTO emp VALUES (1, 'ann', 0), (2, 'bob', 1), (3, 'cat', 1), (4, 'dan', 2)",
+        )
+        .select_expect(
+            "SELECT e.id, e.name, m.id, m.name FROM emp e JOIN emp m ON e.manager = m.id \
+             ORDER BY e.id",
+            "emp.id, emp.name, emp.id, emp.name ; 2, bob, 1, ann ; 3, cat, 1, ann ; 4, dan, 2, bob",
+        )
+        .select_expect(
+            "SELECT m.name, COUNT(e.id) AS n FROM emp e JOIN emp m ON m.id = e.manager \
+             WHERE m.id < e.id GROUP BY m.name ORDER BY m.name",
+            "emp.name, n ; ann, 2 ; bob, 1",
+        );
+
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT *, m.name FROM emp e JOIN emp m ON e.manager = m.id WHERE e.id = 4",
+        "emp.id, emp.name, emp.manager, emp.id, emp.name, emp.manager, emp.name ; \
+         4, dan, 2, 2, bob, 1, bob",
+    );
+
+    // Unqualified names are ambiguous, as are duplicate aliases.
+    let mut session = engine.session();
+    assert!(session
+        .execute("SELECT id FROM emp e JOIN emp m ON e.manager = m.id")
+        .is_err());
+    assert!(session
+        .execute("SELECT e.id FROM emp e JOIN emp e ON e.manager = e.id")
+        .is_err());
+    assert!(session
+        .execute("SELECT x.id FROM emp e JOIN emp m ON e.manager = m.id")
+        .is_err());
+}
+
+#[test]
+fn test_nested_loop_null_keys() {
+    let engine = Local::new(create_storage_engine());
+    let txn = engine.begin().unwrap();
+
+    let left = values(vec![
+        vec![1.into(), "a".into()],
+        vec![Field::Null, "b".into()],
+        vec![2.into(), "c".into()],
+    ]);
+    let right = values(vec![
+        vec![1.into(), "x".into()],
+        vec![Field::Null, "y".into()],
+        vec![2.into(), "z".into()],
+        vec![Field::Null, "w".into()],
+    ]);
+    let join = |predicate, outer| Node::NestedLoopJoin {
+        left: left.clone().into(),
+        right: right.clone().into(),
+        predicate: Some(predicate),
+        outer,
+    };
+    let execute = |node: Node| {
+        let ExecutionResult::Select { rows, .. } = Plan::Select(node.into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        rows.map(|r| r.unwrap().1.to_string(None)).collect_vec()
+    };
+
+    // NULL keys never match, whether the join skips them for an equi-join
+    // predicate or evaluates an equivalent predicate that isn't recognized as
+    // one. Outer joins pad left rows with NULL keys instead.
+    let equal = Expression::Equal(Expression::Column(0).into(), Expression::Column(2).into());
+    let equivalent = Expression::Or(
+        equal.clone().into(),
+        Expression::Constant(false.into()).into(),
+    );
+    for outer in [false, true] {
+        let result = execute(join(equal.clone(), outer));
+        assert_eq!(execute(join(equivalent.clone(), outer)), result);
+
+        let mut expect = vec!["1, a, 1, x", "2, c, 2, z"];
+        if outer {
+            expect.insert(1, "NULL, b, NULL, NULL");
+        }
+        assert_eq!(result, expect);
+    }
+}
+
+#[test]
+fn test_project_all_join() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE a (id INT PRIMARY KEY, name STRING)")
+        .execute("CREATE TABLE b (id INT PRIMARY KEY, a_id INT, score FLOAT)")
+        .execute("INSERT INTO a VALUES (1, 'x'), (2, 'y')")
+        .execute("INSERT INTO b VALUES (10, 1, 1.5), (20, 2, 2.5), (30, 1, 3.5)");
+    let txn = engine.begin().unwrap();
+
+    // Expanding * over a join projects the columns of both sides in order,
+    // labeled by their tables.
+    let scan = |name: &str| Node::Scan {
+        table: txn.must_get_table(name).unwrap(),
+        filter: None,
+        alias: None,
+    };
+    let join = Node::NestedLoopJoin {
+        left: scan("a").into(),
+        right: scan("b").into(),
+        predicate: Some(Expression::Equal(
+            Expression::Column(0).into(),
+            Expression::Column(3).into(),
+        )),
+        outer: false,
+    };
+    let star = join.clone().project_all();
+    assert_eq!(star.columns(), 5);
+    let labels = (0..star.columns())
+        .map(|i| star.column_label(i).to_string())
+        .collect_vec();
+    assert_eq!(labels, vec!["a.id", "a.name", "b.id", "b.a_id", "b.score"]);
+    let Node::Projection { expressions, .. } = &star else {
+        panic!("expected projection");
+    };
+    assert_eq!(*expressions, (0..5).map(Expression::Column).collect_vec());
+
+    // The projection emits the joined rows as is.
+    let execute = |node: Node| {
+        let ExecutionResult::Select { columns, rows, .. } =
+            Plan::Select(node.into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        let rows = rows.map(|r| r.unwrap().1.to_string(None)).collect_vec();
+        (columns, rows)
+    };
+    let (columns, rows) = execute(star);
+    assert_eq!((columns, rows.clone()), execute(join));
+    assert_eq!(
+        rows,
+        vec!["1, x, 10, 1, 1.5", "1, x, 30, 1, 3.5", "2, y, 20, 2, 2.5"]
+    );
+}
+
+#[test]
+fn test_hash_join_signed_zero() {
+    let engine = Local::new(create_storage_engine());
+    let txn = engine.begin().unwrap();
+
+    // 0.0 and -0.0 are equal, so they must hash alike and join. NaN keys are
+    // undefined, and never join.
+    let left = values(vec![
+        vec![Field::Float(0.0), "a".into()],
+        vec![Field::Float(f32::NAN), "b".into()],
+        vec![Field::Float(1.0), "c".into()],
+    ]);
+    let right = values(vec![
+        vec![Field::Float(-0.0), "x".into()],
+        vec![Field::Float(f32::NAN), "y".into()],
+        vec![Field::Float(-1.0), "z".into()],
+    ]);
+    let join = Node::HashJoin {
+        left: left.into(),
+        left_column: 0,
+        right: right.into(),
+        right_column: 0,
+        outer: false,
+        epsilon: None,
+    };
+    let ExecutionResult::Select { rows, .. } = Plan::Select(join.into()).execute(&txn).unwrap()
+    else {
+        panic!("expected select result");
+    };
+    let rows = rows.map(|r| r.unwrap().1.to_string(None)).collect_vec();
+    assert_eq!(rows, vec!["0, a, -0, x"]);
+}
+
+#[test]
+fn test_nested_loop_join_conditions() {
+    let engine = Local::new(create_storage_engine());
+    let txn = engine.begin().unwrap();
+    let execute = |node: Node| {
+        let ExecutionResult::Select { rows, .. } = Plan::Select(node.into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        rows.map(|r| r.unwrap().1.to_string(None)).collect_vec()
+    };
+    let left = || values(vec![vec![1.into(), 5.into()], vec![2.into(), 1.into()]]);
+    let right = || {
+        values(vec![
+            vec![1.into(), 3.into()],
+            vec![1.into(), 7.into()],
+            vec![2.into(), 0.into()],
+            vec![3.into(), 0.into()],
+        ])
+    };
+    // a.x = b.x AND a.y > b.y
+    let conditions = || {
+        vec![
+            Expression::Equal(Expression::Column(0).into(), Expression::Column(2).into()),
+            Expression::GreaterThan(Expression::Column(1).into(), Expression::Column(3).into()),
+        ]
+    };
+
+    // Both conditions must hold for a row pair to be joined.
+    let join = Node::nested_loop_join(left(), right(), conditions(), false);
+    assert!(matches!(
+        &join,
+        Node::NestedLoopJoin {
+            predicate: Some(Expression::And(..)),
+            ..
+        }
+    ));
+    assert_eq!(execute(join), vec!["1, 5, 1, 3", "2, 1, 2, 0"]);
+
+    // Outer joins pad left rows that match only one of the conditions.
+    let left_rows = values(vec![vec![1.into(), 5.into()], vec![3.into(), 0.into()]]);
+    let join = Node::nested_loop_join(left_rows, right(), conditions(), true);
+    assert_eq!(execute(join), vec!["1, 5, 1, 3", "3, 0, NULL, NULL"]);
+
+    // No conditions is a cross join.
+    let join = Node::nested_loop_join(left(), right(), Vec::new(), false);
+    assert!(matches!(
+        &join,
+        Node::NestedLoopJoin {
+            predicate: None,
+            ..
+        }
+    ));
+    assert_eq!(execute(join).len(), 8);
+}
@@ -1,3 +1,27 @@
 #[cfg(test)]
+mod aggregate_tests;
+#[cfg(test)]
+mod create_table_tests;
+#[cfg(test)]
+mod delete_tests;
+#[cfg(test)]
+mod expression_tests;
+#[cfg(test)]
+mod field_tests;
+#[cfg(test)]
+mod insert_tests;
+#[cfg(test)]
+mod join_tests;
+#[cfg(test)]
 mod lab4_student_tests;
+#[cfg(test)]
+mod scan_tests;
+#[cfg(test)]
+mod schema_tests;
+#[cfg(test)]
+mod subquery_tests;
+#[cfg(test)]
+mod transform_tests;
+#[cfg(test)]
+mod update_tests;
 mod utility;
@@ -0,0 +1,920 @@
+use crate::common::{Error, Result};
+use crate::sql::engine::{Catalog, Engine, Local, Session, StatementResult, Transaction};
+use crate::sql::execution::{CancelToken, ExecutionResult};
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::storage::page::{Page, RecordId, TablePage};
+use crate::storage::tuple::{ColumnBatches, Row, Rows, Tuple, TupleMetadata};
+use crate::types::field::{Field, Label};
+use crate::types::{Column, DataType, Table};
+use itertools::Itertools as _;
+use std::sync::{Arc, RwLock};
+
+#[test]
+fn test_batched_scan() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    let values = (0..500).map(|i| format!("({i}, 'row {i}')")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE big (id INT PRIMARY KEY, name STRING)")
+        .execute(&format!("INSERT INTO big VALUES {values}"))
+        .select_expect("SELECT COUNT(*) FROM big", " ; 500");
+
+    // Batched scans emit the same tuples in the same order as a single batch,
+    // regardless of the batch size.
+    let txn = engine.simple.begin().unwrap();
+    let unbatched: Vec<_> = txn
+        .scan_with_batch_size("big", usize::MAX)
+        .try_collect()
+        .unwrap();
+    assert_eq!(unbatched.len(), 500);
+    for batch_size in [1, 3, 499, 500, 501] {
+        let batched: Vec<_> = txn
+            .scan_with_batch_size("big", batch_size)
+            .try_collect()
+            .unwrap();
+        assert_eq!(batched, unbatched, "batch size {batch_size}");
+    }
+    let batched: Vec<_> = txn.scan("big").try_collect().unwrap();
+    assert_eq!(batched, unbatched);
+}
+
+#[test]
+fn test_empty_select_types() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let mut session = engine.session();
+    session
+        .execute("CREATE TABLE empty (id INT PRIMARY KEY, name STRING, score FLOAT, active BOOL)")
+        .unwrap();
+
+    // Column types come from the schema, even though there are no rows.
+    let StatementResult::Select {
+        columns,
+        types,
+        rows,
+    } = session.execute("SELECT * FROM empty").unwrap()
+    else {
+        panic!("expected select result");
+    };
+    assert!(rows.is_empty());
+    assert_eq!(
+        columns,
+        ["id", "name", "score", "active"]
+            .map(|c| Label::Qualified("empty".into(), c.into()))
+            .to_vec()
+    );
+    assert_eq!(
+        types,
+        vec![
+            DataType::Int,
+            DataType::Text,
+            DataType::Float,
+            DataType::Bool
+        ]
+    );
+
+    // Types are traced through projections, joins, and aggregates, and
+    // inferred for computed expressions.
+    let StatementResult::Select { types, rows, .. } = session
+        .execute(
+            "SELECT b.active, a.name, a.id + 1, 'x', COUNT(*), MAX(a.score) \
+             FROM empty a JOIN empty b ON a.id = b.id GROUP BY b.active, a.name, a.id + 1, 'x'",
+        )
+        .unwrap()
+    else {
+        panic!("expected select result");
+    };
+    assert!(rows.is_empty());
+    assert_eq!(
+        types,
+        vec![
+            DataType::Bool,
+            DataType::Text,
+            DataType::Int,
+            DataType::Text,
+            DataType::Int,
+            DataType::Float
+        ]
+    );
+}
+
+#[test]
+fn test_select_alias_types() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let mut session = engine.session();
+    session
+        .execute("CREATE TABLE items (id INT PRIMARY KEY, x INT, name STRING)")
+        .unwrap();
+    session
+        .execute("INSERT INTO items VALUES (1, 2, 'a')")
+        .unwrap();
+
+    // Output columns carry both the alias and the inferred expression type.
+    // Integer division may yield a float, so its type is unknown.
+    let StatementResult::Select {
+        columns,
+        types,
+        rows,
+    } = session
+        .execute(
+            "SELECT CAST(x AS FLOAT) AS y, x * 2.5 AS z, x > 1 AS big, x / 2 AS half, \
+             name FROM items",
+        )
+        .unwrap()
+    else {
+        panic!("expected select result");
+    };
+    assert_eq!(
+        columns,
+        vec![
+            Label::Unqualified("y".into()),
+            Label::Unqualified("z".into()),
+            Label::Unqualified("big".into()),
+            Label::Unqualified("half".into()),
+            Label::Qualified("items".into(), "name".into()),
+        ]
+    );
+    assert_eq!(
+        types,
+        vec![
+            DataType::Float,
+            DataType::Float,
+            DataType::Bool,
+            DataType::Invalid,
+            DataType::Text
+        ]
+    );
+    assert_eq!(
+        rows,
+        vec![Row::from(vec![
+            Field::Float(2.0),
+            Field::Float(5.0),
+            Field::Boolean(true),
+            Field::Integer(1),
+            Field::from("a"),
+        ])]
+    );
+}
+
+#[test]
+fn test_scan_filter_lazy_decode() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    let values = (0..100)
+        .map(|i| format!("({i}, {}, 'first {i}', {}.5, 'second {i}')", i % 10, i))
+        .join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE wide (id INT PRIMARY KEY, k INT, a STRING, f FLOAT, b STRING)")
+        .execute(&format!("INSERT INTO wide VALUES {values}"));
+
+    let txn = engine.begin().unwrap();
+    let scan = |filter: Option<Expression>| {
+        txn.scan("wide", filter)
+            .unwrap()
+            .map(|r| r.unwrap().1)
+            .collect_vec()
+    };
+    let all = scan(None);
+    assert_eq!(all.len(), 100);
+
+    // Filtering on a fixed-length column decodes the text fields of the
+    // matching rows afterwards, with the same results as filtering fully
+    // decoded rows.
+    let filter = Expression::Equal(
+        Expression::Column(1).into(),
+        Expression::Constant(Field::Integer(3)).into(),
+    );
+    let expect = all
+        .iter()
+        .filter(|row| row.get_field(1).unwrap() == Field::Integer(3))
+        .cloned()
+        .collect_vec();
+    let rows = scan(Some(filter));
+    assert_eq!(rows.len(), 10);
+    assert_eq!(rows, expect);
+
+    // Text fields referenced by the filter are decoded before evaluating it,
+    // and the other text field of the matching row after.
+    let filter = Expression::Equal(
+        Expression::Column(2).into(),
+        Expression::Constant("first 42".into()).into(),
+    );
+    let rows = scan(Some(filter));
+    assert_eq!(rows, vec![all[42].clone()]);
+    assert_eq!(rows[0].get_field(4).unwrap(), Field::from("second 42"));
+}
+
+#[test]
+fn test_scan_column_count_mismatch() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, value STRING)")
+        .execute("INSERT INTO t VALUES (1, 'a')");
+    let txn = engine.begin().unwrap();
+
+    // A scan with a stale schema declares 3 columns, but the stored rows only
+    // have 2. Debug builds catch the mismatch when executing the node.
+    let stale = Table::builder()
+        .name("t")
+        .column("id", DataType::Int, false, None, None)
+        .column("value", DataType::Text, true, None, None)
+        .column("extra", DataType::Int, true, None, None)
+        .build();
+    let scan = |table| Node::Scan {
+        table,
+        filter: None,
+        alias: None,
+    };
+    let rows = |table| {
+        let ExecutionResult::Select { rows, .. } =
+            Plan::Select(scan(table).into()).execute(&txn).unwrap()
+        else {
+            panic!("expected select result");
+        };
+        rows.collect::<Result<Vec<_>>>()
+    };
+    assert_eq!(rows(stale).is_err(), cfg!(debug_assertions));
+
+    // The actual schema matches.
+    assert_eq!(rows(txn.must_get_table("t").unwrap()).unwrap().len(), 1);
+}
+
+#[test]
+fn test_column_batches() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name STRING, score FLOAT)")
+        .execute("INSERT INTO t VALUES (1, 'a', 1.5), (2, 'b', 2.5), (3, 'c', 3.5), (4, 'd', 4.5)");
+    let txn = engine.begin().unwrap();
+    let schema = txn.must_get_table("t").unwrap();
+    let rows: Vec<Row> = txn
+        .scan("t", None)
+        .unwrap()
+        .map_ok(|(_, row)| row)
+        .try_collect()
+        .unwrap();
+
+    // Four rows in batches of three yield a full and a partial batch, with one
+    // vector per column.
+    let batches: Vec<_> = ColumnBatches::new(txn.scan("t", None).unwrap(), &schema, 3)
+        .try_collect()
+        .unwrap();
+    assert_eq!(batches.iter().map(|b| b.len()).collect_vec(), vec![3, 1]);
+    assert_eq!(
+        batches[0].columns,
+        vec![
+            vec![Field::Integer(1), Field::Integer(2), Field::Integer(3)],
+            vec![Field::from("a"), Field::from("b"), Field::from("c")],
+            vec![Field::Float(1.5), Field::Float(2.5), Field::Float(3.5)],
+        ]
+    );
+
+    // Transposing the batches back yields the original rows.
+    let transposed = batches
+        .iter()
+        .flat_map(|batch| (0..batch.len()).map(|i| batch.row(i).unwrap()))
+        .collect_vec();
+    assert_eq!(transposed, rows);
+    assert_eq!(batches[1].row(1), None);
+
+    // Rows that don't match the schema width error.
+    let narrow = Table::builder()
+        .name("t")
+        .column("id", DataType::Int, false, None, None)
+        .build();
+    let mut batches = ColumnBatches::new(txn.scan("t", None).unwrap(), &narrow, 3);
+    assert!(batches.next().unwrap().is_err());
+}
+
+#[test]
+fn test_scan_after_pagination() {
+    let engine = Local::new(create_storage_engine());
+    let values = (0..300).map(|i| format!("({i}, 'row {i}')")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE big (id INT PRIMARY KEY, name STRING)")
+        .execute(&format!("INSERT INTO big VALUES {values}"))
+        .execute("DELETE FROM big WHERE id % 10 = 3")
+        .execute("CREATE TABLE other (id INT PRIMARY KEY)")
+        .execute("INSERT INTO other VALUES (1)");
+    let txn = engine.begin().unwrap();
+    let all: Vec<_> = txn.scan("big", None).unwrap().try_collect().unwrap();
+    assert_eq!(all.len(), 270);
+    assert!(all.iter().map(|(rid, _)| rid.page_id()).unique().count() > 1);
+
+    // Paging with the last record id as cursor covers the table in scan order,
+    // across pages and deleted tuples, without overlap.
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = txn.scan_after("big", cursor, 7).unwrap();
+        let Some((rid, _)) = page.last() else {
+            break;
+        };
+        cursor = Some(rid.clone());
+        pages.push(page);
+    }
+    assert_eq!(pages.len(), 39);
+    assert!(pages.iter().all(|page| page.len() <= 7));
+    assert_eq!(pages.concat(), all);
+
+    // A cursor at a deleted tuple resumes after it.
+    let (deleted, _) = all[2].clone();
+    txn.delete("big", &[deleted.clone()]).unwrap();
+    assert_eq!(
+        txn.scan_after("big", Some(deleted.clone()), 2).unwrap(),
+        all[3..5]
+    );
+
+    // Cursors from another table, for a slot that doesn't exist, or from
+    // before a vacuum rewrote the table error.
+    let (other, _) = txn.scan("other", None).unwrap().next().unwrap().unwrap();
+    assert!(txn.scan_after("big", Some(other), 2).is_err());
+    let missing = RecordId::new(deleted.page_id(), u16::MAX - 1);
+    assert!(txn.scan_after("big", Some(missing), 2).is_err());
+    txn.vacuum("big").unwrap();
+    assert!(txn.scan_after("big", Some(deleted), 2).is_err());
+}
+
+#[test]
+fn test_scan_sample() {
+    let engine = Local::new(create_storage_engine());
+    let values = (0..20).map(|i| format!("({i})")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY)")
+        .execute(&format!("INSERT INTO t VALUES {values}"));
+    let txn = engine.begin().unwrap();
+    let sample = |probability, seed| -> Vec<Field> {
+        txn.scan_sample("t", probability, seed)
+            .unwrap()
+            .map_ok(|(_, row)| row.get_field(0).unwrap())
+            .try_collect()
+            .unwrap()
+    };
+
+    // A fixed seed yields the same subset of rows, in scan order.
+    let ids = sample(0.5, 42);
+    let expect = [2, 4, 5, 6, 9, 15, 17, 18, 19];
+    assert_eq!(ids, expect.map(Field::Integer));
+    assert_eq!(sample(0.5, 42), ids);
+    assert_ne!(sample(0.5, 7), ids);
+
+    // The probability bounds include all or no rows, and must be valid.
+    assert_eq!(sample(1.0, 42).len(), 20);
+    assert!(sample(0.0, 42).is_empty());
+    assert!(txn.scan_sample("t", 1.5, 42).is_err());
+    assert!(txn.scan_sample("t", f64::NAN, 42).is_err());
+}
+
+#[test]
+fn test_cancel_execution() {
+    let engine = Local::new(create_storage_engine());
+    let values = (0..100).map(|i| format!("({i})")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY)")
+        .execute(&format!("INSERT INTO t VALUES {values}"));
+    let txn = engine.begin().unwrap();
+    let plan = |sql: &str| {
+        let statement = crate::sql::parser::Parser::new(sql).parse().unwrap();
+        Plan::build(statement, &txn).unwrap().optimize().unwrap()
+    };
+
+    // Cancelling a join mid-iteration stops it with a cancellation error.
+    let cancel = CancelToken::new();
+    let ExecutionResult::Select { mut rows, .. } = plan("SELECT * FROM t a, t b")
+        .execute_with(&txn, &cancel)
+        .unwrap()
+    else {
+        panic!("expected select result");
+    };
+    for _ in 0..150 {
+        rows.next().unwrap().unwrap();
+    }
+    cancel.cancel();
+    assert!(matches!(rows.next(), Some(Err(Error::Cancelled))));
+
+    // A cancelled token fails execution up front, e.g. before sorting.
+    let result = plan("SELECT * FROM t ORDER BY id DESC").execute_with(&txn, &cancel);
+    assert!(matches!(result, Err(Error::Cancelled)));
+
+    // So does INSERT ... VALUES, which doesn't go through the source node's
+    // execution, without inserting anything.
+    let result = plan("INSERT INTO t VALUES (1000), (1001)").execute_with(&txn, &cancel);
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert_eq!(txn.scan("t", None).unwrap().count(), 100);
+
+    // Fused scan-filter-project pipelines check the token for rows that are
+    // filtered out, rather than scanning to the end. The plan isn't optimized,
+    // to keep the Filter node instead of pushing it into the scan.
+    let cancel = CancelToken::new();
+    let statement = crate::sql::parser::Parser::new("SELECT id FROM t WHERE id > 1000")
+        .parse()
+        .unwrap();
+    let ExecutionResult::Select { mut rows, .. } = Plan::build(statement, &txn)
+        .unwrap()
+        .execute_with(&txn, &cancel)
+        .unwrap()
+    else {
+        panic!("expected select result");
+    };
+    cancel.cancel();
+    assert!(matches!(rows.next(), Some(Err(Error::Cancelled))));
+}
+
+#[test]
+fn test_constant_filter_skips_scan() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY)")
+        .execute("INSERT INTO t VALUES (1), (2)");
+
+    // Store a tuple that can't be decoded, which would fail any query that
+    // reads the table. A false constant filter yields no rows without reading
+    // it, also below a projection.
+    let tuple = Tuple::from(vec![1, 2, 3]);
+    engine.simple.begin().unwrap().insert("t", tuple).unwrap();
+    SqlStudentRunner::new(&engine)
+        .select_expect("SELECT id FROM t WHERE FALSE", "t.id")
+        .select_expect("SELECT id * 2 AS d FROM t WHERE NULL", "d");
+}
+
+#[test]
+fn test_tuple_checksum() {
+    let mut page = TablePage::builder().page_id(3).build();
+    let tuples = [Tuple::from(vec![1, 2, 3, 4]), Tuple::from(vec![5, 6, 7, 8])];
+    for tuple in tuples.clone() {
+        page.insert_tuple(TupleMetadata::new(false), tuple).unwrap();
+    }
+    let (first, second) = (RecordId::new(3, 0), RecordId::new(3, 1));
+    assert_eq!(page.get_tuple(&first).unwrap(), tuples[0]);
+    assert_eq!(
+        page.get_tuple_metadata(&first).unwrap().checksum(),
+        tuples[0].checksum()
+    );
+
+    // The checksum survives page serialization. Flipping a byte of a
+    // serialized tuple is detected when the tuple is read, but doesn't affect
+    // other tuples.
+    let offset = page.tuple_info[0].offset as usize;
+    let mut bytes = page.serialize();
+    bytes[offset + 2] ^= 0xff;
+    let corrupt = TablePage::deserialize(&bytes);
+    assert!(matches!(
+        corrupt.get_tuple(&first),
+        Err(Error::InvalidData(_))
+    ));
+    assert_eq!(corrupt.get_tuple(&second).unwrap(), tuples[1]);
+
+    // Scans yield the error rather than skipping the tuple.
+    let scanned = TablePage::iter(Arc::new(RwLock::new(corrupt))).collect_vec();
+    assert!(matches!(scanned[0], Err(Error::InvalidData(_))));
+    assert_eq!(
+        scanned[1].as_ref().unwrap(),
+        &(second.clone(), tuples[1].clone())
+    );
+
+    // Updating the tuple or its metadata keeps the checksum valid.
+    let updated = Tuple::from(vec![9, 9, 9, 9]);
+    page.update_tuple_in_place_unchecked(TupleMetadata::new(false), updated.clone(), &first)
+        .unwrap();
+    assert_eq!(page.get_tuple(&first).unwrap(), updated);
+    page.update_tuple_metadata(&TupleMetadata::new(false), &second)
+        .unwrap();
+    assert_eq!(page.get_tuple(&second).unwrap(), tuples[1]);
+}
+
+#[test]
+fn test_get_by_record_id() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine).execute("CREATE TABLE t (id INT PRIMARY KEY, name STRING)");
+    let txn = engine.begin().unwrap();
+    let rows = (1..=3)
+        .map(|id| Row::from(vec![Field::Integer(id), Field::from(format!("row {id}"))]))
+        .collect_vec();
+    let rids = txn
+        .insert("t", rows.clone())
+        .unwrap()
+        .into_iter()
+        .map(|(rid, _)| rid)
+        .collect_vec();
+
+    assert_eq!(txn.get("t", &rids[1]).unwrap(), Some(rows[1].clone()));
+
+    // Deleted and out of range record ids are missing.
+    txn.delete("t", &rids[1..2]).unwrap();
+    assert_eq!(txn.get("t", &rids[1]).unwrap(), None);
+    let missing = RecordId::new(rids[2].page_id(), rids[2].slot_id() + 1);
+    assert_eq!(txn.get("t", &missing).unwrap(), None);
+    assert_eq!(txn.get("t", &rids[2]).unwrap(), Some(rows[2].clone()));
+
+    assert!(txn.get("missing", &rids[0]).is_err());
+}
+
+#[test]
+fn test_execution_result_table_string() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let txn = engine.begin().unwrap();
+    let render = |sql: &str| {
+        let statement = crate::sql::parser::Parser::new(sql).parse().unwrap();
+        let plan = Plan::build(statement, &txn).unwrap().optimize().unwrap();
+        plan.execute(&txn).unwrap().to_table_string().unwrap()
+    };
+
+    assert_eq!(
+        render("CREATE TABLE t (id INT PRIMARY KEY, name STRING)"),
+        "table t created"
+    );
+    let long = "x".repeat(40);
+    assert_eq!(
+        render(&format!(
+            "INSERT INTO t VALUES (1, 'alice'), (20, '{long}')"
+        )),
+        "2 rows inserted"
+    );
+    assert_eq!(
+        render("UPDATE t SET name = 'bob' WHERE id = 1"),
+        "1 row updated"
+    );
+
+    // Columns are padded to their widest value, and wide values truncated.
+    let table = render("SELECT * FROM t ORDER BY id");
+    let lines = table.lines().collect_vec();
+    assert_eq!(lines[1], "| t.id | t.name                           |");
+    assert_eq!(lines[3], "| 1    | bob                              |");
+    assert_eq!(lines[4], "| 20   | xxxxxxxxxxxxxxxxxxxxxxxxxxxxx... |");
+    assert_eq!(lines[0], lines[2]);
+    assert_eq!(lines[5], lines[0]);
+    assert_eq!(lines[6], "(2 rows)");
+
+    assert_eq!(
+        render("SELECT id FROM t WHERE id > 100"),
+        "+------+\n| t.id |\n+------+\n(0 rows)"
+    );
+}
+
+#[test]
+fn test_table_string_float_precision() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, f FLOAT)")
+        .execute("INSERT INTO t VALUES (1, 3.14159), (2, 2.5)");
+
+    let txn = engine.begin().unwrap();
+    let render = |sql: &str, precision| {
+        let statement = crate::sql::parser::Parser::new(sql).parse().unwrap();
+        let plan = Plan::build(statement, &txn).unwrap().optimize().unwrap();
+        let table = plan
+            .execute(&txn)
+            .unwrap()
+            .to_table_string_with(precision)
+            .unwrap();
+        // Only the row lines, skipping the header and separators.
+        let lines = table.lines().skip(2).filter(|line| line.starts_with('|'));
+        lines.map(str::to_string).collect_vec()
+    };
+
+    // Floats are rounded for display only, and padded to the precision.
+    assert_eq!(
+        render("SELECT * FROM t ORDER BY id", Some(2)),
+        vec!["| 1    | 3.14 |", "| 2    | 2.50 |"]
+    );
+    assert_eq!(
+        render("SELECT * FROM t ORDER BY id", None),
+        vec!["| 1    | 3.14159 |", "| 2    | 2.5     |"]
+    );
+    assert_eq!(
+        render(
+            "SELECT INFINITY AS inf, ARRAY_AGG(f) AS fs FROM t WHERE id = 1",
+            Some(1)
+        ),
+        vec!["| inf | [3.1] |"]
+    );
+
+    // The stored value and comparisons are unaffected.
+    assert_eq!(
+        render("SELECT id FROM t WHERE f > 3.14", Some(2)),
+        vec!["| 1    |"]
+    );
+    SqlStudentRunner::new(&engine).select_expect("SELECT f FROM t WHERE id = 1", "t.f ; 3.14159");
+}
+
+#[test]
+fn test_exists_value() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    let values = (0..100)
+        .map(|i| format!("({i}, {}, 'first {i}', 'second {i}')", i % 10))
+        .join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE wide (id INT PRIMARY KEY, k INT, a STRING, b STRING)")
+        .execute(&format!("INSERT INTO wide VALUES {values}"));
+
+    let txn = engine.begin().unwrap();
+    let exists = |column, value: Field| txn.exists("wide", column, &value);
+
+    // Present values are found in fixed-length and text columns, including
+    // in the last row.
+    assert!(exists(1, Field::Integer(3)).unwrap());
+    assert!(exists(2, Field::from("first 0")).unwrap());
+    assert!(exists(3, Field::from("second 99")).unwrap());
+
+    // Absent values scan the whole table. NULL never matches.
+    assert!(!exists(1, Field::Integer(10)).unwrap());
+    assert!(!exists(3, Field::from("second 100")).unwrap());
+    assert!(!exists(1, Field::Null).unwrap());
+
+    // The column and table must exist.
+    assert!(matches!(
+        exists(4, Field::Integer(3)),
+        Err(Error::InvalidInput(message)) if message == "column index 4 out of bounds"
+    ));
+    assert!(txn.exists("missing", 0, &Field::Integer(1)).is_err());
+}
+
+#[test]
+fn test_estimate_rows() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    let values = (0..1000).map(|i| format!("({i}, 'name {i}')")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE people (id INT PRIMARY KEY, name STRING)")
+        .execute("CREATE TABLE empty (id INT PRIMARY KEY)")
+        .execute(&format!("INSERT INTO people VALUES {values}"));
+
+    // The estimate spans many pages.
+    let txn = engine.begin().unwrap();
+    assert_eq!(txn.estimate_rows("people").unwrap(), 1000);
+    assert_eq!(txn.estimate_rows("empty").unwrap(), 0);
+    assert!(txn.estimate_rows("missing").is_err());
+    drop(txn);
+
+    // Deleted rows aren't counted.
+    SqlStudentRunner::new(&engine).execute("DELETE FROM people WHERE id >= 900");
+    let txn = engine.begin().unwrap();
+    assert_eq!(txn.estimate_rows("people").unwrap(), 900);
+
+    // The optimizer's table statistics use the same counts.
+    assert_eq!(txn.table_stats("people").unwrap().row_count, 900);
+    assert!(txn.table_stats("missing").is_err());
+}
+
+#[test]
+fn test_filter_pushdown() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO t VALUES (1, 'a'), (2, 'b'), (3, 'c')");
+
+    let txn = engine.begin().unwrap();
+    let build = |sql: &str| {
+        let statement = crate::sql::parser::Parser::new(sql).parse().unwrap();
+        Plan::build(statement, &txn).unwrap()
+    };
+    let execute = |plan: Plan| {
+        let ExecutionResult::Select { rows, .. } = plan.execute(&txn).unwrap() else {
+            panic!("expected select result");
+        };
+        rows.map(|r| r.unwrap().1.to_string(None)).collect_vec()
+    };
+
+    // The predicate moves into the scan, and yields the same rows.
+    let sql = "SELECT * FROM t WHERE id > 1 AND name != 'c'";
+    let plan = build(sql).optimize().unwrap();
+    let Plan::Select(root) = &plan else {
+        panic!("expected select plan");
+    };
+    assert!(
+        matches!(
+            &**root,
+            Node::Scan {
+                filter: Some(_),
+                ..
+            }
+        ),
+        "expected filtered scan, got {root:?}"
+    );
+    assert_eq!(execute(plan), vec!["2, b"]);
+    assert_eq!(execute(build(sql)), vec!["2, b"]);
+
+    // Constant predicates and subqueries stay in the filter node.
+    for sql in [
+        "SELECT * FROM t WHERE FALSE",
+        "SELECT * FROM t WHERE id < (SELECT MAX(id) FROM t)",
+    ] {
+        let plan = build(sql).optimize().unwrap();
+        let Plan::Select(root) = &plan else {
+            panic!("expected select plan");
+        };
+        assert!(
+            matches!(&**root, Node::Filter { .. }),
+            "expected filter, got {root:?}"
+        );
+    }
+}
+
+#[test]
+fn test_scan_limited() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    let values = (0..1000).map(|i| format!("({i}, 'name {i}')")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE people (id INT PRIMARY KEY, name STRING)")
+        .execute(&format!("INSERT INTO people VALUES {values}"));
+    let txn = engine.begin().unwrap();
+    let ids =
+        |rows: Rows| -> Vec<Field> { rows.map(|r| r.unwrap().1.get_field(0).unwrap()).collect() };
+
+    // Only the rows up to the limit are returned.
+    let rows = txn.scan_limited("people", None, 3).unwrap();
+    assert_eq!(ids(rows), vec![0.into(), 1.into(), 2.into()]);
+
+    // With a filter, the limit counts matching rows, so the scan stops after
+    // id 11.
+    let filter = Expression::Not(Box::new(Expression::Equal(
+        Box::new(Expression::Column(1)),
+        Box::new(Expression::Constant("name 5".into())),
+    )));
+    let rows = txn.scan_limited("people", Some(filter), 11).unwrap();
+    let expect: Vec<Field> = (0..12).filter(|&i| i != 5).map(Field::from).collect();
+    assert_eq!(ids(rows), expect);
+
+    // A zero limit yields nothing, and a large one yields the whole table.
+    assert!(ids(txn.scan_limited("people", None, 0).unwrap()).is_empty());
+    assert_eq!(
+        ids(txn.scan_limited("people", None, 5000).unwrap()).len(),
+        1000
+    );
+}
+
+#[test]
+fn test_explain() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let values = (0..100).map(|i| format!("({i}, 'row {i}')")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name STRING)")
+        .execute(&format!("INSERT INTO t VALUES {values}"));
+
+    let mut session = Session::new(&engine);
+    let explain = |session: &mut Session<_>, sql: &str| match session.execute(sql).unwrap() {
+        StatementResult::Explain(plan) => plan,
+        result => panic!("expected explain result, got {result:?}"),
+    };
+
+    // The pushed-down filter is estimated to keep a quarter of the rows.
+    let plan = explain(&mut session, "EXPLAIN SELECT name FROM t WHERE id > 10");
+    assert_eq!(
+        plan,
+        "Projection: t.name [rows=25]\n└─ Scan: t (t.id > 10) [rows=25]"
+    );
+
+    // Joins multiply their inputs, and a limit caps the estimate.
+    let sql = "EXPLAIN SELECT a.id FROM t a JOIN t b ON a.id = b.id ORDER BY a.id LIMIT 5";
+    let plan = explain(&mut session, sql);
+    assert_eq!(
+        plan.lines().collect_vec(),
+        vec![
+            "Limit: 5 [rows=5]",
+            "└─ Order: t.id asc [rows=2500]",
+            "   └─ Projection: t.id [rows=2500]",
+            "      └─ NestedLoopJoin: inner on t.id = t.id [rows=2500]",
+            "         ├─ Scan: t as a [rows=100]",
+            "         └─ Scan: t as b [rows=100]",
+        ]
+    );
+
+    // Writes show the statement above their source, and aren't executed.
+    let plan = explain(&mut session, "EXPLAIN INSERT INTO t VALUES (100, 'x')");
+    assert_eq!(plan, "Insert: t\n└─ Values: (100, 'x') [rows=1]");
+    let plan = explain(&mut session, "EXPLAIN DELETE FROM t WHERE id < 3");
+    assert_eq!(plan, "Delete: t\n└─ Scan: t (t.id < 3) [rows=25]");
+    SqlStudentRunner::new(&engine).select_expect("SELECT COUNT(*) AS n FROM t", "n ; 100");
+}
+
+#[test]
+fn test_scan_validated() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO t VALUES (1, 'a'), (2, 'b')");
+
+    // Add columns with defaults, changing the tuple layout. Existing tuples
+    // aren't rewritten, so they're decoded with the old schema and migrated.
+    let mut altered = Table::builder()
+        .name("t")
+        .column("id", DataType::Int, false, None, None)
+        .column(
+            "score",
+            DataType::Float,
+            false,
+            Some(Field::Float(1.5)),
+            None,
+        )
+        .column("name", DataType::Text, false, None, None)
+        .primary_key("id")
+        .build();
+    let mut bonus = Column::new("bonus", DataType::Int, false, None, None);
+    bonus.set_default_expr("-1");
+    altered.add_column(&bonus);
+    engine
+        .simple
+        .begin()
+        .unwrap()
+        .update_table(altered)
+        .unwrap();
+    let txn = engine.begin().unwrap();
+    let row = Row::from(vec![
+        Field::Integer(3),
+        Field::Float(2.5),
+        Field::from("c"),
+        Field::Integer(4),
+    ]);
+    txn.insert("t", vec![row]).unwrap();
+
+    let scan = |filter: Option<Expression>| {
+        let rows: Result<Vec<_>> = txn.scan_validated("t", filter).unwrap().try_collect();
+        rows.map(|rows| {
+            rows.into_iter()
+                .map(|(_, row)| row.to_string(None))
+                .collect_vec()
+        })
+    };
+    assert_eq!(
+        scan(None).unwrap(),
+        vec!["1, 1.5, a, -1", "2, 1.5, b, -1", "3, 2.5, c, 4"]
+    );
+    let all_rids = txn
+        .scan_validated("t", None)
+        .unwrap()
+        .map(|r| r.unwrap().0)
+        .collect_vec();
+
+    // Filters apply to the migrated rows.
+    let filter = Expression::LessThan(
+        Expression::Column(1).into(),
+        Expression::Constant(Field::Float(2.0)).into(),
+    );
+    assert_eq!(
+        scan(Some(filter)).unwrap(),
+        vec!["1, 1.5, a, -1", "2, 1.5, b, -1"]
+    );
+
+    // Columns are tracked across renames and further layout changes, rather
+    // than matched by name.
+    txn.rename_column("t", "name", "title").unwrap();
+    let mut altered = txn.must_get_table("t").unwrap();
+    altered.add_column(&Column::new(
+        "extra",
+        DataType::Int,
+        true,
+        Some(Field::Null),
+        None,
+    ));
+    engine
+        .simple
+        .begin()
+        .unwrap()
+        .update_table(altered)
+        .unwrap();
+    assert_eq!(
+        scan(None).unwrap(),
+        vec![
+            "1, 1.5, a, -1, NULL",
+            "2, 1.5, b, -1, NULL",
+            "3, 2.5, c, 4, NULL"
+        ]
+    );
+    let versions = || {
+        engine
+            .simple
+            .begin()
+            .unwrap()
+            .fetch_table_versions("t")
+            .unwrap()
+    };
+    assert_eq!(versions().len(), 2);
+
+    // Versions are dropped once no stored tuple has their layout.
+    txn.delete("t", &[all_rids[0].clone(), all_rids[1].clone()])
+        .unwrap();
+    txn.vacuum("t").unwrap();
+    assert_eq!(versions().len(), 1);
+    assert_eq!(scan(None).unwrap(), vec!["3, 2.5, c, 4, NULL"]);
+
+    // A tuple that matches no schema version errors instead of decoding as
+    // garbage.
+    let tuple = Tuple::from(vec![1, 2, 3]);
+    assert!(Row::check_layout(&tuple.data, &txn.must_get_table("t").unwrap()).is_err());
+    engine.simple.begin().unwrap().insert("t", tuple).unwrap();
+    assert!(matches!(scan(None), Err(Error::InvalidData(_))));
+}
@@ -0,0 +1,366 @@
+use crate::common::Error;
+use crate::storage::tuple::Row;
+use crate::types::field::Field;
+use crate::types::{Column, ColumnChange, DataType, Table};
+
+#[test]
+fn test_column_index_lookup() {
+    let mut table = Table::builder()
+        .name("people")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .column("age", DataType::Int, true, None, None)
+        .build();
+
+    let lookup = |table: &Table, name: &str| table.field_name_to_index(Some(&name.to_string()));
+    assert_eq!(lookup(&table, "id"), Some(0));
+    assert_eq!(lookup(&table, "name"), Some(1));
+    assert_eq!(lookup(&table, "age"), Some(2));
+    assert_eq!(lookup(&table, "missing"), None);
+    assert_eq!(table.field_name_to_index(None), None);
+
+    // Added columns are indexed too.
+    table.add_column(&Column::new("email", DataType::Text, true, None, None));
+    assert_eq!(lookup(&table, "email"), Some(3));
+    assert_eq!(lookup(&table, "age"), Some(2));
+
+    // Merged schemas resolve duplicate names to the first column.
+    let merged = Table::merge(&table, &table);
+    assert_eq!(merged.col_count(), 8);
+    assert_eq!(lookup(&merged, "name"), Some(1));
+    assert_eq!(lookup(&merged, "email"), Some(3));
+}
+
+#[test]
+fn test_table_bytes_roundtrip() {
+    let table = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .column(
+            "name",
+            DataType::Text,
+            false,
+            Some(Field::from("it's")),
+            Some(32),
+        )
+        .column(
+            "price",
+            DataType::Float,
+            true,
+            Some(Field::Float(1.5)),
+            None,
+        )
+        .column("note", DataType::Text, true, None, None)
+        .column(
+            "active",
+            DataType::Bool,
+            false,
+            Some(Field::Boolean(true)),
+            None,
+        )
+        .column("count", DataType::Int, true, Some(Field::Integer(-7)), None)
+        .primary_key("id")
+        .build();
+
+    let bytes = table.to_bytes();
+    let decoded = Table::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, table);
+    assert_eq!(decoded.primary_key(), Some(0));
+    assert_eq!(decoded.get_column(3).default(), Some(&Field::Null));
+    assert_eq!(
+        decoded.field_name_to_index(Some(&"active".to_string())),
+        Some(4)
+    );
+
+    // The encoding is deterministic.
+    assert_eq!(decoded.to_bytes(), bytes);
+
+    // Default expressions round-trip.
+    let single = Table::builder()
+        .name("single")
+        .column("id", DataType::Int, false, None, None)
+        .build();
+    let mut computed = Column::new("n", DataType::Int, false, None, None);
+    computed.set_default_expr("40 + 2");
+    let mut with_expr = single.clone();
+    with_expr.add_column(&computed);
+    let decoded = Table::from_bytes(&with_expr.to_bytes()).unwrap();
+    assert_eq!(decoded, with_expr);
+    assert_eq!(
+        decoded.get_column(1).default_expr(),
+        computed.default_expr()
+    );
+
+    // An empty table round-trips too.
+    let empty = Table::new("empty");
+    assert_eq!(Table::from_bytes(&empty.to_bytes()).unwrap(), empty);
+}
+
+#[test]
+fn test_table_bytes_invalid() {
+    let table = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, true, Some(Field::from("x")), None)
+        .build();
+    let bytes = table.to_bytes();
+
+    // Every truncation errors rather than panicking.
+    for len in 0..bytes.len() {
+        assert!(
+            Table::from_bytes(&bytes[..len]).is_err(),
+            "truncated to {len} bytes"
+        );
+    }
+
+    // So do trailing bytes and unknown versions.
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(Table::from_bytes(&trailing).is_err());
+
+    let mut version = bytes.clone();
+    version[0] = 99;
+    assert!(Table::from_bytes(&version).is_err());
+}
+
+#[test]
+fn test_table_diff() {
+    let old = Table::builder()
+        .name("people")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .column("age", DataType::Int, false, None, None)
+        .build();
+    let new = Table::builder()
+        .name("people")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .column("age", DataType::Int, true, None, None)
+        .column("email", DataType::Text, true, None, None)
+        .build();
+
+    // An added column and a nullability change.
+    let diff = old.diff(&new);
+    assert_eq!(diff.added, vec![new.get_column(3).clone()]);
+    assert!(diff.dropped.is_empty());
+    assert_eq!(
+        diff.changed,
+        vec![ColumnChange {
+            name: "age".to_string(),
+            data_type: None,
+            nullable: Some(true),
+        }]
+    );
+
+    // The reverse diff drops the column and reverts the change.
+    let diff = new.diff(&old);
+    assert!(diff.added.is_empty());
+    assert_eq!(diff.dropped, vec![new.get_column(3).clone()]);
+    assert_eq!(diff.changed[0].nullable, Some(false));
+
+    // Type changes are reported with the old and new type.
+    let retyped = Table::builder()
+        .name("people")
+        .column("id", DataType::Float, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .column("age", DataType::Int, false, None, None)
+        .build();
+    let diff = old.diff(&retyped);
+    assert_eq!(
+        diff.changed[0].data_type,
+        Some((DataType::Int, DataType::Float))
+    );
+    assert!(old.diff(&old).is_empty());
+}
+
+#[test]
+fn test_row_from_tuple_columns() {
+    let table = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .column("note", DataType::Text, true, None, None)
+        .column("price", DataType::Float, false, None, None)
+        .build();
+    let row = Row::from(vec![
+        Field::Integer(7),
+        Field::from("widget"),
+        Field::from("blue"),
+        Field::Float(2.5),
+    ]);
+    let tuple = row.to_tuple(&table).unwrap();
+
+    // Only columns 0 and 2 are decoded, the second of them variable-length.
+    let decoded = Row::from_tuple_columns(tuple.clone(), &table, &[0, 2]).unwrap();
+    let expect = vec![
+        Field::Integer(7),
+        Field::Null,
+        Field::from("blue"),
+        Field::Null,
+    ];
+    assert_eq!(decoded, Row::from(expect));
+
+    // Decoding all columns matches from_tuple().
+    let all = Row::from_tuple_columns(tuple.clone(), &table, &[0, 1, 2, 3]).unwrap();
+    assert_eq!(all, row);
+
+    assert!(matches!(
+        Row::from_tuple_columns(tuple, &table, &[4]),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn test_column_nullable_default() {
+    // An explicit default is kept for nullable columns, and only a missing
+    // one falls back to NULL.
+    let status = Column::new("status", DataType::Text, true, Some("active".into()), None);
+    let note = Column::new("note", DataType::Text, true, None, None);
+    let title = Column::new("title", DataType::Text, false, None, None);
+    assert_eq!(status.default(), Some(&Field::from("active")));
+    assert_eq!(note.default(), Some(&Field::Null));
+    assert_eq!(title.default(), None);
+
+    // The column builder does the same, and accepts an explicit NULL.
+    let build = |default: Option<Field>| {
+        let builder = Column::builder()
+            .name("status".to_string())
+            .data_type(DataType::Text)
+            .nullable(true);
+        match default {
+            Some(default) => builder.default(default).build(),
+            None => builder.build(),
+        }
+    };
+    assert_eq!(
+        build(Some("active".into())).default(),
+        Some(&Field::from("active"))
+    );
+    assert_eq!(build(Some(Field::Null)).default(), Some(&Field::Null));
+    assert_eq!(build(None).default(), Some(&Field::Null));
+
+    // Defaults survive a schema roundtrip.
+    let table = Table::builder()
+        .name("tasks")
+        .column("id", DataType::Int, false, None, None)
+        .column_from_definition(status)
+        .column_from_definition(note)
+        .build();
+    let decoded = Table::from_bytes(&table.to_bytes()).unwrap();
+    assert_eq!(
+        decoded.get_column(1).default(),
+        Some(&Field::from("active"))
+    );
+    assert_eq!(decoded.get_column(2).default(), Some(&Field::Null));
+}
+
+#[test]
+fn test_table_merge_offsets() {
+    let left = Table::builder()
+        .name("people")
+        .column("id", DataType::Int, false, None, None)
+        .column("name", DataType::Text, false, None, None)
+        .column("email", DataType::Text, true, None, None)
+        .build();
+    let right = Table::builder()
+        .name("pets")
+        .column("name", DataType::Text, false, None, None)
+        .column("weight", DataType::Float, false, None, None)
+        .column("kind", DataType::Text, false, None, None)
+        .build();
+    let merged = Table::merge(&left, &right);
+
+    // Variable-length columns are renumbered in merged order, and fixed-length
+    // columns get byte offsets past the left table's.
+    let offsets = (0..merged.col_count())
+        .map(|i| merged.get_column(i).stored_offset())
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0, 0, 1, 2, 4, 3]);
+    assert_eq!(merged.variable_length_fields(), 4);
+    assert_eq!(
+        merged.fixed_field_size_bytes(),
+        DataType::Int.length_bytes() + DataType::Float.length_bytes()
+    );
+
+    // Rows roundtrip through tuples encoded with the merged schema.
+    let row = Row::from(vec![
+        Field::Integer(1),
+        Field::from("alice"),
+        Field::from("alice@example.com"),
+        Field::from("rex"),
+        Field::Float(12.5),
+        Field::from("dog"),
+    ]);
+    let tuple = row.to_tuple(&merged).unwrap();
+    assert_eq!(Row::from_tuple(tuple, &merged).unwrap(), row);
+}
+
+#[test]
+fn test_table_validate_row() {
+    let table = Table::builder()
+        .name("items")
+        .column("id", DataType::Int, false, None, None)
+        .column("price", DataType::Float, true, None, None)
+        .build();
+    let validate = |values: Vec<Field>| table.validate_row(&Row::from(values));
+
+    assert!(validate(vec![Field::Integer(1), Field::Float(1.5)]).is_ok());
+    assert!(validate(vec![Field::Integer(1), Field::Null]).is_ok());
+    assert!(matches!(
+        validate(vec![Field::Integer(1), Field::Integer(2)]),
+        Err(Error::InvalidInput(message)) if message == "invalid value 2 for float column price"
+    ));
+    assert!(matches!(
+        validate(vec![Field::Integer(1)]),
+        Err(Error::InvalidInput(message)) if message == "expected 2 columns, got 1"
+    ));
+}
+
+#[test]
+fn test_table_primary_key_values() {
+    let table = Table::builder()
+        .name("people")
+        .column("name", DataType::Text, false, None, None)
+        .column("id", DataType::Int, false, None, None)
+        .primary_key("id")
+        .build();
+    let row = Row::from(vec![Field::from("alice"), Field::Integer(7)]);
+    assert_eq!(
+        table.primary_key_values(&row).unwrap(),
+        vec![Field::Integer(7)]
+    );
+    assert!(matches!(
+        table.primary_key_values(&Row::from(vec![Field::from("alice"), Field::Null])),
+        Err(Error::InvalidInput(message)) if message == "NULL primary key value for column id"
+    ));
+
+    // A composite key yields the key values in column order.
+    let mut columns = vec![
+        Column::new("region", DataType::Text, false, None, None),
+        Column::new("price", DataType::Float, true, None, None),
+        Column::new("sku", DataType::Int, false, None, None),
+    ];
+    columns[0].set_primary_key(true);
+    columns[2].set_primary_key(true);
+    let table = Table::builder().name("items").columns(columns).build();
+    let row = Row::from(vec![
+        Field::from("eu"),
+        Field::Float(1.5),
+        Field::Integer(3),
+    ]);
+    assert_eq!(
+        table.primary_key_values(&row).unwrap(),
+        vec![Field::from("eu"), Field::Integer(3)]
+    );
+    let row = Row::from(vec![Field::from("eu"), Field::Float(1.5), Field::Null]);
+    assert!(table.primary_key_values(&row).is_err());
+
+    let table = Table::builder()
+        .name("plain")
+        .column("id", DataType::Int, false, None, None)
+        .build();
+    assert!(matches!(
+        table.primary_key_values(&Row::from(vec![Field::Integer(1)])),
+        Err(Error::InvalidInput(message)) if message == "table plain has no primary key"
+    ));
+}
@@ -0,0 +1,128 @@
+use crate::sql::engine::Local;
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+
+#[test]
+fn test_correlated_scalar_subquery() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Each outer row yields the scalar computed from its own matches, or NULL
+    // if the subquery doesn't yield any rows.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE orders (id INT PRIMARY KEY, customer INT, amount INT)")
+        .execute("CREATE TABLE customers (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO customers VALUES (1, 'alice'), (2, 'bob'), (3, 'carol')")
+        .execute("INSERT INTO orders VALUES (1, 1, 10), (2, 1, 30), (3, 2, 5)")
+        .select_expect(
+            "SELECT name, (SELECT MAX(amount) FROM orders WHERE orders.customer = customers.id) \
+             FROM customers",
+            "customers.name, ; alice, 30 ; bob, 5 ; carol, NULL",
+        );
+}
+
+#[test]
+fn test_not_exists() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Only customers without any matching orders are kept, and other WHERE
+    // conditions still apply.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE orders (id INT PRIMARY KEY, customer INT, amount INT)")
+        .execute("CREATE TABLE customers (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO customers VALUES (1, 'alice'), (2, 'bob'), (3, 'carol'), (4, 'dave')")
+        .execute("INSERT INTO orders VALUES (1, 1, 10), (2, 1, 30), (3, 2, 5)")
+        .select_expect(
+            "SELECT name FROM customers \
+             WHERE NOT EXISTS (SELECT 1 FROM orders WHERE orders.customer = customers.id)",
+            "customers.name ; carol ; dave",
+        )
+        .select_expect(
+            "SELECT name FROM customers WHERE id > 3 AND \
+             NOT EXISTS (SELECT 1 FROM orders WHERE orders.customer = customers.id)",
+            "customers.name ; dave",
+        )
+        .select_expect(
+            "SELECT name FROM customers WHERE NOT EXISTS \
+             (SELECT 1 FROM orders WHERE orders.customer = customers.id AND amount > 20)",
+            "customers.name ; bob ; carol ; dave",
+        );
+
+    // EXISTS can't be used outside of WHERE conditions.
+    let mut session = engine.session();
+    assert!(session
+        .execute("SELECT NOT EXISTS (SELECT 1 FROM orders) FROM customers")
+        .is_err());
+}
+
+#[test]
+fn test_exists() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // EXISTS and NOT EXISTS over the same correlated data are complementary.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE orders (id INT PRIMARY KEY, customer INT, amount INT)")
+        .execute("CREATE TABLE customers (id INT PRIMARY KEY, name STRING)")
+        .execute("INSERT INTO customers VALUES (1, 'alice'), (2, 'bob'), (3, 'carol')")
+        .execute("INSERT INTO orders VALUES (1, 1, 10), (2, 1, 30), (3, 3, 5)")
+        .select_expect(
+            "SELECT name FROM customers \
+             WHERE EXISTS (SELECT 1 FROM orders WHERE orders.customer = customers.id)",
+            "customers.name ; alice ; carol",
+        )
+        .select_expect(
+            "SELECT name FROM customers \
+             WHERE NOT EXISTS (SELECT 1 FROM orders WHERE orders.customer = customers.id)",
+            "customers.name ; bob",
+        )
+        // The subquery's SELECT list isn't evaluated, so the division by zero
+        // for alice's order with amount 10 doesn't error.
+        .select_expect(
+            "SELECT name FROM customers WHERE EXISTS \
+             (SELECT 1 / (amount - 10) FROM orders WHERE orders.customer = customers.id \
+              ORDER BY amount)",
+            "customers.name ; alice ; carol",
+        );
+}
+
+#[test]
+fn test_uncorrelated_scalar_subquery() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // The subquery doesn't reference the outer row, so it's evaluated once and
+    // each row is compared against the cached average of 20.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE orders (id INT PRIMARY KEY, customer INT, amount FLOAT)")
+        .execute("CREATE TABLE customers (id INT PRIMARY KEY, name STRING, budget FLOAT)")
+        .execute(
+            "INSERT INTO customers VALUES \
+             (1, 'alice', 10.0), (2, 'bob', 25.0), (3, 'carol', 40.0)",
+        )
+        .execute("INSERT INTO orders VALUES (1, 1, 10.0), (2, 1, 30.0), (3, 2, 20.0)")
+        .select_expect(
+            "SELECT name FROM customers WHERE budget > (SELECT AVG(amount) FROM orders)",
+            "customers.name ; bob ; carol",
+        )
+        .select_expect(
+            "SELECT name FROM customers \
+             WHERE budget > (SELECT AVG(amount) FROM orders) AND id < 3",
+            "customers.name ; bob",
+        )
+        // An empty subquery yields NULL, which doesn't match any rows.
+        .select_expect(
+            "SELECT name FROM customers \
+             WHERE budget > (SELECT amount FROM orders WHERE amount > 100.0)",
+            "customers.name",
+        );
+
+    // The subquery must yield a single value.
+    let mut session = engine.session();
+    assert!(session
+        .execute("SELECT name FROM customers WHERE budget > (SELECT amount FROM orders)")
+        .is_err());
+    assert!(session
+        .execute("SELECT name FROM customers WHERE budget > (SELECT amount, id FROM orders)")
+        .is_err());
+}
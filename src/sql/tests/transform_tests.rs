@@ -0,0 +1,461 @@
+use crate::common::Error;
+use crate::sql::engine::{Catalog, Engine, Local, Session, StatementResult, Transaction};
+use crate::sql::execution::transform;
+use crate::sql::execution::ExecutionResult;
+use crate::sql::parser::Parser;
+use crate::sql::planner::{Direction, Expression, Node, NullOrder, Plan};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::storage::page::INVALID_RID;
+use crate::storage::tuple::{Row, Rows};
+use crate::types::field::Field;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+#[test]
+fn test_inspect() {
+    let items = vec![
+        Ok((INVALID_RID, Row::from(vec![Field::Integer(1)]))),
+        Err(Error::InvalidData("boom".to_string())),
+        Ok((INVALID_RID, Row::from(vec![Field::Integer(2)]))),
+    ];
+    let source: Rows = Box::new(items.clone().into_iter());
+
+    // The closure sees every successful row, but not errors.
+    let seen = Rc::new(Cell::new(0));
+    let rows = transform::inspect(source, {
+        let seen = seen.clone();
+        move |(_, row)| {
+            assert_ne!(row.size(), 0);
+            seen.set(seen.get() + 1)
+        }
+    });
+    let output: Vec<_> = rows.collect();
+    assert_eq!(seen.get(), 2);
+
+    // The stream itself is unchanged, including the error.
+    assert_eq!(output, items);
+}
+
+#[test]
+fn test_order_mixed_directions() {
+    let rows = [(1, 1), (2, 5), (1, 3), (2, 2), (1, 2)];
+    let source: Rows = Box::new(rows.into_iter().map(|(a, b)| {
+        Ok((
+            INVALID_RID,
+            Row::from(vec![Field::Integer(a), Field::Integer(b)]),
+        ))
+    }));
+
+    // Ties in the first key are broken by the second key, in its own direction.
+    let order = vec![
+        (Expression::Column(0), Direction::Ascending, None),
+        (Expression::Column(1), Direction::Descending, None),
+    ];
+    let ordered: Vec<_> = transform::order(source, order)
+        .unwrap()
+        .map(|item| {
+            let (_, row) = item.unwrap();
+            (row.get_field(0).unwrap(), row.get_field(1).unwrap())
+        })
+        .collect();
+    let expect: Vec<_> = [(1, 3), (1, 2), (1, 1), (2, 5), (2, 2)]
+        .into_iter()
+        .map(|(a, b)| (Field::Integer(a), Field::Integer(b)))
+        .collect();
+    assert_eq!(ordered, expect);
+}
+
+#[test]
+fn test_order_nulls() {
+    // NULLIF() yields NULL for scores of 0, since stored NULLs aren't supported.
+    let setup = |runner: &mut SqlStudentRunner| {
+        runner
+            .execute("CREATE TABLE scores (id INT PRIMARY KEY, score INT)")
+            .execute("INSERT INTO scores VALUES (1, 20), (2, 0), (3, 10)");
+    };
+
+    // By default, NULLs sort as the smallest value.
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .bind(setup)
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s",
+            "scores.id, s ; 2, NULL ; 3, 10 ; 1, 20",
+        )
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s DESC",
+            "scores.id, s ; 1, 20 ; 3, 10 ; 2, NULL",
+        )
+        // NULLS FIRST and LAST apply regardless of direction.
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s NULLS LAST",
+            "scores.id, s ; 3, 10 ; 1, 20 ; 2, NULL",
+        )
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s DESC NULLS FIRST",
+            "scores.id, s ; 2, NULL ; 1, 20 ; 3, 10",
+        );
+
+    // The engine default applies to keys without an explicit placement, in
+    // both directions, while explicit placements still win.
+    let engine = Local::new(create_storage_engine()).with_null_order(NullOrder::Last);
+    SqlStudentRunner::new(&engine)
+        .bind(setup)
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s",
+            "scores.id, s ; 3, 10 ; 1, 20 ; 2, NULL",
+        )
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s DESC",
+            "scores.id, s ; 1, 20 ; 3, 10 ; 2, NULL",
+        )
+        .select_expect(
+            "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s NULLS FIRST",
+            "scores.id, s ; 2, NULL ; 3, 10 ; 1, 20",
+        );
+
+    let mut session = engine.session();
+    assert!(session
+        .execute("SELECT id FROM scores ORDER BY id NULLS MIDDLE")
+        .is_err());
+}
+
+#[test]
+fn test_session_variables() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE scores (id INT PRIMARY KEY, score INT)")
+        .execute("INSERT INTO scores VALUES (1, 20), (2, 0), (3, 10)");
+
+    let mut session = engine.session();
+    let ids = |session: &mut Session<_>, sql: &str| {
+        let StatementResult::Select { rows, .. } = session.execute(sql).unwrap() else {
+            panic!("expected select result");
+        };
+        rows.iter()
+            .map(|row| row.get_field(0).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let sql = "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s";
+    assert_eq!(ids(&mut session, sql), vec![2.into(), 3.into(), 1.into()]);
+    assert_eq!(session.get("default_null_order"), None);
+
+    // The session's default NULL placement applies to subsequent statements,
+    // while explicit placements still win.
+    session.set("default_null_order", "LAST".into()).unwrap();
+    assert_eq!(session.get("default_null_order"), Some(&"LAST".into()));
+    assert_eq!(ids(&mut session, sql), vec![3.into(), 1.into(), 2.into()]);
+    let first = "SELECT id, NULLIF(score, 0) AS s FROM scores ORDER BY s NULLS FIRST";
+    assert_eq!(ids(&mut session, first), vec![2.into(), 3.into(), 1.into()]);
+
+    // Other sessions aren't affected.
+    assert_eq!(
+        ids(&mut engine.session(), sql),
+        vec![2.into(), 3.into(), 1.into()]
+    );
+
+    // NULL resets the variable to the engine default.
+    session.set("default_null_order", Field::Null).unwrap();
+    assert_eq!(session.get("default_null_order"), None);
+    assert_eq!(ids(&mut session, sql), vec![2.into(), 3.into(), 1.into()]);
+
+    // Invalid values and unknown variables error.
+    assert!(session.set("default_null_order", "middle".into()).is_err());
+    assert!(session.set("default_null_order", 1.into()).is_err());
+    assert!(session.set("collation", "binary".into()).is_err());
+    assert_eq!(session.get("collation"), None);
+}
+
+#[test]
+fn test_order_by_mixed_directions() {
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE scores (id INT PRIMARY KEY, team STRING, points INT)")
+        .execute(
+            "INSERT INTO scores VALUES (1, 'red', 10), (2, 'blue', 7), (3, 'red', 30), \
+             (4, 'blue', 9), (5, 'red', 20)",
+        )
+        .select_expect(
+            "SELECT team, points FROM scores ORDER BY team ASC, points DESC",
+            "scores.team, scores.points ; blue, 9 ; blue, 7 ; red, 30 ; red, 20 ; red, 10",
+        )
+        .select_expect(
+            "SELECT team, points FROM scores ORDER BY team DESC, points",
+            "scores.team, scores.points ; red, 10 ; red, 20 ; red, 30 ; blue, 7 ; blue, 9",
+        )
+        // Keys after the deciding one don't affect the order.
+        .select_expect(
+            "SELECT id FROM scores ORDER BY points DESC, team ASC, id DESC",
+            "scores.id ; 3 ; 5 ; 1 ; 4 ; 2",
+        );
+}
+
+#[test]
+fn test_filter_constant_predicate() {
+    let rows = vec![
+        Ok((INVALID_RID, Row::from(vec![Field::Integer(1)]))),
+        Ok((INVALID_RID, Row::from(vec![Field::Integer(2)]))),
+    ];
+    let pulled = Rc::new(Cell::new(0));
+    let source = || -> Rows {
+        let pulled = pulled.clone();
+        transform::inspect(Box::new(rows.clone().into_iter()), move |_| {
+            pulled.set(pulled.get() + 1)
+        })
+    };
+
+    // A constant false or NULL predicate emits nothing, without pulling rows.
+    for value in [Field::Boolean(false), Field::Null] {
+        let output: Vec<_> =
+            transform::filter(source(), Expression::Constant(value), None).collect();
+        assert!(output.is_empty());
+    }
+    assert_eq!(pulled.get(), 0);
+
+    // A constant true predicate passes the rows through.
+    let output: Vec<_> =
+        transform::filter(source(), Expression::Constant(true.into()), None).collect();
+    assert_eq!(output, rows);
+    assert_eq!(pulled.get(), 2);
+
+    // WHERE FALSE yields an empty result.
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY)")
+        .execute("INSERT INTO t VALUES (1), (2)")
+        .select_expect("SELECT id FROM t WHERE FALSE", "t.id")
+        .select_expect("SELECT id FROM t WHERE TRUE", "t.id ; 1 ; 2");
+}
+
+#[test]
+fn test_sample() {
+    let source = || -> Rows {
+        Box::new((0..1000).map(|i| Ok((INVALID_RID, Row::from(vec![Field::Integer(i)])))))
+    };
+    let sample = |size, seed| -> Vec<i32> {
+        transform::sample(source(), size, seed)
+            .unwrap()
+            .map(|item| match item.unwrap().1.get_field(0).unwrap() {
+                Field::Integer(i) => i,
+                value => panic!("unexpected value {value}"),
+            })
+            .collect()
+    };
+
+    // A fixed seed yields the same sample of distinct rows, in source order.
+    let rows = sample(10, 42);
+    assert_eq!(rows, vec![61, 125, 199, 540, 544, 575, 725, 755, 869, 952]);
+    assert_eq!(sample(10, 42), rows);
+    assert_ne!(sample(10, 7), rows);
+
+    // Samples at least as large as the source emit all rows.
+    assert_eq!(sample(1000, 1), (0..1000).collect::<Vec<_>>());
+    assert_eq!(sample(5000, 1).len(), 1000);
+    assert!(sample(0, 1).is_empty());
+
+    // SAMPLE applies to the query result, and REPEATABLE fixes the seed.
+    let engine = Local::new(create_storage_engine());
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY)")
+        .execute("INSERT INTO t VALUES (1), (2), (3), (4), (5), (6)")
+        .select_expect(
+            "SELECT id FROM t SAMPLE 3 REPEATABLE (42)",
+            "t.id ; 1 ; 3 ; 5",
+        )
+        .select_expect(
+            "SELECT id FROM t WHERE id > 3 SAMPLE 10",
+            "t.id ; 4 ; 5 ; 6",
+        );
+}
+
+/// Sets up a table with the given number of rows for the scan-filter-project
+/// tests, and returns the engine.
+fn scan_filter_project_engine(rows: usize) -> Local<impl crate::storage::Engine> {
+    let engine = Local::new(create_storage_engine());
+    let values = (0..rows)
+        .map(|i| format!("({i}, 'row {i}')"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE t (id INT PRIMARY KEY, name STRING)")
+        .execute(&format!("INSERT INTO t VALUES {values}"));
+    engine
+}
+
+/// Executes the SELECT plan, returning its rows.
+fn select_rows(plan: Plan, txn: &(impl Transaction + Catalog)) -> Vec<Row> {
+    let ExecutionResult::Select { rows, .. } = plan.execute(txn).unwrap() else {
+        panic!("expected select result");
+    };
+    rows.map(|r| r.unwrap().1).collect()
+}
+
+/// Executes a scan, filter, and projection as separate boxed iterators.
+fn scan_filter_project_boxed(
+    txn: &impl Transaction,
+    predicate: &Expression,
+    expressions: &[Expression],
+) -> Vec<Row> {
+    let rows = txn.scan("t", None).unwrap();
+    let rows = transform::filter(rows, predicate.clone(), None);
+    transform::project(rows, expressions.to_vec())
+        .map(|r| r.unwrap().1)
+        .collect()
+}
+
+#[test]
+fn test_fused_scan_filter_project() {
+    let engine = scan_filter_project_engine(300);
+    let txn = engine.begin().unwrap();
+    let sql = "SELECT id * 2, name FROM t WHERE id % 3 = 0";
+    let plan = Plan::build(Parser::new(sql).parse().unwrap(), &txn).unwrap();
+
+    // The unoptimized plan is a projection of a filtered scan, and the
+    // optimizer pushes the filter into the scan. Both are fused, and yield the
+    // same rows as executing each node separately.
+    let Plan::Select(node) = &plan else {
+        panic!("expected select plan")
+    };
+    let Node::Projection {
+        source,
+        expressions,
+        ..
+    } = &*node.inner
+    else {
+        panic!("expected projection");
+    };
+    let Node::Filter { predicate, .. } = &*source.inner else {
+        panic!("expected filter")
+    };
+    let expect = scan_filter_project_boxed(&txn, predicate, expressions);
+    assert_eq!(expect.len(), 100);
+    assert_eq!(
+        expect[1],
+        Row::from(vec![Field::Integer(6), Field::from("row 3")])
+    );
+
+    assert_eq!(select_rows(plan.clone(), &txn), expect);
+    assert_eq!(select_rows(plan.optimize().unwrap(), &txn), expect);
+}
+
+/// Compares the fused scan-filter-project pipeline with the boxed one. Run
+/// with: cargo test --release bench_fused_scan_filter_project -- --ignored --nocapture
+#[test]
+#[ignore]
+fn bench_fused_scan_filter_project() {
+    let engine = scan_filter_project_engine(5000);
+    let txn = engine.begin().unwrap();
+    let sql = "SELECT id * 2, name FROM t WHERE id % 3 = 0";
+    let plan = Plan::build(Parser::new(sql).parse().unwrap(), &txn).unwrap();
+    let Plan::Select(node) = &plan else {
+        panic!("expected select plan")
+    };
+    let Node::Projection {
+        source,
+        expressions,
+        ..
+    } = &*node.inner
+    else {
+        panic!("expected projection");
+    };
+    let Node::Filter { predicate, .. } = &*source.inner else {
+        panic!("expected filter")
+    };
+
+    const ITERATIONS: u32 = 50;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        scan_filter_project_boxed(&txn, predicate, expressions);
+    }
+    let boxed = start.elapsed() / ITERATIONS;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        select_rows(plan.clone(), &txn);
+    }
+    let fused = start.elapsed() / ITERATIONS;
+    println!("boxed: {boxed:?} per query, fused: {fused:?} per query");
+}
+
+#[test]
+fn test_project_columns_in_place() {
+    let rows = (0..3).map(|i| {
+        Row::from(vec![
+            Field::Integer(i),
+            Field::from(format!("row {i}")),
+            Field::Float(i as f32),
+        ])
+    });
+    let source = || -> Rows { Box::new(rows.clone().map(|row| Ok((INVALID_RID, row)))) };
+
+    // A reordering with duplicates uses the in-place path, and yields the same
+    // rows as the general path for computed expressions (here, adding 0).
+    let columns = vec![
+        Expression::Column(2),
+        Expression::Column(0),
+        Expression::Column(2),
+    ];
+    let computed = vec![
+        Expression::Column(2),
+        Expression::Add(
+            Box::new(Expression::Column(0)),
+            Box::new(Expression::Constant(Field::Integer(0))),
+        ),
+        Expression::Column(2),
+    ];
+    let in_place: Vec<_> = transform::project(source(), columns)
+        .map(|r| r.unwrap().1)
+        .collect();
+    let general: Vec<_> = transform::project(source(), computed)
+        .map(|r| r.unwrap().1)
+        .collect();
+    assert_eq!(in_place, general);
+    assert_eq!(
+        in_place[1],
+        Row::from(vec![
+            Field::Float(1.0),
+            Field::Integer(1),
+            Field::Float(1.0)
+        ])
+    );
+
+    // A subset of the columns.
+    let subset: Vec<_> = transform::project(source(), vec![Expression::Column(1)])
+        .map(|r| r.unwrap().1)
+        .collect();
+    assert_eq!(subset[2], Row::from(vec![Field::from("row 2")]));
+
+    // Out of bounds column references error.
+    let mut output = transform::project(source(), vec![Expression::Column(3)]);
+    assert!(matches!(output.next(), Some(Err(Error::OutOfBounds))));
+}
+
+#[test]
+fn test_remap() {
+    let source = || -> Rows {
+        let row = Row::from(vec![Field::Integer(1), "a".into(), Field::Boolean(true)]);
+        Box::new(std::iter::once(Ok((INVALID_RID, row))))
+    };
+    let remap = |targets: Vec<Option<usize>>| -> Result<Vec<Row>, Error> {
+        transform::remap(source(), targets)?
+            .map(|r| r.map(|(_, row)| row))
+            .collect()
+    };
+
+    // Columns are moved to their targets, and unmapped targets are NULL.
+    assert_eq!(
+        remap(vec![Some(3), None, Some(0)]).unwrap(),
+        vec![Row::from(vec![
+            Field::Boolean(true),
+            Field::Null,
+            Field::Null,
+            Field::Integer(1)
+        ])]
+    );
+
+    // Colliding targets error rather than overwriting each other.
+    let Err(Error::InvalidInput(message)) = remap(vec![Some(1), Some(0), Some(1)]) else {
+        panic!("expected colliding targets to error");
+    };
+    assert_eq!(message, "multiple columns remapped to column 1");
+}
@@ -0,0 +1,208 @@
+use crate::common::Error;
+use crate::sql::engine::{Catalog, Engine, Local, StatementResult};
+use crate::sql::planner::{Expression, Node, Plan};
+use crate::sql::tests::utility::{create_storage_engine, SqlStudentRunner};
+use crate::types::field::Field;
+use itertools::Itertools as _;
+
+#[test]
+fn test_update_from_join() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+
+    // Only the accounts with a matching bonus are updated, using values from
+    // the joined table, and the joined table itself is left unchanged.
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE accounts (id INT PRIMARY KEY, owner STRING, balance INT)")
+        .execute("CREATE TABLE bonuses (id INT PRIMARY KEY, account INT, amount INT)")
+        .execute("INSERT INTO accounts VALUES (1, 'alice', 100), (2, 'bob', 50), (3, 'carol', 10)")
+        .execute("INSERT INTO bonuses VALUES (1, 3, 5), (2, 1, 20)")
+        .execute(
+            "UPDATE accounts SET balance = balance + amount FROM bonuses \
+             WHERE bonuses.account = accounts.id",
+        )
+        .select_expect(
+            "SELECT * FROM accounts",
+            "accounts.id, accounts.owner, accounts.balance ; 1, alice, 120 ; 2, bob, 50 ; 3, carol, 15",
+        )
+        .select_expect(
+            "SELECT * FROM bonuses",
+            "bonuses.id, bonuses.account, bonuses.amount ; 1, 3, 5 ; 2, 1, 20",
+        )
+        // The joined table can also be filtered on, and aliased.
+        .execute(
+            "UPDATE accounts SET owner = 'big' FROM bonuses b \
+             WHERE b.account = accounts.id AND b.amount > 10",
+        )
+        .select_expect(
+            "SELECT * FROM accounts ORDER BY id",
+            "accounts.id, accounts.owner, accounts.balance ; 1, big, 120 ; 2, bob, 50 ; 3, carol, 15",
+        );
+
+    // SET columns must belong to the updated table.
+    let mut session = engine.session();
+    assert!(session
+        .execute("UPDATE accounts SET amount = 1 FROM bonuses WHERE bonuses.account = accounts.id")
+        .is_err());
+}
+
+#[test]
+fn test_update_from_reorder_joins() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE accounts (id INT PRIMARY KEY, balance INT)")
+        .execute("CREATE TABLE bonuses (id INT PRIMARY KEY, account INT, amount INT)")
+        .execute("INSERT INTO accounts VALUES (1, 10), (2, 20), (3, 30), (4, 40), (5, 50), (6, 60)")
+        .execute("INSERT INTO bonuses VALUES (1, 2, 1), (2, 4, 2), (3, 6, 3)");
+
+    // Join reordering doesn't change which accounts are updated.
+    let mut session = engine.session();
+    session.set("reorder_joins", Field::Boolean(true)).unwrap();
+    let result = session
+        .execute(
+            "UPDATE accounts SET balance = balance + amount FROM bonuses \
+             WHERE bonuses.account = accounts.id",
+        )
+        .unwrap();
+    assert_eq!(result, StatementResult::Update { count: 3 });
+    drop(session);
+
+    // The filtered accounts are estimated to be fewer than the bonuses, but
+    // the hash join must keep the accounts on the left to overwrite their
+    // record IDs, so it isn't reordered.
+    let txn = engine.begin().unwrap();
+    let accounts = txn.must_get_table("accounts").unwrap();
+    let bonuses = Node::Scan {
+        table: txn.must_get_table("bonuses").unwrap(),
+        filter: None,
+        alias: None,
+    };
+    let plan = Plan::Update {
+        table: accounts.clone(),
+        source: Node::HashJoin {
+            left: Node::Scan {
+                table: accounts,
+                filter: Some(Expression::GreaterThan(
+                    Expression::Column(0).into(),
+                    Expression::Constant(Field::Integer(0)).into(),
+                )),
+                alias: None,
+            }
+            .into(),
+            left_column: 0,
+            right: bonuses.into(),
+            right_column: 1,
+            outer: false,
+            epsilon: None,
+        }
+        .into(),
+        expressions: vec![(
+            1,
+            Expression::Add(Expression::Column(1).into(), Expression::Column(4).into()),
+        )],
+    };
+    assert_eq!(plan.clone().reorder_joins(&txn).unwrap(), plan);
+    plan.reorder_joins(&txn).unwrap().execute(&txn).unwrap();
+    drop(txn);
+
+    SqlStudentRunner::new(&engine)
+        .select_expect(
+            "SELECT * FROM accounts ORDER BY id",
+            "accounts.id, accounts.balance ; 1, 10 ; 2, 22 ; 3, 30 ; 4, 44 ; 5, 50 ; 6, 66",
+        )
+        .select_expect(
+            "SELECT * FROM bonuses",
+            "bonuses.id, bonuses.account, bonuses.amount ; 1, 2, 1 ; 2, 4, 2 ; 3, 6, 3",
+        );
+}
+
+#[test]
+fn test_update_not_null() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE accounts (id INT PRIMARY KEY, owner STRING NOT NULL, balance INT)")
+        .execute("INSERT INTO accounts VALUES (1, 'alice', 100), (2, 'bob', 50)");
+
+    // Setting a NOT NULL column to NULL errors, whether given directly or
+    // computed, and leaves the rows unchanged.
+    let mut session = engine.session();
+    for sql in [
+        "UPDATE accounts SET owner = NULL WHERE id = 1",
+        "UPDATE accounts SET owner = NULLIF(owner, 'bob')",
+    ] {
+        assert!(matches!(
+            session.execute(sql),
+            Err(Error::InvalidInput(message)) if message == "column owner can't be NULL"
+        ));
+    }
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT * FROM accounts ORDER BY id",
+        "accounts.id, accounts.owner, accounts.balance ; 1, alice, 100 ; 2, bob, 50",
+    );
+}
+
+#[test]
+fn test_update_compound_assignment() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE counters (id INT PRIMARY KEY, counter INT, ratio FLOAT)")
+        .execute("INSERT INTO counters VALUES (1, 10, 1.0), (2, 2147483646, 2.0)")
+        .execute("UPDATE counters SET counter += 1, ratio *= 2 WHERE id = 1")
+        .execute("UPDATE counters SET counter -= 5 WHERE id = 1")
+        .execute("UPDATE counters SET counter %= 4, ratio /= 4 WHERE id = 1")
+        .select_expect(
+            "SELECT * FROM counters ORDER BY id",
+            "counters.id, counters.counter, counters.ratio ; 1, 2, 0.5 ; 2, 2147483646, 2",
+        )
+        // The near-max counter can still be incremented once.
+        .execute("UPDATE counters SET counter += 1 WHERE id = 2");
+
+    // Overflowing the integer column errors instead of panicking, and leaves
+    // the rows unchanged.
+    let mut session = engine.session();
+    for sql in [
+        "UPDATE counters SET counter += 1",
+        "UPDATE counters SET counter = counter * 2 WHERE id = 2",
+    ] {
+        assert!(matches!(session.execute(sql), Err(Error::OverflowError)));
+    }
+    assert!(session
+        .execute("UPDATE counters SET counter += DEFAULT")
+        .is_err());
+    SqlStudentRunner::new(&engine).select_expect(
+        "SELECT counter FROM counters ORDER BY id",
+        "counters.counter ; 2 ; 2147483647",
+    );
+}
+
+#[test]
+fn test_update_many_rows() {
+    let storage_engine = create_storage_engine();
+    let engine = Local::new(storage_engine);
+    let values = (0..1000).map(|i| format!("({i}, {i})")).join(", ");
+    SqlStudentRunner::new(&engine)
+        .execute("CREATE TABLE counters (id INT PRIMARY KEY, counter INT)")
+        .execute(&format!("INSERT INTO counters VALUES {values}"));
+
+    // Updating every row spans many pages, and all of them are written.
+    let mut session = engine.session();
+    assert!(matches!(
+        session.execute("UPDATE counters SET counter = counter + 1000"),
+        Ok(StatementResult::Update { count: 1000 })
+    ));
+    SqlStudentRunner::new(&engine)
+        .select_expect(
+            "SELECT COUNT(*) AS n, MIN(counter) AS lo, MAX(counter) AS hi FROM counters \
+             WHERE counter = id + 1000",
+            "n, lo, hi ; 1000, 1000, 1999",
+        )
+        // A subset of the rows can be batched too, leaving the others alone.
+        .execute("UPDATE counters SET counter = 0 WHERE id >= 500")
+        .select_expect(
+            "SELECT COUNT(*) AS n, SUM(counter) AS total FROM counters WHERE counter = 0",
+            "n, total ; 500, 0",
+        );
+}
@@ -87,9 +87,18 @@ pub fn create_storage_engine() -> HeapTableManager {
     HeapTableManager::new(&bpm)
 }
 
+/// Drains the rows into a grid of field values, for concise assertions.
+/// Returns the first error, if any.
+#[cfg(test)]
+pub fn collect_into_table(
+    rows: crate::storage::tuple::Rows,
+) -> crate::common::Result<Vec<Vec<crate::types::field::Field>>> {
+    rows.map_ok(|(_, row)| row.into_iter().collect()).collect()
+}
+
 pub fn handle(result: StatementResult, expected: &str) {
     match result {
-        StatementResult::Select { columns, rows } => {
+        StatementResult::Select { columns, rows, .. } => {
             let lines = expected.split(";").map(&str::trim).collect::<Vec<&str>>();
             let (expected_columns, expected_rows) = lines.split_at(1);
             let expected_rows: Vec<&str> = expected_rows.iter().filter(|row| !row.is_empty()).cloned().collect();
@@ -4,6 +4,17 @@ use crate::storage::tuple::Tuple;
 use crate::types::Table;
 use serde::{Deserialize, Serialize};
 
+/// An earlier schema of a table, see Engine::get_table_versions().
+#[derive(Clone, Debug)]
+pub struct TableVersion {
+    /// The earlier schema, which some stored tuples still have the layout of.
+    pub schema: Table,
+    /// For each column of the current schema, the index of the same column in
+    /// the earlier schema, or None if it didn't exist then. Columns are tracked
+    /// across renames and layout changes.
+    pub columns: Vec<Option<usize>>,
+}
+
 pub struct Key<'a> {
     pub table_name: &'a str,
     pub record_id: &'a RecordId,
@@ -39,6 +50,23 @@ pub trait Engine: Send {
     /// Gets a table with the given table name.
     fn get_table(&mut self, table_name: &str) -> Result<Option<Table>>;
 
+    /// Replaces the schema of the table with the same name. Stored tuples
+    /// aren't rewritten, so if the schema changes their layout, they can only
+    /// be decoded with the replaced schema, see get_table_versions(). Columns
+    /// are matched with the replaced schema's by name, so renames must use
+    /// rename_column() instead. Errors if the table doesn't exist.
+    fn update_table(&mut self, table: Table) -> Result<()>;
+
+    /// Renames a column of a table. This doesn't change the tuple layout, so
+    /// the earlier schema versions are kept as is. Errors if the table or
+    /// column doesn't exist, or if the new name is taken.
+    fn rename_column(&mut self, table_name: &str, old: &str, new: &str) -> Result<()>;
+
+    /// Gets the earlier schemas of a table that were replaced by
+    /// update_table(), oldest first. Only versions that some stored tuples
+    /// still have the layout of are kept. Empty if the table doesn't exist.
+    fn get_table_versions(&mut self, table_name: &str) -> Result<Vec<TableVersion>>;
+
     /// Deletes a key if one exists. Otherwise, does nothing.
     fn delete(&mut self, key: Key) -> Result<()>;
 
@@ -54,12 +82,36 @@ pub trait Engine: Send {
     where
         Self: Sized;
 
+    /// Like scan(), but resumes after the given record id, which must come from
+    /// a previous scan of the same table. Errors if it doesn't.
+    fn scan_after(&mut self, table_name: &str, after: &RecordId) -> Result<Self::ScanIterator<'_>>
+    where
+        Self: Sized;
+
     /// Scan, but can be used from trait objects. This iterator uses
     /// dynamic dispatch, which incurs a runtime performance penalty.
     fn scan_dyn(&mut self) -> Box<dyn ScanIterator + '_>;
 
     /// Updates a tuple corresponding to the given record id with the provided value.
-    fn update(&mut self, key: Key, value: Tuple) -> Result<()>;
+    /// Returns the tuple's record id, which changes if the update moves it.
+    fn update(&mut self, key: Key, value: Tuple) -> Result<RecordId>;
+
+    /// Updates a batch of tuples in the given table in a single call, which
+    /// lets the engine look up the table and its pages once rather than per
+    /// tuple. Stops at the first error; earlier updates are not rolled back.
+    /// Returns the tuples' record ids in order, like update().
+    fn update_batch(
+        &mut self,
+        table_name: &str,
+        values: Vec<(RecordId, Tuple)>,
+    ) -> Result<Vec<RecordId>>;
+
+    /// Returns the number of live tuples in a table, without decoding them.
+    fn tuple_count(&mut self, table_name: &str) -> Result<u64>;
+
+    /// Physically removes deleted tuples from a table, compacting its storage.
+    /// Returns the number of tuples reclaimed.
+    fn vacuum(&mut self, table_name: &str) -> Result<u64>;
 
     /// Returns engine status.
     fn status(&mut self) -> Result<Status>;
@@ -2,11 +2,15 @@ use crate::common::constants::{
     COULD_NOT_UNWRAP_BPM_MSG, INVALID_PID, NEW_PAGE_ERR_MSG, TUPLE_DOESNT_FIT_MSG,
 };
 use crate::common::{Error, Result};
+use crate::errinput;
 use crate::storage::buffer::buffer_pool_manager::BufferPoolManager;
 use crate::storage::disk::disk_manager::PageId;
 use crate::storage::page::{Page, RecordId, TablePage, TablePageHandle, TablePageIterator};
-use crate::storage::tuple::{Tuple, TupleMetadata};
+use crate::storage::tuple::{Row, Tuple, TupleMetadata};
+use crate::storage::TableVersion;
 use crate::types::Table;
+use itertools::Itertools as _;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, RwLock};
 
 /// Represents a table stored on disk.
@@ -14,6 +18,13 @@ use std::sync::{Arc, RwLock};
 pub struct TableHeap {
     pub(crate) page_cnt: u32,
     pub(crate) schema: Table,
+    /// Ids identifying the schema's columns across schema changes, such that
+    /// columns can be matched up with earlier schemas' columns.
+    pub(crate) column_ids: Vec<u32>,
+    /// Earlier schemas replaced by replace_schema(), oldest first, along with
+    /// their column ids. Tuples aren't rewritten, so some may still have an
+    /// earlier schema's layout. Schemas that no tuple has are dropped.
+    pub(crate) previous_schemas: Vec<(Table, Vec<u32>)>,
     // reference to the buffer pool manager instance shared between heap files
     pub(crate) buffer_pool_manager: Arc<RwLock<BufferPoolManager>>,
     pub(crate) first_page_id: PageId,
@@ -27,7 +38,9 @@ impl TableHeap {
 
         TableHeap {
             page_cnt: 1,
+            column_ids: (0..schema.col_count() as u32).collect(),
             schema,
+            previous_schemas: Vec::new(),
             buffer_pool_manager: bpm,
             first_page_id,
             last_page_id: first_page_id,
@@ -42,6 +55,80 @@ impl TableHeap {
         self.page_cnt
     }
 
+    /// Replaces the heap's schema, keeping the replaced schema as an earlier
+    /// version for the tuples that have its layout. Columns keep their ids if
+    /// the replaced schema has a column with the same name, and get new ids
+    /// otherwise. Earlier versions that no tuple has are dropped.
+    pub fn replace_schema(&mut self, schema: Table) -> Result<()> {
+        if schema == self.schema {
+            return Ok(());
+        }
+        let previous_ids = self.previous_schemas.iter().flat_map(|(_, ids)| ids);
+        let mut next_id = previous_ids
+            .chain(&self.column_ids)
+            .max()
+            .map_or(0, |id| id + 1);
+        let mut column_ids = Vec::new();
+        for column in schema.columns() {
+            let id = match self.schema.field_name_to_index(Some(&column.get_name())) {
+                Some(index) => self.column_ids[index],
+                None => {
+                    next_id += 1;
+                    next_id - 1
+                }
+            };
+            column_ids.push(id);
+        }
+        let previous = std::mem::replace(&mut self.schema, schema);
+        let previous_ids = std::mem::replace(&mut self.column_ids, column_ids);
+        self.previous_schemas.push((previous, previous_ids));
+        self.prune_schemas()
+    }
+
+    /// Returns the earlier schema versions, oldest first, with their columns
+    /// matched up with the current schema's by id.
+    pub fn table_versions(&self) -> Vec<TableVersion> {
+        self.previous_schemas
+            .iter()
+            .map(|(schema, ids)| TableVersion {
+                schema: schema.clone(),
+                columns: self
+                    .column_ids
+                    .iter()
+                    .map(|id| ids.iter().position(|i| i == id))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Drops the earlier schema versions that no live tuple is decoded with.
+    /// A tuple that doesn't have the current schema's layout is decoded with
+    /// the newest earlier version whose layout it has (see Row::check_layout),
+    /// so dropping the other versions doesn't change how any tuple decodes.
+    fn prune_schemas(&mut self) -> Result<()> {
+        if self.previous_schemas.is_empty() {
+            return Ok(());
+        }
+        let mut used = vec![false; self.previous_schemas.len()];
+        for item in self.iter() {
+            let (_, tuple) = item?;
+            if Row::check_layout(&tuple.data, &self.schema).is_ok() {
+                continue;
+            }
+            let version = self
+                .previous_schemas
+                .iter()
+                .rposition(|(schema, _)| Row::check_layout(&tuple.data, schema).is_ok());
+            if let Some(index) = version {
+                used[index] = true;
+            }
+        }
+        let mut used = used.into_iter();
+        self.previous_schemas
+            .retain(|_| used.next().unwrap_or(false));
+        Ok(())
+    }
+
     /// creates a new page and updates corresponding heap metadata.
     pub fn create_new_page(&mut self) -> Result<PageId> {
         let binding = Arc::clone(&self.buffer_pool_manager);
@@ -62,7 +149,11 @@ impl TableHeap {
         }
     }
 
-    /// Fetches the tuple payload corresponding to the given record ID from the table heap.
+    /// Deletes the tuple corresponding to the given record ID from the table heap.
+    ///
+    /// The tuple is only marked as deleted (i.e. tombstoned) in its metadata, and
+    /// its payload remains on the page. Tombstoned tuples are skipped by `iter()`
+    /// and can't be fetched with `get_tuple()`.
     pub fn delete_tuple(&self, rid: &RecordId) -> Result<()> {
         let page = self.fetch_page_handle(&rid.page_id());
         let mut page_guard = page.write()?;
@@ -70,6 +161,14 @@ impl TableHeap {
         page_guard.update_tuple_metadata(&TupleMetadata::deleted_payload_metadata(), rid)
     }
 
+    /// Fetches the metadata of the tuple corresponding to the given record ID,
+    /// including for tombstoned tuples.
+    pub fn get_tuple_metadata(&self, rid: &RecordId) -> Result<TupleMetadata> {
+        let page = self.fetch_page_handle(&rid.page_id());
+        let page_guard = page.read()?;
+        page_guard.get_tuple_metadata(rid)
+    }
+
     pub fn get_tuple(&self, rid: &RecordId) -> Result<Tuple> {
         let page = self.fetch_page_handle(&rid.page_id());
         let page_guard = page.read()?;
@@ -93,25 +192,108 @@ impl TableHeap {
         Ok(RecordId::new(self.last_page_id, slot_id))
     }
 
-    pub fn update_tuple(&self, rid: &RecordId, payload: Tuple) -> Result<()> {
+    /// Updates a tuple, returning its record id, which changes if the tuple is
+    /// moved. See update_tuple_on_page().
+    pub fn update_tuple(&self, rid: &RecordId, payload: Tuple) -> Result<RecordId> {
         let page_id = rid.page_id();
 
         let page = self.fetch_page_handle(&page_id);
         let mut page_guard = page.write().unwrap();
+        Self::update_tuple_on_page(&mut page_guard, rid, payload)
+    }
+
+    /// Updates a batch of tuples, fetching and write-locking each page once per
+    /// run of consecutive record ids on it. Record ids sorted by page (e.g. from
+    /// a BTreeMap) thus touch every page only once. Returns the tuples' record
+    /// ids in order, which change for moved tuples.
+    pub fn update_tuples(&self, updates: Vec<(RecordId, Tuple)>) -> Result<Vec<RecordId>> {
+        let mut rids = Vec::with_capacity(updates.len());
+        for (page_id, group) in &updates.into_iter().chunk_by(|(rid, _)| rid.page_id()) {
+            let page = self.fetch_page_handle(&page_id);
+            let mut page_guard = page.write()?;
+            for (rid, payload) in group {
+                rids.push(Self::update_tuple_on_page(&mut page_guard, &rid, payload)?);
+            }
+        }
+        Ok(rids)
+    }
+
+    /// Updates a tuple on its page, returning its record id. A tuple whose size
+    /// changes is moved to a new slot on the page.
+    fn update_tuple_on_page(
+        page_guard: &mut TablePage,
+        rid: &RecordId,
+        payload: Tuple,
+    ) -> Result<RecordId> {
         let metadata = page_guard.get_tuple_metadata(rid)?;
 
         // If the tuple has a variable length field and the size of the updated tuple is different
         // from the existing tuple, delete the existing tuple and insert the new tuple.
         let existing_size = page_guard.get_tuple(rid)?.data.len();
         match existing_size == payload.data.len() {
-            true => page_guard.update_tuple_in_place_unchecked(metadata, payload, rid),
+            true => {
+                page_guard.update_tuple_in_place_unchecked(metadata, payload, rid)?;
+                Ok(rid.clone())
+            }
             false => {
                 page_guard
                     .update_tuple_metadata(&TupleMetadata::deleted_payload_metadata(), rid)?;
-                page_guard.insert_tuple(TupleMetadata::new(false), payload);
-                Ok(())
+                let slot_id = page_guard
+                    .insert_tuple(TupleMetadata::new(false), payload)
+                    .expect(TUPLE_DOESNT_FIT_MSG);
+                Ok(RecordId::new(rid.page_id(), slot_id))
+            }
+        }
+    }
+
+    /// Physically removes tombstoned tuples by rewriting the live tuples into
+    /// new pages, returning the number of tuples reclaimed. This changes the
+    /// record IDs of the live tuples.
+    ///
+    /// Like dropping a table, the old pages are simply left behind in the
+    /// buffer pool.
+    pub fn vacuum(&mut self) -> Result<u64> {
+        let (_, reclaimed) = self.tuple_counts()?;
+        if reclaimed == 0 {
+            return Ok(0);
+        }
+
+        // Tuples are copied as is, so the schema history is kept, apart from
+        // the versions that only deleted tuples had.
+        let tuples = self.iter().map_ok(|(_, tuple)| tuple).collect::<Result<Vec<_>>>()?;
+        let column_ids = std::mem::take(&mut self.column_ids);
+        let previous_schemas = std::mem::take(&mut self.previous_schemas);
+        *self = TableHeap::new(self.schema(), &self.buffer_pool_manager);
+        self.column_ids = column_ids;
+        self.previous_schemas = previous_schemas;
+        for tuple in tuples {
+            self.insert_tuple(tuple)?;
+        }
+        self.prune_schemas()?;
+        Ok(reclaimed)
+    }
+
+    /// Returns the number of live (i.e. not tombstoned) tuples in the heap,
+    /// read from the page headers without decoding any tuple.
+    pub fn live_tuple_count(&self) -> Result<u64> {
+        Ok(self.tuple_counts()?.0)
+    }
+
+    /// Sums the live and tombstoned tuple counts of every page's header.
+    fn tuple_counts(&self) -> Result<(u64, u64)> {
+        let (mut live, mut deleted) = (0, 0);
+        let mut page_id = self.first_page_id;
+        loop {
+            let page = self.fetch_page_handle(&page_id);
+            let page_guard = page.read()?;
+            live += page_guard.tuple_count() as u64;
+            deleted += page_guard.deleted_tuple_count() as u64;
+            if page_id == self.last_page_id {
+                break;
             }
+            page_id = page_guard.get_next_page_id();
         }
+        Ok((live, deleted))
     }
 
     pub fn iter(&self) -> TableHeapIterator {
@@ -125,6 +307,38 @@ impl TableHeap {
         }
     }
 
+    /// Returns an iterator over the tuples following the given record id, in
+    /// the order iter() emits them. This resumes from the record id's page,
+    /// only following the page headers of the preceding pages to check that
+    /// the page belongs to the heap, without rescanning their tuples. The
+    /// record id need not be live, but must refer to one of the heap's slots,
+    /// so record ids from other tables or from before a vacuum error.
+    pub fn iter_after(&self, rid: &RecordId) -> Result<TableHeapIterator<'_>> {
+        let current_page_id = rid.page_id();
+        let mut page_id = self.first_page_id;
+        while page_id != current_page_id {
+            if page_id == self.last_page_id {
+                return errinput!("record id {} isn't in the table", rid.to_string());
+            }
+            page_id = self.fetch_page_handle(&page_id).read()?.get_next_page_id();
+        }
+        let page = self.fetch_page_handle(&current_page_id);
+        if rid.slot_id() as usize >= page.read()?.tuple_info.len() {
+            return errinput!("record id {} isn't in the table", rid.to_string());
+        }
+
+        let current_page_iterator = TablePage::iter(page);
+        current_page_iterator
+            .index
+            .store(rid.slot_id().saturating_add(1), Ordering::SeqCst);
+
+        Ok(TableHeapIterator {
+            heap_file: self,
+            current_page_id,
+            current_page_iterator,
+        })
+    }
+
     pub(crate) fn fetch_page_handle(&self, page_id: &PageId) -> TablePageHandle {
         let mut bpm = self
             .buffer_pool_manager
@@ -140,8 +354,9 @@ impl TableHeap {
     }
 }
 
-/// Iterator that sequentially iterates over all the tuples in a heap file.
-/// It does not outlive the lifetime of its underlying heap file.
+/// Iterator that sequentially iterates over all the live tuples in a heap file,
+/// skipping tombstoned tuples. It does not outlive the lifetime of its
+/// underlying heap file.
 pub struct TableHeapIterator<'a> {
     heap_file: &'a TableHeap,
     current_page_id: PageId,
@@ -149,10 +364,11 @@ pub struct TableHeapIterator<'a> {
 }
 
 impl Iterator for TableHeapIterator<'_> {
-    type Item = (RecordId, Tuple);
+    type Item = Result<(RecordId, Tuple)>;
 
     /// Returns `Some(tuple)` if a tuple exists at the iterator's current slot in the page, and
-    /// `None` if the iterator is at the end of the page and there aren't anymore tuples.
+    /// `None` if the iterator is at the end of the page and there aren't anymore tuples. Yields an
+    /// error if the tuple doesn't match its checksum.
     fn next(&mut self) -> Option<Self::Item> {
         while self.current_page_id <= self.heap_file.last_page_id {
             // our page iterator produced a valid tuple!
@@ -8,5 +8,5 @@ pub mod simple;
 mod tables;
 pub mod tuple;
 
-pub use engine::{Engine, Key, ScanIterator};
+pub use engine::{Engine, Key, ScanIterator, TableVersion};
 pub use tables::{HeapTableManager, KeyDirectory};
@@ -1,5 +1,6 @@
 use crate::common::constants::INVALID_PID;
 use crate::common::{Error, Result};
+use crate::errdata;
 use crate::config::config::RUSTY_DB_PAGE_SIZE_BYTES;
 use crate::storage::disk::disk_manager::PageId;
 use crate::storage::page::record_id::RecordId;
@@ -83,7 +84,8 @@ impl TablePage {
         // tuples are positioned at the end of the page growing inward, with new tuples appended to
         // the front, e.g. | ... t_{n}, t_{n-1}, ... t_{0} |.
         let tuples_start = (tuples_end - tuple_size_bytes) as u16;
-        let header_size = 8 + (self.total_tuple_count() + 1) * 4;
+        // Each slot stores the tuple's offset, size, and checksum.
+        let header_size = 8 + (self.total_tuple_count() + 1) * 8;
 
         // Recall that the header and tuples are positioned on opposite sides of the page, growing
         // inward toward each other, i.e. | header => free space <= tuples |.
@@ -109,7 +111,7 @@ impl TablePage {
         let old_meta = self.tuple_info[slot].metadata;
         self.update_tuple_cnt(&old_meta.is_deleted(), &meta.is_deleted());
 
-        self.tuple_info[slot].metadata = meta;
+        self.tuple_info[slot].metadata = meta.with_checksum(tuple.checksum());
 
         // Along with tuple data.
         let offset = self.tuple_info[slot].offset as usize;
@@ -165,7 +167,8 @@ impl Page for TablePage {
     ///
     /// # Returns
     /// - `Some(Tuple)`: A Tuple instance
-    /// - `Error`: If the `RecordID` is invalid to be used on this page
+    /// - `Error`: If the `RecordID` is invalid to be used on this page, or the tuple's checksum
+    ///   doesn't match its data
     fn get_tuple(&self, rid: &RecordId) -> Result<Tuple> {
         // Invalid Record ID
         if rid.page_id() != self.page_id || rid.slot_id() >= self.total_tuple_count() {
@@ -181,7 +184,11 @@ impl Page for TablePage {
         // Fetch and return the tuple
         let offset = tuple_info.offset as usize;
         let size_bytes = tuple_info.size_bytes as usize;
-        Ok(Tuple::from(&self.data[offset..(offset + size_bytes)]))
+        let tuple = Tuple::from(&self.data[offset..(offset + size_bytes)]);
+        if tuple.checksum() != tuple_info.metadata.checksum() {
+            return errdata!("checksum mismatch for tuple {}", rid.to_string());
+        }
+        Ok(tuple)
     }
 
     /// Insert a tuple into the table page, and returns its corresponding `SlotID` on the page
@@ -209,7 +216,7 @@ impl Page for TablePage {
         let tuple_info = TupleInfo {
             offset: offset as u16,
             size_bytes: tuple.data.len() as u16,
-            metadata: meta,
+            metadata: meta.with_checksum(tuple.checksum()),
         };
         // Return the slot id
         let slot = self.total_tuple_count();
@@ -251,7 +258,7 @@ impl Page for TablePage {
     ///
     /// If the `RecordId` is valid to be fetched on this page, returns an `InvalidInput` Error.
     /// Otherwise, fetch the corresponding metadata of the tuple using the `RecordId`, and updates
-    /// its metadata.
+    /// its metadata. The payload is unchanged, so its checksum is kept.
     ///
     /// # Parameters
     /// - `TupleMetadata`: The new metadata of the tuple
@@ -270,7 +277,7 @@ impl Page for TablePage {
         let old_meta = self.tuple_info[slot].metadata;
         self.update_tuple_cnt(&old_meta.is_deleted(), &metadata.is_deleted());
 
-        self.tuple_info[slot].metadata = *metadata;
+        self.tuple_info[slot].metadata = metadata.with_checksum(old_meta.checksum());
         Ok(())
     }
 
@@ -342,8 +349,8 @@ impl Page for TablePage {
             match info.metadata.is_deleted() {
                 true => {
                     // this slot is vacant
-                    result[cursor..(cursor + 4)].fill(0);
-                    cursor += 4;
+                    result[cursor..(cursor + 8)].fill(0);
+                    cursor += 8;
                 }
                 false => {
                     let offset_bytes = info.offset.to_le_bytes();
@@ -353,6 +360,10 @@ impl Page for TablePage {
                     let size_bytes = info.size_bytes.to_le_bytes();
                     result[cursor..(cursor + 2)].copy_from_slice(&size_bytes);
                     cursor += 2;
+
+                    let checksum_bytes = info.metadata.checksum().to_le_bytes();
+                    result[cursor..(cursor + 4)].copy_from_slice(&checksum_bytes);
+                    cursor += 4;
                 }
             }
         });
@@ -399,12 +410,16 @@ impl Page for TablePage {
             let size = u16::from_le_bytes(size_bytes.try_into().unwrap());
             cursor += 2;
 
+            let checksum_bytes = buffer[cursor..(cursor + 4)].to_vec();
+            let checksum = u32::from_le_bytes(checksum_bytes.try_into().unwrap());
+            cursor += 4;
+
             let mut deleted = false;
             if size == 0 && offset == 0 {
                 deleted = true;
             }
 
-            let meta = TupleMetadata::new(deleted);
+            let meta = TupleMetadata::new(deleted).with_checksum(checksum);
             let tuple_info = TupleInfo {
                 offset,
                 size_bytes: size,
@@ -441,12 +456,13 @@ impl TablePageIterator {
         self.page.read().unwrap().get_next_page_id()
     }
 
-    /// Returns the next tuple payload on the table, if one exists.
+    /// Returns the next tuple payload on the table, if one exists. Errors if
+    /// the payload doesn't match its checksum.
     fn tuple_if_exists(
         &self,
         page_slot: u16,
         page_guard: &RwLockReadGuard<TablePage>,
-    ) -> Option<(RecordId, Tuple)> {
+    ) -> Option<Result<(RecordId, Tuple)>> {
         match page_guard.tuple_info[page_slot as usize]
             .metadata
             .is_deleted()
@@ -456,16 +472,14 @@ impl TablePageIterator {
             // tuple is not deleted; return it!
             false => {
                 let rid = RecordId::new(page_guard.page_id, page_slot);
-                page_guard
-                    .get_tuple(&rid)
-                    .map_or_else(|_| None, |payload| Some((rid, payload)))
+                Some(page_guard.get_tuple(&rid).map(|payload| (rid, payload)))
             }
         }
     }
 }
 
 impl Iterator for TablePageIterator {
-    type Item = (RecordId, Tuple);
+    type Item = Result<(RecordId, Tuple)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let page_guard = self.page.read().unwrap();
@@ -2,7 +2,7 @@ use crate::common::Result;
 use crate::storage::engine::Engine;
 use crate::storage::page::RecordId;
 use crate::storage::tuple::Tuple;
-use crate::storage::Key;
+use crate::storage::{Key, TableVersion};
 use crate::types::Table;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
@@ -75,6 +75,24 @@ impl<E: Engine> Transaction<E> {
         engine.get_table(table_name)
     }
 
+    /// Replaces a table's schema.
+    pub fn update_table(&self, table: Table) -> Result<()> {
+        let mut engine = self.engine.lock()?;
+        engine.update_table(table)
+    }
+
+    /// Renames a column of a table.
+    pub fn rename_column(&self, table_name: &str, old: &str, new: &str) -> Result<()> {
+        let mut engine = self.engine.lock()?;
+        engine.rename_column(table_name, old, new)
+    }
+
+    /// Fetches a table's earlier schemas, oldest first.
+    pub fn fetch_table_versions(&self, table_name: &str) -> Result<Vec<TableVersion>> {
+        let mut engine = self.engine.lock()?;
+        engine.get_table_versions(table_name)
+    }
+
     /// Deletes a key.
     pub fn delete(&self, key: Key) -> Result<()> {
         let mut engine = self.engine.lock()?;
@@ -94,39 +112,87 @@ impl<E: Engine> Transaction<E> {
         engine.insert(table_name, value)
     }
 
-    /// Updates a key's value.
-    pub fn update(&self, key: Key, value: Tuple) -> Result<()> {
+    /// Updates a key's value, returning its record id, which changes if the
+    /// update moves the tuple.
+    pub fn update(&self, key: Key, value: Tuple) -> Result<RecordId> {
         let mut engine = self.engine.lock()?;
         engine.update(key, value)
     }
 
-    /// Returns an iterator over the key/value items of the table.
+    /// Updates a batch of keys' values in a table, holding the engine lock
+    /// for the whole batch. Returns their record ids in order, like update().
+    pub fn update_batch(
+        &self,
+        table_name: &str,
+        values: Vec<(RecordId, Tuple)>,
+    ) -> Result<Vec<RecordId>> {
+        let mut engine = self.engine.lock()?;
+        engine.update_batch(table_name, values)
+    }
+
+    /// Returns the number of live tuples in a table.
+    pub fn tuple_count(&self, table_name: &str) -> Result<u64> {
+        let mut engine = self.engine.lock()?;
+        engine.tuple_count(table_name)
+    }
+
+    /// Reclaims the space of deleted tuples in a table.
+    pub fn vacuum(&self, table_name: &str) -> Result<u64> {
+        let mut engine = self.engine.lock()?;
+        engine.vacuum(table_name)
+    }
+
+    /// Returns up to limit key/value items of the table following the given
+    /// record id, or from the start if none. Unlike skipping items of a scan,
+    /// this doesn't revisit the preceding items.
+    pub fn scan_after(
+        &self,
+        table: &str,
+        after: Option<&RecordId>,
+        limit: usize,
+    ) -> Result<Vec<(RecordId, Tuple)>> {
+        let mut engine = self.engine.lock()?;
+        match after {
+            Some(after) => engine.scan_after(table, after)?.take(limit).collect(),
+            None => engine.scan(table).take(limit).collect(),
+        }
+    }
+
+    /// Returns an iterator over the key/value items of the table, reading
+    /// them from the engine in batches.
     pub fn scan(&self, table: &str) -> ScanIterator<E> {
-        ScanIterator::new(Arc::clone(&self.engine), table)
+        ScanIterator::new(Arc::clone(&self.engine), table, ScanIterator::<E>::BUFFER_SIZE)
+    }
+
+    /// Like scan(), but reads batch_size items from the engine at a time.
+    pub fn scan_with_batch_size(&self, table: &str, batch_size: usize) -> ScanIterator<E> {
+        ScanIterator::new(Arc::clone(&self.engine), table, batch_size)
     }
 }
 
-// todo(eyoon): buffer the scaniterator
-//
-// /// An iterator over the latest live and visible key/value pairs for the txn.
-// ///
-// /// The (single-threaded) engine is protected by a mutex, and holding the mutex
-// /// for the duration of the iteration can cause deadlocks (e.g. when the local
-// /// SQL engine pulls from two tables concurrently during a join). Instead, we
-// /// pull and buffer a batch of rows at a time, and release the mutex in between.
-// ///
-// /// This does not implement DoubleEndedIterator (reverse scans), since the SQL
-// /// layer doesn't currently need it.
-// #[allow(clippy::type_complexity)]
+/// An iterator over the key/value items of a table.
+///
+/// The (single-threaded) engine is protected by a mutex, and holding the mutex
+/// for the duration of the iteration can cause deadlocks (e.g. when the local
+/// SQL engine pulls from two tables concurrently during a join). Instead, we
+/// pull and buffer a batch of items at a time, and release the mutex in between.
+///
+/// This does not implement DoubleEndedIterator (reverse scans), since the SQL
+/// layer doesn't currently need it.
 pub struct ScanIterator<E: Engine> {
     /// The engine.
     engine: Arc<Mutex<E>>,
-    /// A buffer of live and visible key/value pairs to emit.
+    /// A buffer of key/value pairs to emit.
     buffer: VecDeque<(RecordId, Tuple)>,
     /// The name of the table this iterates over
     table: String,
-    /// The position of the current tuple in the iterator
-    i: usize,
+    /// The record id of the last item pulled from the engine, if any. The
+    /// next batch resumes after it.
+    last: Option<RecordId>,
+    /// The number of items to pull from the engine at a time.
+    batch_size: usize,
+    /// Whether the engine scan has been exhausted.
+    done: bool,
 }
 
 /// Implement Clone manually. Deriving it requires Engine: Clone.
@@ -136,48 +202,54 @@ impl<E: Engine> Clone for ScanIterator<E> {
             engine: self.engine.clone(),
             buffer: self.buffer.clone(),
             table: self.table.clone(),
-            i: self.i,
+            last: self.last.clone(),
+            batch_size: self.batch_size,
+            done: self.done,
         }
     }
 }
-//
+
 impl<E: Engine> ScanIterator<E> {
-    /// The number of live keys to pull from the engine at a time.
+    /// The number of keys to pull from the engine at a time.
     #[cfg(not(test))]
     const BUFFER_SIZE: usize = 1000;
-    /// Pull only 2 keys in tests, to exercise this more often.
+    /// Pull only 4 keys in tests, to exercise this more often.
     #[cfg(test)]
     const BUFFER_SIZE: usize = 4;
 
     /// Creates a new scan iterator.
-    fn new(engine: Arc<Mutex<E>>, table: &str) -> Self {
-        let buffer = VecDeque::with_capacity(Self::BUFFER_SIZE);
+    fn new(engine: Arc<Mutex<E>>, table: &str, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
         Self {
             engine,
-            buffer,
+            buffer: VecDeque::new(),
             table: table.to_string(),
-            i: 0,
+            last: None,
+            batch_size,
+            done: false,
         }
     }
 
-    /// Fills the buffer, if there's any pending items.
+    /// Fills the buffer with the next batch of items, if there are any.
     fn fill_buffer(&mut self) -> Result<()> {
-        // Check if there's anything to buffer.
-        if self.buffer.len() >= Self::BUFFER_SIZE {
+        if !self.buffer.is_empty() || self.done {
             return Ok(());
         }
-
         let mut engine = self.engine.lock()?;
-        let mut iter = engine.scan(&self.table).peekable();
-        // Iterator is exhausted; no more tuples to insert into the buffer.
-        if iter.peek().into_iter().skip(self.i).next().is_none() {
-            return Ok(());
+        // The engine iterator borrows the engine, so it can't be kept across
+        // batches. Resume the scan after the last item pulled, which only
+        // reads the page headers of the preceding pages again.
+        let iter = match &self.last {
+            Some(last) => engine.scan_after(&self.table, last)?,
+            None => engine.scan(&self.table),
+        };
+        for item in iter.take(self.batch_size) {
+            self.buffer.push_back(item?);
         }
-        // Skip to the current
-        while let Some((rid, tuple)) = iter.next().transpose()? {
-            self.buffer.push_back((rid, tuple));
-            self.i += 1;
+        if let Some((rid, _)) = self.buffer.back() {
+            self.last = Some(rid.clone());
         }
+        self.done = self.buffer.len() < self.batch_size;
         Ok(())
     }
 }
@@ -4,7 +4,7 @@ use crate::storage::engine::Status;
 use crate::storage::heap::{TableHeap, TableHeapIterator};
 use crate::storage::page::RecordId;
 use crate::storage::tuple::Tuple;
-use crate::storage::{engine, Engine, Key};
+use crate::storage::{engine, Engine, Key, TableVersion};
 use crate::types::Table;
 use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, RwLock};
@@ -62,6 +62,29 @@ impl Engine for HeapTableManager {
         }
     }
 
+    fn update_table(&mut self, table: Table) -> Result<()> {
+        let heap = self
+            .heaps
+            .get_mut(table.name())
+            .ok_or_else(|| Error::InvalidData(table.name().to_string()))?;
+        heap.replace_schema(table)
+    }
+
+    fn rename_column(&mut self, table_name: &str, old: &str, new: &str) -> Result<()> {
+        let heap = self
+            .heaps
+            .get_mut(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        heap.schema.rename_column(old, new)
+    }
+
+    fn get_table_versions(&mut self, table_name: &str) -> Result<Vec<TableVersion>> {
+        match self.heaps.get(table_name) {
+            Some(heap) => Ok(heap.table_versions()),
+            None => Ok(Vec::new()),
+        }
+    }
+
     fn delete(&mut self, key: Key) -> Result<()> {
         let heap = self
             .heaps
@@ -97,11 +120,24 @@ impl Engine for HeapTableManager {
         ScanIterator { inner: heap.iter() }
     }
 
+    fn scan_after(&mut self, table_name: &str, after: &RecordId) -> Result<Self::ScanIterator<'_>>
+    where
+        Self: Sized,
+    {
+        let heap = self
+            .heaps
+            .get_mut(table_name)
+            .unwrap_or_else(|| panic!("Could not access table {table_name}"));
+        Ok(ScanIterator {
+            inner: heap.iter_after(after)?,
+        })
+    }
+
     fn scan_dyn(&mut self) -> Box<dyn engine::ScanIterator + '_> {
         todo!()
     }
 
-    fn update(&mut self, key: Key, value: Tuple) -> Result<()> {
+    fn update(&mut self, key: Key, value: Tuple) -> Result<RecordId> {
         let heap = self
             .heaps
             .get_mut(key.table_name)
@@ -109,6 +145,34 @@ impl Engine for HeapTableManager {
         heap.update_tuple(key.record_id, value)
     }
 
+    fn update_batch(
+        &mut self,
+        table_name: &str,
+        values: Vec<(RecordId, Tuple)>,
+    ) -> Result<Vec<RecordId>> {
+        let heap = self
+            .heaps
+            .get_mut(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        heap.update_tuples(values)
+    }
+
+    fn tuple_count(&mut self, table_name: &str) -> Result<u64> {
+        let heap = self
+            .heaps
+            .get(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        heap.live_tuple_count()
+    }
+
+    fn vacuum(&mut self, table_name: &str) -> Result<u64> {
+        let heap = self
+            .heaps
+            .get_mut(table_name)
+            .ok_or_else(|| Error::InvalidData(table_name.to_string()))?;
+        heap.vacuum()
+    }
+
     fn status(&mut self) -> Result<Status> {
         todo!()
     }
@@ -122,6 +186,6 @@ impl Iterator for ScanIterator<'_> {
     type Item = Result<(RecordId, Tuple)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(Ok)
+        self.inner.next()
     }
 }
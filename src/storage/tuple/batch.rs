@@ -0,0 +1,97 @@
+use crate::common::Result;
+use crate::errdata;
+use crate::storage::tuple::{Row, Rows};
+use crate::types::field::Field;
+use crate::types::Table;
+
+/// A batch of rows in columnar form, with one vector of values per column.
+/// All column vectors have the same length, i.e. the number of rows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnBatch {
+    pub columns: Vec<Vec<Field>>,
+}
+
+impl ColumnBatch {
+    /// Creates an empty batch with the given number of columns.
+    fn new(width: usize, capacity: usize) -> Self {
+        Self {
+            columns: (0..width).map(|_| Vec::with_capacity(capacity)).collect(),
+        }
+    }
+
+    /// Returns the number of rows in the batch.
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    /// Returns true if the batch has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the row at the given index, if any.
+    pub fn row(&self, index: usize) -> Option<Row> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(Row::from(
+            self.columns
+                .iter()
+                .map(|column| &column[index])
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    /// Appends a row to the batch, transposing its values into the columns.
+    fn push(&mut self, row: Row) -> Result<()> {
+        if row.size() != self.columns.len() {
+            return errdata!(
+                "expected {} columns, found {}",
+                self.columns.len(),
+                row.size()
+            );
+        }
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+        Ok(())
+    }
+}
+
+/// An iterator that drains rows into column batches of up to batch_size rows.
+/// The schema gives the number of columns, and rows of a different width
+/// yield an error. The final batch may be smaller, but batches are never empty
+/// (so a schema without columns yields no batches).
+pub struct ColumnBatches {
+    rows: Rows,
+    width: usize,
+    batch_size: usize,
+}
+
+impl ColumnBatches {
+    pub fn new(rows: Rows, schema: &Table, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        Self {
+            rows,
+            width: schema.col_count(),
+            batch_size,
+        }
+    }
+}
+
+impl Iterator for ColumnBatches {
+    type Item = Result<ColumnBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut batch = ColumnBatch::new(self.width, self.batch_size);
+        for item in self.rows.by_ref().take(self.batch_size) {
+            if let Err(err) = item.and_then(|(_, row)| batch.push(row)) {
+                return Some(Err(err));
+            }
+        }
+        if batch.is_empty() {
+            return None;
+        }
+        Some(Ok(batch))
+    }
+}
@@ -3,11 +3,17 @@ use serde::{Deserialize, Serialize};
 #[derive(PartialEq, Eq, Hash, Clone, Debug, Copy, Deserialize, Serialize)]
 pub struct TupleMetadata {
     is_deleted: bool,
+    /// CRC32 checksum of the tuple payload, set by the table page when the
+    /// tuple is written and verified when it's read.
+    checksum: u32,
 }
 
 impl TupleMetadata {
     pub fn new(is_deleted: bool) -> Self {
-        Self { is_deleted }
+        Self {
+            is_deleted,
+            checksum: 0,
+        }
     }
 
     pub fn deleted_payload_metadata() -> TupleMetadata {
@@ -22,6 +28,16 @@ impl TupleMetadata {
         self.is_deleted
     }
 
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// Returns the metadata with the given payload checksum.
+    pub fn with_checksum(mut self, checksum: u32) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
     pub fn to_string(&self) -> String {
         format!("Deleted: {})", self.is_deleted)
     }
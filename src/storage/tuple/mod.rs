@@ -1,8 +1,10 @@
+mod batch;
 mod metadata;
 mod row;
 mod tuple;
 
 
+pub use batch::{ColumnBatch, ColumnBatches};
 pub use metadata::TupleMetadata;
 pub use row::{Row, RowIterator, Rows};
 pub use tuple::Tuple;
@@ -1,4 +1,5 @@
 use crate::common::{Error, Result};
+use crate::{errdata, errinput};
 use crate::storage::page::RecordId;
 use crate::storage::tuple::Tuple;
 use crate::types::field::Field;
@@ -6,6 +7,8 @@ use crate::types::{DataType, Table};
 use dyn_clone::DynClone;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::slice::Iter;
 
 /// A row iterator.
@@ -49,6 +52,30 @@ impl PartialEq for Row {
     }
 }
 
+// Rows compare and hash by their field values, using Field's definitions:
+// unlike SQL =, NULLs equal each other, as do NaNs and signed zeros. This is the
+// row equality used when deduplicating rows, e.g. for DISTINCT and set
+// operations, and rows order lexicographically by field.
+impl Eq for Row {}
+
+impl Hash for Row {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.values.hash(state)
+    }
+}
+
+impl PartialOrd for Row {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Row {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.values.cmp(&other.values)
+    }
+}
+
 impl IntoIterator for Row {
     type Item = Field;
     type IntoIter = std::vec::IntoIter<Field>;
@@ -60,9 +87,7 @@ impl IntoIterator for Row {
 
 impl Row {
     fn new(values: Vec<Field>) -> Row {
-        Row {
-            values: values.to_vec(),
-        }
+        Row { values }
     }
 
     pub fn iter(&self) -> Iter<Field> {
@@ -81,6 +106,37 @@ impl Row {
             .clone())
     }
 
+    /// Projects the row in place to the given column indexes, which may be a
+    /// subset or reordering of its columns (including duplicates). The fields
+    /// are staged in the scratch buffer and moved back into the row's own
+    /// vector, so neither allocates once the scratch buffer has grown.
+    pub fn project_columns(&mut self, columns: &[usize], scratch: &mut Vec<Field>) -> Result<()> {
+        scratch.clear();
+        for &index in columns {
+            scratch.push(self.values.get(index).ok_or(Error::OutOfBounds)?.clone());
+        }
+        self.values.clear();
+        self.values.append(scratch);
+        Ok(())
+    }
+
+    /// Returns a new row with the other row's fields appended to this row's,
+    /// e.g. to combine the left and right rows of a join.
+    pub fn append(&self, other: &Row) -> Row {
+        Row::new(self.values.iter().chain(&other.values).cloned().collect())
+    }
+
+    /// Returns a new row with the fields at the given column indexes, like
+    /// project_columns() but leaving this row as is. Errors if an index is out
+    /// of bounds.
+    pub fn project(&self, indexes: &[usize]) -> Result<Row> {
+        let values = indexes
+            .iter()
+            .map(|&index| self.values.get(index).cloned().ok_or(Error::OutOfBounds))
+            .collect::<Result<_>>()?;
+        Ok(Row::new(values))
+    }
+
     pub fn update_field(&mut self, index: usize, new: Field) -> Result<()> {
         let field = self
             .values
@@ -92,7 +148,7 @@ impl Row {
                 *field = new;
                 Ok(())
             }
-            false => Result::from(Error::InvalidInput(new.to_string())),
+            false => errinput!("can't update {} field to {new}", field.get_type()),
         }
     }
 
@@ -101,13 +157,13 @@ impl Row {
             .iter()
             .map(|field| match field.get_type() {
                 DataType::Text => {
-                    let mut text = field.to_string();
+                    let mut text = field.to_plain_string();
                     if let Some(len) = str_len {
                         text.truncate(len);
                     }
                     text
                 }
-                _ => field.to_string(),
+                _ => field.to_plain_string(),
             })
             .join(", ")
     }
@@ -120,6 +176,15 @@ impl Row {
         Ok(Self::deserialize(tuple.data, schema))
     }
 
+    /// Like from_tuple(), but only decodes the given column indexes, leaving
+    /// the other columns NULL. Used to push projections down into scans.
+    pub fn from_tuple_columns(tuple: Tuple, schema: &Table, columns: &[usize]) -> Result<Row> {
+        if let Some(index) = columns.iter().find(|&&i| i >= schema.col_count()) {
+            return errinput!("column index {index} out of bounds");
+        }
+        Ok(Self::deserialize_columns(&tuple.data, schema, columns))
+    }
+
     /// Serializes the Row's header and data into a byte-stream, structured as follows:
     ///
     /// | variable length field offset map | field data in bytes |
@@ -193,6 +258,25 @@ impl Row {
     /// `bytes` contains u16 offsets for variable-length fields, followed
     /// by fixed-length fields, with variable-length fields at the end.
     pub fn deserialize(bytes: Vec<u8>, schema: &Table) -> Self {
+        let columns = (0..schema.col_count()).collect_vec();
+        Self::deserialize_columns(&bytes, schema, &columns)
+    }
+
+    /// Like deserialize(), but only deserializes the given columns, leaving the
+    /// others NULL. The remaining columns can be filled in later with
+    /// deserialize_into(), e.g. to avoid decoding text fields of rows that are
+    /// filtered out anyway.
+    pub fn deserialize_columns(bytes: &[u8], schema: &Table, columns: &[usize]) -> Self {
+        let mut row = Self {
+            values: vec![Field::Null; schema.col_count()],
+        };
+        row.deserialize_into(bytes, schema, columns);
+        row
+    }
+
+    /// Deserializes the given columns of a byte stream into the row, replacing
+    /// their current values.
+    pub fn deserialize_into(&mut self, bytes: &[u8], schema: &Table, columns: &[usize]) {
         // Get the offsets of the variable length text fields, if any exist.
         let variable_field_offsets: Vec<u16> = (0..schema.variable_length_fields())
             .map(|i| u16::from_le_bytes([bytes[2 * i], bytes[(2 * i) + 1]]))
@@ -201,10 +285,9 @@ impl Row {
         // The first byte in `bytes` of the field data
         let field_data_start = variable_field_offsets.len() * 2;
 
-        let values = schema
-            .columns()
-            .iter()
-            .map(|column| match column.get_data_type() {
+        for &index in columns {
+            let column = schema.get_column(index);
+            self.values[index] = match column.get_data_type() {
                 DataType::Text => {
                     // Get the index into the variable length field offset array.
                     let offset_index = column.stored_offset() as usize;
@@ -225,8 +308,62 @@ impl Row {
 
                     Field::deserialize(&bytes[start..end], datatype)
                 }
-            })
-            .collect();
-        Self { values }
+            };
+        }
+    }
+
+    /// Checks that a byte stream has the layout serialize() produces for the
+    /// schema, i.e. that it can be deserialized without reading out of bounds
+    /// or yielding invalid values: the variable-length field offsets must be
+    /// ordered and in bounds, the fixed-length fields must fit between the
+    /// offsets and the text data, booleans must be 0 or 1, and text must be
+    /// valid UTF-8. Schemas with the same layout can't be told apart, e.g. an
+    /// INT column changed to FLOAT.
+    pub fn check_layout(bytes: &[u8], schema: &Table) -> Result<()> {
+        if schema.col_count() == 0 {
+            return match bytes.len() {
+                0 => Ok(()),
+                len => errdata!("expected empty tuple, got {len} bytes"),
+            };
+        }
+        let header_size = 2 * schema.variable_length_fields();
+        let data_start = header_size + schema.fixed_field_size_bytes() as usize;
+        if bytes.len() < data_start {
+            return errdata!("expected at least {data_start} tuple bytes, got {}", bytes.len());
+        }
+        if header_size == 0 && bytes.len() != data_start {
+            return errdata!("expected {data_start} tuple bytes, got {}", bytes.len());
+        }
+
+        // The text fields follow the fixed-length fields, in order.
+        let offsets = (0..schema.variable_length_fields())
+            .map(|i| u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]) as usize)
+            .chain(std::iter::once(bytes.len()))
+            .collect_vec();
+        if header_size > 0 && offsets[0] != data_start {
+            return errdata!("expected text data at byte {data_start}, got {}", offsets[0]);
+        }
+        for (start, end) in offsets.iter().tuple_windows() {
+            if start > end {
+                return errdata!("invalid text field bytes {start}..{end}");
+            }
+        }
+
+        for column in schema.columns() {
+            let offset = column.stored_offset() as usize;
+            match column.get_data_type() {
+                DataType::Text => {
+                    let text = &bytes[offsets[offset]..offsets[offset + 1]];
+                    if std::str::from_utf8(text).is_err() {
+                        return errdata!("invalid UTF-8 in column {}", column.get_name());
+                    }
+                }
+                DataType::Bool if bytes[header_size + offset] > 1 => {
+                    return errdata!("invalid boolean in column {}", column.get_name());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
     }
 }
@@ -5,6 +5,13 @@ pub struct Tuple {
     pub data: Vec<u8>,
 }
 
+impl Tuple {
+    /// Returns the CRC32 checksum of the tuple data.
+    pub fn checksum(&self) -> u32 {
+        crc32fast::hash(&self.data)
+    }
+}
+
 impl From<Vec<u8>> for Tuple {
     fn from(data: Vec<u8>) -> Self {
         Self { data }
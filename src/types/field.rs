@@ -2,15 +2,38 @@ use crate::common::{Error, Result};
 use crate::errinput;
 use crate::types::DataType;
 use serde::{Deserialize, Serialize};
+use itertools::Itertools as _;
+use std::collections::HashSet;
 use std::ops::{Add, Div, Mul, Rem, Sub};
+use std::sync::Arc;
 
+/// A value. Strings are reference-counted, so cloning a field is cheap, and
+/// equal strings can share an allocation (see Interner).
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Field {
     Null,
     Boolean(bool),
     Integer(i32),
     Float(f32),
-    String(String),
+    String(Arc<str>),
+    /// A time of day, as seconds since midnight (0 to 86399).
+    Time(u32),
+    /// A signed time interval in seconds, e.g. the difference of two times.
+    /// Like arrays, intervals only exist during query evaluation.
+    Interval(i32),
+    /// An array of values, e.g. from ARRAY_AGG. Arrays only exist during query
+    /// evaluation, and can't be stored in tables.
+    Array(Vec<Field>),
+}
+
+/// How integer arithmetic handles overflow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Errors on overflow, e.g. i32::MAX + 1 is an error.
+    #[default]
+    Error,
+    /// Wraps around on overflow, e.g. i32::MAX + 1 is i32::MIN.
+    Wrap,
 }
 
 impl PartialEq for Field {
@@ -37,6 +60,18 @@ impl PartialEq for Field {
                 Field::String(s2) => s == s2,
                 _ => false,
             },
+            Field::Time(t) => match other {
+                Field::Time(t2) => t == t2,
+                _ => false,
+            },
+            Field::Interval(i) => match other {
+                Field::Interval(i2) => i == i2,
+                _ => false,
+            },
+            Field::Array(a) => match other {
+                Field::Array(a2) => a == a2,
+                _ => false,
+            },
         }
     }
 }
@@ -49,14 +84,21 @@ impl std::hash::Hash for Field {
             Field::Null => 0.hash(state),
             Field::Boolean(b) => b.hash(state),
             Field::Integer(i) => i.hash(state),
+            // Hashes must match equality, so all NaNs hash alike (they're
+            // equal to each other), as do 0.0 and -0.0.
             Field::Float(f) => {
                 if f.is_nan() {
                     0.hash(state);
+                } else if *f == 0.0 {
+                    0.0f32.to_bits().hash(state);
                 } else {
                     f.to_bits().hash(state);
                 }
             }
             Field::String(s) => s.hash(state),
+            Field::Time(t) => t.hash(state),
+            Field::Interval(i) => i.hash(state),
+            Field::Array(a) => a.hash(state),
         }
     }
 }
@@ -78,13 +120,24 @@ impl Ord for Field {
                 (false, false) => f.partial_cmp(f2).unwrap_or(std::cmp::Ordering::Equal),
             },
             (Field::String(s), Field::String(s2)) => s.cmp(s2),
+            (Field::Time(t), Field::Time(t2)) => t.cmp(t2),
+            (Field::Interval(i), Field::Interval(i2)) => i.cmp(i2),
+            (Field::Array(a), Field::Array(a2)) => a.cmp(a2),
             (Field::Boolean(_), _) => std::cmp::Ordering::Less,
             (Field::Integer(_), Field::Boolean(_)) => std::cmp::Ordering::Greater,
             (Field::Integer(_), _) => std::cmp::Ordering::Less,
             (Field::Float(_), Field::Boolean(_)) => std::cmp::Ordering::Greater,
             (Field::Float(_), Field::Integer(_)) => std::cmp::Ordering::Greater,
             (Field::Float(_), _) => std::cmp::Ordering::Less,
+            (Field::Time(_), Field::Boolean(_) | Field::Integer(_) | Field::Float(_)) => {
+                std::cmp::Ordering::Greater
+            }
+            (Field::Time(_), _) => std::cmp::Ordering::Less,
+            (Field::Interval(_), Field::String(_) | Field::Array(_)) => std::cmp::Ordering::Less,
+            (Field::Interval(_), _) => std::cmp::Ordering::Greater,
+            (Field::String(_), Field::Array(_)) => std::cmp::Ordering::Less,
             (Field::String(_), _) => std::cmp::Ordering::Greater,
+            (Field::Array(_), _) => std::cmp::Ordering::Greater,
         }
     }
 }
@@ -140,6 +193,12 @@ impl Rem for Field {
     }
 }
 
+/// Formats the value as a SQL literal, e.g. for use in error messages and plans.
+/// Text is quoted (escaping ' as ''), booleans and NULL are written as the
+/// corresponding keywords, and floats use the shortest representation that
+/// round-trips, always including a decimal point (e.g. 1.0). Times and intervals
+/// are written as typed literals, e.g. TIME '12:30:00' and INTERVAL '-01:30:00'.
+/// Arrays are written as their bracketed elements, e.g. [1, 'a'].
 impl std::fmt::Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -147,12 +206,32 @@ impl std::fmt::Display for Field {
             Self::Boolean(true) => f.write_str("TRUE"),
             Self::Boolean(false) => f.write_str("FALSE"),
             Self::Integer(integer) => integer.fmt(f),
+            Self::Float(float) if float.is_nan() => f.write_str("NaN"),
+            Self::Float(float) if float.is_infinite() && *float > 0.0 => f.write_str("Infinity"),
+            Self::Float(float) if float.is_infinite() => f.write_str("-Infinity"),
             Self::Float(float) => write!(f, "{float:?}"),
-            Self::String(string) => write!(f, "'{}'", string.escape_debug()),
+            Self::String(string) => write!(f, "'{}'", string.replace('\'', "''")),
+            Self::Time(time) => write!(f, "TIME '{}'", format_seconds(*time as i64)),
+            Self::Interval(interval) => {
+                write!(f, "INTERVAL '{}'", format_seconds(*interval as i64))
+            }
+            Self::Array(values) => write!(f, "[{}]", values.iter().join(", ")),
         }
     }
 }
 
+/// Formats a number of seconds as [-]HH:MM:SS. Hours aren't wrapped into days.
+fn format_seconds(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+    format!("{sign}{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Adds a number of seconds to a time of day, wrapping around midnight.
+fn add_time(time: u32, seconds: i64) -> u32 {
+    (time as i64 + seconds).rem_euclid(86400) as u32
+}
+
 impl From<f32> for Field {
     fn from(v: f32) -> Self {
         Field::Float(v)
@@ -167,13 +246,13 @@ impl From<i32> for Field {
 
 impl From<String> for Field {
     fn from(v: String) -> Self {
-        Field::String(v)
+        Field::String(v.into())
     }
 }
 
 impl From<&str> for Field {
     fn from(v: &str) -> Self {
-        Field::String(v.to_owned())
+        Field::String(v.into())
     }
 }
 
@@ -191,9 +270,163 @@ impl Field {
             DataType::Int => Field::from(0i32),
             DataType::Float => Field::from(0.0),
             DataType::Text => Field::from("".to_string()),
+            DataType::Time => Field::Time(0),
             DataType::Invalid => Field::Null,
         }
     }
+    /// Parses a value of the given data type from a string. See parse_integer()
+    /// for the accepted integer formats. Floats also accept NaN, Infinity, and
+    /// -Infinity (or inf and -inf), case-insensitively.
+    pub fn parse(value: &str, data_type: DataType) -> Result<Field> {
+        Ok(match data_type {
+            DataType::Bool => match value.to_lowercase().as_str() {
+                "true" => Field::Boolean(true),
+                "false" => Field::Boolean(false),
+                _ => return errinput!("invalid boolean {value}"),
+            },
+            DataType::Int => Field::Integer(Self::parse_integer(value)?),
+            DataType::Float => Field::Float(value.parse()?),
+            DataType::Text => Field::String(value.into()),
+            DataType::Time => Field::Time(Self::parse_time(value)?),
+            DataType::Invalid => return errinput!("can't parse {value} as {data_type}"),
+        })
+    }
+
+    /// Converts the value to the given data type, e.g. for CAST or when
+    /// inserting into a column. NULL converts to any type. Integers widen to
+    /// floats, while floats only narrow to integers if they're integral and in
+    /// range. Strings are parsed as the data type (see parse()), ignoring
+    /// surrounding whitespace. Other conversions error.
+    pub fn coerce_to(&self, data_type: DataType) -> Result<Field> {
+        Ok(match (self, data_type) {
+            (Field::Null, _) => Field::Null,
+            (value, data_type) if value.get_type() == data_type => value.clone(),
+            (Field::Integer(i), DataType::Float) => Field::Float(*i as f32),
+            // i32::MAX isn't representable as f32, the nearest value is 2^31.
+            (Field::Float(f), DataType::Int)
+                if f.fract() == 0.0 && *f >= i32::MIN as f32 && *f < i32::MAX as f32 =>
+            {
+                Field::Integer(*f as i32)
+            }
+            (Field::String(s), data_type) => Field::parse(s.trim(), data_type)?,
+            (value, data_type) => return errinput!("can't convert {value} to {data_type}"),
+        })
+    }
+
+    /// Converts the value to the given data type for an explicit CAST. Like
+    /// coerce_to(), but booleans, numbers, and times also convert to text, in
+    /// their displayed form which parses back, e.g. 1.5 to '1.5' and a time to
+    /// '09:30:00'. Implicit conversions, e.g. for INSERT, don't do this.
+    pub fn cast_to(&self, data_type: DataType) -> Result<Field> {
+        Ok(match (self, data_type) {
+            (Field::Boolean(_) | Field::Integer(_) | Field::Float(_), DataType::Text) => {
+                Field::String(self.to_string().into())
+            }
+            (Field::Time(_), DataType::Text) => Field::String(self.to_plain_string().into()),
+            (value, data_type) => value.coerce_to(data_type)?,
+        })
+    }
+
+    /// Parses an optionally signed integer, either in decimal or in hexadecimal
+    /// or binary with a 0x or 0b prefix (e.g. 0xFF or -0b1010). Errors on
+    /// invalid digits or values that don't fit in an integer.
+    pub fn parse_integer(value: &str) -> Result<i32> {
+        let (negative, unsigned) = match value.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        let (radix, digits) = match unsigned.get(..2) {
+            Some("0x" | "0X") => (16, &unsigned[2..]),
+            Some("0b" | "0B") => (2, &unsigned[2..]),
+            _ => (10, unsigned),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return errinput!("invalid integer {value}");
+        }
+        // The digits are valid, so this can only fail on overflow.
+        let Ok(magnitude) = u64::from_str_radix(digits, radix) else {
+            return errinput!("integer {value} out of range");
+        };
+        let integer = match negative {
+            true => -i128::from(magnitude),
+            false => i128::from(magnitude),
+        };
+        i32::try_from(integer).or_else(|_| errinput!("integer {value} out of range"))
+    }
+
+    /// Parses a time of day as HH:MM or HH:MM:SS, e.g. 09:30 or 23:59:59, into
+    /// seconds since midnight.
+    pub fn parse_time(value: &str) -> Result<u32> {
+        let parts: Vec<&str> = value.trim().split(':').collect();
+        let parse = |part: &str, max: u32| match part.parse::<u32>() {
+            Ok(n) if n <= max && part.len() == 2 => Some(n),
+            _ => None,
+        };
+        let (hours, minutes, seconds) = match parts[..] {
+            [h, m] => (parse(h, 23), parse(m, 59), Some(0)),
+            [h, m, s] => (parse(h, 23), parse(m, 59), parse(s, 59)),
+            _ => (None, None, None),
+        };
+        match (hours, minutes, seconds) {
+            (Some(h), Some(m), Some(s)) => Ok(h * 3600 + m * 60 + s),
+            _ => errinput!("invalid time {value}"),
+        }
+    }
+
+    /// Parses an interval into seconds. The interval is a sequence of signed
+    /// quantities with units (second, minute, hour, or day, optionally plural),
+    /// and/or [-]HH:MM:SS durations, e.g. '1 day 2 hours', '-90 seconds', or
+    /// '01:30:00'.
+    pub fn parse_interval(value: &str) -> Result<i32> {
+        let mut seconds: i64 = 0;
+        let mut words = value.split_whitespace().peekable();
+        if words.peek().is_none() {
+            return errinput!("invalid interval {value}");
+        }
+        while let Some(word) = words.next() {
+            let amount = if word.contains(':') {
+                let (negative, duration) = match word.strip_prefix('-') {
+                    Some(duration) => (true, duration),
+                    None => (false, word),
+                };
+                let parts: Vec<&str> = duration.split(':').collect();
+                let numbers: Vec<i64> = match parts.iter().map(|part| part.parse()).try_collect() {
+                    Ok(numbers) if parts.len() == 3 && parts[1..].iter().all(|p| p.len() == 2) => {
+                        numbers
+                    }
+                    _ => return errinput!("invalid interval {value}"),
+                };
+                if numbers[1] > 59 || numbers[2] > 59 {
+                    return errinput!("invalid interval {value}");
+                }
+                let amount = numbers[0] * 3600 + numbers[1] * 60 + numbers[2];
+                if negative {
+                    -amount
+                } else {
+                    amount
+                }
+            } else {
+                let Ok(quantity) = word.parse::<i64>() else {
+                    return errinput!("invalid interval {value}");
+                };
+                let unit = match words.next().map(|unit| unit.to_lowercase()) {
+                    Some(unit) => unit,
+                    None => return errinput!("invalid interval {value}"),
+                };
+                let unit = match unit.strip_suffix('s').unwrap_or(&unit) {
+                    "second" => 1,
+                    "minute" => 60,
+                    "hour" => 3600,
+                    "day" => 86400,
+                    _ => return errinput!("invalid interval unit {unit}"),
+                };
+                quantity.saturating_mul(unit)
+            };
+            seconds = seconds.saturating_add(amount);
+        }
+        i32::try_from(seconds).or_else(|_| errinput!("interval {value} out of range"))
+    }
+
     pub fn get_type(&self) -> DataType {
         match self {
             Field::Null => DataType::Invalid,
@@ -201,6 +434,8 @@ impl Field {
             Field::Integer(_) => DataType::Int,
             Field::Float(_) => DataType::Float,
             Field::String(_) => DataType::Text,
+            Field::Time(_) => DataType::Time,
+            Field::Interval(_) | Field::Array(_) => DataType::Invalid,
         }
     }
     // size in bytes
@@ -211,15 +446,38 @@ impl Field {
             Field::Integer(_) => 4,
             Field::Float(_) => 4,
             Field::String(s) => s.len() as u16,
+            Field::Time(_) => 4,
+            Field::Interval(_) | Field::Array(_) => 0,
         }
     }
-    pub fn to_string(&self) -> String {
+    /// Formats the value for tabular result output. Unlike Display, text is
+    /// not quoted, booleans are lowercase, and floats use Rust's formatting
+    /// (e.g. 1 and inf).
+    pub fn to_plain_string(&self) -> String {
+        self.to_plain_string_with(None)
+    }
+
+    /// Like to_plain_string(), but formats finite floats with the given
+    /// number of decimal places, if any, e.g. 3.14159 as 3.14 with 2. This
+    /// only affects the output, not the value.
+    pub fn to_plain_string_with(&self, float_precision: Option<usize>) -> String {
         match self {
-            Field::Null => "NULL".to_string(),
             Field::Boolean(b) => b.to_string(),
-            Field::Integer(i) => i.to_string(),
-            Field::Float(f) => f.to_string(),
-            Field::String(s) => s.clone(),
+            Field::Float(f) => match float_precision {
+                Some(precision) if f.is_finite() => format!("{f:.precision$}"),
+                _ => f.to_string(),
+            },
+            Field::String(s) => s.to_string(),
+            Field::Time(time) => format_seconds(*time as i64),
+            Field::Interval(interval) => format_seconds(*interval as i64),
+            Field::Array(values) => format!(
+                "[{}]",
+                values
+                    .iter()
+                    .map(|value| value.to_plain_string_with(float_precision))
+                    .join(", ")
+            ),
+            field => field.to_string(),
         }
     }
     pub fn checked_add(&self, other: &Field) -> Result<Field> {
@@ -235,13 +493,18 @@ impl Field {
             }
             (Float(lhs), Integer(rhs)) => Ok(Float(lhs + (*rhs as f32))),
             (Float(lhs), Float(rhs)) => Ok(Float(lhs + rhs)),
-            (Null, Integer(_)) | (Null, Float(_)) => Ok(Null),
-            (Integer(_), Null) | (Float(_), Null) => Ok(Null),
-            (Null, Null) => Ok(Null),
-            _ => {
-                let msg = format!("Cannot add {:?} and {:?}", self, other);
-                Result::from(Error::InvalidData(msg))
+            // Times wrap around midnight.
+            (Time(time), Interval(interval)) | (Interval(interval), Time(time)) => {
+                Ok(Time(add_time(*time, *interval as i64)))
             }
+            (Interval(lhs), Interval(rhs)) => match lhs.checked_add(*rhs) {
+                Some(v) => Ok(Interval(v)),
+                None => Result::from(Error::OverflowError),
+            },
+            (Null, Integer(_) | Float(_) | Time(_) | Interval(_)) => Ok(Null),
+            (Integer(_) | Float(_) | Time(_) | Interval(_), Null) => Ok(Null),
+            (Null, Null) => Ok(Null),
+            _ => errinput!("can't add {self} and {other}"),
         }
     }
 
@@ -255,13 +518,17 @@ impl Field {
             (Integer(lhs), Float(rhs)) => Ok(Float((*lhs as f32) - rhs)),
             (Float(lhs), Integer(rhs)) => Ok(Float(lhs - (*rhs as f32))),
             (Float(lhs), Float(rhs)) => Ok(Float(lhs - rhs)),
-            (Null, Integer(_)) | (Null, Float(_)) => Ok(Null),
-            (Integer(_), Null) | (Float(_), Null) => Ok(Null),
+            // Times wrap around midnight, and their difference is an interval.
+            (Time(time), Interval(interval)) => Ok(Time(add_time(*time, -(*interval as i64)))),
+            (Time(lhs), Time(rhs)) => Ok(Interval(*lhs as i32 - *rhs as i32)),
+            (Interval(lhs), Interval(rhs)) => match lhs.checked_sub(*rhs) {
+                Some(v) => Ok(Interval(v)),
+                None => Result::from(Error::OverflowError),
+            },
+            (Null, Integer(_) | Float(_) | Time(_) | Interval(_)) => Ok(Null),
+            (Integer(_) | Float(_) | Time(_) | Interval(_), Null) => Ok(Null),
             (Null, Null) => Ok(Null),
-            _ => {
-                let msg = format!("Cannot subtract {:?} and {:?}", self, other);
-                Result::from(Error::InvalidData(msg))
-            }
+            _ => errinput!("can't subtract {self} and {other}"),
         }
     }
 
@@ -278,10 +545,7 @@ impl Field {
             (Null, Integer(_)) | (Null, Float(_)) => Ok(Null),
             (Integer(_), Null) | (Float(_), Null) => Ok(Null),
             (Null, Null) => Ok(Null),
-            _ => {
-                let msg = format!("Cannot multiply {:?} and {:?}", self, other);
-                Result::from(Error::InvalidData(msg))
-            }
+            _ => errinput!("can't multiply {self} and {other}"),
         }
     }
 
@@ -289,7 +553,7 @@ impl Field {
         use Field::*;
 
         if matches!(other, Integer(0) | Float(0.0)) {
-            return Err(Error::InvalidData("Division by zero".to_string()));
+            return errinput!("can't divide by zero");
         }
 
         match (self, other) {
@@ -306,10 +570,7 @@ impl Field {
             (Null, Integer(_)) | (Null, Float(_)) => Ok(Null),
             (Integer(_), Null) | (Float(_), Null) => Ok(Null),
             (Null, Null) => Ok(Null),
-            _ => {
-                let msg = format!("Cannot divide {:?} and {:?}", self, other);
-                Err(Error::InvalidData(msg))
-            }
+            _ => errinput!("can't divide {self} and {other}"),
         }
     }
 
@@ -338,6 +599,11 @@ impl Field {
 
     pub fn checked_mod(&self, other: &Field) -> Result<Field> {
         use Field::*;
+
+        if matches!(other, Integer(0) | Float(0.0)) {
+            return errinput!("can't divide by zero");
+        }
+
         match (&self, other) {
             (Integer(lhs), Integer(rhs)) => match lhs.checked_rem(*rhs) {
                 Some(v) => Ok(Integer(v)),
@@ -352,14 +618,100 @@ impl Field {
             (Null, Integer(_)) | (Null, Float(_)) => Ok(Null),
             (Integer(_), Null) | (Float(_), Null) => Ok(Null),
             (Null, Null) => Ok(Null),
-            _ => {
-                let msg = format!("Cannot mod {:?} and {:?}", self, other);
-                Result::from(Error::InvalidData(msg))
-            }
+            _ => errinput!("can't mod {self} and {other}"),
         }
         //  _ =>  Null,
     }
 
+    /// Rounds the number to the given number of decimal digits, rounding
+    /// halfway cases away from zero. Negative digits round to the left of the
+    /// decimal point, e.g. 1234 rounded to -2 digits is 1200.
+    pub fn checked_round(&self, digits: &Field) -> Result<Field> {
+        use Field::*;
+        match (self, digits) {
+            (Integer(i), Integer(d)) if *d >= 0 => Ok(Integer(*i)),
+            (Integer(i), Integer(d)) => {
+                // Rounding to more digits than an integer has yields 0.
+                let Some(factor) = 10_i64.checked_pow(d.unsigned_abs()) else {
+                    return Ok(Integer(0));
+                };
+                let rounded = (i64::from(i.unsigned_abs()) + factor / 2) / factor * factor;
+                i32::try_from(rounded * i64::from(i.signum()))
+                    .map(Integer)
+                    .map_err(|_| Error::OverflowError)
+            }
+            (Float(f), Integer(d)) => {
+                let factor = 10_f32.powi(*d);
+                match (f * factor).round() / factor {
+                    rounded if rounded.is_finite() || !f.is_finite() => Ok(Float(rounded)),
+                    // Very large digit counts overflow the factor, but don't
+                    // change the value, and very small ones round it to 0.
+                    _ if factor == 0.0 => Ok(Float(0.0)),
+                    _ => Ok(Float(*f)),
+                }
+            }
+            (Null, Integer(_) | Null) | (Integer(_) | Float(_), Null) => Ok(Null),
+            _ => errinput!("can't round {self} to {digits} digits"),
+        }
+    }
+
+    /// Adds two values like checked_add(), but integer overflow wraps around.
+    pub fn wrapping_add(&self, other: &Field) -> Result<Field> {
+        match (self, other) {
+            (Field::Integer(lhs), Field::Integer(rhs)) => {
+                Ok(Field::Integer(lhs.wrapping_add(*rhs)))
+            }
+            _ => self.checked_add(other),
+        }
+    }
+
+    /// Subtracts two values like checked_sub(), but integer overflow wraps
+    /// around.
+    pub fn wrapping_sub(&self, other: &Field) -> Result<Field> {
+        match (self, other) {
+            (Field::Integer(lhs), Field::Integer(rhs)) => {
+                Ok(Field::Integer(lhs.wrapping_sub(*rhs)))
+            }
+            _ => self.checked_sub(other),
+        }
+    }
+
+    /// Multiplies two values like checked_mul(), but integer overflow wraps
+    /// around.
+    pub fn wrapping_mul(&self, other: &Field) -> Result<Field> {
+        match (self, other) {
+            (Field::Integer(lhs), Field::Integer(rhs)) => {
+                Ok(Field::Integer(lhs.wrapping_mul(*rhs)))
+            }
+            _ => self.checked_mul(other),
+        }
+    }
+
+    /// Computes the bitwise AND of two integers. NULLs yield NULL.
+    pub fn bit_and(&self, other: &Self) -> Result<Self> {
+        self.bitwise(other, "AND", |lhs, rhs| lhs & rhs)
+    }
+
+    /// Computes the bitwise OR of two integers. NULLs yield NULL.
+    pub fn bit_or(&self, other: &Self) -> Result<Self> {
+        self.bitwise(other, "OR", |lhs, rhs| lhs | rhs)
+    }
+
+    /// Computes the bitwise XOR of two integers. NULLs yield NULL.
+    pub fn bit_xor(&self, other: &Self) -> Result<Self> {
+        self.bitwise(other, "XOR", |lhs, rhs| lhs ^ rhs)
+    }
+
+    /// Applies a bitwise operation to two integers. Errors on other types.
+    fn bitwise(&self, other: &Self, name: &str, op: impl Fn(i32, i32) -> i32) -> Result<Self> {
+        use Field::*;
+        Ok(match (self, other) {
+            (Integer(lhs), Integer(rhs)) => Integer(op(*lhs, *rhs)),
+            (Integer(_) | Null, Null) | (Null, Integer(_)) => Null,
+            (lhs, rhs) => return errinput!("can't bitwise {name} {lhs} and {rhs}"),
+        })
+    }
+
     pub fn is_null(&self) -> bool {
         match self {
             Field::Null => true,
@@ -380,6 +732,9 @@ impl Field {
             Field::Integer(i) => i.to_le_bytes().to_vec(),
             Field::Float(f) => f.to_le_bytes().to_vec(),
             Field::String(s) => s.as_bytes().to_vec(),
+            Field::Time(t) => t.to_le_bytes().to_vec(),
+            // Intervals and arrays can't be stored, and are written like NULL.
+            Field::Interval(_) | Field::Array(_) => vec![0],
         }
     }
 
@@ -394,7 +749,8 @@ impl Field {
             }
             DataType::Int => Field::Integer(i32::from_le_bytes(data.try_into().unwrap())),
             DataType::Float => Field::Float(f32::from_le_bytes(data.try_into().unwrap())),
-            DataType::Text => Field::String(String::from_utf8(data.to_vec()).unwrap()),
+            DataType::Text => Field::String(String::from_utf8(data.to_vec()).unwrap().into()),
+            DataType::Time => Field::Time(u32::from_le_bytes(data.try_into().unwrap())),
             _ => Field::Null,
         }
     }
@@ -403,6 +759,58 @@ impl Field {
     pub fn is_undefined(&self) -> bool {
         *self == Self::Null || matches!(self, Self::Float(f) if f.is_nan())
     }
+
+    /// Returns the field's key when comparing floats with the given tolerance.
+    /// Floats are bucketed by rounding them to the nearest multiple of epsilon,
+    /// such that e.g. 1.1 + 2.2 and 3.3 share a key, although floats close to
+    /// a bucket boundary may not. Other fields are returned as is. Errors if
+    /// epsilon isn't positive and finite.
+    pub fn float_key(self, epsilon: f32) -> Result<Field> {
+        Self::check_epsilon(epsilon)?;
+        Ok(match self {
+            // Adding 0.0 normalizes -0.0 to 0.0.
+            Self::Float(f) if f.is_finite() => Self::Float((f / epsilon).round() * epsilon + 0.0),
+            field => field,
+        })
+    }
+
+    /// Checks that a float comparison tolerance is positive and finite.
+    pub fn check_epsilon(epsilon: f32) -> Result<()> {
+        if !epsilon.is_finite() || epsilon <= 0.0 {
+            return errinput!("invalid float epsilon {epsilon}, must be positive and finite");
+        }
+        Ok(())
+    }
+}
+
+/// Interns strings, such that equal string fields share a single allocation.
+/// Useful when buffering many rows with repeated, low-cardinality strings,
+/// e.g. on the build side of a hash join.
+#[derive(Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// Creates a new, empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Interns a field. A string is replaced by a previously interned equal
+    /// string, if any. Other fields are returned as is.
+    pub fn intern(&mut self, field: Field) -> Field {
+        let Field::String(string) = field else {
+            return field;
+        };
+        match self.strings.get(&string) {
+            Some(shared) => Field::String(shared.clone()),
+            None => {
+                self.strings.insert(string.clone());
+                Field::String(string)
+            }
+        }
+    }
 }
 
 /// A column label, used in query results and plans.
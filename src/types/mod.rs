@@ -1,4 +1,4 @@
 pub mod field;
 mod schema;
 
-pub use schema::{Column, DataType, Table, TableBuilder};
+pub use schema::{Column, ColumnChange, DataType, SchemaDiff, Table, TableBuilder};
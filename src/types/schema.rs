@@ -1,7 +1,12 @@
+use crate::common::Result;
+use crate::storage::tuple::Row;
+use crate::{errdata, errinput};
 use crate::types::field::Field;
 use core::ops::Deref;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug, Copy, Serialize, Deserialize)]
@@ -10,6 +15,7 @@ pub enum DataType {
     Int,
     Float,
     Text,
+    Time,
     Invalid,
 }
 
@@ -20,6 +26,7 @@ impl fmt::Display for DataType {
             DataType::Int => write!(f, "int"),
             DataType::Float => write!(f, "float"),
             DataType::Text => write!(f, "varchar"),
+            DataType::Time => write!(f, "time"),
             DataType::Invalid => write!(f, "invalid"),
         }
     }
@@ -32,12 +39,38 @@ impl DataType {
             "Int" => DataType::Int,
             "Float" => DataType::Float,
             "Text" => DataType::Text,
+            "Time" => DataType::Time,
             "Invalid" => DataType::Invalid,
             "Null" => DataType::Invalid,
             _ => panic!("Unknown data type"),
         }
     }
 
+    /// Returns the data type's tag in the binary schema encoding.
+    fn to_tag(self) -> u8 {
+        match self {
+            DataType::Bool => 0,
+            DataType::Int => 1,
+            DataType::Float => 2,
+            DataType::Text => 3,
+            DataType::Invalid => 4,
+            DataType::Time => 5,
+        }
+    }
+
+    /// Decodes a data type tag from the binary schema encoding.
+    fn from_tag(tag: u8) -> Result<DataType> {
+        Ok(match tag {
+            0 => DataType::Bool,
+            1 => DataType::Int,
+            2 => DataType::Float,
+            3 => DataType::Text,
+            4 => DataType::Invalid,
+            5 => DataType::Time,
+            tag => return errdata!("invalid data type tag {tag}"),
+        })
+    }
+
     // not for use with strings
     pub fn length_bytes(&self) -> u16 {
         match self {
@@ -45,6 +78,7 @@ impl DataType {
             DataType::Int => 4,
             DataType::Float => 4,
             DataType::Text => 0,
+            DataType::Time => 4,
             DataType::Invalid => 0,
         }
     }
@@ -58,6 +92,8 @@ pub struct Column {
     data_type: DataType,
     /// Whether the column allows null values. Not legal for primary keys.
     nullable: bool,
+    /// Whether the column is the table's primary key.
+    primary_key: bool,
     /// The column's default value. If None, the user must specify an explicit
     /// value. Must match the column datatype. Nullable columns require a
     /// default (often Null), and Null is only a valid default when nullable.,
@@ -69,6 +105,10 @@ pub struct Column {
     ///
     /// See `[crate::Row::to_bytes()]` for more detail about the data layout.
     stored_offset: u16,
+    /// A computed default as SQL expression text, parsed and evaluated by the
+    /// planner whenever a row is inserted without a value for the column.
+    /// Takes precedence over the literal default.
+    default_expr: Option<String>,
 }
 
 impl Column {
@@ -83,6 +123,7 @@ impl Column {
             name: column_name.to_string(),
             data_type: dt,
             nullable,
+            primary_key: false,
             default: match default {
                 Some(expr) => Some(expr),
                 None if nullable => Some(Field::Null),
@@ -90,6 +131,7 @@ impl Column {
             },
             max_str_len: max_str_chars.unwrap_or(0),
             stored_offset: 0,
+            default_expr: None,
         }
     }
 
@@ -126,6 +168,26 @@ impl Column {
         self.default.as_ref()
     }
 
+    pub fn default_expr(&self) -> Option<&str> {
+        self.default_expr.as_deref()
+    }
+
+    pub fn set_default_expr(&mut self, sql: &str) {
+        self.default_expr = Some(sql.to_string());
+    }
+
+    pub fn set_primary_key(&mut self, primary_key: bool) {
+        self.primary_key = primary_key;
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    pub fn is_primary_key(&self) -> bool {
+        self.primary_key
+    }
+
     pub fn length_bytes(&self) -> u16 {
         self.data_type.length_bytes() + self.max_str_len
     }
@@ -173,9 +235,11 @@ impl ColumnBuilder {
         self
     }
 
+    /// Sets the column's default value. Without one, nullable columns default
+    /// to NULL and other columns have no default.
     pub fn default(mut self, default: Field) -> Self {
         assert!(self.data_type.is_some());
-        assert_eq!(default.get_type(), self.data_type.unwrap());
+        assert!(default.is_null() || default.get_type() == self.data_type.unwrap());
         self.default = Some(default);
         self
     }
@@ -193,6 +257,7 @@ impl ColumnBuilder {
                 .data_type
                 .expect("data_type must be specified before building."),
             nullable,
+            primary_key: false,
             default: match self.default {
                 Some(expr) => Some(expr),
                 None if nullable => Some(Field::Null),
@@ -200,6 +265,7 @@ impl ColumnBuilder {
             },
             max_str_len: self.max_str_len.unwrap_or(0),
             stored_offset: 0,
+            default_expr: None,
         }
     }
 }
@@ -210,9 +276,11 @@ impl From<DataType> for Column {
             name: "".to_string(),
             data_type: dt,
             nullable: false,
+            primary_key: false,
             default: None,
             max_str_len: 0,
             stored_offset: 0,
+            default_expr: None,
         }
     }
 }
@@ -223,14 +291,17 @@ impl From<(DataType, u16)> for Column {
             name: "".to_string(),
             data_type: dt,
             nullable: false,
+            primary_key: false,
             default: None,
             max_str_len: str_len,
             stored_offset: 0,
+            default_expr: None,
         }
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(from = "TableFields")]
 pub struct Table {
     /// The name of the table
     name: String,
@@ -238,6 +309,55 @@ pub struct Table {
     fixed_field_size_bytes: u16,
     /// The column definitions of the table
     columns: Vec<Column>,
+    /// Column indexes by name, for constant-time name lookups. If several
+    /// columns have the same name (e.g. in joined schemas), this holds the
+    /// first one. It's derived from columns, so it isn't serialized.
+    #[serde(skip)]
+    column_indexes: HashMap<String, usize>,
+}
+
+/// The serialized fields of a Table, used to rebuild the column index map
+/// during deserialization.
+#[derive(Deserialize)]
+struct TableFields {
+    name: String,
+    fixed_field_size_bytes: u16,
+    columns: Vec<Column>,
+}
+
+impl From<TableFields> for Table {
+    fn from(fields: TableFields) -> Self {
+        let mut table = Table {
+            name: fields.name,
+            fixed_field_size_bytes: fields.fixed_field_size_bytes,
+            columns: fields.columns,
+            column_indexes: HashMap::new(),
+        };
+        table.index_columns();
+        table
+    }
+}
+
+/// Hashes the table definition. The column index map is derived from the
+/// columns, so it's omitted (HashMap doesn't implement Hash anyway).
+impl Hash for Table {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.fixed_field_size_bytes.hash(state);
+        self.columns.hash(state);
+    }
+}
+
+/// Omits the derived column index map, which would otherwise be printed in
+/// arbitrary order.
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("name", &self.name)
+            .field("fixed_field_size_bytes", &self.fixed_field_size_bytes)
+            .field("columns", &self.columns)
+            .finish()
+    }
 }
 
 impl Table {
@@ -246,6 +366,7 @@ impl Table {
             name: table_name.to_string(),
             fixed_field_size_bytes: 0,
             columns: Vec::new(),
+            column_indexes: HashMap::new(),
         }
     }
 
@@ -265,6 +386,9 @@ impl Table {
         let data_type = column.get_data_type();
         let mut to_push = column.clone();
 
+        self.column_indexes
+            .entry(column.get_name())
+            .or_insert(self.columns.len());
         if data_type == DataType::Text {
             to_push.stored_offset = self.variable_length_fields() as u16;
             self.columns.push(to_push);
@@ -308,6 +432,47 @@ impl Table {
         self.columns.len()
     }
 
+    /// Checks that the row has the table's columns, and that each value has
+    /// the column's type or is NULL. Nullability is not checked.
+    pub fn validate_row(&self, row: &Row) -> Result<()> {
+        if row.size() != self.col_count() {
+            return errinput!("expected {} columns, got {}", self.col_count(), row.size());
+        }
+        for (field, column) in row.iter().zip(&self.columns) {
+            let data_type = column.get_data_type();
+            if !field.is_null() && field.get_type() != data_type {
+                return errinput!("invalid value {field} for {data_type} column {}", column.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the primary key column, if any.
+    pub fn primary_key(&self) -> Option<usize> {
+        self.columns.iter().position(|column| column.primary_key)
+    }
+
+    /// Returns the row's primary key values, in column order. A composite key
+    /// is made up of every column marked as a primary key. Errors if the table
+    /// has no primary key or a key value is NULL.
+    pub fn primary_key_values(&self, row: &Row) -> Result<Vec<Field>> {
+        let mut values = Vec::new();
+        for (index, column) in self.columns.iter().enumerate() {
+            if !column.primary_key {
+                continue;
+            }
+            let value = row.get_field(index)?;
+            if value.is_null() {
+                return errinput!("NULL primary key value for column {}", column.name);
+            }
+            values.push(value);
+        }
+        if values.is_empty() {
+            return errinput!("table {} has no primary key", self.name);
+        }
+        Ok(values)
+    }
+
     pub fn get_column_name(&self, index: usize) -> String {
         self.columns[index].get_name()
     }
@@ -319,14 +484,30 @@ impl Table {
     // if a field exists return its offset in the schema
     // otherwise return None
     pub fn field_name_to_index(&self, field_name: Option<&String>) -> Option<usize> {
-        let f = field_name?;
+        self.column_indexes.get(field_name?).copied()
+    }
+
+    /// Renames a column. Only the name changes, so rows stored with the table
+    /// are unaffected. Errors if the column doesn't exist, or if another column
+    /// already has the new name.
+    pub fn rename_column(&mut self, old: &str, new: &str) -> Result<()> {
+        let Some(index) = self.columns.iter().position(|column| column.name == old) else {
+            return errinput!("unknown column {old} in table {}", self.name);
+        };
+        if old != new && self.columns.iter().any(|column| column.name == new) {
+            return errinput!("column {new} already exists in table {}", self.name);
+        }
+        self.columns[index].name = new.to_string();
+        self.index_columns();
+        Ok(())
+    }
 
+    /// Rebuilds the column index map from the columns.
+    fn index_columns(&mut self) {
+        self.column_indexes.clear();
         for (i, column) in self.columns.iter().enumerate() {
-            if column.get_name() == *f {
-                return Some(i);
-            }
+            self.column_indexes.entry(column.get_name()).or_insert(i);
         }
-        None
     }
 
     // max possible size for tuple
@@ -350,20 +531,252 @@ impl Table {
             .count()
     }
 
+    /// Concatenates the columns of two schemas, e.g. for a join's output rows.
+    /// Stored offsets are recomputed for the merged column order, as with
+    /// add_column(): fixed-length columns get byte offsets, and variable-length
+    /// columns are renumbered by their position among the merged ones.
     pub fn merge(d1: &Table, d2: &Table) -> Table {
         let mut schema = Table::new("");
         schema.columns.append(&mut d1.columns.clone());
         schema.columns.append(&mut d2.columns.clone());
+        schema.index_columns();
 
         schema.fixed_field_size_bytes = 0;
-        for i in 0..schema.col_count() {
-            if schema.columns[i].data_type != DataType::Text {
-                schema.columns[i].stored_offset = schema.fixed_field_size_bytes;
-                schema.fixed_field_size_bytes += schema.columns[i].data_type.length_bytes();
+        let mut variable_length_fields = 0;
+        for column in &mut schema.columns {
+            if column.data_type == DataType::Text {
+                column.stored_offset = variable_length_fields;
+                variable_length_fields += 1;
+            } else {
+                column.stored_offset = schema.fixed_field_size_bytes;
+                schema.fixed_field_size_bytes += column.data_type.length_bytes();
             }
         }
         schema
     }
+
+    /// Returns the column differences from this schema to the other, e.g. to
+    /// migrate a table to a new schema version. Columns are matched by name.
+    pub fn diff(&self, other: &Table) -> SchemaDiff {
+        let mut diff = SchemaDiff::default();
+        for column in &self.columns {
+            let Some(index) = other.column_indexes.get(&column.name) else {
+                diff.dropped.push(column.clone());
+                continue;
+            };
+            let new = &other.columns[*index];
+            let change = ColumnChange {
+                name: column.name.clone(),
+                data_type: (column.data_type != new.data_type)
+                    .then_some((column.data_type, new.data_type)),
+                nullable: (column.nullable != new.nullable).then_some(new.nullable),
+            };
+            if change.data_type.is_some() || change.nullable.is_some() {
+                diff.changed.push(change);
+            }
+        }
+        diff.added = other
+            .columns
+            .iter()
+            .filter(|column| !self.column_indexes.contains_key(&column.name))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+/// The differences between two versions of a table schema, see Table::diff().
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemaDiff {
+    /// Columns only in the new schema, in its column order.
+    pub added: Vec<Column>,
+    /// Columns only in the old schema, in its column order.
+    pub dropped: Vec<Column>,
+    /// Columns in both schemas whose definition changed.
+    pub changed: Vec<ColumnChange>,
+}
+
+impl SchemaDiff {
+    /// Returns true if the schemas have the same columns.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.dropped.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A change to a column present in both schemas of a SchemaDiff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnChange {
+    /// The column name.
+    pub name: String,
+    /// The old and new data type, if changed.
+    pub data_type: Option<(DataType, DataType)>,
+    /// The new nullability, if changed.
+    pub nullable: Option<bool>,
+}
+
+/// The current version of the binary schema encoding.
+const SCHEMA_ENCODING_VERSION: u8 = 1;
+
+/// Binary schema encoding, used to store catalog entries independently of
+/// serde's formats. All integers are little-endian, and strings and byte
+/// strings are prefixed by their u16 length. The layout is:
+///
+/// * version: u8
+/// * table name: string
+/// * fixed field size: u16
+/// * column count: u16, followed by each column:
+///   * name: string
+///   * data type: u8 tag
+///   * nullable: u8 (0 or 1)
+///   * primary key: u8 (0 or 1)
+///   * max string length: u16
+///   * stored offset: u16
+///   * default: u8 (0 if none), followed by the value's data type tag and
+///     serialized bytes if 1. NULL is encoded as the invalid data type.
+///   * default expression: u8 (0 if none), followed by the expression's SQL
+///     text as a string if 1.
+impl Table {
+    /// Encodes the table schema as bytes. See from_bytes().
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![SCHEMA_ENCODING_VERSION];
+        let put_bytes = |bytes: &mut Vec<u8>, value: &[u8]| {
+            bytes.extend((value.len() as u16).to_le_bytes());
+            bytes.extend(value);
+        };
+        put_bytes(&mut bytes, self.name.as_bytes());
+        bytes.extend(self.fixed_field_size_bytes.to_le_bytes());
+        bytes.extend((self.columns.len() as u16).to_le_bytes());
+        for column in &self.columns {
+            put_bytes(&mut bytes, column.name.as_bytes());
+            bytes.push(column.data_type.to_tag());
+            bytes.push(column.nullable as u8);
+            bytes.push(column.primary_key as u8);
+            bytes.extend(column.max_str_len.to_le_bytes());
+            bytes.extend(column.stored_offset.to_le_bytes());
+            match &column.default {
+                None => bytes.push(0),
+                Some(value) => {
+                    bytes.push(1);
+                    bytes.push(value.get_type().to_tag());
+                    match value {
+                        Field::Null => put_bytes(&mut bytes, &[]),
+                        value => put_bytes(&mut bytes, &value.serialize()),
+                    }
+                }
+            }
+            match &column.default_expr {
+                None => bytes.push(0),
+                Some(sql) => {
+                    bytes.push(1);
+                    put_bytes(&mut bytes, sql.as_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a table schema encoded by to_bytes(). Errors on malformed or
+    /// truncated input, or an unknown encoding version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Table> {
+        let mut reader = SchemaReader { bytes };
+        let version = reader.u8()?;
+        if version != SCHEMA_ENCODING_VERSION {
+            return errdata!("unsupported schema encoding version {version}");
+        }
+        let mut table = Table::new(&reader.string()?);
+        table.fixed_field_size_bytes = reader.u16()?;
+        let count = reader.u16()?;
+        for _ in 0..count {
+            let name = reader.string()?;
+            let data_type = DataType::from_tag(reader.u8()?)?;
+            let nullable = match reader.u8()? {
+                0 => false,
+                1 => true,
+                value => return errdata!("invalid nullable flag {value}"),
+            };
+            let primary_key = match reader.u8()? {
+                0 => false,
+                1 => true,
+                value => return errdata!("invalid primary key flag {value}"),
+            };
+            let max_str_len = reader.u16()?;
+            let stored_offset = reader.u16()?;
+            let default = match reader.u8()? {
+                0 => None,
+                1 => Some(reader.field()?),
+                value => return errdata!("invalid default flag {value}"),
+            };
+            let default_expr = match reader.u8()? {
+                0 => None,
+                1 => Some(reader.string()?),
+                value => return errdata!("invalid default expression flag {value}"),
+            };
+            table.columns.push(Column {
+                name,
+                data_type,
+                nullable,
+                primary_key,
+                default,
+                max_str_len,
+                stored_offset,
+                default_expr,
+            });
+        }
+        if !reader.bytes.is_empty() {
+            return errdata!("unexpected {} trailing bytes in schema", reader.bytes.len());
+        }
+        table.index_columns();
+        Ok(table)
+    }
+}
+
+/// Reads values from a binary schema encoding, erroring on truncated input.
+struct SchemaReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl SchemaReader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        if self.bytes.len() < len {
+            return errdata!("truncated schema, expected {len} bytes, found {}", self.bytes.len());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn byte_string(&mut self) -> Result<&[u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.byte_string()?.to_vec())?)
+    }
+
+    fn field(&mut self) -> Result<Field> {
+        let data_type = DataType::from_tag(self.u8()?)?;
+        let bytes = self.byte_string()?;
+        let expected = match data_type {
+            DataType::Text => bytes.len(),
+            data_type => data_type.length_bytes() as usize,
+        };
+        if bytes.len() != expected {
+            return errdata!("invalid {data_type} value of {} bytes", bytes.len());
+        }
+        Ok(match data_type {
+            DataType::Text => Field::String(String::from_utf8(bytes.to_vec())?.into()),
+            data_type => Field::deserialize(bytes, data_type),
+        })
+    }
 }
 
 // set up anonymous columns by type.
@@ -448,6 +861,14 @@ impl TableBuilder {
         self
     }
 
+    /// Marks the given, previously added column as the primary key.
+    pub fn primary_key(&mut self, column_name: &str) -> &mut Self {
+        for column in &mut self.columns {
+            column.primary_key = column.name == column_name;
+        }
+        self
+    }
+
     pub fn build(&mut self) -> Table {
         let name = self
             .name